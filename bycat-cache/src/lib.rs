@@ -6,9 +6,10 @@ use std::{
 };
 
 use bycat::Work;
-use bycat_error::Error;
 use pin_project::pin_project;
 
+pub use bycat_error::Error;
+
 pub type Bytes = Vec<u8>;
 
 pub trait CacheStore {
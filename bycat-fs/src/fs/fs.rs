@@ -12,7 +12,7 @@ use std::{
 };
 
 use super::{Body, ReadDir, ResolvedPath, WalkDir, WalkDirStream};
-use crate::virtual_fs::VirtualFS;
+use crate::virtual_fs::{RemovableFS, VirtualFS};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Fs {
@@ -73,6 +73,15 @@ impl VirtualFS for Fs {
     }
 }
 
+impl RemovableFS for Fs {
+    type Remove<'a> = BoxFuture<'a, Result<(), Self::Error>>;
+
+    fn remove<'a>(&'a self, path: impl AsRef<RelativePath>) -> Self::Remove<'a> {
+        let path = path.as_ref().to_logical_path(&self.root);
+        Box::pin(async move { tokio::fs::remove_file(path).await.map_err(Error::new) })
+    }
+}
+
 impl<C> Source<C> for Fs {
     type Item = Package<Body>;
 
@@ -12,7 +12,7 @@ pub use mime::{self, Mime};
 #[cfg(feature = "std")]
 pub use mime_guess;
 
-pub use self::virtual_fs::VirtualFS;
+pub use self::virtual_fs::{RemovableFS, VirtualFS};
 
 #[cfg(feature = "std")]
 pub use self::fs::{Body, FileResolver, Fs, ReadDir, WalkDir};
@@ -28,3 +28,12 @@ pub trait VirtualFS {
 
     fn write<'a>(&'a self, package: Package<Self::Body>) -> Self::Write<'a>;
 }
+
+/// A [`VirtualFS`] files can be removed from.
+pub trait RemovableFS: VirtualFS {
+    type Remove<'a>: Future<Output = Result<(), Self::Error>>
+    where
+        Self: 'a;
+
+    fn remove<'a>(&'a self, path: impl AsRef<RelativePath>) -> Self::Remove<'a>;
+}
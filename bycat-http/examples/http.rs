@@ -19,7 +19,7 @@ async fn main() -> Result<()> {
             let value: u64 = session.get("counter").map(|m| m.unwrap_or_default())?;
             session.set("counter", value + 1);
 
-            session.regenerate_id().await?;
+            session.regenerate().await?;
 
             Result::Ok(format!("Count: {}", value))
         })
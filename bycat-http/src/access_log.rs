@@ -0,0 +1,581 @@
+use crate::{
+    Error, IntoResponse,
+    body::HttpBody,
+    extract::{ClientAddr, PeerAddr},
+};
+use alloc::{format, string::String, sync::Arc};
+use bycat::{Middleware, Work};
+use bytes::{Buf, Bytes};
+use core::{
+    fmt::Write as _,
+    marker::PhantomData,
+    net::IpAddr,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use http::{
+    HeaderName, Method, Request, Response, StatusCode, Version,
+    header::{REFERER, USER_AGENT},
+};
+use pin_project_lite::pin_project;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// The line format of an [`AccessLog`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// The Common Log Format.
+    #[default]
+    Common,
+    /// The Combined Log Format, adding the referer and user agent.
+    Combined,
+    /// A JSON object per line, adding the latency and request id.
+    Json,
+}
+
+type Writer = dyn Fn(&str) + Send + Sync;
+
+/// Logs a line per request, once its response body is sent.
+///
+/// Lines go to `tracing` at info level, under the `bycat_http::access`
+/// target, unless another writer is set. The latency includes sending the
+/// body. Requests dropped before their handler answered, like when the client
+/// goes away, are logged with a status of `-`, or `null` in JSON.
+#[derive(Clone)]
+pub struct AccessLog {
+    format: LogFormat,
+    writer: Arc<Writer>,
+}
+
+impl Default for AccessLog {
+    fn default() -> Self {
+        AccessLog::new(LogFormat::default())
+    }
+}
+
+impl core::fmt::Debug for AccessLog {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("AccessLog")
+            .field("format", &self.format)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AccessLog {
+    pub fn new(format: LogFormat) -> AccessLog {
+        AccessLog {
+            format,
+            writer: Arc::new(|line: &str| {
+                tracing::info!(target: "bycat_http::access", "{line}");
+            }),
+        }
+    }
+
+    pub fn common() -> AccessLog {
+        AccessLog::new(LogFormat::Common)
+    }
+
+    pub fn combined() -> AccessLog {
+        AccessLog::new(LogFormat::Combined)
+    }
+
+    pub fn json() -> AccessLog {
+        AccessLog::new(LogFormat::Json)
+    }
+
+    /// Write the lines with `writer` instead.
+    pub fn writer<F>(mut self, writer: F) -> Self
+    where
+        F: Fn(&str) + Send + Sync + 'static,
+    {
+        self.writer = Arc::new(writer);
+        self
+    }
+}
+
+/// What's logged about a request.
+struct Record {
+    format: LogFormat,
+    writer: Arc<Writer>,
+    client: Option<IpAddr>,
+    method: Method,
+    target: String,
+    version: Version,
+    referer: Option<String>,
+    user_agent: Option<String>,
+    #[cfg(feature = "request-id")]
+    request_id: Option<crate::request_id::RequestId>,
+    started: Instant,
+    time: SystemTime,
+    status: Option<StatusCode>,
+    bytes: u64,
+}
+
+impl Record {
+    fn new<B>(log: &AccessLog, req: &Request<B>) -> Record {
+        let header = |name: HeaderName| {
+            req.headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(String::from)
+        };
+
+        let client = match req.extensions().get::<ClientAddr>() {
+            Some(addr) => Some(addr.0),
+            None => req.extensions().get::<PeerAddr>().map(|peer| peer.0.ip()),
+        };
+
+        Record {
+            format: log.format,
+            writer: log.writer.clone(),
+            client,
+            method: req.method().clone(),
+            target: req
+                .uri()
+                .path_and_query()
+                .map_or("/", |path| path.as_str())
+                .into(),
+            version: req.version(),
+            referer: header(REFERER),
+            user_agent: header(USER_AGENT),
+            #[cfg(feature = "request-id")]
+            request_id: req
+                .extensions()
+                .get::<crate::request_id::RequestId>()
+                .cloned(),
+            started: Instant::now(),
+            time: SystemTime::now(),
+            status: None,
+            bytes: 0,
+        }
+    }
+
+    #[cfg(feature = "request-id")]
+    fn request_id(&self) -> Option<&str> {
+        self.request_id.as_ref().map(|id| id.as_str())
+    }
+
+    #[cfg(not(feature = "request-id"))]
+    fn request_id(&self) -> Option<&str> {
+        None
+    }
+
+    fn line(&self) -> String {
+        let client = self
+            .client
+            .map_or_else(|| String::from("-"), |client| format!("{client}"));
+        let status = self.status.map(|status| status.as_u16());
+
+        match self.format {
+            LogFormat::Common | LogFormat::Combined => {
+                let mut line = format!(
+                    "{client} - - [{}] \"{} {} {:?}\" {} {}",
+                    clf_time(self.time),
+                    self.method,
+                    self.target,
+                    self.version,
+                    status.map_or_else(|| String::from("-"), |status| format!("{status}")),
+                    self.bytes,
+                );
+
+                if self.format == LogFormat::Combined {
+                    let quoted = |value: &Option<String>| match value {
+                        Some(value) => format!("\"{}\"", value.replace('"', "\\\"")),
+                        None => String::from("\"-\""),
+                    };
+                    let _ = write!(
+                        line,
+                        " {} {}",
+                        quoted(&self.referer),
+                        quoted(&self.user_agent)
+                    );
+                }
+
+                line
+            }
+            LogFormat::Json => {
+                let latency = self.started.elapsed();
+                serde_json::json!({
+                    "time": self.time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs_f64(),
+                    "client": self.client.map(|client| format!("{client}")),
+                    "method": self.method.as_str(),
+                    "path": self.target,
+                    "version": format!("{:?}", self.version),
+                    "status": status,
+                    "bytes": self.bytes,
+                    "latency_ms": latency.as_secs_f64() * 1000.0,
+                    "referer": self.referer,
+                    "user_agent": self.user_agent,
+                    "request_id": self.request_id(),
+                })
+                .to_string()
+            }
+        }
+    }
+}
+
+impl Drop for Record {
+    fn drop(&mut self) {
+        (self.writer)(&self.line());
+    }
+}
+
+/// `time` in the Common Log Format, eg. `10/Oct/2000:13:55:36 +0000`.
+fn clf_time(time: SystemTime) -> String {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .as_secs();
+    let (days, secs) = ((secs / 86_400) as i64, secs % 86_400);
+
+    // Howard Hinnant's days to civil date
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{day:02}/{}/{year}:{:02}:{:02}:{:02} +0000",
+        MONTHS[(month - 1) as usize],
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+impl<C, B, T> Middleware<C, Request<B>, T> for AccessLog
+where
+    T: Work<C, Request<B>>,
+    T::Output: IntoResponse<B>,
+    T::Error: Into<Error>,
+    B: HttpBody + Send + Sync + 'static,
+    B::Data: Into<Bytes>,
+    B::Error: core::error::Error + Send + Sync + 'static,
+{
+    type Work = AccessLogWork<T, B>;
+
+    fn wrap(&self, handle: T) -> Self::Work {
+        AccessLogWork {
+            work: handle,
+            log: self.clone(),
+            body: PhantomData,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct AccessLogWork<T, B> {
+    work: T,
+    log: AccessLog,
+    body: PhantomData<fn(B)>,
+}
+
+impl<T: Clone, B> Clone for AccessLogWork<T, B> {
+    fn clone(&self) -> Self {
+        AccessLogWork {
+            work: self.work.clone(),
+            log: self.log.clone(),
+            body: PhantomData,
+        }
+    }
+}
+
+impl<T, C, B> Work<C, Request<B>> for AccessLogWork<T, B>
+where
+    T: Work<C, Request<B>>,
+    T::Output: IntoResponse<B>,
+    T::Error: Into<Error>,
+    B: HttpBody + Send + Sync + 'static,
+    B::Data: Into<Bytes>,
+    B::Error: core::error::Error + Send + Sync + 'static,
+{
+    type Output = Response<B>;
+
+    type Error = Error;
+
+    type Future<'a>
+        = AccessLogFuture<'a, T, C, B>
+    where
+        Self: 'a,
+        C: 'a;
+
+    fn call<'a>(&'a self, context: &'a C, req: Request<B>) -> Self::Future<'a> {
+        AccessLogFuture {
+            record: Some(Record::new(&self.log, &req)),
+            future: self.work.call(context, req),
+        }
+    }
+}
+
+pin_project! {
+    pub struct AccessLogFuture<'a, T, C, B>
+    where
+        T: Work<C, Request<B>>,
+        T: 'a,
+        C: 'a,
+    {
+        #[pin]
+        future: T::Future<'a>,
+        record: Option<Record>,
+    }
+}
+
+impl<'a, T, C, B> Future for AccessLogFuture<'a, T, C, B>
+where
+    T: Work<C, Request<B>>,
+    T::Output: IntoResponse<B>,
+    T::Error: Into<Error>,
+    B: HttpBody + Send + Sync + 'static,
+    B::Data: Into<Bytes>,
+    B::Error: core::error::Error + Send + Sync + 'static,
+{
+    type Output = Result<Response<B>, Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let output = match core::task::ready!(this.future.poll(cx)) {
+            Ok(output) => output,
+            Err(err) => {
+                // Logged with the status it's rendered with further out
+                let err = err.into();
+                if let Some(mut record) = this.record.take() {
+                    record.status = Some(err.status());
+                }
+                return Poll::Ready(Err(err));
+            }
+        };
+
+        let resp = output.into_response();
+        let Some(mut record) = this.record.take() else {
+            return Poll::Ready(Ok(resp));
+        };
+        record.status = Some(resp.status());
+
+        Poll::Ready(Ok(resp.map(|body| {
+            B::from_streaming(CountingBody {
+                body,
+                record: Some(record),
+            })
+        })))
+    }
+}
+
+pin_project! {
+    /// Counts the bytes of a response body, logging the record once it ends
+    /// or is dropped.
+    struct CountingBody<B> {
+        #[pin]
+        body: B,
+        record: Option<Record>,
+    }
+}
+
+impl<B> http_body::Body for CountingBody<B>
+where
+    B: http_body::Body,
+{
+    type Data = B::Data;
+
+    type Error = B::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<http_body::Frame<Self::Data>, Self::Error>>> {
+        let this = self.project();
+        let frame = core::task::ready!(this.body.poll_frame(cx));
+
+        match &frame {
+            Some(Ok(frame)) => {
+                if let (Some(data), Some(record)) = (frame.data_ref(), this.record.as_mut()) {
+                    record.bytes += data.remaining() as u64;
+                }
+            }
+            _ => {
+                this.record.take();
+            }
+        }
+
+        Poll::Ready(frame)
+    }
+
+    fn size_hint(&self) -> http_body::SizeHint {
+        self.body.size_hint()
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.body.is_end_stream()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::Body;
+    use alloc::vec::Vec;
+    use core::future::{Pending, Ready, pending, ready};
+    use std::sync::Mutex;
+
+    struct Hello;
+
+    impl Work<(), Request<Body>> for Hello {
+        type Output = Response<Body>;
+        type Error = Error;
+        type Future<'a>
+            = Ready<Result<Response<Body>, Error>>
+        where
+            Self: 'a;
+
+        fn call<'a>(&'a self, _context: &'a (), _req: Request<Body>) -> Self::Future<'a> {
+            let mut resp = Response::new(Body::from(String::from("Hello, World")));
+            *resp.status_mut() = StatusCode::CREATED;
+            ready(Ok(resp))
+        }
+    }
+
+    struct Missing;
+
+    impl Work<(), Request<Body>> for Missing {
+        type Output = Response<Body>;
+        type Error = Error;
+        type Future<'a>
+            = Ready<Result<Response<Body>, Error>>
+        where
+            Self: 'a;
+
+        fn call<'a>(&'a self, _context: &'a (), _req: Request<Body>) -> Self::Future<'a> {
+            ready(Err(Error::not_found()))
+        }
+    }
+
+    struct Hang;
+
+    impl Work<(), Request<Body>> for Hang {
+        type Output = Response<Body>;
+        type Error = Error;
+        type Future<'a>
+            = Pending<Result<Response<Body>, Error>>
+        where
+            Self: 'a;
+
+        fn call<'a>(&'a self, _context: &'a (), _req: Request<Body>) -> Self::Future<'a> {
+            pending()
+        }
+    }
+
+    fn logged(format: LogFormat) -> (AccessLog, Arc<Mutex<Vec<String>>>) {
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let sink = lines.clone();
+        let log = AccessLog::new(format).writer(move |line| {
+            sink.lock().unwrap().push(String::from(line));
+        });
+        (log, lines)
+    }
+
+    fn request() -> Request<Body> {
+        let mut req = Request::builder()
+            .method(Method::POST)
+            .uri("/items?page=2")
+            .header(USER_AGENT, "curl/8.0")
+            .body(Body::empty())
+            .unwrap();
+        req.extensions_mut()
+            .insert(PeerAddr("192.0.2.7:4000".parse().unwrap()));
+        req
+    }
+
+    #[tokio::test]
+    async fn combined() {
+        let (log, lines) = logged(LogFormat::Combined);
+        let work = Middleware::<(), Request<Body>, _>::wrap(&log, Hello);
+
+        let resp = work.call(&(), request()).await.unwrap();
+        assert!(lines.lock().unwrap().is_empty());
+
+        let body = crate::body::to_bytes(resp.into_body()).await.unwrap();
+        assert_eq!(body, "Hello, World");
+
+        let lines = lines.lock().unwrap();
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].starts_with("192.0.2.7 - - ["), "{}", lines[0]);
+        assert!(
+            lines[0].ends_with("] \"POST /items?page=2 HTTP/1.1\" 201 12 \"-\" \"curl/8.0\""),
+            "{}",
+            lines[0]
+        );
+    }
+
+    #[tokio::test]
+    async fn json() {
+        let (log, lines) = logged(LogFormat::Json);
+        let work = Middleware::<(), Request<Body>, _>::wrap(&log, Hello);
+
+        // Dropping the body unread still logs
+        drop(work.call(&(), request()).await.unwrap());
+
+        let lines = lines.lock().unwrap();
+        let line: serde_json::Value = serde_json::from_str(&lines[0]).unwrap();
+        assert_eq!(line["client"], "192.0.2.7");
+        assert_eq!(line["method"], "POST");
+        assert_eq!(line["path"], "/items?page=2");
+        assert_eq!(line["status"], 201);
+        assert_eq!(line["bytes"], 0);
+        assert!(line["latency_ms"].is_number());
+    }
+
+    #[tokio::test]
+    async fn error_status() {
+        let (log, lines) = logged(LogFormat::Common);
+        let work = Middleware::<(), Request<Body>, _>::wrap(&log, Missing);
+
+        let err = work.call(&(), request()).await.unwrap_err();
+        assert_eq!(err.status(), StatusCode::NOT_FOUND);
+
+        let lines = lines.lock().unwrap();
+        assert_eq!(lines.len(), 1);
+        assert!(
+            lines[0].ends_with("] \"POST /items?page=2 HTTP/1.1\" 404 0"),
+            "{}",
+            lines[0]
+        );
+    }
+
+    #[test]
+    fn cancelled() {
+        let (log, lines) = logged(LogFormat::Common);
+        let work = Middleware::<(), Request<Body>, _>::wrap(&log, Hang);
+
+        // The handler never answered, so there's no status to log
+        drop(work.call(&(), request()));
+
+        let lines = lines.lock().unwrap();
+        assert_eq!(lines.len(), 1);
+        assert!(
+            lines[0].ends_with("] \"POST /items?page=2 HTTP/1.1\" - 0"),
+            "{}",
+            lines[0]
+        );
+
+        let (log, lines) = logged(LogFormat::Json);
+        let work = Middleware::<(), Request<Body>, _>::wrap(&log, Hang);
+        drop(work.call(&(), request()));
+
+        let line: serde_json::Value = serde_json::from_str(&lines.lock().unwrap()[0]).unwrap();
+        assert!(line["status"].is_null());
+    }
+
+    #[test]
+    fn time() {
+        let time = UNIX_EPOCH + Duration::from_secs(971_186_136);
+        assert_eq!(clf_time(time), "10/Oct/2000:13:55:36 +0000");
+        assert_eq!(clf_time(UNIX_EPOCH), "01/Jan/1970:00:00:00 +0000");
+    }
+}
@@ -0,0 +1,161 @@
+use crate::{Error, rejection::Unauthorized, util::constant_time_eq};
+use alloc::{format, string::String, vec::Vec};
+use core::future::{Ready, ready};
+use http::{HeaderName, HeaderValue, request::Parts};
+
+use super::Authenticator;
+
+/// Authentication with a key sent in a header, `x-api-key` by default.
+///
+/// `verify` gets the key, and returns the principal when it is valid.
+#[derive(Debug, Clone)]
+pub struct ApiKey<F> {
+    header: HeaderName,
+    verify: F,
+}
+
+impl<F> ApiKey<F> {
+    pub fn new(verify: F) -> ApiKey<F> {
+        ApiKey {
+            header: HeaderName::from_static("x-api-key"),
+            verify,
+        }
+    }
+
+    pub fn header(mut self, header: HeaderName) -> Self {
+        self.header = header;
+        self
+    }
+}
+
+impl<P> ApiKey<KeyList<P>> {
+    /// Accept a fixed set of keys, each mapping to a principal.
+    pub fn keys<K, I>(keys: I) -> ApiKey<KeyList<P>>
+    where
+        I: IntoIterator<Item = (K, P)>,
+        K: Into<String>,
+    {
+        ApiKey::new(KeyList(
+            keys.into_iter().map(|(key, p)| (key.into(), p)).collect(),
+        ))
+    }
+}
+
+/// A fixed set of keys, see [`ApiKey::keys`].
+#[derive(Debug, Clone)]
+pub struct KeyList<P>(Vec<(String, P)>);
+
+impl<P: Clone> KeyList<P> {
+    fn find(&self, key: &str) -> Option<P> {
+        // Compare every key, so the time taken doesn't reveal which matched
+        self.0.iter().fold(None, |found, (candidate, principal)| {
+            if constant_time_eq(candidate.as_bytes(), key.as_bytes()) {
+                Some(principal.clone())
+            } else {
+                found
+            }
+        })
+    }
+}
+
+/// Verifies keys for [`ApiKey`].
+pub trait VerifyKey {
+    type Principal;
+
+    fn verify(&self, key: &str) -> Option<Self::Principal>;
+}
+
+impl<F, P> VerifyKey for F
+where
+    F: Fn(&str) -> Option<P>,
+{
+    type Principal = P;
+
+    fn verify(&self, key: &str) -> Option<P> {
+        self(key)
+    }
+}
+
+impl<P: Clone> VerifyKey for KeyList<P> {
+    type Principal = P;
+
+    fn verify(&self, key: &str) -> Option<P> {
+        self.find(key)
+    }
+}
+
+impl<F> Authenticator for ApiKey<F>
+where
+    F: VerifyKey,
+    F::Principal: Clone + Send + Sync + 'static,
+{
+    type Principal = F::Principal;
+
+    type Future<'a>
+        = Ready<Result<Option<F::Principal>, Error>>
+    where
+        Self: 'a;
+
+    fn authenticate<'a>(&'a self, parts: &Parts) -> Self::Future<'a> {
+        let Some(key) = parts.headers.get(&self.header) else {
+            return ready(Ok(None));
+        };
+
+        let principal = key.to_str().ok().and_then(|key| self.verify.verify(key));
+
+        ready(match principal {
+            Some(principal) => Ok(Some(principal)),
+            None => Err(Unauthorized::new(self.challenge())
+                .reason("invalid api key")
+                .into()),
+        })
+    }
+
+    fn challenge(&self) -> Option<HeaderValue> {
+        HeaderValue::try_from(format!("ApiKey header=\"{}\"", self.header)).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::tests::parts;
+
+    fn with_key(key: &str) -> Parts {
+        let mut parts = parts(None);
+        parts.headers.insert("x-api-key", key.parse().unwrap());
+        parts
+    }
+
+    #[test]
+    fn keys() {
+        let auth = ApiKey::keys([("first", 1), ("second", 2)]);
+
+        assert_eq!(
+            auth.authenticate(&with_key("second")).into_inner().unwrap(),
+            Some(2)
+        );
+        assert_eq!(auth.authenticate(&parts(None)).into_inner().unwrap(), None);
+        assert!(auth.authenticate(&with_key("third")).into_inner().is_err());
+    }
+
+    #[test]
+    fn custom_header() {
+        let auth = ApiKey::new(|key: &str| (key == "secret").then_some("service"))
+            .header(HeaderName::from_static("x-token"));
+
+        let mut request = parts(None);
+        request.headers.insert("x-token", "secret".parse().unwrap());
+        assert_eq!(
+            auth.authenticate(&request).into_inner().unwrap(),
+            Some("service")
+        );
+
+        // The default header is ignored
+        assert_eq!(
+            auth.authenticate(&with_key("secret")).into_inner().unwrap(),
+            None
+        );
+        assert_eq!(auth.challenge().unwrap(), "ApiKey header=\"x-token\"");
+    }
+}
@@ -0,0 +1,126 @@
+use crate::{Error, rejection::Unauthorized};
+use alloc::{format, string::String};
+use base64::engine::{Engine as _, general_purpose::STANDARD};
+use core::future::{Ready, ready};
+use http::{HeaderValue, request::Parts};
+
+use super::{Authenticator, credentials};
+
+/// HTTP Basic authentication (RFC 7617).
+///
+/// `verify` gets the user name and password, and returns the principal when
+/// they are valid.
+#[derive(Debug, Clone)]
+pub struct Basic<F> {
+    challenge: HeaderValue,
+    verify: F,
+}
+
+impl<F> Basic<F> {
+    /// # Panics
+    ///
+    /// If `realm` can't be sent in a header, like when it contains a
+    /// newline.
+    pub fn new(realm: &str, verify: F) -> Basic<F> {
+        let challenge =
+            HeaderValue::try_from(format!("Basic realm=\"{realm}\", charset=\"UTF-8\""))
+                .expect("realm is a valid header value");
+
+        Basic { challenge, verify }
+    }
+
+    fn reject(&self, reason: &'static str) -> Error {
+        Unauthorized::new(Some(self.challenge.clone()))
+            .reason(reason)
+            .into()
+    }
+}
+
+impl<F, P> Authenticator for Basic<F>
+where
+    F: Fn(&str, &str) -> Option<P>,
+    P: Clone + Send + Sync + 'static,
+{
+    type Principal = P;
+
+    type Future<'a>
+        = Ready<Result<Option<P>, Error>>
+    where
+        Self: 'a;
+
+    fn authenticate<'a>(&'a self, parts: &Parts) -> Self::Future<'a> {
+        let Some(credentials) = credentials(parts, "Basic") else {
+            return ready(Ok(None));
+        };
+
+        let Some((user, password)) = STANDARD
+            .decode(credentials)
+            .ok()
+            .and_then(|decoded| String::from_utf8(decoded).ok())
+            .and_then(|decoded| {
+                decoded
+                    .split_once(':')
+                    .map(|(user, password)| (String::from(user), String::from(password)))
+            })
+        else {
+            return ready(Err(self.reject("malformed credentials")));
+        };
+
+        ready(match (self.verify)(&user, &password) {
+            Some(principal) => Ok(Some(principal)),
+            None => Err(self.reject("invalid credentials")),
+        })
+    }
+
+    fn challenge(&self) -> Option<HeaderValue> {
+        Some(self.challenge.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::tests::parts;
+    use http::StatusCode;
+
+    #[test]
+    fn basic() {
+        let auth = Basic::new("admin", |user: &str, password: &str| {
+            (user == "rasmus" && password == "se:cret").then_some(1u32)
+        });
+
+        let encoded = STANDARD.encode("rasmus:se:cret");
+        let principal = auth
+            .authenticate(&parts(Some(&format!("Basic {encoded}"))))
+            .into_inner()
+            .unwrap();
+        assert_eq!(principal, Some(1));
+
+        assert_eq!(auth.authenticate(&parts(None)).into_inner().unwrap(), None);
+        assert_eq!(
+            auth.authenticate(&parts(Some("Bearer abc")))
+                .into_inner()
+                .unwrap(),
+            None
+        );
+
+        let err = auth
+            .authenticate(&parts(Some("Basic !!!")))
+            .into_inner()
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::UNAUTHORIZED);
+
+        let encoded = STANDARD.encode("rasmus:wrong");
+        let err = auth
+            .authenticate(&parts(Some(&format!("Basic {encoded}"))))
+            .into_inner()
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    #[should_panic]
+    fn newline_in_realm() {
+        let _ = Basic::new("a\nb", |_: &str, _: &str| Some(()));
+    }
+}
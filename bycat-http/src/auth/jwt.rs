@@ -0,0 +1,378 @@
+use crate::{Error, rejection::Unauthorized};
+use alloc::{borrow::Cow, format, string::String, sync::Arc, vec::Vec};
+use core::{
+    future::{Ready, ready},
+    marker::PhantomData,
+};
+use http::{HeaderValue, request::Parts};
+use jsonwebtoken::{
+    DecodingKey, Validation,
+    jwk::{AlgorithmParameters, JwkSet},
+};
+use serde::de::DeserializeOwned;
+use std::path::Path;
+
+pub use jsonwebtoken::Algorithm;
+
+use super::{Authenticator, credentials};
+
+const HMAC: &[Algorithm] = &[Algorithm::HS256, Algorithm::HS384, Algorithm::HS512];
+
+const RSA: &[Algorithm] = &[
+    Algorithm::RS256,
+    Algorithm::RS384,
+    Algorithm::RS512,
+    Algorithm::PS256,
+    Algorithm::PS384,
+    Algorithm::PS512,
+];
+
+const EC: &[Algorithm] = &[Algorithm::ES256, Algorithm::ES384];
+
+const ED: &[Algorithm] = &[Algorithm::EdDSA];
+
+struct Key {
+    id: Option<String>,
+    key: DecodingKey,
+    /// The algorithms of the key type, since a token may only be validated
+    /// against algorithms of the same type as the key
+    algorithms: &'static [Algorithm],
+}
+
+/// Bearer token authentication (RFC 6750) with JSON web tokens.
+///
+/// The claims are deserialized into `T`, which becomes the principal. The
+/// signature and `exp` are always validated, `iss` and `aud` when configured.
+pub struct Jwt<T> {
+    keys: Arc<[Key]>,
+    validation: Validation,
+    realm: Cow<'static, str>,
+    claims: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for Jwt<T> {
+    fn clone(&self) -> Self {
+        Jwt {
+            keys: self.keys.clone(),
+            validation: self.validation.clone(),
+            realm: self.realm.clone(),
+            claims: PhantomData,
+        }
+    }
+}
+
+impl<T> Jwt<T> {
+    fn new(keys: Vec<Key>) -> Jwt<T> {
+        let mut validation = Validation::default();
+        validation.algorithms = [HMAC, RSA, EC, ED].concat();
+        validation.validate_aud = false;
+
+        Jwt {
+            keys: keys.into(),
+            validation,
+            realm: Cow::Borrowed("api"),
+            claims: PhantomData,
+        }
+    }
+
+    /// Tokens signed with a shared secret (`HS256`, `HS384`, `HS512`).
+    pub fn hmac(secret: &[u8]) -> Jwt<T> {
+        Jwt::new(alloc::vec![Key {
+            id: None,
+            key: DecodingKey::from_secret(secret),
+            algorithms: HMAC,
+        }])
+    }
+
+    /// Tokens signed with an RSA key (`RS*`, `PS*`), from a PEM encoded public key.
+    pub fn rsa_pem(pem: &[u8]) -> Result<Jwt<T>, Error> {
+        let key = DecodingKey::from_rsa_pem(pem).map_err(Error::custom)?;
+        Ok(Jwt::new(alloc::vec![Key {
+            id: None,
+            key,
+            algorithms: RSA,
+        }]))
+    }
+
+    /// Tokens signed with an elliptic curve key (`ES256`, `ES384`), from a
+    /// PEM encoded public key.
+    pub fn ec_pem(pem: &[u8]) -> Result<Jwt<T>, Error> {
+        let key = DecodingKey::from_ec_pem(pem).map_err(Error::custom)?;
+        Ok(Jwt::new(alloc::vec![Key {
+            id: None,
+            key,
+            algorithms: EC,
+        }]))
+    }
+
+    /// Tokens signed with any of the keys of a JSON web key set.
+    ///
+    /// Tokens are matched to keys by their `kid`, which may only be left out
+    /// when the set has a single key.
+    pub fn jwks(jwks: &str) -> Result<Jwt<T>, Error> {
+        let set: JwkSet = serde_json::from_str(jwks).map_err(Error::custom)?;
+
+        let keys = set
+            .keys
+            .iter()
+            .map(|jwk| {
+                Ok(Key {
+                    id: jwk.common.key_id.clone(),
+                    key: DecodingKey::from_jwk(jwk).map_err(Error::custom)?,
+                    algorithms: match jwk.algorithm {
+                        AlgorithmParameters::OctetKey(_) => HMAC,
+                        AlgorithmParameters::RSA(_) => RSA,
+                        AlgorithmParameters::EllipticCurve(_) => EC,
+                        AlgorithmParameters::OctetKeyPair(_) => ED,
+                    },
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        if keys.is_empty() {
+            return Err(Error::custom("JWK set has no keys"));
+        }
+
+        Ok(Jwt::new(keys))
+    }
+
+    /// Load a JSON web key set from a file, see [`Jwt::jwks`].
+    pub fn jwks_file(path: impl AsRef<Path>) -> Result<Jwt<T>, Error> {
+        let jwks = std::fs::read_to_string(path).map_err(Error::custom)?;
+        Jwt::jwks(&jwks)
+    }
+
+    /// Restrict the accepted algorithms. Defaults to all algorithms matching
+    /// the type of the key.
+    pub fn algorithms(mut self, algorithms: impl IntoIterator<Item = Algorithm>) -> Self {
+        self.validation.algorithms = algorithms.into_iter().collect();
+        self
+    }
+
+    /// Require `iss` to be one of `issuers`.
+    pub fn issuer<I>(mut self, issuers: impl IntoIterator<Item = I>) -> Self
+    where
+        I: ToString,
+    {
+        let issuers = issuers.into_iter().collect::<Vec<_>>();
+        self.validation.set_issuer(&issuers);
+        self
+    }
+
+    /// Require `aud` to contain one of `audiences`.
+    pub fn audience<I>(mut self, audiences: impl IntoIterator<Item = I>) -> Self
+    where
+        I: ToString,
+    {
+        let audiences = audiences.into_iter().collect::<Vec<_>>();
+        self.validation.set_audience(&audiences);
+        self.validation.validate_aud = true;
+        self
+    }
+
+    /// Clock skew allowed when checking `exp` and `nbf`, in seconds. Defaults to 60.
+    pub fn leeway(mut self, seconds: u64) -> Self {
+        self.validation.leeway = seconds;
+        self
+    }
+
+    /// Also check `nbf`, when present.
+    pub fn validate_nbf(mut self, validate: bool) -> Self {
+        self.validation.validate_nbf = validate;
+        self
+    }
+
+    pub fn realm(mut self, realm: impl Into<Cow<'static, str>>) -> Self {
+        self.realm = realm.into();
+        self
+    }
+
+    fn key(&self, token: &str) -> Result<&Key, Error> {
+        let header = jsonwebtoken::decode_header(token).map_err(|err| self.reject(err))?;
+
+        let key = match (&header.kid, &*self.keys) {
+            (_, [key]) if key.id.is_none() || key.id == header.kid => Some(key),
+            (Some(kid), keys) => keys.iter().find(|key| key.id.as_ref() == Some(kid)),
+            (None, _) => None,
+        };
+
+        key.ok_or_else(|| self.reject("unknown signing key"))
+    }
+
+    fn reject(&self, reason: impl ToString) -> Error {
+        let description = reason.to_string().replace('"', "'");
+        let challenge = HeaderValue::try_from(format!(
+            "Bearer realm=\"{}\", error=\"invalid_token\", error_description=\"{description}\"",
+            self.realm
+        ))
+        .ok();
+
+        Unauthorized::new(challenge)
+            .reason(format!("invalid token: {description}"))
+            .into()
+    }
+}
+
+impl<T> Authenticator for Jwt<T>
+where
+    T: DeserializeOwned + Clone + Send + Sync + 'static,
+{
+    type Principal = T;
+
+    type Future<'a>
+        = Ready<Result<Option<T>, Error>>
+    where
+        Self: 'a;
+
+    fn authenticate<'a>(&'a self, parts: &Parts) -> Self::Future<'a> {
+        let Some(token) = credentials(parts, "Bearer") else {
+            return ready(Ok(None));
+        };
+
+        ready(self.key(token).and_then(|key| {
+            let mut validation = self.validation.clone();
+            validation
+                .algorithms
+                .retain(|algorithm| key.algorithms.contains(algorithm));
+
+            if validation.algorithms.is_empty() {
+                return Err(self.reject("algorithm not allowed"));
+            }
+
+            jsonwebtoken::decode::<T>(token, &key.key, &validation)
+                .map(|data| Some(data.claims))
+                .map_err(|err| self.reject(err))
+        }))
+    }
+
+    fn challenge(&self) -> Option<HeaderValue> {
+        HeaderValue::try_from(format!("Bearer realm=\"{}\"", self.realm)).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::tests::parts;
+    use base64::engine::{Engine as _, general_purpose::URL_SAFE_NO_PAD};
+    use http::header::WWW_AUTHENTICATE;
+    use jsonwebtoken::{EncodingKey, Header, encode};
+    use serde::{Deserialize, Serialize};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+    struct Claims {
+        sub: String,
+        iss: String,
+        aud: String,
+        exp: u64,
+    }
+
+    fn claims(exp_offset: i64) -> Claims {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        Claims {
+            sub: "rasmus".into(),
+            iss: "https://auth.example.com".into(),
+            aud: "admin".into(),
+            exp: now.saturating_add_signed(exp_offset),
+        }
+    }
+
+    fn token(header: Header, claims: &Claims, secret: &[u8]) -> String {
+        encode(&header, claims, &EncodingKey::from_secret(secret)).unwrap()
+    }
+
+    fn authenticate(auth: &Jwt<Claims>, token: &str) -> Result<Option<Claims>, Error> {
+        auth.authenticate(&parts(Some(&format!("Bearer {token}"))))
+            .into_inner()
+    }
+
+    #[test]
+    fn hmac() {
+        let auth = Jwt::<Claims>::hmac(b"secret")
+            .issuer(["https://auth.example.com"])
+            .audience(["admin"]);
+
+        let valid = claims(3600);
+        assert_eq!(
+            authenticate(&auth, &token(Header::default(), &valid, b"secret")).unwrap(),
+            Some(valid.clone())
+        );
+
+        // Expired
+        let expired = claims(-3600);
+        assert!(authenticate(&auth, &token(Header::default(), &expired, b"secret")).is_err());
+
+        // Wrong secret
+        assert!(authenticate(&auth, &token(Header::default(), &valid, b"other")).is_err());
+
+        // Wrong issuer and audience
+        let issuer = Claims {
+            iss: "https://evil.example.com".into(),
+            ..valid.clone()
+        };
+        assert!(authenticate(&auth, &token(Header::default(), &issuer, b"secret")).is_err());
+
+        let audience = Claims {
+            aud: "public".into(),
+            ..valid.clone()
+        };
+        assert!(authenticate(&auth, &token(Header::default(), &audience, b"secret")).is_err());
+
+        // No credentials
+        assert_eq!(auth.authenticate(&parts(None)).into_inner().unwrap(), None);
+    }
+
+    #[test]
+    fn invalid_token_challenge() {
+        let auth = Jwt::<Claims>::hmac(b"secret").realm("admin");
+        let err = authenticate(&auth, "not-a-token").unwrap_err();
+
+        let resp: http::Response<crate::body::Body> = crate::IntoResponse::into_response(err);
+        let challenge = resp.headers()[WWW_AUTHENTICATE].to_str().unwrap();
+        assert!(challenge.starts_with("Bearer realm=\"admin\", error=\"invalid_token\""));
+    }
+
+    #[test]
+    fn jwks_file() {
+        let path = std::env::temp_dir().join(format!(
+            "bycat-jwks-{}.json",
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+
+        std::fs::write(
+            &path,
+            format!(
+                r#"{{"keys":[{{"kty":"oct","kid":"first","alg":"HS256","k":"{}"}},{{"kty":"oct","kid":"second","alg":"HS256","k":"{}"}}]}}"#,
+                URL_SAFE_NO_PAD.encode(b"first-secret"),
+                URL_SAFE_NO_PAD.encode(b"second-secret"),
+            ),
+        )
+        .unwrap();
+
+        let auth = Jwt::<Claims>::jwks_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let valid = claims(3600);
+        let mut header = Header::default();
+        header.kid = Some("second".into());
+
+        assert_eq!(
+            authenticate(&auth, &token(header.clone(), &valid, b"second-secret")).unwrap(),
+            Some(valid.clone())
+        );
+
+        // Signed with another key than `kid` says
+        assert!(authenticate(&auth, &token(header, &valid, b"first-secret")).is_err());
+
+        // No `kid` with several keys
+        assert!(authenticate(&auth, &token(Header::default(), &valid, b"first-secret")).is_err());
+    }
+}
@@ -0,0 +1,364 @@
+mod api_key;
+mod basic;
+#[cfg(feature = "auth-jwt")]
+mod jwt;
+
+use crate::{
+    Error, FromRequestParts,
+    rejection::{Forbidden, Unauthorized},
+};
+use alloc::sync::Arc;
+use bycat::{Middleware, Work};
+use core::{
+    future::Ready,
+    marker::PhantomData,
+    pin::Pin,
+    task::{Poll, ready},
+};
+use http::{HeaderValue, Request, header::AUTHORIZATION, request::Parts};
+use pin_project_lite::pin_project;
+
+pub use self::{
+    api_key::{ApiKey, KeyList, VerifyKey},
+    basic::Basic,
+};
+
+#[cfg(feature = "auth-jwt")]
+pub use self::jwt::{Algorithm, Jwt};
+
+/// Resolves the principal making a request from its credentials.
+pub trait Authenticator {
+    type Principal: Clone + Send + Sync + 'static;
+
+    type Future<'a>: Future<Output = Result<Option<Self::Principal>, Error>>
+    where
+        Self: 'a;
+
+    /// Returns `Ok(None)` when the request carries no credentials for this
+    /// scheme, and an [`Unauthorized`] error when they are invalid.
+    fn authenticate<'a>(&'a self, parts: &Parts) -> Self::Future<'a>;
+
+    /// The `WWW-Authenticate` challenge for requests without credentials.
+    fn challenge(&self) -> Option<HeaderValue> {
+        None
+    }
+}
+
+impl<A> Authenticator for Arc<A>
+where
+    A: Authenticator,
+{
+    type Principal = A::Principal;
+
+    type Future<'a>
+        = A::Future<'a>
+    where
+        Self: 'a;
+
+    fn authenticate<'a>(&'a self, parts: &Parts) -> Self::Future<'a> {
+        (**self).authenticate(parts)
+    }
+
+    fn challenge(&self) -> Option<HeaderValue> {
+        (**self).challenge()
+    }
+}
+
+/// The credentials of the `Authorization` header, if it uses `scheme`.
+pub fn credentials<'a>(parts: &'a Parts, scheme: &str) -> Option<&'a str> {
+    let header = parts.headers.get(AUTHORIZATION)?.to_str().ok()?;
+    let (found, credentials) = header.split_once(' ')?;
+    found
+        .eq_ignore_ascii_case(scheme)
+        .then(|| credentials.trim())
+}
+
+/// The authenticated principal, inserted by [`RequireAuth`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Auth<T>(pub T);
+
+impl<C, T> FromRequestParts<C> for Auth<T>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    type Future<'a>
+        = Ready<Result<Self, Error>>
+    where
+        C: 'a;
+
+    fn from_request_parts<'a>(parts: &'a mut Parts, _state: &'a C) -> Self::Future<'a> {
+        core::future::ready(
+            parts
+                .extensions
+                .get::<Auth<T>>()
+                .cloned()
+                .ok_or_else(|| Unauthorized::new(None).into()),
+        )
+    }
+}
+
+type Allow<P> = Arc<dyn Fn(&P) -> bool + Send + Sync>;
+
+/// Middleware rejecting requests that can't be authenticated.
+///
+/// Requests without credentials get a `401` with the challenge of the
+/// authenticator, and principals refused by [`RequireAuth::allow`] a `403`.
+pub struct RequireAuth<A: Authenticator> {
+    auth: Arc<A>,
+    allow: Option<Allow<A::Principal>>,
+}
+
+impl<A: Authenticator> Clone for RequireAuth<A> {
+    fn clone(&self) -> Self {
+        RequireAuth {
+            auth: self.auth.clone(),
+            allow: self.allow.clone(),
+        }
+    }
+}
+
+impl<A: Authenticator> RequireAuth<A> {
+    pub fn new(auth: A) -> RequireAuth<A> {
+        RequireAuth {
+            auth: Arc::new(auth),
+            allow: None,
+        }
+    }
+
+    /// Only let principals matching `allow` through.
+    pub fn allow<F>(mut self, allow: F) -> Self
+    where
+        F: Fn(&A::Principal) -> bool + Send + Sync + 'static,
+    {
+        self.allow = Some(Arc::new(allow));
+        self
+    }
+}
+
+impl<C, B, T, A> Middleware<C, Request<B>, T> for RequireAuth<A>
+where
+    A: Authenticator,
+    T: Work<C, Request<B>>,
+    T::Error: Into<Error>,
+{
+    type Work = RequireAuthWork<C, B, T, A>;
+
+    fn wrap(&self, handler: T) -> Self::Work {
+        RequireAuthWork {
+            work: handler,
+            auth: self.clone(),
+            req: PhantomData,
+        }
+    }
+}
+
+pub struct RequireAuthWork<C, B, T, A: Authenticator> {
+    work: T,
+    auth: RequireAuth<A>,
+    req: PhantomData<(C, B)>,
+}
+
+impl<C, B, T: Clone, A: Authenticator> Clone for RequireAuthWork<C, B, T, A> {
+    fn clone(&self) -> Self {
+        RequireAuthWork {
+            work: self.work.clone(),
+            auth: self.auth.clone(),
+            req: PhantomData,
+        }
+    }
+}
+
+impl<C, B, T, A> Work<C, Request<B>> for RequireAuthWork<C, B, T, A>
+where
+    A: Authenticator,
+    T: Work<C, Request<B>>,
+    T::Error: Into<Error>,
+{
+    type Output = T::Output;
+
+    type Error = Error;
+
+    type Future<'a>
+        = RequireAuthFuture<'a, C, B, T, A>
+    where
+        Self: 'a,
+        C: 'a;
+
+    fn call<'a>(&'a self, context: &'a C, req: Request<B>) -> Self::Future<'a> {
+        let (parts, body) = req.into_parts();
+        let future = self.auth.auth.authenticate(&parts);
+
+        RequireAuthFuture {
+            state: RequireAuthState::Authenticate {
+                future,
+                req: Some((parts, body)),
+                context,
+                work: &self.work,
+            },
+            auth: &self.auth,
+        }
+    }
+}
+
+pin_project! {
+    #[project = RequireAuthStateProj]
+    enum RequireAuthState<'a, C, B, T: 'a, A: 'a>
+    where
+        T: Work<C, Request<B>>,
+        A: Authenticator,
+    {
+        Authenticate {
+            #[pin]
+            future: A::Future<'a>,
+            req: Option<(Parts, B)>,
+            context: &'a C,
+            work: &'a T,
+        },
+        Call {
+            #[pin]
+            future: T::Future<'a>,
+        },
+    }
+}
+
+pin_project! {
+    pub struct RequireAuthFuture<'a, C, B, T, A>
+    where
+        T: Work<C, Request<B>>,
+        A: Authenticator,
+    {
+        #[pin]
+        state: RequireAuthState<'a, C, B, T, A>,
+        auth: &'a RequireAuth<A>,
+    }
+}
+
+impl<'a, C, B, T, A> Future for RequireAuthFuture<'a, C, B, T, A>
+where
+    A: Authenticator,
+    T: Work<C, Request<B>>,
+    T::Error: Into<Error>,
+{
+    type Output = Result<T::Output, Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut core::task::Context<'_>) -> Poll<Self::Output> {
+        loop {
+            let mut this = self.as_mut().project();
+
+            match this.state.as_mut().project() {
+                RequireAuthStateProj::Authenticate {
+                    future,
+                    req,
+                    context,
+                    work,
+                } => {
+                    let principal = match ready!(future.poll(cx))? {
+                        Some(principal) => principal,
+                        None => {
+                            return Poll::Ready(Err(
+                                Unauthorized::new(this.auth.auth.challenge()).into()
+                            ));
+                        }
+                    };
+
+                    if let Some(allow) = &this.auth.allow
+                        && !allow(&principal)
+                    {
+                        return Poll::Ready(Err(Forbidden.into()));
+                    }
+
+                    let (mut parts, body) = req.take().unwrap();
+                    parts.extensions.insert(Auth(principal));
+
+                    let future = work.call(*context, Request::from_parts(parts, body));
+                    this.state.set(RequireAuthState::Call { future });
+                }
+                RequireAuthStateProj::Call { future } => {
+                    return Poll::Ready(ready!(future.poll(cx)).map_err(Into::into));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{body::Body, handler};
+    use alloc::string::String;
+    use bycat::prelude::WorkExt;
+    use http::{
+        Response, StatusCode,
+        header::{AUTHORIZATION, WWW_AUTHENTICATE},
+    };
+
+    pub(crate) fn parts(authorization: Option<&str>) -> Parts {
+        let mut req = Request::builder();
+        if let Some(authorization) = authorization {
+            req = req.header(AUTHORIZATION, authorization);
+        }
+        req.body(()).unwrap().into_parts().0
+    }
+
+    fn basic() -> Basic<impl Fn(&str, &str) -> Option<String> + Send + Sync> {
+        Basic::new("admin", |user: &str, password: &str| {
+            (user == "rasmus" && password == "secret").then(|| String::from(user))
+        })
+    }
+
+    async fn call<W>(work: &W, authorization: Option<&str>) -> Result<Response<Body>, Error>
+    where
+        W: Work<(), Request<Body>, Output = Response<Body>, Error = Error>,
+    {
+        let mut req = Request::builder();
+        if let Some(authorization) = authorization {
+            req = req.header(AUTHORIZATION, authorization);
+        }
+        work.call(&(), req.body(Body::empty()).unwrap()).await
+    }
+
+    #[test]
+    fn credentials_scheme() {
+        let parts = parts(Some("bearer  abc "));
+        assert_eq!(credentials(&parts, "Bearer"), Some("abc"));
+        assert_eq!(credentials(&parts, "Basic"), None);
+    }
+
+    #[tokio::test]
+    async fn require_auth() {
+        let work = handler(|Auth(user): Auth<String>| async move { user })
+            .wrap(RequireAuth::new(basic()).allow(|user: &String| user != "banned"));
+
+        // cmFzbXVzOnNlY3JldA== is rasmus:secret
+        let resp = call(&work, Some("Basic cmFzbXVzOnNlY3JldA=="))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let err = call(&work, None).await.unwrap_err();
+        assert_eq!(err.status(), StatusCode::UNAUTHORIZED);
+
+        let resp: Response<Body> = crate::IntoResponse::into_response(err);
+        assert_eq!(
+            resp.headers()[WWW_AUTHENTICATE],
+            "Basic realm=\"admin\", charset=\"UTF-8\""
+        );
+
+        // cmFzbXVzOndyb25n is rasmus:wrong
+        let err = call(&work, Some("Basic cmFzbXVzOndyb25n"))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn forbidden() {
+        let work = handler(|Auth(user): Auth<String>| async move { user })
+            .wrap(RequireAuth::new(basic()).allow(|_: &String| false));
+
+        let err = call(&work, Some("Basic cmFzbXVzOnNlY3JldA=="))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::FORBIDDEN);
+    }
+}
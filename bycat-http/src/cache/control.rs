@@ -0,0 +1,91 @@
+use core::time::Duration;
+use http::{HeaderMap, header::CACHE_CONTROL};
+
+/// The `Cache-Control` directives the cache acts on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct CacheControl {
+    pub no_store: bool,
+    pub no_cache: bool,
+    pub private: bool,
+    pub public: bool,
+    pub max_age: Option<Duration>,
+    pub s_maxage: Option<Duration>,
+    pub stale_while_revalidate: Option<Duration>,
+}
+
+impl CacheControl {
+    pub fn parse(headers: &HeaderMap) -> CacheControl {
+        let mut control = CacheControl::default();
+
+        let directives = headers
+            .get_all(CACHE_CONTROL)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','));
+
+        for directive in directives {
+            let (name, value) = match directive.split_once('=') {
+                Some((name, value)) => (name.trim(), Some(value.trim().trim_matches('"'))),
+                None => (directive.trim(), None),
+            };
+            let seconds = || {
+                value
+                    .and_then(|value| value.parse().ok())
+                    .map(Duration::from_secs)
+            };
+
+            match name.to_ascii_lowercase().as_str() {
+                "no-store" => control.no_store = true,
+                "no-cache" => control.no_cache = true,
+                "private" => control.private = true,
+                "public" => control.public = true,
+                "max-age" => control.max_age = seconds(),
+                "s-maxage" => control.s_maxage = seconds(),
+                "stale-while-revalidate" => control.stale_while_revalidate = seconds(),
+                _ => {}
+            }
+        }
+
+        control
+    }
+
+    /// Whether a shared cache may store the response.
+    pub fn is_storable(&self) -> bool {
+        !(self.no_store || self.no_cache || self.private)
+    }
+
+    /// How long a response is fresh in a shared cache.
+    pub fn freshness(&self) -> Option<Duration> {
+        self.s_maxage.or(self.max_age)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(value: &'static str) -> CacheControl {
+        let mut headers = HeaderMap::new();
+        headers.insert(CACHE_CONTROL, value.parse().unwrap());
+        CacheControl::parse(&headers)
+    }
+
+    #[test]
+    fn directives() {
+        let control = parse("public, max-age=60, s-maxage=\"120\", stale-while-revalidate=30");
+        assert!(control.public);
+        assert_eq!(control.freshness(), Some(Duration::from_secs(120)));
+        assert_eq!(
+            control.stale_while_revalidate,
+            Some(Duration::from_secs(30))
+        );
+
+        assert!(control.is_storable());
+
+        assert_eq!(parse("Max-Age=5").freshness(), Some(Duration::from_secs(5)));
+        assert_eq!(parse("max-age=abc").freshness(), None);
+        assert!(!parse("max-age=60, private").is_storable());
+        assert!(!parse("no-store").is_storable());
+        assert!(!parse("no-cache").is_storable());
+    }
+}
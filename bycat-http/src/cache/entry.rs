@@ -0,0 +1,231 @@
+use alloc::vec::Vec;
+use bycat_cache::{CacheKey, Cached};
+use bytes::Bytes;
+use core::{
+    future::{Ready, ready},
+    time::Duration,
+};
+use http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode, Uri};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const RESPONSE: u8 = b'R';
+const VARY: u8 = b'V';
+
+pub(crate) fn now() -> Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+/// The key of the responses to a method and uri.
+pub(crate) struct RequestKey<'a> {
+    pub prefix: &'a str,
+    pub method: &'a Method,
+    pub uri: &'a Uri,
+}
+
+impl CacheKey for RequestKey<'_> {
+    fn key(&self) -> Vec<u8> {
+        alloc::format!("{}{} {}", self.prefix, self.method, self.uri).into_bytes()
+    }
+}
+
+/// The key of the variant selected by the `Vary` headers of a response.
+pub(crate) fn variant_key(mut key: Vec<u8>, vary: &[HeaderName], headers: &HeaderMap) -> Vec<u8> {
+    for name in vary {
+        key.push(b'\n');
+        key.extend_from_slice(name.as_str().as_bytes());
+        key.push(b':');
+        for value in headers.get_all(name) {
+            key.extend_from_slice(value.as_bytes());
+            key.push(b',');
+        }
+    }
+    key
+}
+
+/// A value in the cache: a response, or the headers its variants are
+/// selected by.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Record {
+    Response(Entry),
+    Vary(Vec<HeaderName>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Entry {
+    pub stored_at: Duration,
+    pub fresh: Duration,
+    pub stale: Duration,
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+impl Entry {
+    pub fn age(&self, now: Duration) -> Duration {
+        now.saturating_sub(self.stored_at)
+    }
+
+    pub fn is_fresh(&self, now: Duration) -> bool {
+        self.age(now) < self.fresh
+    }
+
+    /// Whether the entry may still be served while it's being refreshed.
+    pub fn is_usable(&self, now: Duration) -> bool {
+        self.age(now) < self.fresh + self.stale
+    }
+}
+
+impl Record {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        match self {
+            Record::Response(entry) => {
+                out.push(RESPONSE);
+                for duration in [entry.stored_at, entry.fresh, entry.stale] {
+                    out.extend_from_slice(&(duration.as_millis() as u64).to_be_bytes());
+                }
+                out.extend_from_slice(&entry.status.as_u16().to_be_bytes());
+                out.extend_from_slice(&(entry.headers.len() as u32).to_be_bytes());
+                for (name, value) in &entry.headers {
+                    put(&mut out, name.as_str().as_bytes());
+                    put(&mut out, value.as_bytes());
+                }
+                put(&mut out, &entry.body);
+            }
+            Record::Vary(names) => {
+                out.push(VARY);
+                out.extend_from_slice(&(names.len() as u32).to_be_bytes());
+                for name in names {
+                    put(&mut out, name.as_str().as_bytes());
+                }
+            }
+        }
+        out
+    }
+
+    /// The record in `bytes`, or `None` if they're empty or malformed.
+    pub fn decode(bytes: &[u8]) -> Option<Record> {
+        let (kind, mut bytes) = bytes.split_first()?;
+        let reader = &mut bytes;
+
+        match *kind {
+            RESPONSE => {
+                let stored_at = Duration::from_millis(u64::from_be_bytes(take_array(reader)?));
+                let fresh = Duration::from_millis(u64::from_be_bytes(take_array(reader)?));
+                let stale = Duration::from_millis(u64::from_be_bytes(take_array(reader)?));
+                let status = StatusCode::from_u16(u16::from_be_bytes(take_array(reader)?)).ok()?;
+
+                let count = u32::from_be_bytes(take_array(reader)?);
+                let mut headers = HeaderMap::new();
+                for _ in 0..count {
+                    let name = HeaderName::from_bytes(take(reader)?).ok()?;
+                    let value = HeaderValue::from_bytes(take(reader)?).ok()?;
+                    headers.append(name, value);
+                }
+                let body = Bytes::copy_from_slice(take(reader)?);
+
+                Some(Record::Response(Entry {
+                    stored_at,
+                    fresh,
+                    stale,
+                    status,
+                    headers,
+                    body,
+                }))
+            }
+            VARY => {
+                let count = u32::from_be_bytes(take_array(reader)?);
+                let names = (0..count)
+                    .map(|_| HeaderName::from_bytes(take(reader)?).ok())
+                    .collect::<Option<Vec<_>>>()?;
+                Some(Record::Vary(names))
+            }
+            _ => None,
+        }
+    }
+}
+
+fn put(out: &mut Vec<u8>, bytes: &[u8]) {
+    out.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+    out.extend_from_slice(bytes);
+}
+
+fn take_array<const N: usize>(bytes: &mut &[u8]) -> Option<[u8; N]> {
+    let (head, rest) = bytes.split_first_chunk::<N>()?;
+    *bytes = rest;
+    Some(*head)
+}
+
+fn take<'a>(bytes: &mut &'a [u8]) -> Option<&'a [u8]> {
+    let len = u32::from_be_bytes(take_array(bytes)?) as usize;
+    if bytes.len() < len {
+        return None;
+    }
+    let (head, rest) = bytes.split_at(len);
+    *bytes = rest;
+    Some(head)
+}
+
+impl Cached for Record {
+    type IntoFuture<'a> = Ready<Result<Vec<u8>, bycat_cache::Error>>;
+
+    type FromFuture<'a> = Ready<Result<Record, bycat_cache::Error>>;
+
+    fn into_cached<'a>(&'a mut self) -> Self::IntoFuture<'a> {
+        ready(Ok(self.encode()))
+    }
+
+    fn from_cached<'a>(bytes: &'a Vec<u8>) -> Self::FromFuture<'a> {
+        ready(
+            Record::decode(bytes).ok_or_else(|| bycat_cache::Error::new("malformed cache record")),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", "text/plain".parse().unwrap());
+        headers.append("x-multi", "a".parse().unwrap());
+        headers.append("x-multi", "b".parse().unwrap());
+
+        let record = Record::Response(Entry {
+            stored_at: Duration::from_millis(1_700_000_000_123),
+            fresh: Duration::from_secs(60),
+            stale: Duration::ZERO,
+            status: StatusCode::NOT_FOUND,
+            headers,
+            body: Bytes::from_static(b"missing"),
+        });
+        assert_eq!(Record::decode(&record.encode()), Some(record));
+
+        let record = Record::Vary(alloc::vec![http::header::ACCEPT_ENCODING]);
+        assert_eq!(Record::decode(&record.encode()), Some(record));
+
+        assert_eq!(Record::decode(&[]), None);
+        assert_eq!(Record::decode(b"R\x00\x01"), None);
+    }
+
+    #[test]
+    fn freshness() {
+        let entry = Entry {
+            stored_at: Duration::from_secs(100),
+            fresh: Duration::from_secs(10),
+            stale: Duration::from_secs(5),
+            status: StatusCode::OK,
+            headers: HeaderMap::new(),
+            body: Bytes::new(),
+        };
+
+        assert!(entry.is_fresh(Duration::from_secs(109)));
+        assert!(!entry.is_fresh(Duration::from_secs(110)));
+        assert!(entry.is_usable(Duration::from_secs(114)));
+        assert!(!entry.is_usable(Duration::from_secs(115)));
+    }
+}
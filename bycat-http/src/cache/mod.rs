@@ -0,0 +1,618 @@
+mod control;
+mod entry;
+
+use self::{
+    control::CacheControl,
+    entry::{Entry, Record, RequestKey, now, variant_key},
+};
+use crate::{
+    Error, IntoResponse, Runtime,
+    body::{HttpBody, to_bytes},
+    error::BoxError,
+};
+use alloc::{boxed::Box, collections::HashSet, sync::Arc, vec::Vec};
+use bycat::{Middleware, Work};
+use bycat_cache::{CacheKey, CacheStore, Cached};
+use core::{marker::PhantomData, time::Duration};
+use futures::future::BoxFuture;
+use http::{
+    HeaderMap, HeaderName, HeaderValue, Method, Request, Response, StatusCode,
+    header::{AGE, AUTHORIZATION, SET_COOKIE, VARY},
+};
+use http_body::Body as _;
+use parking_lot::Mutex;
+
+const X_CACHE: HeaderName = HeaderName::from_static("x-cache");
+
+/// Statuses cacheable without being marked as such, RFC 9110 section 15.1.
+const CACHEABLE: [StatusCode; 11] = [
+    StatusCode::OK,
+    StatusCode::NON_AUTHORITATIVE_INFORMATION,
+    StatusCode::NO_CONTENT,
+    StatusCode::MULTIPLE_CHOICES,
+    StatusCode::MOVED_PERMANENTLY,
+    StatusCode::PERMANENT_REDIRECT,
+    StatusCode::NOT_FOUND,
+    StatusCode::METHOD_NOT_ALLOWED,
+    StatusCode::GONE,
+    StatusCode::URI_TOO_LONG,
+    StatusCode::NOT_IMPLEMENTED,
+];
+
+/// Caches responses to `GET` and `HEAD` requests in a [`CacheStore`].
+///
+/// Responses are keyed by method and uri, and by the request headers named
+/// in their `Vary` header. They are stored for as long as their
+/// `Cache-Control` allows, `s-maxage` or `max-age`, and never when marked
+/// `no-store`, `no-cache` or `private`, when setting cookies, or when
+/// answering a request with credentials without being `public`.
+///
+/// Within `stale-while-revalidate` of expiring, the first request refreshes
+/// the response while requests arriving meanwhile are answered with the
+/// stale one. With a [`runtime`](ResponseCache::runtime) the refresh
+/// runs in the background, so the first request is answered with the stale
+/// response too. Responses carry `X-Cache`, one of `HIT`, `STALE`, `MISS` or
+/// `BYPASS`, and `Age` when served from the cache.
+///
+/// ```ignore
+/// router.route("/articles", get(articles).wrap(ResponseCache::new(store)));
+/// ```
+pub struct ResponseCache<S> {
+    store: Arc<S>,
+    options: Options,
+}
+
+#[derive(Debug, Clone)]
+struct Options {
+    prefix: &'static str,
+    default_ttl: Option<Duration>,
+    max_body_size: u64,
+    runtime: Option<Runtime>,
+}
+
+impl<S> Clone for ResponseCache<S> {
+    fn clone(&self) -> Self {
+        ResponseCache {
+            store: self.store.clone(),
+            options: self.options.clone(),
+        }
+    }
+}
+
+impl<S> ResponseCache<S> {
+    pub fn new(store: S) -> ResponseCache<S> {
+        ResponseCache {
+            store: Arc::new(store),
+            options: Options {
+                prefix: "http:",
+                default_ttl: None,
+                max_body_size: 1024 * 1024,
+                runtime: None,
+            },
+        }
+    }
+
+    /// Prefix of the cache keys. Defaults to `http:`.
+    pub fn prefix(mut self, prefix: &'static str) -> Self {
+        self.options.prefix = prefix;
+        self
+    }
+
+    /// How long responses without `max-age` or `s-maxage` are fresh. By
+    /// default they aren't cached.
+    pub fn default_ttl(mut self, ttl: Duration) -> Self {
+        self.options.default_ttl = Some(ttl);
+        self
+    }
+
+    /// Responses with larger, or unknown, bodies are not cached. Defaults
+    /// to 1MiB.
+    pub fn max_body_size(mut self, size: u64) -> Self {
+        self.options.max_body_size = size;
+        self
+    }
+
+    /// Refresh stale responses in a task spawned on `runtime`.
+    ///
+    /// ```ignore
+    /// ResponseCache::new(store).runtime(Runtime::tokio())
+    /// ```
+    pub fn runtime(mut self, runtime: Runtime) -> Self {
+        self.options.runtime = Some(runtime);
+        self
+    }
+}
+
+impl<S, C, B, T> Middleware<C, Request<B>, T> for ResponseCache<S>
+where
+    S: CacheStore + Send + Sync + 'static,
+    for<'a> S::GetFuture<'a>: Send,
+    for<'a> S::SetFuture<'a>: Send,
+    T: Work<C, Request<B>> + Clone + Send + Sync + 'static,
+    for<'a> T::Future<'a>: Send,
+    T::Output: IntoResponse<B>,
+    T::Error: Into<Error>,
+    C: Clone + Send + Sync + 'static,
+    B: HttpBody + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<BoxError>,
+{
+    type Work = ResponseCacheWork<S, C, B, T>;
+
+    fn wrap(&self, handler: T) -> Self::Work {
+        ResponseCacheWork {
+            shared: Arc::new(Shared {
+                store: self.store.clone(),
+                options: self.options.clone(),
+                revalidating: Arc::default(),
+            }),
+            work: handler,
+            req: PhantomData,
+        }
+    }
+}
+
+struct Shared<S> {
+    store: Arc<S>,
+    options: Options,
+    revalidating: Arc<Mutex<HashSet<Vec<u8>>>>,
+}
+
+/// Built on the [`CacheKey`] and [`Cached`] of `bycat-cache`, but not on its
+/// `CacheWork`: that stores every output under its key for good, while
+/// responses are stored per `Cache-Control` and `Vary`, expire, and may be
+/// served stale while refreshing.
+pub struct ResponseCacheWork<S, C, B, T> {
+    shared: Arc<Shared<S>>,
+    work: T,
+    req: PhantomData<fn() -> (C, B)>,
+}
+
+impl<S, C, B, T: Clone> Clone for ResponseCacheWork<S, C, B, T> {
+    fn clone(&self) -> Self {
+        ResponseCacheWork {
+            shared: self.shared.clone(),
+            work: self.work.clone(),
+            req: PhantomData,
+        }
+    }
+}
+
+impl<S, C, B, T> Work<C, Request<B>> for ResponseCacheWork<S, C, B, T>
+where
+    S: CacheStore + Send + Sync + 'static,
+    for<'a> S::GetFuture<'a>: Send,
+    for<'a> S::SetFuture<'a>: Send,
+    T: Work<C, Request<B>> + Clone + Send + Sync + 'static,
+    for<'a> T::Future<'a>: Send,
+    T::Output: IntoResponse<B>,
+    T::Error: Into<Error>,
+    C: Clone + Send + Sync + 'static,
+    B: HttpBody + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<BoxError>,
+{
+    type Output = Response<B>;
+
+    type Error = Error;
+
+    type Future<'a>
+        = BoxFuture<'a, Result<Response<B>, Error>>
+    where
+        Self: 'a,
+        C: 'a;
+
+    fn call<'a>(&'a self, context: &'a C, req: Request<B>) -> Self::Future<'a> {
+        Box::pin(self.handle(context, req))
+    }
+}
+
+impl<S, C, B, T> ResponseCacheWork<S, C, B, T>
+where
+    S: CacheStore + Send + Sync + 'static,
+    for<'a> S::GetFuture<'a>: Send,
+    for<'a> S::SetFuture<'a>: Send,
+    T: Work<C, Request<B>> + Clone + Send + Sync + 'static,
+    for<'a> T::Future<'a>: Send,
+    T::Output: IntoResponse<B>,
+    T::Error: Into<Error>,
+    C: Clone + Send + Sync + 'static,
+    B: HttpBody + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<BoxError>,
+{
+    async fn handle(&self, context: &C, req: Request<B>) -> Result<Response<B>, Error> {
+        if !matches!(*req.method(), Method::GET | Method::HEAD) {
+            return self.fetch(context, req).await;
+        }
+
+        let control = CacheControl::parse(req.headers());
+        if control.no_store {
+            let mut resp = self.fetch(context, req).await?;
+            resp.headers_mut()
+                .insert(X_CACHE, HeaderValue::from_static("BYPASS"));
+            return Ok(resp);
+        }
+
+        let base = RequestKey {
+            prefix: self.shared.options.prefix,
+            method: req.method(),
+            uri: req.uri(),
+        }
+        .key();
+
+        // The client asking for a fresh response skips the lookup, but the
+        // response is still stored
+        let mut revalidation = None;
+        if !control.no_cache
+            && control.max_age != Some(Duration::ZERO)
+            && let Some((key, entry)) = self.lookup(&base, req.headers()).await
+        {
+            let now = now();
+            if entry.is_fresh(now) {
+                return Ok(respond(entry, now, "HIT"));
+            }
+
+            if entry.is_usable(now) {
+                let Some(guard) = Revalidation::start(&self.shared.revalidating, key) else {
+                    return Ok(respond(entry, now, "STALE"));
+                };
+
+                if let Some(runtime) = self.shared.options.runtime {
+                    let refresh = self.clone().refresh(context.clone(), base, req, guard);
+                    runtime.spawn(refresh);
+                    return Ok(respond(entry, now, "STALE"));
+                }
+
+                revalidation = Some(guard);
+            }
+        }
+
+        let headers = req.headers().clone();
+        let resp = self.fetch(context, req).await?;
+        let resp = self.store(base, &headers, resp).await?;
+        drop(revalidation);
+
+        Ok(resp)
+    }
+
+    /// Fetch and store a response in the background, while the stale one is
+    /// served.
+    async fn refresh(self, context: C, base: Vec<u8>, req: Request<B>, guard: Revalidation) {
+        let headers = req.headers().clone();
+        let result = match self.fetch(&context, req).await {
+            Ok(resp) => self.store(base, &headers, resp).await.map(drop),
+            Err(err) => Err(err),
+        };
+
+        if let Err(err) = result {
+            tracing::warn!("response cache refresh failed: {err}");
+        }
+
+        drop(guard);
+    }
+
+    async fn fetch(&self, context: &C, req: Request<B>) -> Result<Response<B>, Error> {
+        self.work
+            .call(context, req)
+            .await
+            .map(IntoResponse::into_response)
+            .map_err(Into::into)
+    }
+
+    /// The entry for a request, and the key it's stored under.
+    async fn lookup(&self, base: &[u8], headers: &HeaderMap) -> Option<(Vec<u8>, Entry)> {
+        match self.get(base).await? {
+            Record::Response(entry) => Some((base.to_vec(), entry)),
+            Record::Vary(names) => {
+                let key = variant_key(base.to_vec(), &names, headers);
+                match self.get(&key).await? {
+                    Record::Response(entry) => Some((key, entry)),
+                    Record::Vary(_) => None,
+                }
+            }
+        }
+    }
+
+    async fn get(&self, key: &[u8]) -> Option<Record> {
+        let bytes = match self.shared.store.get(key).await {
+            Ok(bytes) if !bytes.is_empty() => bytes,
+            Ok(_) => return None,
+            Err(err) => {
+                tracing::warn!("response cache lookup failed: {err}");
+                return None;
+            }
+        };
+
+        Record::from_cached(&bytes).await.ok()
+    }
+
+    async fn set(&self, key: &[u8], mut record: Record) {
+        let result = match record.into_cached().await {
+            Ok(bytes) => self.shared.store.set(key, &bytes).await,
+            Err(err) => Err(err),
+        };
+
+        if let Err(err) = result {
+            tracing::warn!("response cache store failed: {err}");
+        }
+    }
+
+    /// Store the response if it's cacheable, reading its body into memory.
+    async fn store(
+        &self,
+        base: Vec<u8>,
+        request: &HeaderMap,
+        resp: Response<B>,
+    ) -> Result<Response<B>, Error> {
+        let options = &self.shared.options;
+        let control = CacheControl::parse(resp.headers());
+
+        let vary = resp
+            .headers()
+            .get_all(VARY)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(|name| HeaderName::try_from(name.trim()).ok())
+            .collect::<Option<Vec<_>>>();
+
+        let fresh = control.freshness().or(options.default_ttl);
+        let size = resp.body().size_hint().upper();
+
+        let storable = control.is_storable()
+            && CACHEABLE.contains(&resp.status())
+            && !resp.headers().contains_key(SET_COOKIE)
+            && (control.public || !request.contains_key(AUTHORIZATION))
+            && size.is_some_and(|size| size <= options.max_body_size);
+
+        let (Some(fresh), Some(vary), true) = (fresh, vary, storable) else {
+            let mut resp = resp;
+            resp.headers_mut()
+                .insert(X_CACHE, HeaderValue::from_static("BYPASS"));
+            return Ok(resp);
+        };
+
+        let (mut parts, body) = resp.into_parts();
+        let body = to_bytes(body).await?;
+
+        let mut headers = parts.headers.clone();
+        headers.remove(AGE);
+        headers.remove(X_CACHE);
+
+        let entry = Entry {
+            stored_at: now(),
+            fresh,
+            stale: control.stale_while_revalidate.unwrap_or_default(),
+            status: parts.status,
+            headers,
+            body: body.clone(),
+        };
+
+        if vary.is_empty() {
+            self.set(&base, Record::Response(entry)).await;
+        } else {
+            let key = variant_key(base.clone(), &vary, request);
+            self.set(&base, Record::Vary(vary)).await;
+            self.set(&key, Record::Response(entry)).await;
+        }
+
+        parts
+            .headers
+            .insert(X_CACHE, HeaderValue::from_static("MISS"));
+        Ok(Response::from_parts(parts, B::from_bytes(body)))
+    }
+}
+
+fn respond<B: HttpBody>(entry: Entry, now: Duration, cache: &'static str) -> Response<B> {
+    let mut resp = Response::new(B::from_bytes(entry.body));
+    *resp.status_mut() = entry.status;
+    *resp.headers_mut() = entry.headers;
+    resp.headers_mut()
+        .insert(AGE, HeaderValue::from(entry.age(now).as_secs()));
+    resp.headers_mut()
+        .insert(X_CACHE, HeaderValue::from_static(cache));
+    resp
+}
+
+/// Marks a key as being refreshed, until dropped.
+struct Revalidation {
+    keys: Arc<Mutex<HashSet<Vec<u8>>>>,
+    key: Vec<u8>,
+}
+
+impl Revalidation {
+    /// `None` if the key is already being refreshed.
+    fn start(keys: &Arc<Mutex<HashSet<Vec<u8>>>>, key: Vec<u8>) -> Option<Revalidation> {
+        keys.lock().insert(key.clone()).then(|| Revalidation {
+            keys: keys.clone(),
+            key,
+        })
+    }
+}
+
+impl Drop for Revalidation {
+    fn drop(&mut self) {
+        self.keys.lock().remove(&self.key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{body::Body, test_util::MemoryCache};
+    use alloc::string::String;
+    use core::{
+        future::{Ready, ready},
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+    use http::header::{ACCEPT_ENCODING, CACHE_CONTROL};
+
+    /// Answers with the number of calls so far, and `cache-control`.
+    #[derive(Clone)]
+    struct Counter {
+        calls: Arc<AtomicUsize>,
+        cache_control: &'static str,
+        vary: Option<&'static str>,
+    }
+
+    impl Counter {
+        fn new(cache_control: &'static str) -> Counter {
+            Counter {
+                calls: Arc::default(),
+                cache_control,
+                vary: None,
+            }
+        }
+    }
+
+    impl Work<(), Request<Body>> for Counter {
+        type Output = Response<Body>;
+        type Error = Error;
+        type Future<'a>
+            = Ready<Result<Response<Body>, Error>>
+        where
+            Self: 'a;
+
+        fn call<'a>(&'a self, _context: &'a (), _req: Request<Body>) -> Self::Future<'a> {
+            let calls = self.calls.fetch_add(1, Ordering::Relaxed) + 1;
+            let mut resp = Response::builder().header(CACHE_CONTROL, self.cache_control);
+            if let Some(vary) = self.vary {
+                resp = resp.header(VARY, vary);
+            }
+            ready(Ok(resp
+                .body(Body::from(alloc::format!("{calls}")))
+                .unwrap()))
+        }
+    }
+
+    fn request(uri: &str) -> Request<Body> {
+        Request::builder().uri(uri).body(Body::empty()).unwrap()
+    }
+
+    async fn send<W>(work: &W, req: Request<Body>) -> (String, &'static str)
+    where
+        W: Work<(), Request<Body>, Output = Response<Body>, Error = Error>,
+    {
+        let resp = work.call(&(), req).await.unwrap();
+        let cache = match resp.headers()[X_CACHE].to_str().unwrap() {
+            "HIT" => "HIT",
+            "STALE" => "STALE",
+            "MISS" => "MISS",
+            _ => "BYPASS",
+        };
+        let body = to_bytes(resp.into_body()).await.unwrap();
+        (String::from_utf8(body.to_vec()).unwrap(), cache)
+    }
+
+    #[tokio::test]
+    async fn hit() {
+        let work = ResponseCache::new(MemoryCache::default()).wrap(Counter::new("max-age=60"));
+
+        assert_eq!(send(&work, request("/a")).await, ("1".into(), "MISS"));
+
+        let resp = work.call(&(), request("/a")).await.unwrap();
+        assert_eq!(resp.headers()[X_CACHE], "HIT");
+        assert_eq!(resp.headers()[AGE], "0");
+        assert_eq!(resp.headers()[CACHE_CONTROL], "max-age=60");
+        assert_eq!(to_bytes(resp.into_body()).await.unwrap(), "1");
+
+        // Keyed by uri
+        assert_eq!(
+            send(&work, request("/a?page=2")).await,
+            ("2".into(), "MISS")
+        );
+
+        let mut req = request("/a");
+        req.headers_mut()
+            .insert(CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+        assert_eq!(send(&work, req).await, ("3".into(), "MISS"));
+        assert_eq!(send(&work, request("/a")).await, ("3".into(), "HIT"));
+
+        let mut req = request("/a");
+        req.headers_mut()
+            .insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+        assert_eq!(send(&work, req).await, ("4".into(), "BYPASS"));
+    }
+
+    #[tokio::test]
+    async fn not_stored() {
+        for cache_control in ["no-store", "private, max-age=60", ""] {
+            let work = ResponseCache::new(MemoryCache::default()).wrap(Counter::new(cache_control));
+            assert_eq!(send(&work, request("/")).await, ("1".into(), "BYPASS"));
+            assert_eq!(send(&work, request("/")).await, ("2".into(), "BYPASS"));
+        }
+
+        let work = ResponseCache::new(MemoryCache::default())
+            .default_ttl(Duration::from_secs(60))
+            .wrap(Counter::new(""));
+        send(&work, request("/")).await;
+        assert_eq!(send(&work, request("/")).await, ("1".into(), "HIT"));
+
+        let work = ResponseCache::new(MemoryCache::default()).wrap(Counter::new("max-age=60"));
+        let mut req = request("/");
+        req.headers_mut()
+            .insert(AUTHORIZATION, HeaderValue::from_static("Bearer token"));
+        assert_eq!(send(&work, req).await, ("1".into(), "BYPASS"));
+    }
+
+    #[tokio::test]
+    async fn vary() {
+        let mut counter = Counter::new("max-age=60");
+        counter.vary = Some("accept-encoding");
+        let work = ResponseCache::new(MemoryCache::default()).wrap(counter);
+
+        let encoded = || {
+            let mut req = request("/");
+            req.headers_mut()
+                .insert(ACCEPT_ENCODING, HeaderValue::from_static("gzip"));
+            req
+        };
+
+        assert_eq!(send(&work, request("/")).await, ("1".into(), "MISS"));
+        assert_eq!(send(&work, encoded()).await, ("2".into(), "MISS"));
+        assert_eq!(send(&work, request("/")).await, ("1".into(), "HIT"));
+        assert_eq!(send(&work, encoded()).await, ("2".into(), "HIT"));
+    }
+
+    #[tokio::test]
+    async fn stale_while_revalidate() {
+        let work = ResponseCache::new(MemoryCache::default())
+            .wrap(Counter::new("max-age=0, stale-while-revalidate=60"));
+
+        assert_eq!(send(&work, request("/")).await, ("1".into(), "MISS"));
+
+        // Another request is refreshing the response
+        let key = RequestKey {
+            prefix: "http:",
+            method: &Method::GET,
+            uri: &"/".parse().unwrap(),
+        }
+        .key();
+        let guard = Revalidation::start(&work.shared.revalidating, key).unwrap();
+        assert_eq!(send(&work, request("/")).await, ("1".into(), "STALE"));
+        drop(guard);
+
+        assert_eq!(send(&work, request("/")).await, ("2".into(), "MISS"));
+        assert!(work.shared.revalidating.lock().is_empty());
+    }
+
+    #[cfg(feature = "serve-tokio")]
+    #[tokio::test]
+    async fn background_refresh() {
+        let work = ResponseCache::new(MemoryCache::default())
+            .runtime(Runtime::tokio())
+            .wrap(Counter::new("max-age=0, stale-while-revalidate=60"));
+
+        assert_eq!(send(&work, request("/")).await, ("1".into(), "MISS"));
+
+        // The request starting the refresh doesn't wait for it
+        assert_eq!(send(&work, request("/")).await, ("1".into(), "STALE"));
+
+        while !work.shared.revalidating.lock().is_empty() {
+            tokio::task::yield_now().await;
+        }
+
+        assert_eq!(send(&work, request("/")).await, ("2".into(), "STALE"));
+    }
+}
@@ -0,0 +1,201 @@
+use crate::{Error, IntoResponse, body::HttpBody};
+use alloc::{boxed::Box, string::String};
+use bycat::{Middleware, Work};
+use core::{
+    any::Any,
+    marker::PhantomData,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use futures::{FutureExt, future::CatchUnwind};
+use http::{Request, Response};
+use pin_project_lite::pin_project;
+use std::panic::AssertUnwindSafe;
+
+/// Turns a panic in the handlers it wraps into a `500 Internal Server Error`
+/// response, rather than a dropped connection.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CatchPanic;
+
+impl<C, B, T> Middleware<C, Request<B>, T> for CatchPanic
+where
+    T: Work<C, Request<B>>,
+    T::Output: IntoResponse<B>,
+    B: HttpBody,
+{
+    type Work = CatchPanicWork<T, B>;
+
+    fn wrap(&self, handle: T) -> Self::Work {
+        CatchPanicWork {
+            work: handle,
+            body: PhantomData,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct CatchPanicWork<T, B> {
+    work: T,
+    body: PhantomData<fn(B)>,
+}
+
+impl<T: Clone, B> Clone for CatchPanicWork<T, B> {
+    fn clone(&self) -> Self {
+        CatchPanicWork {
+            work: self.work.clone(),
+            body: PhantomData,
+        }
+    }
+}
+
+impl<T, C, B> Work<C, Request<B>> for CatchPanicWork<T, B>
+where
+    T: Work<C, Request<B>>,
+    T::Output: IntoResponse<B>,
+    B: HttpBody,
+{
+    type Output = Response<B>;
+
+    type Error = T::Error;
+
+    type Future<'a>
+        = CatchPanicFuture<'a, T, C, B>
+    where
+        Self: 'a,
+        C: 'a;
+
+    fn call<'a>(&'a self, context: &'a C, req: Request<B>) -> Self::Future<'a> {
+        // A panic while creating the future is caught as well
+        let future = std::panic::catch_unwind(AssertUnwindSafe(|| self.work.call(context, req)));
+
+        CatchPanicFuture {
+            state: match future {
+                Ok(future) => CatchPanicState::Future {
+                    future: AssertUnwindSafe(future).catch_unwind(),
+                },
+                Err(panic) => CatchPanicState::Panicked { panic: Some(panic) },
+            },
+        }
+    }
+}
+
+pin_project! {
+    #[project = CatchPanicStateProj]
+    enum CatchPanicState<'a, T, C, B>
+    where
+        T: Work<C, Request<B>>,
+        T: 'a,
+        C: 'a,
+    {
+        Future {
+            #[pin]
+            future: CatchUnwind<AssertUnwindSafe<T::Future<'a>>>,
+        },
+        Panicked {
+            panic: Option<Box<dyn Any + Send>>,
+        },
+    }
+}
+
+pin_project! {
+    pub struct CatchPanicFuture<'a, T, C, B>
+    where
+        T: Work<C, Request<B>>,
+        T: 'a,
+        C: 'a,
+    {
+        #[pin]
+        state: CatchPanicState<'a, T, C, B>,
+    }
+}
+
+impl<'a, T, C, B> Future for CatchPanicFuture<'a, T, C, B>
+where
+    T: Work<C, Request<B>>,
+    T::Output: IntoResponse<B>,
+    B: HttpBody,
+{
+    type Output = Result<Response<B>, T::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let panic = match self.project().state.project() {
+            CatchPanicStateProj::Future { future } => match core::task::ready!(future.poll(cx)) {
+                Ok(ret) => return Poll::Ready(ret.map(|output| output.into_response())),
+                Err(panic) => panic,
+            },
+            CatchPanicStateProj::Panicked { panic } => {
+                panic.take().expect("polled after completion")
+            }
+        };
+
+        let message = message(&*panic);
+        tracing::error!("handler panicked: {message}");
+
+        Poll::Ready(Ok(Error::custom(message).into_response()))
+    }
+}
+
+/// The message a panic was started with.
+fn message(panic: &(dyn Any + Send)) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        String::from(*message)
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        String::from("unknown panic")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::Body;
+    use http::StatusCode;
+
+    struct Panics(bool);
+
+    impl Work<(), Request<Body>> for Panics {
+        type Output = Response<Body>;
+        type Error = Error;
+        type Future<'a>
+            = Pin<Box<dyn Future<Output = Result<Response<Body>, Error>> + Send + 'a>>
+        where
+            Self: 'a;
+
+        fn call<'a>(&'a self, _context: &'a (), req: Request<Body>) -> Self::Future<'a> {
+            assert!(self.0, "before the future");
+            Box::pin(async move {
+                if req.uri().path() == "/panic" {
+                    panic!("in the future");
+                }
+                Ok(Response::new(Body::empty()))
+            })
+        }
+    }
+
+    fn request(path: &str) -> Request<Body> {
+        Request::builder().uri(path).body(Body::empty()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn catches() {
+        let work = Middleware::<(), Request<Body>, _>::wrap(&CatchPanic, Panics(true));
+
+        let resp = work.call(&(), request("/")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let resp = work.call(&(), request("/panic")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let work = Middleware::<(), Request<Body>, _>::wrap(&CatchPanic, Panics(false));
+        let resp = work.call(&(), request("/")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[test]
+    fn messages() {
+        assert_eq!(message(&"static"), "static");
+        assert_eq!(message(&String::from("owned")), "owned");
+        assert_eq!(message(&1u8), "unknown panic");
+    }
+}
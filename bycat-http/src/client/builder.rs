@@ -0,0 +1,254 @@
+use alloc::sync::Arc;
+use bycat::{Middleware, Work};
+use core::time::Duration;
+use http::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Method, Request, Url, cookie::Jar};
+
+use super::{ClientError, ClientRequest, HttpWork};
+
+/// An outbound http client.
+///
+/// Requests are sent through a [`Work`], [`HttpWork`] by default, so the
+/// same middlewares used for handlers, like retries and tracing, can wrap
+/// outbound calls with [`Client::wrap`].
+///
+/// ```ignore
+/// let client = Client::builder()
+///     .base_url("https://api.example.com/v1/")
+///     .default_header(ACCEPT, HeaderValue::from_static("application/json"))
+///     .cookies(true)
+///     .build()?
+///     .wrap(Retry::new(3));
+///
+/// let user: User = client.get("users/1").send().await?.json().await?;
+/// ```
+pub struct Client<W = HttpWork> {
+    shared: Arc<Shared>,
+    work: Arc<W>,
+}
+
+struct Shared {
+    base_url: Option<Url>,
+    headers: HeaderMap,
+}
+
+impl<W> Clone for Client<W> {
+    fn clone(&self) -> Self {
+        Client {
+            shared: self.shared.clone(),
+            work: self.work.clone(),
+        }
+    }
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Client::new()
+    }
+}
+
+impl Client {
+    pub fn new() -> Client {
+        Client::builder().build().expect("default client")
+    }
+
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::new()
+    }
+}
+
+impl<W> Client<W> {
+    /// Wrap the work sending requests in `middleware`.
+    pub fn wrap<M>(self, middleware: M) -> Client<M::Work>
+    where
+        W: Clone,
+        M: Middleware<(), Request, W>,
+    {
+        let work = Arc::try_unwrap(self.work).unwrap_or_else(|work| (*work).clone());
+
+        Client {
+            shared: self.shared,
+            work: Arc::new(middleware.wrap(work)),
+        }
+    }
+
+    pub fn work(&self) -> &W {
+        &self.work
+    }
+
+    pub fn base_url(&self) -> Option<&Url> {
+        self.shared.base_url.as_ref()
+    }
+
+    /// Resolve `path` against the base url. Absolute urls are used as they
+    /// are.
+    pub fn url(&self, path: &str) -> Result<Url, ClientError> {
+        match (Url::parse(path), &self.shared.base_url) {
+            (Ok(url), _) => Ok(url),
+            (Err(_), Some(base)) => base.join(path.trim_start_matches('/')),
+            (Err(err), None) => Err(err),
+        }
+        .map_err(|err| ClientError::Builder(err.into()))
+    }
+
+    pub fn request(&self, method: Method, path: &str) -> ClientRequest<'_, W> {
+        let request = self.url(path).map(|url| {
+            let mut request = Request::new(method, url);
+            *request.headers_mut() = self.shared.headers.clone();
+            request
+        });
+
+        ClientRequest::new(self, request)
+    }
+
+    pub fn get(&self, path: &str) -> ClientRequest<'_, W> {
+        self.request(Method::GET, path)
+    }
+
+    pub fn post(&self, path: &str) -> ClientRequest<'_, W> {
+        self.request(Method::POST, path)
+    }
+
+    pub fn put(&self, path: &str) -> ClientRequest<'_, W> {
+        self.request(Method::PUT, path)
+    }
+
+    pub fn patch(&self, path: &str) -> ClientRequest<'_, W> {
+        self.request(Method::PATCH, path)
+    }
+
+    pub fn delete(&self, path: &str) -> ClientRequest<'_, W> {
+        self.request(Method::DELETE, path)
+    }
+
+    pub fn head(&self, path: &str) -> ClientRequest<'_, W> {
+        self.request(Method::HEAD, path)
+    }
+}
+
+impl<C, W> Work<C, Request> for Client<W>
+where
+    W: Work<C, Request>,
+{
+    type Output = W::Output;
+
+    type Error = W::Error;
+
+    type Future<'a>
+        = W::Future<'a>
+    where
+        Self: 'a,
+        C: 'a;
+
+    fn call<'a>(&'a self, context: &'a C, req: Request) -> Self::Future<'a> {
+        self.work.call(context, req)
+    }
+}
+
+pub struct ClientBuilder {
+    builder: reqwest::ClientBuilder,
+    base_url: Option<Url>,
+    headers: HeaderMap,
+    error_for_status: bool,
+    error: Option<ClientError>,
+}
+
+impl Default for ClientBuilder {
+    fn default() -> Self {
+        ClientBuilder::new()
+    }
+}
+
+impl ClientBuilder {
+    pub fn new() -> ClientBuilder {
+        ClientBuilder {
+            builder: reqwest::Client::builder(),
+            base_url: None,
+            headers: HeaderMap::new(),
+            error_for_status: true,
+            error: None,
+        }
+    }
+
+    /// Relative paths of requests are resolved against `url`. A trailing
+    /// slash is added, so `https://host/v1` keeps its `v1` segment.
+    pub fn base_url(mut self, url: &str) -> Self {
+        let url = if url.ends_with('/') {
+            Url::parse(url)
+        } else {
+            Url::parse(&format!("{url}/"))
+        };
+
+        match url {
+            Ok(url) => self.base_url = Some(url),
+            Err(err) => self.error = Some(ClientError::Builder(err.into())),
+        }
+        self
+    }
+
+    /// A header sent with every request, unless the request sets it.
+    pub fn default_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    pub fn user_agent(mut self, value: HeaderValue) -> Self {
+        self.builder = self.builder.user_agent(value);
+        self
+    }
+
+    /// Timeout of whole requests, from connecting until the body is read.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.builder = self.builder.timeout(timeout);
+        self
+    }
+
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.builder = self.builder.connect_timeout(timeout);
+        self
+    }
+
+    /// Keep the cookies set by responses, and send them with later requests.
+    pub fn cookies(mut self, enable: bool) -> Self {
+        self.builder = self.builder.cookie_store(enable);
+        self
+    }
+
+    /// Keep cookies in `jar`, which can be shared between clients.
+    pub fn cookie_jar(mut self, jar: Arc<Jar>) -> Self {
+        self.builder = self.builder.cookie_provider(jar);
+        self
+    }
+
+    /// Turn responses with a 4xx or 5xx status into [`ClientError::Status`].
+    /// Enabled by default.
+    pub fn error_for_status(mut self, enable: bool) -> Self {
+        self.error_for_status = enable;
+        self
+    }
+
+    /// Customize the underlying reqwest client.
+    pub fn configure<F>(mut self, configure: F) -> Self
+    where
+        F: FnOnce(reqwest::ClientBuilder) -> reqwest::ClientBuilder,
+    {
+        self.builder = configure(self.builder);
+        self
+    }
+
+    pub fn build(self) -> Result<Client, ClientError> {
+        if let Some(err) = self.error {
+            return Err(err);
+        }
+
+        let client = self.builder.build()?;
+
+        Ok(Client {
+            shared: Arc::new(Shared {
+                base_url: self.base_url,
+                headers: self.headers,
+            }),
+            work: Arc::new(HttpWork::new(client).error_for_status(self.error_for_status)),
+        })
+    }
+}
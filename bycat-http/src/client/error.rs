@@ -0,0 +1,74 @@
+use crate::error::BoxError;
+use alloc::fmt;
+use bytes::Bytes;
+use reqwest::{StatusCode, Url};
+
+/// Errors of outbound requests.
+#[derive(Debug)]
+pub enum ClientError {
+    /// The request could not be built, from an invalid url or header say.
+    Builder(BoxError),
+    /// The request failed, or its response could not be read.
+    Request(reqwest::Error),
+    /// The server answered with a client or server error status.
+    Status {
+        status: StatusCode,
+        url: Url,
+        /// The start of the response body.
+        body: Bytes,
+    },
+    Encode(BoxError),
+    Decode(BoxError),
+}
+
+impl ClientError {
+    /// The status of the response, for [`ClientError::Status`].
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            ClientError::Status { status, .. } => Some(*status),
+            ClientError::Request(err) => err.status(),
+            _ => None,
+        }
+    }
+
+    /// Whether the request timed out.
+    pub fn is_timeout(&self) -> bool {
+        matches!(self, ClientError::Request(err) if err.is_timeout())
+    }
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Builder(err) => write!(f, "invalid request: {err}"),
+            ClientError::Request(err) => write!(f, "request failed: {err}"),
+            ClientError::Status { status, url, .. } => write!(f, "{url} answered {status}"),
+            ClientError::Encode(err) => write!(f, "could not encode request body: {err}"),
+            ClientError::Decode(err) => write!(f, "could not decode response body: {err}"),
+        }
+    }
+}
+
+impl core::error::Error for ClientError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            ClientError::Builder(err) | ClientError::Encode(err) | ClientError::Decode(err) => {
+                Some(&**err)
+            }
+            ClientError::Request(err) => Some(err),
+            ClientError::Status { .. } => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for ClientError {
+    fn from(value: reqwest::Error) -> Self {
+        ClientError::Request(value)
+    }
+}
+
+impl From<ClientError> for crate::Error {
+    fn from(value: ClientError) -> Self {
+        crate::Error::custom(value)
+    }
+}
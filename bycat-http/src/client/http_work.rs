@@ -0,0 +1,211 @@
+use core::{pin::Pin, task::Poll};
+
+use bycat::Work;
+use bycat_package::{IntoPackage, Package, StreamContent};
+use bytes::{Bytes, BytesMut};
+use futures::{Future, FutureExt, Stream, future::BoxFuture};
+use http_body::Body as _;
+use mime::Mime;
+use reqwest::{Method, Request, Response, Url};
+
+use crate::extract::{Decoder, JsonEncoding};
+
+use super::ClientError;
+
+/// How much of the body of an error response [`ClientError::Status`] keeps.
+const ERROR_BODY_LIMIT: usize = 4 * 1024;
+
+pub fn get(url: &str) -> Result<Request, ClientError> {
+    Ok(Request::new(
+        Method::GET,
+        Url::parse(url).map_err(|err| ClientError::Builder(err.into()))?,
+    ))
+}
+
+pub struct BodyStream(reqwest::Body);
+
+impl Stream for BodyStream {
+    type Item = Result<Bytes, ClientError>;
+
+    fn poll_next(
+        mut self: core::pin::Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+    ) -> core::task::Poll<Option<Self::Item>> {
+        loop {
+            return match futures::ready!(Pin::new(&mut self.0).poll_frame(cx)) {
+                Some(Ok(frame)) => {
+                    // skip non-data frames
+                    if let Ok(buf) = frame.into_data() {
+                        Poll::Ready(Some(Ok(buf)))
+                    } else {
+                        continue;
+                    }
+                }
+                Some(Err(err)) => Poll::Ready(Some(Err(ClientError::Request(err)))),
+                None => Poll::Ready(None),
+            };
+        }
+    }
+}
+
+/// Executes requests with a [`reqwest::Client`].
+#[derive(Debug, Clone)]
+pub struct HttpWork {
+    client: reqwest::Client,
+    error_for_status: bool,
+}
+
+impl Default for HttpWork {
+    fn default() -> Self {
+        HttpWork::new(reqwest::Client::new())
+    }
+}
+
+impl HttpWork {
+    pub fn new(client: reqwest::Client) -> HttpWork {
+        HttpWork {
+            client,
+            error_for_status: false,
+        }
+    }
+
+    /// Turn responses with a 4xx or 5xx status into [`ClientError::Status`].
+    pub fn error_for_status(mut self, enable: bool) -> Self {
+        self.error_for_status = enable;
+        self
+    }
+}
+
+impl<C> Work<C, Request> for HttpWork {
+    type Output = HttpResponse;
+    type Error = ClientError;
+
+    type Future<'a>
+        = BoxFuture<'a, Result<Self::Output, ClientError>>
+    where
+        C: 'a;
+
+    fn call<'a>(&'a self, _ctx: &'a C, package: Request) -> Self::Future<'a> {
+        async move {
+            let mut resp = self.client.execute(package).await?;
+
+            let status = resp.status();
+            if self.error_for_status && (status.is_client_error() || status.is_server_error()) {
+                let url = resp.url().clone();
+                let mut body = BytesMut::new();
+                while body.len() < ERROR_BODY_LIMIT
+                    && let Ok(Some(chunk)) = resp.chunk().await
+                {
+                    body.extend_from_slice(&chunk);
+                }
+                body.truncate(ERROR_BODY_LIMIT);
+
+                return Err(ClientError::Status {
+                    status,
+                    url,
+                    body: body.freeze(),
+                });
+            }
+
+            Ok(HttpResponse(resp))
+        }
+        .boxed()
+    }
+}
+
+#[repr(transparent)]
+pub struct HttpResponse(pub Response);
+
+impl std::ops::Deref for HttpResponse {
+    type Target = Response;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl std::ops::DerefMut for HttpResponse {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl From<HttpResponse> for Response {
+    fn from(value: HttpResponse) -> Self {
+        value.0
+    }
+}
+
+impl HttpResponse {
+    /// Read the body and decode it with `decoder`.
+    pub async fn decode<T, D>(self, decoder: D) -> Result<D::Output, ClientError>
+    where
+        D: Decoder<T>,
+        D::Error: Into<crate::error::BoxError>,
+    {
+        let bytes = self.0.bytes().await?;
+        decoder
+            .decode(&bytes)
+            .map_err(|err| ClientError::Decode(err.into()))
+    }
+
+    /// Read the body as json.
+    pub async fn json<T>(self) -> Result<T, ClientError>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        self.decode(JsonEncoding).await.map(|json| json.0)
+    }
+}
+impl IntoPackage<StreamContent<BodyStream>> for HttpResponse {
+    type Future = ResponseIntoPackageFuture;
+    type Error = ClientError;
+
+    fn into_package(self) -> Self::Future {
+        ResponseIntoPackageFuture {
+            resp: self.0.into(),
+        }
+    }
+}
+
+pin_project_lite::pin_project! {
+    pub struct ResponseIntoPackageFuture {
+        resp: Option<Response>,
+    }
+}
+
+impl Future for ResponseIntoPackageFuture {
+    type Output = Result<Package<StreamContent<BodyStream>>, ClientError>;
+
+    fn poll(self: Pin<&mut Self>, _cx: &mut core::task::Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let Some(mut resp) = this.resp.take() else {
+            panic!("poll after done")
+        };
+
+        let request_path = relative_path::RelativePathBuf::from(resp.url().path());
+
+        let _size = resp.content_length();
+        let content_type = resp
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<Mime>().ok());
+
+        let headers = std::mem::replace(resp.headers_mut(), Default::default());
+        let status = resp.status();
+        let body: reqwest::Body = resp.into();
+
+        let file_name = request_path.file_name().unwrap_or("unknown");
+
+        let mut pkg = Package::new(
+            file_name.to_string(),
+            content_type.unwrap_or(mime::APPLICATION_OCTET_STREAM),
+            StreamContent::new(BodyStream(body)),
+        );
+
+        pkg.meta_mut().insert(headers);
+        pkg.meta_mut().insert(status);
+
+        Poll::Ready(Ok(pkg))
+    }
+}
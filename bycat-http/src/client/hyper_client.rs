@@ -0,0 +1,240 @@
+use crate::{
+    Error,
+    body::{Body, HttpBody},
+};
+use bycat::Work;
+use core::{
+    pin::Pin,
+    task::{Context, Poll, ready},
+};
+use http::{Request, Response};
+use hyper_util::client::legacy::{self, ResponseFuture, connect::Connect};
+use pin_project_lite::pin_project;
+
+#[cfg(feature = "serve-tokio")]
+use hyper_util::client::legacy::connect::HttpConnector;
+
+#[cfg(feature = "serve-smol")]
+pub use self::smol_connector::SmolConnector;
+
+/// A client sending requests with hyper, using the body types of the server.
+///
+/// Request uris must be absolute, `http://host/path`. Use
+/// [`HyperClient::tokio`] or [`HyperClient::smol`] for plain http
+/// connections, or [`HyperClient::from`] a configured hyper client for
+/// anything else.
+pub struct HyperClient<C> {
+    client: legacy::Client<C, Body>,
+}
+
+impl<C: Clone> Clone for HyperClient<C> {
+    fn clone(&self) -> Self {
+        HyperClient {
+            client: self.client.clone(),
+        }
+    }
+}
+
+impl<C> From<legacy::Client<C, Body>> for HyperClient<C> {
+    fn from(client: legacy::Client<C, Body>) -> Self {
+        HyperClient { client }
+    }
+}
+
+#[cfg(feature = "serve-tokio")]
+impl HyperClient<HttpConnector> {
+    /// A client running its connections on the tokio runtime.
+    pub fn tokio() -> HyperClient<HttpConnector> {
+        use hyper_util::rt::{TokioExecutor, TokioTimer};
+
+        let mut connector = HttpConnector::new();
+        connector.set_nodelay(true);
+
+        legacy::Client::builder(TokioExecutor::new())
+            .pool_timer(TokioTimer::new())
+            .build(connector)
+            .into()
+    }
+}
+
+#[cfg(feature = "serve-smol")]
+impl HyperClient<SmolConnector> {
+    /// A client running its connections on the smol executor.
+    pub fn smol() -> HyperClient<SmolConnector> {
+        legacy::Client::builder(crate::serve::SmolExecutor)
+            .build(SmolConnector)
+            .into()
+    }
+}
+
+impl<C> HyperClient<C>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+{
+    pub fn request(&self, req: Request<Body>) -> HyperClientFuture {
+        HyperClientFuture {
+            future: self.client.request(req),
+        }
+    }
+}
+
+impl<T, C> Work<T, Request<Body>> for HyperClient<C>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+{
+    type Output = Response<Body>;
+
+    type Error = Error;
+
+    type Future<'a>
+        = HyperClientFuture
+    where
+        Self: 'a,
+        T: 'a;
+
+    fn call<'a>(&'a self, _context: &'a T, req: Request<Body>) -> Self::Future<'a> {
+        self.request(req)
+    }
+}
+
+pin_project! {
+    pub struct HyperClientFuture {
+        #[pin]
+        future: ResponseFuture,
+    }
+}
+
+impl Future for HyperClientFuture {
+    type Output = Result<Response<Body>, Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let resp = ready!(self.project().future.poll(cx)).map_err(Error::custom)?;
+        Poll::Ready(Ok(resp.map(<Body as HttpBody>::from_streaming)))
+    }
+}
+
+#[cfg(feature = "serve-smol")]
+mod smol_connector {
+    use crate::serve::FuturesIo;
+    use alloc::boxed::Box;
+    use core::{
+        pin::Pin,
+        task::{Context, Poll},
+    };
+    use http::Uri;
+    use hyper_util::client::legacy::connect::{Connected, Connection};
+    use smol::net::TcpStream;
+    use std::io;
+
+    /// Opens plain tcp connections with smol.
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct SmolConnector;
+
+    impl tower_service::Service<Uri> for SmolConnector {
+        type Response = FuturesIo<TcpStream>;
+
+        type Error = io::Error;
+
+        type Future = Pin<Box<dyn Future<Output = io::Result<Self::Response>> + Send>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, uri: Uri) -> Self::Future {
+            Box::pin(async move {
+                if uri.scheme_str() != Some("http") {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "only http uris are supported",
+                    ));
+                }
+
+                let host = uri
+                    .host()
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "missing host"))?;
+                // Ipv6 hosts are bracketed in uris
+                let host = host.trim_start_matches('[').trim_end_matches(']');
+                let port = uri.port_u16().unwrap_or(80);
+
+                let stream = TcpStream::connect((host, port)).await?;
+                stream.set_nodelay(true)?;
+                Ok(FuturesIo::new(stream))
+            })
+        }
+    }
+
+    impl Connection for FuturesIo<TcpStream> {
+        fn connected(&self) -> Connected {
+            Connected::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::to_bytes;
+    use http::{Method, StatusCode};
+
+    const RESPONSE: &str = "HTTP/1.1 201 Created\r\ncontent-length: 4\r\n\r\npong";
+
+    fn request(addr: std::net::SocketAddr) -> Request<Body> {
+        Request::builder()
+            .method(Method::POST)
+            .uri(format!("http://{addr}/ping"))
+            .header("content-length", "4")
+            .body(Body::from("ping"))
+            .unwrap()
+    }
+
+    fn complete(request: &[u8]) -> bool {
+        request.ends_with(b"\r\n\r\nping")
+    }
+
+    #[cfg(feature = "serve-tokio")]
+    #[tokio::test]
+    async fn tokio_roundtrip() {
+        let (addr, mut requests) = crate::test_util::serve(RESPONSE.into(), complete).await;
+
+        let resp = HyperClient::tokio().call(&(), request(addr)).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        assert_eq!(to_bytes(resp.into_body()).await.unwrap(), "pong");
+
+        let raw = requests.recv().await.unwrap();
+        assert!(raw.starts_with("POST /ping HTTP/1.1"));
+    }
+
+    #[cfg(feature = "serve-smol")]
+    #[test]
+    fn smol_roundtrip() {
+        use alloc::vec::Vec;
+        use futures::{AsyncReadExt, AsyncWriteExt};
+        use smol::net::TcpListener;
+
+        smol::block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+
+            let server = smol::spawn(async move {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0; 1024];
+                while !complete(&request) {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    if n == 0 {
+                        break;
+                    }
+                    request.extend_from_slice(&buf[..n]);
+                }
+                stream.write_all(RESPONSE.as_bytes()).await.unwrap();
+            });
+
+            let resp = HyperClient::smol().call(&(), request(addr)).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::CREATED);
+            assert_eq!(to_bytes(resp.into_body()).await.unwrap(), "pong");
+
+            server.await;
+        });
+    }
+}
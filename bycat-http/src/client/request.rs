@@ -0,0 +1,298 @@
+use crate::{
+    error::BoxError,
+    extract::{Encoder, FormEncoding, JsonEncoding},
+};
+use bycat::Work;
+use core::time::Duration;
+use http::{
+    HeaderName, HeaderValue,
+    header::{AUTHORIZATION, CONTENT_TYPE},
+};
+use reqwest::Request;
+
+use super::{Client, ClientError};
+
+/// A request being built by a [`Client`].
+///
+/// Errors building the request are kept until it is sent.
+pub struct ClientRequest<'a, W> {
+    client: &'a Client<W>,
+    request: Result<Request, ClientError>,
+}
+
+impl<'a, W> ClientRequest<'a, W> {
+    pub(super) fn new(
+        client: &'a Client<W>,
+        request: Result<Request, ClientError>,
+    ) -> ClientRequest<'a, W> {
+        ClientRequest { client, request }
+    }
+
+    fn with<F>(mut self, func: F) -> Self
+    where
+        F: FnOnce(&mut Request) -> Result<(), ClientError>,
+    {
+        if let Ok(request) = &mut self.request
+            && let Err(err) = func(request)
+        {
+            self.request = Err(err);
+        }
+        self
+    }
+
+    /// Set a header, replacing any default of the client.
+    pub fn header<K, V>(self, name: K, value: V) -> Self
+    where
+        K: TryInto<HeaderName>,
+        K::Error: Into<BoxError>,
+        V: TryInto<HeaderValue>,
+        V::Error: Into<BoxError>,
+    {
+        self.with(|request| {
+            let name = name
+                .try_into()
+                .map_err(|err| ClientError::Builder(err.into()))?;
+            let value = value
+                .try_into()
+                .map_err(|err| ClientError::Builder(err.into()))?;
+            request.headers_mut().insert(name, value);
+            Ok(())
+        })
+    }
+
+    pub fn bearer_auth(self, token: &str) -> Self {
+        self.header(AUTHORIZATION, format!("Bearer {token}"))
+    }
+
+    /// Append `query` to the query string of the url.
+    pub fn query<T: serde::Serialize>(self, query: &T) -> Self {
+        self.with(|request| {
+            let encoded = serde_urlencoded::to_string(query)
+                .map_err(|err| ClientError::Encode(err.into()))?;
+            if encoded.is_empty() {
+                return Ok(());
+            }
+
+            let url = request.url_mut();
+            let query = match url.query() {
+                Some(existing) if !existing.is_empty() => format!("{existing}&{encoded}"),
+                _ => encoded,
+            };
+            url.set_query(Some(&query));
+            Ok(())
+        })
+    }
+
+    pub fn body<B: Into<reqwest::Body>>(self, body: B) -> Self {
+        self.with(|request| {
+            *request.body_mut() = Some(body.into());
+            Ok(())
+        })
+    }
+
+    /// Encode `value` as the body with `encoder`.
+    pub fn encode<T, E>(self, encoder: E, content_type: &'static str, value: &T) -> Self
+    where
+        E: Encoder<T>,
+        E::Error: Into<BoxError>,
+    {
+        self.with(|request| {
+            let bytes = encoder
+                .encode(value)
+                .map_err(|err| ClientError::Encode(err.into()))?;
+            request
+                .headers_mut()
+                .insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+            *request.body_mut() = Some(bytes.into());
+            Ok(())
+        })
+    }
+
+    pub fn json<T: serde::Serialize>(self, value: &T) -> Self {
+        self.encode(JsonEncoding, "application/json", value)
+    }
+
+    pub fn form<T: serde::Serialize>(self, value: &T) -> Self {
+        self.encode(FormEncoding, "application/x-www-form-urlencoded", value)
+    }
+
+    pub fn timeout(self, timeout: Duration) -> Self {
+        self.with(|request| {
+            *request.timeout_mut() = Some(timeout);
+            Ok(())
+        })
+    }
+
+    pub fn build(self) -> Result<Request, ClientError> {
+        self.request
+    }
+
+    /// Send the request through the work of the client.
+    pub async fn send(self) -> Result<W::Output, ClientError>
+    where
+        W: Work<(), Request>,
+        W::Error: Into<ClientError>,
+    {
+        let request = self.request?;
+        self.client.call(&(), request).await.map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        client::HttpResponse,
+        test_util::{head_complete, serve},
+    };
+    use alloc::string::String;
+    use bycat::Middleware;
+    use futures::{FutureExt, future::LocalBoxFuture};
+    use http::StatusCode;
+    use tokio::sync::mpsc::UnboundedReceiver;
+
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct User {
+        name: String,
+    }
+
+    /// Answer requests with `response`, sending the raw requests over the channel.
+    async fn server(response: &'static str) -> (String, UnboundedReceiver<String>) {
+        let (addr, requests) = serve(response.into(), head_complete).await;
+        (format!("http://{addr}/api"), requests)
+    }
+
+    #[test]
+    fn build() {
+        let client = Client::builder()
+            .base_url("https://example.com/v1")
+            .default_header(
+                HeaderName::from_static("x-client"),
+                HeaderValue::from_static("bycat"),
+            )
+            .build()
+            .unwrap();
+
+        let request = client
+            .post("/users?active=true")
+            .query(&[("page", 2)])
+            .header("x-client", "override")
+            .json(&User {
+                name: String::from("rasmus"),
+            })
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            request.url().as_str(),
+            "https://example.com/v1/users?active=true&page=2"
+        );
+        assert_eq!(request.headers()["x-client"], "override");
+        assert_eq!(request.headers()[CONTENT_TYPE], "application/json");
+        assert_eq!(
+            request.body().unwrap().as_bytes().unwrap(),
+            br#"{"name":"rasmus"}"#
+        );
+
+        let request = client
+            .get("https://other.com/")
+            .form(&[("a", "b c")])
+            .build()
+            .unwrap();
+        assert_eq!(request.url().as_str(), "https://other.com/");
+        assert_eq!(request.headers()["x-client"], "bycat");
+        assert_eq!(request.body().unwrap().as_bytes().unwrap(), b"a=b+c");
+
+        assert!(client.get("/").header("bad header", "x").build().is_err());
+    }
+
+    #[tokio::test]
+    async fn decode() {
+        let (url, mut requests) = server(
+            "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: 17\r\n\r\n{\"name\":\"rasmus\"}",
+        )
+        .await;
+
+        let client = Client::builder().base_url(&url).build().unwrap();
+        let user: User = client
+            .get("users/1")
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            user,
+            User {
+                name: String::from("rasmus")
+            }
+        );
+        let raw = requests.recv().await.unwrap();
+        assert!(raw.starts_with("GET /api/users/1 "));
+    }
+
+    #[tokio::test]
+    async fn error_for_status() {
+        let (url, _requests) =
+            server("HTTP/1.1 404 Not Found\r\ncontent-length: 7\r\n\r\nmissing").await;
+
+        let err = Client::builder()
+            .base_url(&url)
+            .build()
+            .unwrap()
+            .get("users/2")
+            .send()
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.status(), Some(StatusCode::NOT_FOUND));
+        assert!(matches!(err, ClientError::Status { body, .. } if body == "missing"));
+    }
+
+    struct Tag;
+
+    struct TagWork<W>(W);
+
+    impl<W> Middleware<(), Request, W> for Tag
+    where
+        W: Work<(), Request, Output = HttpResponse, Error = ClientError>,
+    {
+        type Work = TagWork<W>;
+
+        fn wrap(&self, handle: W) -> Self::Work {
+            TagWork(handle)
+        }
+    }
+
+    impl<W> Work<(), Request> for TagWork<W>
+    where
+        W: Work<(), Request, Output = HttpResponse, Error = ClientError>,
+    {
+        type Output = StatusCode;
+        type Error = ClientError;
+        type Future<'a>
+            = LocalBoxFuture<'a, Result<StatusCode, ClientError>>
+        where
+            Self: 'a;
+
+        fn call<'a>(&'a self, context: &'a (), mut req: Request) -> Self::Future<'a> {
+            req.headers_mut()
+                .insert("x-tag", HeaderValue::from_static("tagged"));
+            let future = self.0.call(context, req);
+            async move { future.await.map(|resp| resp.status()) }.boxed_local()
+        }
+    }
+
+    #[tokio::test]
+    async fn middleware() {
+        let (url, mut requests) = server("HTTP/1.1 204 No Content\r\n\r\n").await;
+
+        let client = Client::builder().base_url(&url).build().unwrap().wrap(Tag);
+        let status = client.delete("users/3").send().await.unwrap();
+
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        assert!(requests.recv().await.unwrap().contains("x-tag: tagged"));
+    }
+}
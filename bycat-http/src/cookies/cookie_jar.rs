@@ -10,7 +10,7 @@ use parking_lot::RwLock;
 
 use crate::FromRequestParts;
 
-use super::SignedJar;
+use super::{PrivateJar, SignedJar};
 
 #[derive(Debug, Clone)]
 pub struct CookieJar {
@@ -39,6 +39,10 @@ impl CookieJar {
     pub fn signed<'a>(&self, key: &'a cookie::Key) -> SignedJar<'a> {
         super::SignedJar::new(self.clone(), key)
     }
+
+    pub fn private(&self, key: &cookie::Key) -> PrivateJar {
+        super::PrivateJar::new(self.clone(), key.clone())
+    }
 }
 
 impl CookieJar {
@@ -5,4 +5,4 @@ mod signed;
 
 pub use self::{cookie_jar::*, middleware::*, private::*, signed::*};
 
-pub use cookie::{Cookie, CookieBuilder};
+pub use cookie::{Cookie, CookieBuilder, Key, SameSite};
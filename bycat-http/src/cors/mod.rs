@@ -11,7 +11,7 @@ use http::{
     HeaderMap, HeaderValue, Method, Request, Response,
     header::{
         ACCESS_CONTROL_ALLOW_CREDENTIALS, ACCESS_CONTROL_ALLOW_METHODS,
-        ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_REQUEST_METHOD,
+        ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_REQUEST_METHOD, ALLOW,
     },
 };
 use pin_project_lite::pin_project;
@@ -105,9 +105,25 @@ impl CorsOptions {
         if preflight {
             self.headers.apply(headers);
 
+            // The router answers preflight requests with the methods routed for the path,
+            // so only advertise those
+            let allowed = headers
+                .get(ALLOW)
+                .and_then(|allow| allow.to_str().ok())
+                .map(|allow| {
+                    allow
+                        .split(',')
+                        .filter_map(|method| Method::from_bytes(method.trim().as_bytes()).ok())
+                        .fold(MethodFilter::empty(), |filter, method| {
+                            filter | MethodFilter::from(method)
+                        })
+                })
+                .map(|allowed| allowed & self.methods)
+                .unwrap_or(self.methods);
+
             let mut methods = String::new();
 
-            for (idx, method) in self.methods.iter().enumerate() {
+            for (idx, method) in allowed.iter().enumerate() {
                 if idx > 0 {
                     methods.push(',');
                 }
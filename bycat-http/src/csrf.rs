@@ -0,0 +1,847 @@
+use crate::{
+    Error, FromRequestParts,
+    body::{HttpBody, ToBytes, to_bytes},
+    cookies::CookieJar,
+    error::BoxError,
+    rejection::{CsrfRejection, PayloadTooLarge},
+    session::{SessionId, SessionStore},
+    util::constant_time_eq,
+};
+use alloc::{
+    borrow::{Cow, ToOwned},
+    boxed::Box,
+    string::String,
+    sync::Arc,
+};
+use base64::engine::{Engine as _, general_purpose::URL_SAFE_NO_PAD};
+use bycat::{Middleware, Work};
+use bycat_value::{Map, Value};
+use cookie::{Cookie, Key, SameSite};
+use core::{
+    fmt,
+    future::Ready,
+    marker::PhantomData,
+    pin::Pin,
+    task::{Poll, ready},
+};
+use futures::future::BoxFuture;
+use http::{
+    HeaderName, Method, Request,
+    header::{CONTENT_LENGTH, CONTENT_TYPE},
+    request::Parts,
+};
+use http_body_util::Limited;
+use parking_lot::Mutex;
+use pin_project_lite::pin_project;
+
+const SESSION_KEY: &str = "_csrf";
+
+/// Protects unsafe requests against cross-site request forgery.
+///
+/// Tokens are kept in the [`Session`](crate::session::Session) when the
+/// [`Sessions`](crate::session::Sessions) middleware runs before this one,
+/// and in a signed cookie otherwise (the double-submit pattern), which needs
+/// the [`Cookies`](crate::cookies::Cookies) middleware.
+///
+/// Requests with a method other than `GET`, `HEAD`, `OPTIONS` and `TRACE`
+/// must send the token back in the `x-csrf-token` header, or in the
+/// `csrf_token` field of an urlencoded form. Use the [`CsrfToken`] extractor
+/// to render it.
+#[derive(Clone)]
+pub struct Csrf {
+    config: Config,
+}
+
+#[derive(Clone)]
+struct Config {
+    header: HeaderName,
+    field: Cow<'static, str>,
+    cookie_name: Cow<'static, str>,
+    key: Key,
+    secure: bool,
+    form_limit: u64,
+}
+
+impl Default for Csrf {
+    fn default() -> Self {
+        Csrf::new()
+    }
+}
+
+impl Csrf {
+    pub fn new() -> Csrf {
+        Csrf {
+            config: Config {
+                header: HeaderName::from_static("x-csrf-token"),
+                field: Cow::Borrowed("csrf_token"),
+                cookie_name: Cow::Borrowed("csrf_token"),
+                key: Key::generate(),
+                secure: true,
+                form_limit: 64 * 1024,
+            },
+        }
+    }
+
+    pub fn header(mut self, header: HeaderName) -> Self {
+        self.config.header = header;
+        self
+    }
+
+    /// The form field holding the token.
+    pub fn field<T>(mut self, field: T) -> Self
+    where
+        T: Into<Cow<'static, str>>,
+    {
+        self.config.field = field.into();
+        self
+    }
+
+    pub fn cookie_name<T>(mut self, name: T) -> Self
+    where
+        T: Into<Cow<'static, str>>,
+    {
+        self.config.cookie_name = name.into();
+        self
+    }
+
+    /// The key signing the cookie, when tokens aren't kept in the session.
+    pub fn key(mut self, key: Key) -> Self {
+        self.config.key = key;
+        self
+    }
+
+    /// Defaults to `true`.
+    pub fn secure(mut self, secure: bool) -> Self {
+        self.config.secure = secure;
+        self
+    }
+
+    /// The largest form body read looking for the token. Defaults to 64KiB.
+    pub fn form_limit(mut self, limit: u64) -> Self {
+        self.config.form_limit = limit;
+        self
+    }
+}
+
+impl Config {
+    fn read_cookie(&self, jar: &CookieJar) -> Option<String> {
+        jar.signed(&self.key)
+            .get(&self.cookie_name)
+            .map(|cookie| cookie.value().to_owned())
+    }
+
+    fn write_cookie(&self, jar: &CookieJar, token: String) {
+        let mut cookie = Cookie::new(self.cookie_name.clone(), token);
+        cookie.set_path("/");
+        cookie.set_http_only(true);
+        cookie.set_secure(self.secure);
+        cookie.set_same_site(SameSite::Strict);
+        jar.signed(&self.key).add(cookie);
+    }
+}
+
+impl<C, B, T> Middleware<C, Request<B>, T> for Csrf
+where
+    T: Work<C, Request<B>>,
+    T::Error: Into<Error>,
+    B: HttpBody,
+    B::Error: Into<BoxError>,
+{
+    type Work = CsrfWork<C, B, T>;
+
+    fn wrap(&self, handler: T) -> Self::Work {
+        CsrfWork {
+            work: handler,
+            config: Arc::new(self.config.clone()),
+            req: PhantomData,
+        }
+    }
+}
+
+pub struct CsrfWork<C, B, T> {
+    work: T,
+    config: Arc<Config>,
+    req: PhantomData<(C, B)>,
+}
+
+impl<C, B, T: Clone> Clone for CsrfWork<C, B, T> {
+    fn clone(&self) -> Self {
+        Self {
+            work: self.work.clone(),
+            config: self.config.clone(),
+            req: PhantomData,
+        }
+    }
+}
+
+impl<C, B, T> Work<C, Request<B>> for CsrfWork<C, B, T>
+where
+    T: Work<C, Request<B>>,
+    T::Error: Into<Error>,
+    B: HttpBody,
+    B::Error: Into<BoxError>,
+{
+    type Output = T::Output;
+
+    type Error = Error;
+
+    type Future<'a>
+        = CsrfWorkFuture<'a, C, B, T>
+    where
+        Self: 'a,
+        C: 'a;
+
+    fn call<'a>(&'a self, context: &'a C, req: Request<B>) -> Self::Future<'a> {
+        CsrfWorkFuture {
+            state: CsrfWorkState::Init {
+                req: Some(req),
+                context,
+                work: &self.work,
+            },
+            config: &self.config,
+            backend: None,
+            slot: None,
+        }
+    }
+}
+
+/// Where the token of the current request is kept.
+#[derive(Clone)]
+enum Backend {
+    Session { store: SessionStore, id: SessionId },
+    Cookie(CookieJar),
+}
+
+fn load_token(
+    store: SessionStore,
+    id: SessionId,
+) -> BoxFuture<'static, Result<Option<String>, Error>> {
+    Box::pin(async move {
+        let session = store.load(id).await?;
+        Ok(match session.get(SESSION_KEY) {
+            Some(Value::String(token)) => Some(token.as_str().to_owned()),
+            _ => None,
+        })
+    })
+}
+
+fn save_token(
+    store: SessionStore,
+    id: SessionId,
+    token: String,
+) -> BoxFuture<'static, Result<(), Error>> {
+    Box::pin(async move {
+        let mut session: Map = store.load(id.clone()).await?;
+        session.insert(SESSION_KEY, token);
+
+        if id.id().is_none() {
+            id.generate();
+        }
+
+        store.save(id, &session).await
+    })
+}
+
+enum Next<B> {
+    Call(Request<B>),
+    ReadForm(Request<B>),
+}
+
+fn is_safe(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE
+    )
+}
+
+fn is_form<B>(req: &Request<B>) -> bool {
+    req.headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|essence| {
+            essence
+                .trim()
+                .eq_ignore_ascii_case("application/x-www-form-urlencoded")
+        })
+}
+
+fn verify(expected: Option<&str>, found: Option<&[u8]>) -> Result<(), Error> {
+    match (expected, found) {
+        (Some(expected), Some(found)) if constant_time_eq(expected.as_bytes(), found) => Ok(()),
+        (Some(_), Some(_)) => Err(CsrfRejection::Mismatch.into()),
+        _ => Err(CsrfRejection::Missing.into()),
+    }
+}
+
+impl Config {
+    fn check<B>(&self, req: Request<B>, expected: Option<&str>) -> Result<Next<B>, Error> {
+        if is_safe(req.method()) {
+            return Ok(Next::Call(req));
+        }
+
+        if let Some(found) = req.headers().get(&self.header) {
+            verify(expected, Some(found.as_bytes()))?;
+            return Ok(Next::Call(req));
+        }
+
+        if expected.is_some() && is_form(&req) {
+            if let Some(len) = req
+                .headers()
+                .get(CONTENT_LENGTH)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse::<u64>().ok())
+                && len > self.form_limit
+            {
+                return Err(PayloadTooLarge {
+                    limit: Some(self.form_limit),
+                }
+                .into());
+            }
+
+            return Ok(Next::ReadForm(req));
+        }
+
+        Err(CsrfRejection::Missing.into())
+    }
+
+    fn check_form(&self, body: &[u8], expected: Option<&str>) -> Result<(), Error> {
+        let found = form_urlencoded::parse(body)
+            .find(|(name, _)| *name == self.field)
+            .map(|(_, value)| value);
+
+        verify(expected, found.as_deref().map(str::as_bytes))
+    }
+}
+
+pin_project! {
+    #[project = CsrfWorkStateProj]
+    enum CsrfWorkState<'a, C, B, T: 'a>
+    where
+        T: Work<C, Request<B>>,
+        B: http_body::Body,
+        B::Error: Into<BoxError>,
+    {
+        Init {
+            req: Option<Request<B>>,
+            context: &'a C,
+            work: &'a T,
+        },
+        Load {
+            #[pin]
+            future: BoxFuture<'static, Result<Option<String>, Error>>,
+            req: Option<Request<B>>,
+            context: &'a C,
+            work: &'a T,
+        },
+        Form {
+            #[pin]
+            future: ToBytes<Limited<B>>,
+            parts: Option<Parts>,
+            context: &'a C,
+            work: &'a T,
+        },
+        Call {
+            #[pin]
+            future: T::Future<'a>,
+        },
+        Save {
+            #[pin]
+            future: BoxFuture<'static, Result<(), Error>>,
+            output: Option<T::Output>,
+        },
+        Done,
+    }
+}
+
+pin_project! {
+    pub struct CsrfWorkFuture<'a, C, B, T>
+    where
+        T: Work<C, Request<B>>,
+        B: http_body::Body,
+        B::Error: Into<BoxError>,
+    {
+        #[pin]
+        state: CsrfWorkState<'a, C, B, T>,
+        config: &'a Arc<Config>,
+        backend: Option<Backend>,
+        slot: Option<TokenSlot>,
+    }
+}
+
+impl<'a, C, B, T> CsrfWorkFuture<'a, C, B, T>
+where
+    T: Work<C, Request<B>>,
+    T::Error: Into<Error>,
+    B: HttpBody,
+    B::Error: Into<BoxError>,
+{
+    /// Verify the request against the token in `slot`, then move on to reading
+    /// the form or calling the handler.
+    fn next(
+        mut self: Pin<&mut Self>,
+        req: Request<B>,
+        context: &'a C,
+        work: &'a T,
+    ) -> Result<(), Error> {
+        let mut this = self.as_mut().project();
+        let slot = this.slot.as_ref().unwrap();
+        let expected = slot.token();
+
+        match this.config.check(req, expected.as_deref())? {
+            Next::Call(mut req) => {
+                req.extensions_mut().insert(slot.clone());
+                let future = work.call(context, req);
+                this.state.set(CsrfWorkState::Call { future });
+            }
+            Next::ReadForm(req) => {
+                let (parts, body) = req.into_parts();
+                let future = to_bytes(Limited::new(body, this.config.form_limit as usize));
+                this.state.set(CsrfWorkState::Form {
+                    future,
+                    parts: Some(parts),
+                    context,
+                    work,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a, C, B, T> Future for CsrfWorkFuture<'a, C, B, T>
+where
+    T: Work<C, Request<B>>,
+    T::Error: Into<Error>,
+    B: HttpBody,
+    B::Error: Into<BoxError>,
+{
+    type Output = Result<T::Output, Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut core::task::Context<'_>) -> Poll<Self::Output> {
+        loop {
+            let mut this = self.as_mut().project();
+
+            match this.state.as_mut().project() {
+                CsrfWorkStateProj::Init { req, context, work } => {
+                    let req = req.take().unwrap();
+                    let (context, work) = (*context, *work);
+
+                    let session = req
+                        .extensions()
+                        .get::<SessionStore>()
+                        .zip(req.extensions().get::<SessionId>());
+
+                    if let Some((store, id)) = session {
+                        let (store, id) = (store.clone(), id.clone());
+                        let future = load_token(store.clone(), id.clone());
+                        *this.backend = Some(Backend::Session { store, id });
+                        this.state.set(CsrfWorkState::Load {
+                            future,
+                            req: Some(req),
+                            context,
+                            work,
+                        });
+                        continue;
+                    }
+
+                    let jar = CookieJar::from_request(&req)?;
+                    let token = this.config.read_cookie(&jar);
+                    *this.backend = Some(Backend::Cookie(jar));
+                    *this.slot = Some(TokenSlot::new(token, this.config.clone()));
+
+                    if let Err(err) = self.as_mut().next(req, context, work) {
+                        self.project().state.set(CsrfWorkState::Done);
+                        return Poll::Ready(Err(err));
+                    }
+                }
+                CsrfWorkStateProj::Load {
+                    future,
+                    req,
+                    context,
+                    work,
+                } => {
+                    let token = match ready!(future.poll(cx)) {
+                        Ok(token) => token,
+                        Err(err) => {
+                            this.state.set(CsrfWorkState::Done);
+                            return Poll::Ready(Err(err));
+                        }
+                    };
+
+                    let req = req.take().unwrap();
+                    let (context, work) = (*context, *work);
+                    *this.slot = Some(TokenSlot::new(token, this.config.clone()));
+
+                    if let Err(err) = self.as_mut().next(req, context, work) {
+                        self.project().state.set(CsrfWorkState::Done);
+                        return Poll::Ready(Err(err));
+                    }
+                }
+                CsrfWorkStateProj::Form {
+                    future,
+                    parts,
+                    context,
+                    work,
+                } => {
+                    let body = match ready!(future.poll(cx)) {
+                        Ok(body) => body,
+                        Err(err) => {
+                            this.state.set(CsrfWorkState::Done);
+                            return Poll::Ready(Err(err));
+                        }
+                    };
+
+                    let slot = this.slot.as_ref().unwrap();
+
+                    if let Err(err) = this.config.check_form(&body, slot.token().as_deref()) {
+                        this.state.set(CsrfWorkState::Done);
+                        return Poll::Ready(Err(err));
+                    }
+
+                    let mut req = Request::from_parts(parts.take().unwrap(), B::from_bytes(body));
+                    req.extensions_mut().insert(slot.clone());
+
+                    let future = work.call(*context, req);
+                    this.state.set(CsrfWorkState::Call { future });
+                }
+                CsrfWorkStateProj::Call { future } => {
+                    let output = match ready!(future.poll(cx)) {
+                        Ok(output) => output,
+                        Err(err) => {
+                            this.state.set(CsrfWorkState::Done);
+                            return Poll::Ready(Err(err.into()));
+                        }
+                    };
+
+                    let issued = this.slot.as_ref().and_then(TokenSlot::issued);
+
+                    match (issued, this.backend.take()) {
+                        (Some(token), Some(Backend::Session { store, id })) => {
+                            this.state.set(CsrfWorkState::Save {
+                                future: save_token(store, id, token),
+                                output: Some(output),
+                            });
+                        }
+                        (Some(token), Some(Backend::Cookie(jar))) => {
+                            this.config.write_cookie(&jar, token);
+                            this.state.set(CsrfWorkState::Done);
+                            return Poll::Ready(Ok(output));
+                        }
+                        _ => {
+                            this.state.set(CsrfWorkState::Done);
+                            return Poll::Ready(Ok(output));
+                        }
+                    }
+                }
+                CsrfWorkStateProj::Save { future, output } => {
+                    let ret = ready!(future.poll(cx));
+                    let output = output.take().unwrap();
+                    this.state.set(CsrfWorkState::Done);
+                    return Poll::Ready(ret.map(|_| output));
+                }
+                CsrfWorkStateProj::Done => {
+                    panic!("Poll after done")
+                }
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct Slot {
+    token: Option<String>,
+    issued: bool,
+}
+
+/// The token of the current request, shared between the middleware and the
+/// [`CsrfToken`] extractor.
+#[derive(Clone)]
+struct TokenSlot {
+    slot: Arc<Mutex<Slot>>,
+    config: Arc<Config>,
+}
+
+impl TokenSlot {
+    fn new(token: Option<String>, config: Arc<Config>) -> TokenSlot {
+        TokenSlot {
+            slot: Arc::new(Mutex::new(Slot {
+                token,
+                issued: false,
+            })),
+            config,
+        }
+    }
+
+    fn token(&self) -> Option<String> {
+        self.slot.lock().token.clone()
+    }
+
+    /// The token, generating one when the client doesn't have one yet.
+    fn get_or_issue(&self) -> Result<String, Error> {
+        let mut slot = self.slot.lock();
+
+        if let Some(token) = &slot.token {
+            return Ok(token.clone());
+        }
+
+        let mut bytes = [0u8; 32];
+        getrandom::fill(&mut bytes).map_err(Error::custom)?;
+        let token = URL_SAFE_NO_PAD.encode(bytes);
+
+        slot.token = Some(token.clone());
+        slot.issued = true;
+
+        Ok(token)
+    }
+
+    /// The token, if it was generated during this request.
+    fn issued(&self) -> Option<String> {
+        let slot = self.slot.lock();
+        slot.issued.then(|| slot.token.clone()).flatten()
+    }
+}
+
+/// The CSRF token to include in forms or requests made by scripts.
+///
+/// ```ignore
+/// handler(|token: CsrfToken| async move {
+///     Html(format!(
+///         r#"<form method="post"><input type="hidden" name="{}" value="{}"></form>"#,
+///         token.field(),
+///         token
+///     ))
+/// })
+/// .wrap(Csrf::default())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsrfToken {
+    token: String,
+    field: Cow<'static, str>,
+    header: HeaderName,
+}
+
+impl CsrfToken {
+    pub fn as_str(&self) -> &str {
+        &self.token
+    }
+
+    /// Name of the form field the middleware reads the token from.
+    pub fn field(&self) -> &str {
+        &self.field
+    }
+
+    /// Name of the header the middleware reads the token from.
+    pub fn header(&self) -> &HeaderName {
+        &self.header
+    }
+}
+
+impl fmt::Display for CsrfToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.token)
+    }
+}
+
+impl<C> FromRequestParts<C> for CsrfToken {
+    type Future<'a>
+        = Ready<Result<Self, Error>>
+    where
+        C: 'a;
+
+    fn from_request_parts<'a>(parts: &'a mut Parts, _state: &'a C) -> Self::Future<'a> {
+        let Some(slot) = parts.extensions.get::<TokenSlot>() else {
+            return core::future::ready(Err(Error::custom("Csrf middleware not registered")));
+        };
+
+        core::future::ready(slot.get_or_issue().map(|token| CsrfToken {
+            token,
+            field: slot.config.field.clone(),
+            header: slot.config.header.clone(),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        body::Body,
+        cookies::Cookies,
+        handler,
+        session::{MemoryStore, Sessions},
+    };
+    use alloc::{format, string::ToString, vec::Vec};
+    use bycat::prelude::WorkExt;
+    use http::{
+        Response, StatusCode,
+        header::{COOKIE, SET_COOKIE},
+    };
+
+    async fn echo(token: CsrfToken, body: Body) -> Result<String, Error> {
+        let body = to_bytes(body).await?;
+        Ok(format!("{token} {}", String::from_utf8_lossy(&body)))
+    }
+
+    async fn send<W>(work: &W, req: Request<Body>) -> Result<String, Error>
+    where
+        W: Work<(), Request<Body>, Output = Response<Body>, Error = Error>,
+    {
+        let resp = work.call(&(), req).await?;
+        let body = to_bytes(resp.into_body()).await?;
+        Ok(String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    /// Issue a token, returning it along with the `Cookie` header to send it with.
+    async fn issue<W>(work: &W) -> (String, String)
+    where
+        W: Work<(), Request<Body>, Output = Response<Body>, Error = Error>,
+    {
+        let resp = work
+            .call(&(), Request::get("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        let cookies = resp
+            .headers()
+            .get_all(SET_COOKIE)
+            .iter()
+            .map(|value| {
+                value
+                    .to_str()
+                    .unwrap()
+                    .split(';')
+                    .next()
+                    .unwrap()
+                    .to_string()
+            })
+            .collect::<Vec<_>>()
+            .join("; ");
+
+        let body = to_bytes(resp.into_body()).await.unwrap();
+        let token = String::from_utf8(body.to_vec()).unwrap();
+
+        (token.trim().to_string(), cookies)
+    }
+
+    fn post(cookies: &str) -> http::request::Builder {
+        Request::post("/").header(COOKIE, cookies)
+    }
+
+    fn status(result: Result<String, Error>) -> StatusCode {
+        result.unwrap_err().status()
+    }
+
+    #[tokio::test]
+    async fn double_submit_cookie() {
+        let work = handler(echo).wrap(Csrf::new()).wrap(Cookies);
+
+        let (token, cookies) = issue(&work).await;
+        assert!(cookies.starts_with("csrf_token="));
+        assert!(!token.is_empty());
+
+        // Header
+        let body = send(
+            &work,
+            post(&cookies)
+                .header("x-csrf-token", &token)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(body, format!("{token} "));
+
+        // Form field, with the body still readable by the handler
+        let form = format!("name=rasmus&csrf_token={token}");
+        let body = send(
+            &work,
+            post(&cookies)
+                .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+                .body(Body::from(form.clone()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(body, format!("{token} {form}"));
+
+        // Missing
+        let result = send(&work, post(&cookies).body(Body::empty()).unwrap()).await;
+        assert_eq!(status(result), StatusCode::FORBIDDEN);
+
+        // Mismatch
+        let result = send(
+            &work,
+            post(&cookies)
+                .header("x-csrf-token", "forged")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status(result), StatusCode::FORBIDDEN);
+
+        // A token without its cookie
+        let result = send(
+            &work,
+            post("")
+                .header("x-csrf-token", &token)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status(result), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn session_bound() {
+        let work = handler(echo)
+            .wrap(Csrf::new())
+            .wrap(Sessions::new(MemoryStore::default()))
+            .wrap(Cookies);
+
+        let (token, cookies) = issue(&work).await;
+        assert!(cookies.starts_with("sess_id="));
+        assert!(!cookies.contains("csrf_token"));
+
+        let body = send(
+            &work,
+            post(&cookies)
+                .header("x-csrf-token", &token)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(body, format!("{token} "));
+
+        // Another session can't use the token
+        let (_, other) = issue(&work).await;
+        let result = send(
+            &work,
+            post(&other)
+                .header("x-csrf-token", &token)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status(result), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn form_limit() {
+        let work = handler(echo).wrap(Csrf::new().form_limit(8)).wrap(Cookies);
+        let (token, cookies) = issue(&work).await;
+
+        let result = send(
+            &work,
+            post(&cookies)
+                .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+                .header(CONTENT_LENGTH, 64)
+                .body(Body::from(format!("csrf_token={token}")))
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status(result), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+}
@@ -1,5 +1,9 @@
-use crate::{IntoResponse, body::HttpBody};
-use alloc::{convert::Infallible, fmt};
+use crate::{
+    IntoResponse,
+    body::HttpBody,
+    rejection::{BadRequest, Rejection},
+};
+use alloc::{boxed::Box, convert::Infallible, fmt};
 use bytes::Bytes;
 use http::{Error as HttpError, Response, StatusCode};
 
@@ -11,7 +15,8 @@ pub type Result<T> = core::result::Result<T, Error>;
 enum ErrorKind {
     NotFound,
     MaxSizeReached,
-    Internal(BoxError),
+    Rejection(Box<dyn Rejection>),
+    Internal(StatusCode, BoxError),
     Http(HttpError),
 }
 
@@ -33,11 +38,55 @@ impl Error {
         }
     }
 
+    pub fn bad_request<T: Into<BoxError>>(error: T) -> Error {
+        Error::reject(BadRequest(error.into()))
+    }
+
+    pub fn reject<T: Rejection>(rejection: T) -> Error {
+        Error {
+            kind: ErrorKind::Rejection(Box::new(rejection)),
+        }
+    }
+
     pub fn custom<T: Into<BoxError>>(custom: T) -> Error {
+        // Errors boxed on their way through a body are kept as they were
+        match custom.into().downcast::<Error>() {
+            Ok(error) => *error,
+            Err(custom) => Error::custom_with_status(StatusCode::INTERNAL_SERVER_ERROR, custom),
+        }
+    }
+
+    /// A server error answered with `status` instead of a 500, like a 502
+    /// when an upstream fails.
+    pub fn custom_with_status<T: Into<BoxError>>(status: StatusCode, custom: T) -> Error {
+        debug_assert!(status.is_server_error(), "{status} is not a server error");
         Error {
-            kind: ErrorKind::Internal(custom.into()),
+            kind: ErrorKind::Internal(status, custom.into()),
         }
     }
+
+    pub fn status(&self) -> StatusCode {
+        match &self.kind {
+            ErrorKind::NotFound => StatusCode::NOT_FOUND,
+            ErrorKind::MaxSizeReached => StatusCode::PAYLOAD_TOO_LARGE,
+            ErrorKind::Rejection(rejection) => rejection.status(),
+            ErrorKind::Internal(status, _) => *status,
+            ErrorKind::Http(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Returns the rejection, if the error was caused by the client.
+    pub fn rejection(&self) -> Option<&dyn Rejection> {
+        match &self.kind {
+            ErrorKind::Rejection(rejection) => Some(&**rejection),
+            _ => None,
+        }
+    }
+
+    /// Whether the error message is safe to expose to clients.
+    pub fn is_public(&self) -> bool {
+        !matches!(self.kind, ErrorKind::Internal(..) | ErrorKind::Http(_))
+    }
 }
 
 impl fmt::Display for Error {
@@ -49,10 +98,13 @@ impl fmt::Display for Error {
             ErrorKind::MaxSizeReached => {
                 write!(f, "Maximum Size Reached")
             }
+            ErrorKind::Rejection(rejection) => {
+                write!(f, "{rejection}")
+            }
             ErrorKind::Http(err) => {
                 write!(f, "HTTP Error: {err}")
             }
-            ErrorKind::Internal(error) => {
+            ErrorKind::Internal(_, error) => {
                 write!(f, "{error}")
             }
         }
@@ -64,8 +116,9 @@ impl core::error::Error for Error {
         match &self.kind {
             ErrorKind::NotFound => None,
             ErrorKind::MaxSizeReached => None,
+            ErrorKind::Rejection(rejection) => Some(&**rejection),
             ErrorKind::Http(error) => Some(&*error),
-            ErrorKind::Internal(error) => Some(&**error),
+            ErrorKind::Internal(_, error) => Some(&**error),
         }
     }
 }
@@ -81,19 +134,29 @@ impl<B: HttpBody> IntoResponse<B> for Error {
                 B::from_bytes(Bytes::from("Maximum Size Reached")),
                 StatusCode::PAYLOAD_TOO_LARGE,
             ),
+            ErrorKind::Rejection(rejection) => (
+                B::from_bytes(Bytes::from(rejection.to_string())),
+                rejection.status(),
+            ),
             ErrorKind::Http(http) => {
                 let body = B::from_bytes(Bytes::from(format!("HTTP Error: {}", http)));
                 (body, StatusCode::INTERNAL_SERVER_ERROR)
             }
-            ErrorKind::Internal(_) => (
-                B::from_bytes(Bytes::from("Internal Server Error")),
-                StatusCode::INTERNAL_SERVER_ERROR,
+            ErrorKind::Internal(status, _) => (
+                B::from_bytes(Bytes::from(
+                    status.canonical_reason().unwrap_or("Internal Server Error"),
+                )),
+                *status,
             ),
         };
 
         let mut resp = Response::new(body);
         *resp.status_mut() = status;
 
+        if let ErrorKind::Rejection(rejection) = &self.kind {
+            rejection.headers(resp.headers_mut());
+        }
+
         resp
     }
 }
@@ -109,7 +172,7 @@ impl From<HttpError> for Error {
 impl From<Infallible> for Error {
     fn from(value: Infallible) -> Self {
         Error {
-            kind: ErrorKind::Internal(value.into()),
+            kind: ErrorKind::Internal(StatusCode::INTERNAL_SERVER_ERROR, value.into()),
         }
     }
 }
@@ -0,0 +1,500 @@
+use crate::{Error, IntoResponse, body::HttpBody, rejection::DetailValue, rejection::Details};
+use alloc::{borrow::Cow, string::String, sync::Arc};
+use bycat::{Middleware, Work};
+use bytes::Bytes;
+use core::{
+    fmt::Write as _,
+    marker::PhantomData,
+    task::{Poll, ready},
+};
+use http::{HeaderValue, Method, Request, Response, StatusCode, Uri, header::CONTENT_TYPE};
+use pin_project_lite::pin_project;
+
+/// The parts of the request needed to render an error.
+#[derive(Debug, Clone)]
+pub struct ErrorContext {
+    method: Method,
+    uri: Uri,
+    accept: Option<HeaderValue>,
+}
+
+impl ErrorContext {
+    pub fn from_request<B>(req: &Request<B>) -> ErrorContext {
+        ErrorContext {
+            method: req.method().clone(),
+            uri: req.uri().clone(),
+            accept: req.headers().get(http::header::ACCEPT).cloned(),
+        }
+    }
+
+    pub fn method(&self) -> &Method {
+        &self.method
+    }
+
+    pub fn uri(&self) -> &Uri {
+        &self.uri
+    }
+
+    /// Whether the client explicitly lists the given media type, ignoring wildcards.
+    pub fn lists(&self, mime: &str) -> bool {
+        self.accept_items()
+            .any(|item| item.eq_ignore_ascii_case(mime))
+    }
+
+    fn accept_items(&self) -> impl Iterator<Item = &str> {
+        self.accept
+            .as_ref()
+            .and_then(|m| m.to_str().ok())
+            .unwrap_or_default()
+            .split(',')
+            .map(|item| item.split(';').next().unwrap_or_default().trim())
+    }
+}
+
+/// Renders errors returned from handlers into responses.
+pub trait ErrorHandler<B> {
+    fn render(&self, ctx: &ErrorContext, error: Error) -> Response<B>;
+}
+
+impl<F, B> ErrorHandler<B> for F
+where
+    F: Fn(&ErrorContext, Error) -> Response<B>,
+{
+    fn render(&self, ctx: &ErrorContext, error: Error) -> Response<B> {
+        (self)(ctx, error)
+    }
+}
+
+/// The members defined by RFC 9457.
+const RESERVED: [&str; 5] = ["type", "title", "status", "detail", "instance"];
+
+/// A problem details object as described in RFC 9457.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    pub status: StatusCode,
+    pub type_uri: Cow<'static, str>,
+    pub title: Cow<'static, str>,
+    pub detail: Option<String>,
+    pub instance: Option<String>,
+    pub details: Details,
+}
+
+impl Problem {
+    pub fn from_error(ctx: &ErrorContext, error: &Error, type_base: Option<&str>) -> Problem {
+        let status = error.status();
+        let mut details = Details::default();
+
+        let type_uri = match (error.rejection(), type_base) {
+            (Some(rejection), Some(base)) => Cow::Owned(format!("{base}{}", rejection.kind())),
+            _ => Cow::Borrowed("about:blank"),
+        };
+
+        if let Some(rejection) = error.rejection() {
+            rejection.details(&mut details);
+        }
+
+        Problem {
+            status,
+            type_uri,
+            title: Cow::Borrowed(status.canonical_reason().unwrap_or("Unknown Error")),
+            detail: error.is_public().then(|| error.to_string()),
+            instance: Some(ctx.uri().path().into()),
+            details,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut output = serde_json::Map::new();
+
+        output.insert("type".into(), self.type_uri.as_ref().into());
+        output.insert("title".into(), self.title.as_ref().into());
+        output.insert("status".into(), self.status.as_u16().into());
+
+        if let Some(detail) = &self.detail {
+            output.insert("detail".into(), detail.as_str().into());
+        }
+
+        if let Some(instance) = &self.instance {
+            output.insert("instance".into(), instance.as_str().into());
+        }
+
+        for (key, value) in &self.details {
+            // Members of rejections must not override the standard members
+            if RESERVED.contains(key) {
+                continue;
+            }
+
+            let value = match value {
+                DetailValue::String(value) => value.as_str().into(),
+                DetailValue::Number(value) => (*value).into(),
+            };
+            output.insert((*key).into(), value);
+        }
+
+        serde_json::Value::Object(output).to_string()
+    }
+
+    pub fn to_html(&self) -> String {
+        let mut output = String::new();
+
+        write!(
+            output,
+            "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>{status} {title}</title></head><body><h1>{status} {title}</h1>",
+            status = self.status.as_u16(),
+            title = HtmlStr(&self.title)
+        )
+        .expect("write");
+
+        if let Some(detail) = &self.detail {
+            write!(output, "<p>{}</p>", HtmlStr(detail)).expect("write");
+        }
+
+        if !self.details.is_empty() {
+            output.push_str("<dl>");
+            for (key, value) in &self.details {
+                match value {
+                    DetailValue::String(value) => {
+                        write!(
+                            output,
+                            "<dt>{}</dt><dd>{}</dd>",
+                            HtmlStr(key),
+                            HtmlStr(value)
+                        )
+                    }
+                    DetailValue::Number(value) => {
+                        write!(output, "<dt>{}</dt><dd>{}</dd>", HtmlStr(key), value)
+                    }
+                }
+                .expect("write");
+            }
+            output.push_str("</dl>");
+        }
+
+        output.push_str("</body></html>");
+        output
+    }
+}
+
+/// Render errors as `application/problem+json` (RFC 9457).
+#[derive(Debug, Clone, Default)]
+pub struct ProblemDetails {
+    type_base: Option<Cow<'static, str>>,
+}
+
+impl ProblemDetails {
+    pub fn new() -> ProblemDetails {
+        ProblemDetails::default()
+    }
+
+    /// Base uri used for the problem `type` of rejections, eg. `https://example.com/problems/`.
+    /// The kind of the rejection is appended. Without a base, the type is `about:blank`.
+    pub fn type_base(mut self, base: impl Into<Cow<'static, str>>) -> Self {
+        self.type_base = Some(base.into());
+        self
+    }
+}
+
+impl<B: HttpBody> ErrorHandler<B> for ProblemDetails {
+    fn render(&self, ctx: &ErrorContext, error: Error) -> Response<B> {
+        let problem = Problem::from_error(ctx, &error, self.type_base.as_deref());
+        render(
+            &error,
+            problem.status,
+            "application/problem+json",
+            Bytes::from(problem.to_json()),
+        )
+    }
+}
+
+/// Render errors as a minimal html page.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HtmlErrors;
+
+impl<B: HttpBody> ErrorHandler<B> for HtmlErrors {
+    fn render(&self, ctx: &ErrorContext, error: Error) -> Response<B> {
+        let problem = Problem::from_error(ctx, &error, None);
+        render(
+            &error,
+            problem.status,
+            "text/html; charset=utf-8",
+            Bytes::from(problem.to_html()),
+        )
+    }
+}
+
+/// Render problem details for clients accepting json, and html otherwise.
+#[derive(Debug, Clone, Default)]
+pub struct Negotiate {
+    problem: ProblemDetails,
+}
+
+impl Negotiate {
+    pub fn new(problem: ProblemDetails) -> Negotiate {
+        Negotiate { problem }
+    }
+}
+
+impl<B: HttpBody> ErrorHandler<B> for Negotiate {
+    fn render(&self, ctx: &ErrorContext, error: Error) -> Response<B> {
+        let wants_json = ctx.lists("application/problem+json") || ctx.lists("application/json");
+        if !wants_json && ctx.lists("text/html") {
+            HtmlErrors.render(ctx, error)
+        } else {
+            self.problem.render(ctx, error)
+        }
+    }
+}
+
+fn render<B: HttpBody>(
+    error: &Error,
+    status: StatusCode,
+    content_type: &'static str,
+    body: Bytes,
+) -> Response<B> {
+    let mut resp = Response::new(B::from_bytes(body));
+    *resp.status_mut() = status;
+    resp.headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+    if let Some(rejection) = error.rejection() {
+        rejection.headers(resp.headers_mut());
+    }
+    resp
+}
+
+struct HtmlStr<'a>(&'a str);
+
+impl<'a> core::fmt::Display for HtmlStr<'a> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '<' => f.write_str("&lt;")?,
+                '>' => f.write_str("&gt;")?,
+                '&' => f.write_str("&amp;")?,
+                '"' => f.write_str("&quot;")?,
+                '\'' => f.write_str("&#39;")?,
+                c => f.write_char(c)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Middleware rendering errors from the wrapped work with an [`ErrorHandler`].
+pub struct HandleError<H, B> {
+    handler: Arc<H>,
+    body: PhantomData<fn() -> B>,
+}
+
+impl<H, B> HandleError<H, B> {
+    pub fn new(handler: H) -> HandleError<H, B> {
+        HandleError {
+            handler: Arc::new(handler),
+            body: PhantomData,
+        }
+    }
+}
+
+impl<H, B> Clone for HandleError<H, B> {
+    fn clone(&self) -> Self {
+        Self {
+            handler: self.handler.clone(),
+            body: PhantomData,
+        }
+    }
+}
+
+impl<H, C, B, T> Middleware<C, Request<B>, T> for HandleError<H, B>
+where
+    H: ErrorHandler<B>,
+    T: Work<C, Request<B>>,
+    T::Output: IntoResponse<B>,
+    T::Error: Into<Error>,
+{
+    type Work = HandleErrorWork<H, T>;
+
+    fn wrap(&self, handle: T) -> Self::Work {
+        HandleErrorWork {
+            handler: self.handler.clone(),
+            work: handle,
+        }
+    }
+}
+
+pub struct HandleErrorWork<H, T> {
+    handler: Arc<H>,
+    work: T,
+}
+
+impl<H, T: Clone> Clone for HandleErrorWork<H, T> {
+    fn clone(&self) -> Self {
+        Self {
+            handler: self.handler.clone(),
+            work: self.work.clone(),
+        }
+    }
+}
+
+impl<H, C, B, T> Work<C, Request<B>> for HandleErrorWork<H, T>
+where
+    H: ErrorHandler<B>,
+    T: Work<C, Request<B>>,
+    T::Output: IntoResponse<B>,
+    T::Error: Into<Error>,
+{
+    type Output = Response<B>;
+
+    type Error = Error;
+
+    type Future<'a>
+        = HandleErrorFuture<'a, T::Future<'a>, H, B>
+    where
+        Self: 'a,
+        C: 'a;
+
+    fn call<'a>(&'a self, context: &'a C, req: Request<B>) -> Self::Future<'a> {
+        HandleErrorFuture {
+            ctx: Some(ErrorContext::from_request(&req)),
+            future: self.work.call(context, req),
+            handler: Some(&*self.handler),
+            body: PhantomData,
+        }
+    }
+}
+
+pin_project! {
+    pub struct HandleErrorFuture<'a, F, H: ?Sized, B> {
+        #[pin]
+        pub(crate) future: F,
+        pub(crate) ctx: Option<ErrorContext>,
+        pub(crate) handler: Option<&'a H>,
+        pub(crate) body: PhantomData<fn() -> B>,
+    }
+}
+
+impl<'a, F, H, B, O, E> Future for HandleErrorFuture<'a, F, H, B>
+where
+    F: Future<Output = Result<O, E>>,
+    H: ErrorHandler<B> + ?Sized,
+    O: IntoResponse<B>,
+    E: Into<Error>,
+{
+    type Output = Result<Response<B>, Error>;
+
+    fn poll(
+        self: core::pin::Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+    ) -> Poll<Self::Output> {
+        let this = self.project();
+        match ready!(this.future.poll(cx)) {
+            Ok(ret) => Poll::Ready(Ok(ret.into_response())),
+            Err(err) => match (this.handler.take(), this.ctx.take()) {
+                (Some(handler), Some(ctx)) => Poll::Ready(Ok(handler.render(&ctx, err.into()))),
+                _ => Poll::Ready(Err(err.into())),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        body::Body,
+        rejection::{PayloadTooLarge, Rejection},
+    };
+
+    fn context(accept: Option<&'static str>) -> ErrorContext {
+        let mut req = Request::builder().uri("/upload");
+        if let Some(accept) = accept {
+            req = req.header(http::header::ACCEPT, accept);
+        }
+        ErrorContext::from_request(&req.body(()).unwrap())
+    }
+
+    #[test]
+    fn problem_json() {
+        let error = Error::from(PayloadTooLarge { limit: Some(1024) });
+        let problem = Problem::from_error(&context(None), &error, Some("https://errors.dev/"));
+
+        let json: serde_json::Value = serde_json::from_str(&problem.to_json()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "type": "https://errors.dev/payload-too-large",
+                "title": "Payload Too Large",
+                "status": 413,
+                "detail": "Payload exceeds the limit of 1024 bytes",
+                "instance": "/upload",
+                "limit": 1024,
+            })
+        );
+    }
+
+    #[derive(Debug)]
+    struct Clashing;
+
+    impl core::fmt::Display for Clashing {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            f.write_str("clashing")
+        }
+    }
+
+    impl core::error::Error for Clashing {}
+
+    impl Rejection for Clashing {
+        fn status(&self) -> StatusCode {
+            StatusCode::BAD_REQUEST
+        }
+
+        fn kind(&self) -> &'static str {
+            "clashing"
+        }
+
+        fn details(&self, details: &mut Details) {
+            details.insert("status", 200u64.into());
+            details.insert("type", "forged".into());
+            details.insert("field", "name".into());
+        }
+    }
+
+    #[test]
+    fn details_keep_standard_members() {
+        let error = Error::reject(Clashing);
+        let problem = Problem::from_error(&context(None), &error, None);
+
+        let output = problem.to_json();
+        assert_eq!(output.matches("\"status\"").count(), 1);
+
+        let json: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(json["status"], 400);
+        assert_eq!(json["type"], "about:blank");
+        assert_eq!(json["field"], "name");
+    }
+
+    #[test]
+    fn internal_errors_are_hidden() {
+        let error = Error::custom("database password is hunter2");
+        let problem = Problem::from_error(&context(None), &error, None);
+
+        assert_eq!(problem.status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(problem.type_uri, "about:blank");
+        assert!(problem.detail.is_none());
+    }
+
+    #[test]
+    fn negotiate() {
+        let handler = Negotiate::default();
+
+        let browser = context(Some("text/html,application/xhtml+xml,*/*;q=0.8"));
+        let resp: Response<Body> = handler.render(&browser, Error::not_found());
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        assert_eq!(resp.headers()[CONTENT_TYPE], "text/html; charset=utf-8");
+
+        let api = context(Some("application/json"));
+        let resp: Response<Body> = handler.render(&api, Error::not_found());
+        assert_eq!(resp.headers()[CONTENT_TYPE], "application/problem+json");
+
+        let resp: Response<Body> = handler.render(&context(None), Error::not_found());
+        assert_eq!(resp.headers()[CONTENT_TYPE], "application/problem+json");
+    }
+}
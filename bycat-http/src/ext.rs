@@ -3,7 +3,7 @@ use http::Request;
 
 use crate::{
     Error, IntoResponse,
-    matcher::{FilterWork, FilteredWork, Or},
+    matcher::{FilterWork, FilteredWork, HostMatcher, HostWork, Or},
 };
 
 pub trait HttpWorkExt<C, B>: Work<C, Request<B>> {
@@ -15,6 +15,17 @@ pub trait HttpWorkExt<C, B>: Work<C, Request<B>> {
         FilterWork::new(self, matcher)
     }
 
+    /// Only handles requests sent to a host matching `pattern`, see
+    /// [`HostMatcher`]. The captured parts are available as
+    /// [`HostParams`](crate::matcher::HostParams).
+    fn with_host(self, pattern: &str) -> FilterWork<HostWork<Self>, HostMatcher>
+    where
+        Self: Sized,
+    {
+        let host = HostMatcher::new(pattern);
+        FilterWork::new(HostWork::new(self, host.clone()), host)
+    }
+
     fn or<T2>(self, other: T2) -> Or<Self, T2>
     where
         Self: Sized,
@@ -0,0 +1,401 @@
+use alloc::{format, sync::Arc, vec::Vec};
+use bycat::{Middleware, Work};
+use core::{
+    any::Any,
+    fmt,
+    future::{self, Ready},
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+};
+use http::{HeaderMap, Request, request::Parts};
+
+use crate::{Error, FromRequestParts};
+
+/// The address of the connected peer.
+///
+/// Inserted into the request extensions by the server. Behind a proxy this
+/// is the address of the proxy, unless the listener speaks the PROXY protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeerAddr(pub SocketAddr);
+
+impl PeerAddr {
+    pub(crate) fn from_addr<A: Any>(addr: &A) -> Option<PeerAddr> {
+        (addr as &dyn Any)
+            .downcast_ref::<SocketAddr>()
+            .copied()
+            .map(PeerAddr)
+    }
+}
+
+impl<C> FromRequestParts<C> for PeerAddr {
+    type Future<'a>
+        = Ready<Result<Self, Error>>
+    where
+        C: 'a;
+
+    fn from_request_parts<'a>(parts: &'a mut Parts, _state: &'a C) -> Self::Future<'a> {
+        future::ready(
+            parts
+                .extensions
+                .get::<PeerAddr>()
+                .copied()
+                .ok_or_else(|| Error::custom("Missing peer address")),
+        )
+    }
+}
+
+/// The address of the client.
+///
+/// Resolved by [`TrustedProxies`] when the request passed through it,
+/// otherwise it's the address of the connected peer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientAddr(pub IpAddr);
+
+impl<C> FromRequestParts<C> for ClientAddr {
+    type Future<'a>
+        = Ready<Result<Self, Error>>
+    where
+        C: 'a;
+
+    fn from_request_parts<'a>(parts: &'a mut Parts, _state: &'a C) -> Self::Future<'a> {
+        let addr = match parts.extensions.get::<ClientAddr>() {
+            Some(addr) => Some(*addr),
+            None => parts
+                .extensions
+                .get::<PeerAddr>()
+                .map(|peer| ClientAddr(peer.0.ip())),
+        };
+
+        future::ready(addr.ok_or_else(|| Error::custom("Missing client address")))
+    }
+}
+
+/// A range of ip addresses, eg. `10.0.0.0/8` or `fd00::/8`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpCidr {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl IpCidr {
+    pub fn new(addr: IpAddr, prefix: u8) -> Result<IpCidr, InvalidCidr> {
+        let max = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+
+        if prefix > max {
+            return Err(InvalidCidr(format!("{addr}/{prefix}")));
+        }
+
+        Ok(IpCidr { addr, prefix })
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for IpCidr {
+    type Err = InvalidCidr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidCidr(s.into());
+
+        match s.split_once('/') {
+            Some((addr, prefix)) => IpCidr::new(
+                addr.parse().map_err(|_| invalid())?,
+                prefix.parse().map_err(|_| invalid())?,
+            ),
+            None => {
+                let addr: IpAddr = s.parse().map_err(|_| invalid())?;
+                IpCidr::new(addr, if addr.is_ipv4() { 32 } else { 128 })
+            }
+        }
+    }
+}
+
+impl From<IpAddr> for IpCidr {
+    fn from(addr: IpAddr) -> Self {
+        let prefix = if addr.is_ipv4() { 32 } else { 128 };
+        IpCidr { addr, prefix }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidCidr(alloc::string::String);
+
+impl fmt::Display for InvalidCidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid cidr: {}", self.0)
+    }
+}
+
+impl core::error::Error for InvalidCidr {}
+
+/// Middleware resolving the [`ClientAddr`] from the `Forwarded` or
+/// `X-Forwarded-For` headers.
+///
+/// The headers are only trusted when the peer is one of the configured
+/// proxies. The hops are then walked from the nearest, and the first
+/// address that's not a trusted proxy is the client.
+///
+/// ```ignore
+/// let router = SendRouterBuilder::new()
+///     .with_middleware(TrustedProxies::new(["10.0.0.0/8".parse()?]))
+///     .with_get("/", handler(async |ClientAddr(ip): ClientAddr| ip.to_string()));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies {
+    cidrs: Arc<[IpCidr]>,
+}
+
+impl TrustedProxies {
+    pub fn new(cidrs: impl IntoIterator<Item = IpCidr>) -> TrustedProxies {
+        TrustedProxies {
+            cidrs: cidrs.into_iter().collect(),
+        }
+    }
+
+    pub fn parse<S: AsRef<str>>(
+        cidrs: impl IntoIterator<Item = S>,
+    ) -> Result<TrustedProxies, InvalidCidr> {
+        let cidrs = cidrs
+            .into_iter()
+            .map(|cidr| cidr.as_ref().parse())
+            .collect::<Result<Vec<IpCidr>, _>>()?;
+        Ok(TrustedProxies::new(cidrs))
+    }
+
+    pub fn is_trusted(&self, ip: IpAddr) -> bool {
+        self.cidrs.iter().any(|cidr| cidr.contains(ip))
+    }
+
+    pub fn resolve(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        if !self.is_trusted(peer) {
+            return peer;
+        }
+
+        let hops = if headers.contains_key(http::header::FORWARDED) {
+            forwarded(headers)
+        } else {
+            x_forwarded_for(headers)
+        };
+
+        let mut client = peer;
+
+        for hop in hops.iter().rev() {
+            let Some(ip) = hop else {
+                break;
+            };
+
+            client = *ip;
+
+            if !self.is_trusted(client) {
+                break;
+            }
+        }
+
+        client
+    }
+}
+
+fn header_values<'a>(headers: &'a HeaderMap, name: &str) -> impl Iterator<Item = &'a str> {
+    headers
+        .get_all(name)
+        .into_iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+}
+
+fn forwarded(headers: &HeaderMap) -> Vec<Option<IpAddr>> {
+    header_values(headers, "forwarded")
+        .map(|element| {
+            element
+                .split(';')
+                .filter_map(|pair| pair.split_once('='))
+                .find(|(key, _)| key.trim().eq_ignore_ascii_case("for"))
+                .and_then(|(_, node)| parse_node(node))
+        })
+        .collect()
+}
+
+fn x_forwarded_for(headers: &HeaderMap) -> Vec<Option<IpAddr>> {
+    header_values(headers, "x-forwarded-for")
+        .map(parse_node)
+        .collect()
+}
+
+/// Parses a node, eg. `192.0.2.43`, `"192.0.2.43:47011"` or `"[2001:db8::1]:4711"`.
+/// Obfuscated and `unknown` nodes yield `None`.
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim().trim_matches('"');
+
+    if let Some(rest) = node.strip_prefix('[') {
+        let (ip, _) = rest.split_once(']')?;
+        return ip.parse().ok();
+    }
+
+    node.parse::<IpAddr>()
+        .ok()
+        .or_else(|| node.parse::<SocketAddr>().ok().map(|addr| addr.ip()))
+}
+
+impl<C, B, T> Middleware<C, Request<B>, T> for TrustedProxies
+where
+    T: Work<C, Request<B>>,
+{
+    type Work = TrustedProxiesWork<T>;
+
+    fn wrap(&self, handle: T) -> Self::Work {
+        TrustedProxiesWork {
+            work: handle,
+            proxies: self.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TrustedProxiesWork<T> {
+    work: T,
+    proxies: TrustedProxies,
+}
+
+impl<T, C, B> Work<C, Request<B>> for TrustedProxiesWork<T>
+where
+    T: Work<C, Request<B>>,
+{
+    type Output = T::Output;
+
+    type Error = T::Error;
+
+    type Future<'a>
+        = T::Future<'a>
+    where
+        Self: 'a,
+        C: 'a;
+
+    fn call<'a>(&'a self, context: &'a C, mut req: Request<B>) -> Self::Future<'a> {
+        if let Some(peer) = req.extensions().get::<PeerAddr>() {
+            let client = self.proxies.resolve(peer.0.ip(), req.headers());
+            req.extensions_mut().insert(ClientAddr(client));
+        }
+
+        self.work.call(context, req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::HeaderValue;
+
+    fn ip(ip: &str) -> IpAddr {
+        ip.parse().unwrap()
+    }
+
+    fn header_map(name: &'static str, values: &[&'static str]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for value in values {
+            headers.append(name, HeaderValue::from_static(value));
+        }
+        headers
+    }
+
+    #[test]
+    fn cidr() {
+        let net: IpCidr = "10.0.0.0/8".parse().unwrap();
+        assert!(net.contains(ip("10.1.2.3")));
+        assert!(net.contains(ip("::ffff:10.1.2.3")));
+        assert!(!net.contains(ip("11.0.0.1")));
+
+        let net: IpCidr = "fd00::/8".parse().unwrap();
+        assert!(net.contains(ip("fd12::1")));
+        assert!(!net.contains(ip("fe80::1")));
+
+        let all: IpCidr = "0.0.0.0/0".parse().unwrap();
+        assert!(all.contains(ip("1.2.3.4")));
+
+        assert!(
+            "10.0.0.1"
+                .parse::<IpCidr>()
+                .unwrap()
+                .contains(ip("10.0.0.1"))
+        );
+        assert!("10.0.0.0/33".parse::<IpCidr>().is_err());
+        assert!("nope/8".parse::<IpCidr>().is_err());
+    }
+
+    #[test]
+    fn untrusted_peer() {
+        let proxies = TrustedProxies::parse(["10.0.0.0/8"]).unwrap();
+        let headers = header_map("x-forwarded-for", &["1.1.1.1"]);
+        assert_eq!(proxies.resolve(ip("2.2.2.2"), &headers), ip("2.2.2.2"));
+    }
+
+    #[test]
+    fn x_forwarded_for_chain() {
+        let proxies = TrustedProxies::parse(["10.0.0.0/8"]).unwrap();
+
+        // The leftmost value is client controlled and must be skipped.
+        let headers = header_map("x-forwarded-for", &["6.6.6.6, 1.1.1.1", "10.0.0.2"]);
+        assert_eq!(proxies.resolve(ip("10.0.0.1"), &headers), ip("1.1.1.1"));
+
+        let headers = header_map("x-forwarded-for", &["10.0.0.3, 10.0.0.2"]);
+        assert_eq!(proxies.resolve(ip("10.0.0.1"), &headers), ip("10.0.0.3"));
+
+        assert_eq!(
+            proxies.resolve(ip("10.0.0.1"), &HeaderMap::new()),
+            ip("10.0.0.1")
+        );
+    }
+
+    #[test]
+    fn forwarded_header() {
+        let proxies = TrustedProxies::parse(["10.0.0.0/8"]).unwrap();
+
+        let headers = header_map(
+            "forwarded",
+            &[r#"for="[2001:db8:cafe::17]:4711";proto=https, for=10.0.0.2:80"#],
+        );
+        assert_eq!(
+            proxies.resolve(ip("10.0.0.1"), &headers),
+            ip("2001:db8:cafe::17")
+        );
+
+        // Obfuscated nodes stop the walk at the last known hop.
+        let headers = header_map("forwarded", &["for=1.1.1.1, for=_hidden, for=10.0.0.2"]);
+        assert_eq!(proxies.resolve(ip("10.0.0.1"), &headers), ip("10.0.0.2"));
+    }
+
+    #[tokio::test]
+    async fn extract() {
+        let (mut parts, _) = Request::new(()).into_parts();
+        parts
+            .extensions
+            .insert(PeerAddr("10.0.0.1:1234".parse().unwrap()));
+        parts
+            .headers
+            .insert("x-forwarded-for", HeaderValue::from_static("1.1.1.1"));
+
+        let addr = ClientAddr::from_request_parts(&mut parts, &()).await;
+        assert_eq!(addr.unwrap(), ClientAddr(ip("10.0.0.1")));
+
+        let proxies = TrustedProxies::parse(["10.0.0.0/8"]).unwrap();
+        let client = proxies.resolve(ip("10.0.0.1"), &parts.headers);
+        parts.extensions.insert(ClientAddr(client));
+
+        let addr = ClientAddr::from_request_parts(&mut parts, &()).await;
+        assert_eq!(addr.unwrap(), ClientAddr(ip("1.1.1.1")));
+    }
+}
@@ -1,13 +1,14 @@
 use core::marker::PhantomData;
 use core::task::{Poll, ready};
 
-use bycat_error::{BoxError, Error};
 use bytes::Bytes;
-use http::Response;
+use http::{HeaderMap, Response, header::CONTENT_TYPE};
 use pin_project_lite::pin_project;
 
 use crate::body::{HttpBody, ToBytes, to_bytes};
-use crate::{FromRequest, IntoResponse};
+use crate::error::BoxError;
+use crate::rejection::{FormRejection, JsonRejection, UnsupportedMediaType};
+use crate::{Error, FromRequest, IntoResponse};
 
 pub trait Decoder<T> {
     type Error;
@@ -29,6 +30,7 @@ pin_project! {
         #[pin]
         inner: ToBytes<B>,
         decoder: D,
+        rejection: Option<Error>,
         ph: PhantomData<T>
     }
 }
@@ -38,7 +40,7 @@ where
     B: http_body::Body,
     B::Error: Into<BoxError>,
     D: Decoder<T>,
-    D::Error: Into<BoxError>,
+    D::Error: Into<Error>,
 {
     type Output = Result<D::Output, Error>;
 
@@ -48,15 +50,62 @@ where
     ) -> core::task::Poll<Self::Output> {
         let this = self.project();
 
+        if let Some(rejection) = this.rejection.take() {
+            return Poll::Ready(Err(rejection));
+        }
+
         match ready!(this.inner.poll(cx)) {
-            Ok(ret) => Poll::Ready(this.decoder.decode(&ret).map_err(Error::new)),
+            Ok(ret) => Poll::Ready(this.decoder.decode(&ret).map_err(Into::into)),
             Err(err) => Poll::Ready(Err(err)),
         }
     }
 }
 
+fn check_content_type(
+    headers: &HeaderMap,
+    expected: &'static str,
+    accept: fn(&str) -> bool,
+) -> Result<(), Error> {
+    let Some(content_type) = headers.get(CONTENT_TYPE) else {
+        return Err(UnsupportedMediaType {
+            expected,
+            found: None,
+        }
+        .into());
+    };
+
+    let found = content_type.to_str().unwrap_or_default();
+    let essence = found.split(';').next().unwrap_or_default().trim();
+
+    if accept(essence) {
+        Ok(())
+    } else {
+        Err(UnsupportedMediaType {
+            expected,
+            found: Some(found.into()),
+        }
+        .into())
+    }
+}
+
+fn is_json(essence: &str) -> bool {
+    let Some((ty, subtype)) = essence.split_once('/') else {
+        return false;
+    };
+
+    let subtype = subtype.as_bytes();
+
+    ty.eq_ignore_ascii_case("application")
+        && (subtype.eq_ignore_ascii_case(b"json")
+            || subtype.len() > 5 && subtype[subtype.len() - 5..].eq_ignore_ascii_case(b"+json"))
+}
+
+fn is_form(essence: &str) -> bool {
+    essence.eq_ignore_ascii_case("application/x-www-form-urlencoded")
+}
+
 macro_rules! encoding {
-    ($mime: literal, $name: ident, $extract: ident, $error: ty, $from_bytes: expr, $to_bytes: expr) => {
+    ($mime: literal, $name: ident, $extract: ident, $error: ty, $rejection: ty, $accept: expr, $from_bytes: expr, $to_bytes: expr) => {
         #[derive(Debug, Clone, Copy, PartialEq, Eq)]
         pub struct $name;
 
@@ -76,11 +125,11 @@ macro_rules! encoding {
         where
             T: serde::de::DeserializeOwned,
         {
-            type Error = $error;
+            type Error = $rejection;
             type Output = $extract<T>;
 
             fn decode(&self, data: &Bytes) -> Result<Self::Output, Self::Error> {
-                Ok($extract($from_bytes(data)?))
+                Ok($extract($from_bytes(data).map_err(<$rejection>::from)?))
             }
         }
 
@@ -99,9 +148,12 @@ macro_rules! encoding {
                 C: 'a;
 
             fn from_request<'a>(parts: http::Request<B>, _state: &'a C) -> Self::Future<'a> {
+                let rejection = check_content_type(parts.headers(), $mime, $accept).err();
+
                 DecodeFuture {
                     inner: to_bytes(parts.into_body()),
                     decoder: $name,
+                    rejection,
                     ph: PhantomData,
                 }
             }
@@ -110,17 +162,20 @@ macro_rules! encoding {
         impl<T, B> IntoResponse<B> for $extract<T>
         where
             T: serde::Serialize,
-            B: From<Bytes>,
+            B: HttpBody,
         {
-            type Error = Error;
-            fn into_response(self) -> Result<Response<B>, Self::Error> {
-                let bytes: Bytes = $to_bytes(&self.0).map_err(Error::new)?.into();
-                let resp = Response::builder()
-                    .header(http::header::CONTENT_TYPE, $mime)
-                    .header(http::header::CONTENT_LENGTH, bytes.len())
-                    .body(B::from(bytes));
-
-                resp.map_err(Error::new)
+            fn into_response(self) -> Response<B> {
+                let bytes: Bytes = match $to_bytes(&self.0) {
+                    Ok(bytes) => bytes.into(),
+                    Err(err) => return Error::custom(err).into_response(),
+                };
+
+                let mut resp = Response::new(B::from_bytes(bytes));
+                resp.headers_mut().insert(
+                    http::header::CONTENT_TYPE,
+                    http::HeaderValue::from_static($mime),
+                );
+                resp
             }
         }
     };
@@ -131,6 +186,19 @@ encoding!(
     JsonEncoding,
     Json,
     serde_json::Error,
+    JsonRejection,
+    is_json,
     serde_json::from_slice,
     serde_json::to_vec
 );
+
+encoding!(
+    "application/x-www-form-urlencoded",
+    FormEncoding,
+    Form,
+    serde_urlencoded::ser::Error,
+    FormRejection,
+    is_form,
+    serde_urlencoded::from_bytes,
+    serde_urlencoded::to_string
+);
@@ -2,10 +2,10 @@ use core::task::{Poll, ready};
 
 use bycat::{Middleware, Work};
 use bytes::Bytes;
-use http::{Request, Response, StatusCode, header::CONTENT_LENGTH};
+use http::{Request, Response, header::CONTENT_LENGTH};
 use pin_project_lite::pin_project;
 
-use crate::{IntoResponse, body::HttpBody};
+use crate::{Error, IntoResponse, body::HttpBody, error::BoxError, rejection::PayloadTooLarge};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct RequestBodyLimit(pub u64);
@@ -119,9 +119,11 @@ where
                         && let Ok(len) = u64::from_str_radix(str, 10)
                     {
                         if len > *limit {
-                            let mut resp = Response::new(B::empty());
+                            let resp = Error::from(PayloadTooLarge {
+                                limit: Some(*limit),
+                            })
+                            .into_response();
 
-                            *resp.status_mut() = StatusCode::PAYLOAD_TOO_LARGE;
                             this.state.set(RequestBodyLimitWorkState::Done);
                             return Poll::Ready(Ok(RequestBodyLimitWorkResponse::Error(resp)));
                         }
@@ -171,6 +173,8 @@ where
 }
 
 pin_project! {
+    /// Fails with [`PayloadTooLarge`] once the body exceeds the limit, for
+    /// bodies without a `Content-Length` or sending more than it claimed.
     pub struct RequestBodyLimitBody<T> {
         #[pin]
         body: T,
@@ -184,30 +188,115 @@ impl<T> http_body::Body for RequestBodyLimitBody<T>
 where
     T: http_body::Body,
     T::Data: AsRef<[u8]>,
+    T::Error: Into<BoxError>,
 {
     type Data = T::Data;
 
-    type Error = T::Error;
+    type Error = Error;
 
     fn poll_frame(
         self: core::pin::Pin<&mut Self>,
         cx: &mut core::task::Context<'_>,
     ) -> core::task::Poll<Option<Result<http_body::Frame<Self::Data>, Self::Error>>> {
         let this = self.project();
-        if this.read >= this.limit {
-            return Poll::Ready(None);
-        }
 
         match ready!(this.body.poll_frame(cx)) {
             Some(Ok(frame)) => {
                 if let Some(data) = frame.data_ref() {
-                    *this.limit += data.as_ref().len();
+                    let read = *this.read + data.as_ref().len();
+                    if read > *this.limit {
+                        return Poll::Ready(Some(Err(PayloadTooLarge {
+                            limit: Some(*this.limit as u64),
+                        }
+                        .into())));
+                    }
+                    *this.read = read;
                 }
 
                 Poll::Ready(Some(Ok(frame)))
             }
-            Some(Err(err)) => Poll::Ready(Some(Err(err))),
+            Some(Err(err)) => Poll::Ready(Some(Err(Error::custom(err)))),
             None => Poll::Ready(None),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::{Body, to_bytes};
+    use alloc::{boxed::Box, vec::Vec};
+    use core::{convert::Infallible, pin::Pin};
+    use http::StatusCode;
+
+    /// Echoes the request body.
+    struct Echo;
+
+    impl Work<(), Request<Body>> for Echo {
+        type Output = Response<Body>;
+        type Error = Error;
+        type Future<'a>
+            = Pin<Box<dyn Future<Output = Result<Response<Body>, Error>> + Send + 'a>>
+        where
+            Self: 'a;
+
+        fn call<'a>(&'a self, _context: &'a (), req: Request<Body>) -> Self::Future<'a> {
+            Box::pin(async move {
+                let body = to_bytes(req.into_body()).await?;
+                Ok(Response::new(Body::from_bytes(body)))
+            })
+        }
+    }
+
+    /// A body without a known length, sent in chunks.
+    struct Chunks(Vec<&'static str>);
+
+    impl http_body::Body for Chunks {
+        type Data = Bytes;
+        type Error = Infallible;
+
+        fn poll_frame(
+            mut self: Pin<&mut Self>,
+            _cx: &mut core::task::Context<'_>,
+        ) -> Poll<Option<Result<http_body::Frame<Bytes>, Infallible>>> {
+            if self.0.is_empty() {
+                return Poll::Ready(None);
+            }
+            let chunk = self.0.remove(0);
+            Poll::Ready(Some(Ok(http_body::Frame::data(Bytes::from(chunk)))))
+        }
+    }
+
+    fn chunked(chunks: &[&'static str]) -> Request<Body> {
+        Request::new(Body::from_streaming(Chunks(chunks.to_vec())))
+    }
+
+    #[tokio::test]
+    async fn content_length() {
+        let work = Middleware::<(), Request<Body>, _>::wrap(&RequestBodyLimit(8), Echo);
+
+        let req = Request::builder()
+            .header(CONTENT_LENGTH, "9")
+            .body(Body::from("123456789"))
+            .unwrap();
+        let resp: Response<Body> = work.call(&(), req).await.unwrap().into_response();
+        assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn chunked_body() {
+        let work = Middleware::<(), Request<Body>, _>::wrap(&RequestBodyLimit(8), Echo);
+
+        let resp = work.call(&(), chunked(&["1234", "5678"])).await.unwrap();
+        let resp: Response<Body> = resp.into_response();
+        assert_eq!(to_bytes(resp.into_body()).await.unwrap(), "12345678");
+
+        // The chunk crossing the limit fails the body instead of ending it
+        let err = match work.call(&(), chunked(&["1234", "56789", "0"])).await {
+            Ok(_) => panic!("read past the limit"),
+            Err(err) => err,
+        };
+        assert_eq!(err.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(err.rejection().unwrap().kind(), "payload-too-large");
+    }
+}
@@ -1,3 +1,4 @@
+mod client_addr;
 #[cfg(all(feature = "std", feature = "serde"))]
 pub mod encoding;
 mod ext;
@@ -8,7 +9,11 @@ mod limit;
 mod state;
 
 pub use self::{
-    ext::Ext, from_request::FromRequest, from_request_parts::FromRequestParts, state::State,
+    client_addr::{ClientAddr, InvalidCidr, IpCidr, PeerAddr, TrustedProxies, TrustedProxiesWork},
+    ext::Ext,
+    from_request::FromRequest,
+    from_request_parts::FromRequestParts,
+    state::State,
 };
 
 #[cfg(feature = "std")]
@@ -0,0 +1,529 @@
+use crate::{Error, Runtime, body::HttpBody};
+use alloc::{
+    borrow::Cow,
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use bycat::Work;
+use bycat_service::{Service, Shutdown};
+use bytes::Bytes;
+use core::{
+    fmt::Display,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use futures::future::{BoxFuture, Either, join_all, select};
+use http::{HeaderValue, Request, Response, StatusCode, header::CONTENT_TYPE};
+use parking_lot::Mutex;
+use pin_project_lite::pin_project;
+use serde_json::{Map, Value, json};
+
+type CheckFn = dyn Fn() -> BoxFuture<'static, Result<(), String>> + Send + Sync;
+
+/// The probes answered by a [`Health`] registry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Probe {
+    /// Whether the process works at all, or should be restarted.
+    Liveness,
+    /// Whether the process should receive traffic.
+    Readiness,
+}
+
+struct Check {
+    name: Cow<'static, str>,
+    probe: Probe,
+    check: Box<CheckFn>,
+}
+
+/// The state of a service watched by a [`Health`] registry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServiceState {
+    /// Registered, but not served yet.
+    Starting,
+    Running,
+    /// Returned after serving, eg. on shutdown.
+    Stopped,
+    /// Returned with an error.
+    Failed(String),
+}
+
+struct ServiceEntry {
+    name: Cow<'static, str>,
+    state: Mutex<ServiceState>,
+}
+
+impl ServiceEntry {
+    fn set(&self, state: ServiceState) {
+        *self.state.lock() = state;
+    }
+}
+
+#[derive(Default)]
+struct Inner {
+    checks: Mutex<Vec<Arc<Check>>>,
+    services: Mutex<Vec<Arc<ServiceEntry>>>,
+    shutdowns: Mutex<Vec<Shutdown>>,
+    timeout: Mutex<Option<(Duration, Runtime)>>,
+}
+
+/// A registry of health checks and services, answering liveness and
+/// readiness probes with a JSON report.
+///
+/// Services registered with [`Health::service`] are ready while they're
+/// serving, and fail the liveness probe once they've returned an error.
+/// Once the [`Shutdown`] they're served with is triggered, the readiness
+/// probe fails, so load balancers stop routing to the server while it
+/// drains.
+///
+/// ```ignore
+/// let health = Health::new();
+/// health.check("database", move || {
+///     let pool = pool.clone();
+///     async move { pool.ping().await }
+/// });
+///
+/// router.get("/healthz", health.liveness())?;
+/// router.get("/readyz", health.readiness())?;
+///
+/// let server = health.service("http", server);
+/// ```
+#[derive(Clone, Default)]
+pub struct Health {
+    inner: Arc<Inner>,
+}
+
+impl core::fmt::Debug for Health {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let checks = self
+            .inner
+            .checks
+            .lock()
+            .iter()
+            .map(|check| check.name.clone())
+            .collect::<Vec<_>>();
+        let services = self
+            .inner
+            .services
+            .lock()
+            .iter()
+            .map(|service| service.name.clone())
+            .collect::<Vec<_>>();
+
+        f.debug_struct("Health")
+            .field("checks", &checks)
+            .field("services", &services)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Health {
+    pub fn new() -> Health {
+        Health::default()
+    }
+
+    /// Add a check to the readiness probe.
+    pub fn check<F, U, E>(&self, name: impl Into<Cow<'static, str>>, check: F) -> &Self
+    where
+        F: Fn() -> U + Send + Sync + 'static,
+        U: Future<Output = Result<(), E>> + Send + 'static,
+        E: Display,
+    {
+        self.add(name.into(), Probe::Readiness, check)
+    }
+
+    /// Add a check to both probes. A failing liveness check asks for the
+    /// process to be restarted, so keep them to what a restart fixes.
+    pub fn liveness_check<F, U, E>(&self, name: impl Into<Cow<'static, str>>, check: F) -> &Self
+    where
+        F: Fn() -> U + Send + Sync + 'static,
+        U: Future<Output = Result<(), E>> + Send + 'static,
+        E: Display,
+    {
+        self.add(name.into(), Probe::Liveness, check)
+    }
+
+    /// Fail checks that take longer than `timeout`, so a hung check doesn't
+    /// hang the probe. The timeout waits on the timers of `runtime`.
+    pub fn timeout(&self, timeout: Duration, runtime: Runtime) -> &Self {
+        *self.inner.timeout.lock() = Some((timeout, runtime));
+        self
+    }
+
+    fn add<F, U, E>(&self, name: Cow<'static, str>, probe: Probe, check: F) -> &Self
+    where
+        F: Fn() -> U + Send + Sync + 'static,
+        U: Future<Output = Result<(), E>> + Send + 'static,
+        E: Display,
+    {
+        let check = move || {
+            let future = check();
+            Box::pin(async move { future.await.map_err(|err| err.to_string()) })
+                as BoxFuture<'static, _>
+        };
+
+        self.inner.checks.lock().push(Arc::new(Check {
+            name,
+            probe,
+            check: Box::new(check),
+        }));
+        self
+    }
+
+    /// Watch `service`, and the [`Shutdown`] it's served with.
+    pub fn service<S>(&self, name: impl Into<Cow<'static, str>>, service: S) -> HealthService<S> {
+        let entry = Arc::new(ServiceEntry {
+            name: name.into(),
+            state: Mutex::new(ServiceState::Starting),
+        });
+        self.inner.services.lock().push(entry.clone());
+
+        HealthService {
+            service,
+            entry,
+            health: self.clone(),
+        }
+    }
+
+    /// Fail the readiness probe once `shutdown` is triggered.
+    pub fn watch_shutdown(&self, shutdown: &Shutdown) -> &Self {
+        let mut shutdowns = self.inner.shutdowns.lock();
+        if !shutdowns.iter().any(|watched| watched.ptr_eq(shutdown)) {
+            shutdowns.push(shutdown.clone());
+        }
+        self
+    }
+
+    /// The state of the service `name`.
+    pub fn service_state(&self, name: &str) -> Option<ServiceState> {
+        self.inner
+            .services
+            .lock()
+            .iter()
+            .find(|service| service.name == name)
+            .map(|service| service.state.lock().clone())
+    }
+
+    /// A handler answering the liveness probe, eg. at `/healthz`.
+    pub fn liveness(&self) -> HealthHandler {
+        HealthHandler {
+            health: self.clone(),
+            probe: Probe::Liveness,
+        }
+    }
+
+    /// A handler answering the readiness probe, eg. at `/readyz`.
+    pub fn readiness(&self) -> HealthHandler {
+        HealthHandler {
+            health: self.clone(),
+            probe: Probe::Readiness,
+        }
+    }
+
+    /// Run the checks of `probe`, returning whether it passed and the
+    /// report.
+    pub async fn report(&self, probe: Probe) -> (bool, Value) {
+        let mut entries = Map::new();
+        let mut passed = true;
+        let mut entry = |name: &str, result: Result<(), String>| {
+            let value = match result {
+                Ok(()) => json!({ "status": "up" }),
+                Err(error) => {
+                    passed = false;
+                    json!({ "status": "down", "error": error })
+                }
+            };
+            entries.insert(name.into(), value);
+        };
+
+        if probe == Probe::Readiness {
+            let shutdown = self
+                .inner
+                .shutdowns
+                .lock()
+                .iter()
+                .any(Shutdown::is_shutdown);
+            if shutdown {
+                entry("shutdown", Err("shutting down".into()));
+            }
+        }
+
+        let services = self.inner.services.lock().clone();
+        for service in services {
+            let state = service.state.lock().clone();
+            let result = match (probe, state) {
+                (_, ServiceState::Running) => Ok(()),
+                (_, ServiceState::Failed(error)) => Err(error),
+                (Probe::Liveness, _) => Ok(()),
+                (Probe::Readiness, ServiceState::Starting) => Err("starting".into()),
+                (Probe::Readiness, ServiceState::Stopped) => Err("stopped".into()),
+            };
+            entry(&service.name, result);
+        }
+
+        let checks = self
+            .inner
+            .checks
+            .lock()
+            .iter()
+            .filter(|check| probe == Probe::Readiness || check.probe == Probe::Liveness)
+            .cloned()
+            .collect::<Vec<_>>();
+        let timeout = *self.inner.timeout.lock();
+        let results = join_all(checks.iter().map(|check| async move {
+            let future = (check.check)();
+            let Some((timeout, runtime)) = timeout else {
+                return future.await;
+            };
+
+            match select(future, runtime.sleep(timeout)).await {
+                Either::Left((result, _)) => result,
+                Either::Right(_) => Err(format!("timed out after {timeout:?}")),
+            }
+        }))
+        .await;
+        for (check, result) in checks.iter().zip(results) {
+            entry(&check.name, result);
+        }
+
+        let status = if passed { "up" } else { "down" };
+        (passed, json!({ "status": status, "checks": entries }))
+    }
+}
+
+/// Answers a probe of a [`Health`] registry with a JSON report, with a
+/// `503 Service Unavailable` status when a check fails.
+#[derive(Debug, Clone)]
+pub struct HealthHandler {
+    health: Health,
+    probe: Probe,
+}
+
+impl<C, B> Work<C, Request<B>> for HealthHandler
+where
+    B: HttpBody,
+{
+    type Output = Response<B>;
+
+    type Error = Error;
+
+    type Future<'a>
+        = BoxFuture<'a, Result<Response<B>, Error>>
+    where
+        Self: 'a,
+        C: 'a;
+
+    fn call<'a>(&'a self, _context: &'a C, _req: Request<B>) -> Self::Future<'a> {
+        Box::pin(async move {
+            let (passed, report) = self.health.report(self.probe).await;
+
+            let mut resp = Response::new(B::from_bytes(Bytes::from(report.to_string())));
+            if !passed {
+                *resp.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+            }
+            resp.headers_mut()
+                .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+            resp.headers_mut().insert(
+                http::header::CACHE_CONTROL,
+                HeaderValue::from_static("no-store"),
+            );
+
+            Ok(resp)
+        })
+    }
+}
+
+/// A service watched by a [`Health`] registry.
+pub struct HealthService<S> {
+    service: S,
+    entry: Arc<ServiceEntry>,
+    health: Health,
+}
+
+impl<S> Service for HealthService<S>
+where
+    S: Service,
+    S::Error: Display,
+{
+    type Error = S::Error;
+
+    type Future<'a>
+        = HealthServiceFuture<'a, S>
+    where
+        Self: 'a;
+
+    fn serve<'a>(&'a self, shutdown: &'a Shutdown) -> Self::Future<'a> {
+        self.health.watch_shutdown(shutdown);
+        self.entry.set(ServiceState::Running);
+
+        HealthServiceFuture {
+            future: self.service.serve(shutdown),
+            guard: Guard(&self.entry),
+        }
+    }
+}
+
+/// Marks a service stopped if its future is dropped while serving.
+struct Guard<'a>(&'a ServiceEntry);
+
+impl Drop for Guard<'_> {
+    fn drop(&mut self) {
+        let mut state = self.0.state.lock();
+        if *state == ServiceState::Running {
+            *state = ServiceState::Stopped;
+        }
+    }
+}
+
+pin_project! {
+    pub struct HealthServiceFuture<'a, S>
+    where
+        S: Service,
+        S: 'a,
+    {
+        #[pin]
+        future: S::Future<'a>,
+        guard: Guard<'a>,
+    }
+}
+
+impl<'a, S> Future for HealthServiceFuture<'a, S>
+where
+    S: Service,
+    S::Error: Display,
+{
+    type Output = Result<(), S::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let ret = core::task::ready!(this.future.poll(cx));
+
+        match &ret {
+            Ok(()) => this.guard.0.set(ServiceState::Stopped),
+            Err(err) => this.guard.0.set(ServiceState::Failed(err.to_string())),
+        }
+
+        Poll::Ready(ret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::{Body, to_bytes};
+    use core::sync::atomic::{AtomicBool, Ordering};
+
+    /// Serves until shutdown, failing if told to.
+    struct Waits(bool);
+
+    impl Service for Waits {
+        type Error = &'static str;
+        type Future<'a>
+            = BoxFuture<'a, Result<(), &'static str>>
+        where
+            Self: 'a;
+
+        fn serve<'a>(&'a self, shutdown: &'a Shutdown) -> Self::Future<'a> {
+            Box::pin(async move {
+                shutdown.wait().await;
+                if self.0 { Err("crashed") } else { Ok(()) }
+            })
+        }
+    }
+
+    async fn probe(handler: &HealthHandler) -> (StatusCode, Value) {
+        let resp: Response<Body> = handler
+            .call(&(), Request::new(Body::empty()))
+            .await
+            .unwrap();
+        let status = resp.status();
+        let body = to_bytes(resp.into_body()).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn checks() {
+        let health = Health::new();
+        let up = Arc::new(AtomicBool::new(true));
+
+        let flag = up.clone();
+        health.check("database", move || {
+            let up = flag.load(Ordering::Relaxed);
+            async move { if up { Ok(()) } else { Err("timed out") } }
+        });
+        health.liveness_check("memory", || async { Ok::<_, &str>(()) });
+
+        let (status, report) = probe(&health.readiness()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(report["status"], "up");
+        assert_eq!(report["checks"]["database"]["status"], "up");
+        assert_eq!(report["checks"]["memory"]["status"], "up");
+
+        up.store(false, Ordering::Relaxed);
+        let (status, report) = probe(&health.readiness()).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(report["checks"]["database"]["error"], "timed out");
+
+        // Readiness checks don't affect liveness
+        let (status, report) = probe(&health.liveness()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(report["checks"].get("database").is_none());
+    }
+
+    #[cfg(feature = "serve-tokio")]
+    #[tokio::test]
+    async fn timeout() {
+        let health = Health::new();
+        health
+            .timeout(Duration::from_millis(10), Runtime::tokio())
+            .check("hung", futures::future::pending::<Result<(), &str>>)
+            .check("database", || async { Ok::<_, &str>(()) });
+
+        let (status, report) = probe(&health.readiness()).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(report["checks"]["hung"]["error"], "timed out after 10ms");
+        assert_eq!(report["checks"]["database"]["status"], "up");
+    }
+
+    #[tokio::test]
+    async fn services() {
+        let health = Health::new();
+        let shutdown = Shutdown::new();
+        let service = health.service("server", Waits(false));
+        let failing = health.service("worker", Waits(true));
+
+        let (status, report) = probe(&health.readiness()).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(report["checks"]["server"]["error"], "starting");
+        assert_eq!(probe(&health.liveness()).await.0, StatusCode::OK);
+
+        let serving = service.serve(&shutdown);
+        let failing = failing.serve(&shutdown);
+        assert_eq!(health.service_state("server"), Some(ServiceState::Running));
+        assert_eq!(probe(&health.readiness()).await.0, StatusCode::OK);
+
+        shutdown.shutdown();
+        let (status, report) = probe(&health.readiness()).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(report["checks"]["shutdown"]["status"], "down");
+
+        // Both services are served with the same shutdown, watched once
+        assert_eq!(health.inner.shutdowns.lock().len(), 1);
+
+        serving.await.unwrap();
+        assert_eq!(failing.await, Err("crashed"));
+        assert_eq!(health.service_state("server"), Some(ServiceState::Stopped));
+        assert_eq!(
+            health.service_state("worker"),
+            Some(ServiceState::Failed("crashed".into()))
+        );
+
+        let (status, report) = probe(&health.liveness()).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(report["checks"]["worker"]["error"], "crashed");
+        assert_eq!(report["checks"]["server"]["status"], "up");
+    }
+}
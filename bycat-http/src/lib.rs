@@ -2,7 +2,9 @@
 extern crate std as alloc;
 
 pub mod error;
+pub mod error_handler;
 mod ext;
+pub mod rejection;
 
 #[cfg(feature = "std")]
 pub mod body;
@@ -14,26 +16,69 @@ pub mod router;
 
 pub mod extract;
 
+#[cfg(feature = "access-log")]
+pub mod access_log;
+#[cfg(feature = "auth")]
+pub mod auth;
+
+#[cfg(feature = "cache")]
+pub mod cache;
+#[cfg(feature = "catch-panic")]
+pub mod catch_panic;
+#[cfg(any(feature = "client", feature = "client-hyper"))]
+pub mod client;
 #[cfg(feature = "cookies")]
 pub mod cookies;
 pub mod cors;
+#[cfg(feature = "csrf")]
+pub mod csrf;
+#[cfg(feature = "health")]
+pub mod health;
 #[cfg(feature = "multipart")]
 pub mod multipart;
+#[cfg(feature = "openapi")]
+pub mod openapi;
+#[cfg(feature = "proxy")]
+pub mod proxy;
+#[cfg(feature = "request-id")]
+pub mod request_id;
 #[cfg(feature = "serve")]
 pub mod serve;
 #[cfg(feature = "session")]
 pub mod session;
+#[cfg(feature = "sse")]
+pub mod sse;
 #[cfg(feature = "statics")]
 mod statics;
+#[cfg(feature = "templates")]
+pub mod template;
 #[cfg(feature = "ws")]
 pub mod ws;
 
-mod matcher;
+pub mod matcher;
+#[cfg(any(
+    feature = "proxy",
+    feature = "cache",
+    feature = "sse",
+    feature = "health"
+))]
+mod runtime;
 pub mod util;
 
+#[cfg(test)]
+mod test_util;
+
 #[cfg(feature = "serve-tokio")]
 pub use self::serve::serve;
 
+#[cfg(any(
+    feature = "proxy",
+    feature = "cache",
+    feature = "sse",
+    feature = "health"
+))]
+pub use self::runtime::Runtime;
+
 pub use self::{
     error::Error,
     extract::{from_request::FromRequest, from_request_parts::FromRequestParts},
@@ -0,0 +1,348 @@
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
+use bycat::{Matcher, Work};
+use core::future::{Ready, ready};
+use http::{Request, header::HOST, request::Parts};
+
+use crate::{Error, FromRequestParts};
+
+/// The host a request was sent to, without the port.
+///
+/// It's taken from the uri of absolute-form and http/2 requests, and from the
+/// `Host` header otherwise.
+pub(crate) fn request_host<B>(req: &Request<B>) -> Option<&str> {
+    let host = match req.uri().host() {
+        Some(host) => host,
+        None => strip_port(req.headers().get(HOST)?.to_str().ok()?),
+    };
+
+    Some(host.trim_end_matches('.'))
+}
+
+fn strip_port(authority: &str) -> &str {
+    if authority.starts_with('[') {
+        return match authority.find(']') {
+            Some(end) => &authority[..=end],
+            None => authority,
+        };
+    }
+
+    match authority.rsplit_once(':') {
+        Some((host, port)) if port.bytes().all(|b| b.is_ascii_digit()) => host,
+        _ => authority,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Label {
+    Exact(Box<str>),
+    Any,
+    Param(Arc<str>),
+}
+
+/// Matches requests on the host they were sent to.
+///
+/// Patterns are compared label by label, ignoring case and port:
+///
+/// - `example.com` only matches `example.com`
+/// - `*.example.com` matches any subdomain of `example.com`, which is
+///   captured as [`HostParams::subdomain`]
+/// - `:tenant.example.com` matches a single label, captured by name
+/// - `*` in any other position matches a single label
+#[derive(Debug, Clone)]
+pub struct HostMatcher {
+    labels: Arc<[Label]>,
+    subdomain: bool,
+}
+
+impl HostMatcher {
+    pub fn new(pattern: &str) -> HostMatcher {
+        let pattern = pattern.trim_end_matches('.');
+
+        let (subdomain, rest) = if pattern == "*" {
+            (true, "")
+        } else if let Some(rest) = pattern.strip_prefix("*.") {
+            (true, rest)
+        } else {
+            (false, pattern)
+        };
+
+        let labels = rest
+            .split('.')
+            .filter(|_| !rest.is_empty())
+            .map(|label| match label {
+                "*" => Label::Any,
+                label => match label.strip_prefix(':') {
+                    Some(name) => Label::Param(Arc::from(name)),
+                    None => Label::Exact(label.to_ascii_lowercase().into_boxed_str()),
+                },
+            })
+            .collect();
+
+        HostMatcher { labels, subdomain }
+    }
+
+    /// The params captured from the host of `req`, if it matches.
+    pub fn captures<B>(&self, req: &Request<B>) -> Option<HostParams> {
+        let mut params = HostParams::default();
+        if self.matches(request_host(req)?, Some(&mut params)) {
+            Some(params)
+        } else {
+            None
+        }
+    }
+
+    fn matches(&self, host: &str, mut params: Option<&mut HostParams>) -> bool {
+        let mut rest = Some(host);
+
+        for label in self.labels.iter().rev() {
+            let Some(current) = rest else {
+                return false;
+            };
+
+            let part = match current.rsplit_once('.') {
+                Some((head, part)) => {
+                    rest = Some(head);
+                    part
+                }
+                None => {
+                    rest = None;
+                    current
+                }
+            };
+
+            if part.is_empty() {
+                return false;
+            }
+
+            match label {
+                Label::Exact(exact) if !part.eq_ignore_ascii_case(exact) => return false,
+                Label::Param(name) => {
+                    if let Some(params) = params.as_deref_mut() {
+                        params
+                            .params
+                            .push((name.clone(), Arc::from(part.to_ascii_lowercase())));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let matched = match rest {
+            None => !self.subdomain,
+            Some(subdomain) => self.subdomain && subdomain.split('.').all(|l| !l.is_empty()),
+        };
+
+        if let (true, Some(params)) = (matched, params) {
+            // Labels are matched right to left
+            params.params.reverse();
+            params.subdomain = rest.map(|subdomain| Arc::from(subdomain.to_ascii_lowercase()));
+        }
+
+        matched
+    }
+}
+
+impl<B> Matcher<Request<B>> for HostMatcher {
+    fn is_match(&self, req: &Request<B>) -> bool {
+        request_host(req).is_some_and(|host| self.matches(host, None))
+    }
+}
+
+pub fn match_host(pattern: &str) -> HostMatcher {
+    HostMatcher::new(pattern)
+}
+
+/// The parts of the host captured by the [`HostMatcher`] a request was
+/// routed with.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HostParams {
+    subdomain: Option<Arc<str>>,
+    params: Vec<(Arc<str>, Arc<str>)>,
+}
+
+impl HostParams {
+    /// The labels matched by a leading `*.` in the pattern.
+    pub fn subdomain(&self) -> Option<&str> {
+        self.subdomain.as_deref()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| &**key == name)
+            .map(|(_, value)| &**value)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.params.iter().map(|(key, value)| (&**key, &**value))
+    }
+}
+
+impl<C> FromRequestParts<C> for HostParams {
+    type Future<'a>
+        = Ready<Result<Self, Error>>
+    where
+        C: 'a;
+
+    fn from_request_parts<'a>(parts: &'a mut Parts, _state: &'a C) -> Self::Future<'a> {
+        ready(
+            parts
+                .extensions
+                .get::<HostParams>()
+                .cloned()
+                .ok_or_else(|| Error::custom("Missing host params")),
+        )
+    }
+}
+
+/// Adds the [`HostParams`] captured by a [`HostMatcher`] to the requests it
+/// passes on. See [`HttpWorkExt::with_host`](crate::prelude::HttpWorkExt::with_host).
+#[derive(Debug, Clone)]
+pub struct HostWork<T> {
+    inner: T,
+    host: HostMatcher,
+}
+
+impl<T> HostWork<T> {
+    pub fn new(inner: T, host: HostMatcher) -> Self {
+        HostWork { inner, host }
+    }
+}
+
+impl<T, C, B> Work<C, Request<B>> for HostWork<T>
+where
+    T: Work<C, Request<B>>,
+{
+    type Output = T::Output;
+    type Error = T::Error;
+
+    type Future<'a>
+        = T::Future<'a>
+    where
+        Self: 'a,
+        C: 'a;
+
+    fn call<'a>(&'a self, ctx: &'a C, mut req: Request<B>) -> Self::Future<'a> {
+        if let Some(params) = self.host.captures(&req) {
+            req.extensions_mut().insert(params);
+        }
+        self.inner.call(ctx, req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        body::{Body, to_bytes},
+        handler::handler,
+        prelude::HttpWorkExt,
+    };
+    use alloc::format;
+
+    fn request(host: &str) -> Request<Body> {
+        Request::builder()
+            .uri("/")
+            .header(HOST, host)
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[test]
+    fn exact() {
+        let host = match_host("example.com");
+        assert!(host.is_match(&request("example.com")));
+        assert!(host.is_match(&request("Example.COM:8080")));
+        assert!(host.is_match(&request("example.com.")));
+        assert!(!host.is_match(&request("www.example.com")));
+        assert!(!host.is_match(&request("example.org")));
+        assert!(!host.is_match(&Request::new(Body::empty())));
+
+        let absolute = Request::builder()
+            .uri("http://example.com:3000/")
+            .body(Body::empty())
+            .unwrap();
+        assert!(host.is_match(&absolute));
+
+        assert!(match_host("[::1]").is_match(&request("[::1]:8080")));
+    }
+
+    #[test]
+    fn wildcard() {
+        let host = match_host("*.example.com");
+        assert!(!host.is_match(&request("example.com")));
+        assert!(!host.is_match(&request(".example.com")));
+
+        let params = host.captures(&request("Acme.example.com")).unwrap();
+        assert_eq!(params.subdomain(), Some("acme"));
+
+        let params = host.captures(&request("eu.acme.example.com")).unwrap();
+        assert_eq!(params.subdomain(), Some("eu.acme"));
+
+        let any = match_host("api.*.example.com");
+        assert!(any.is_match(&request("api.eu.example.com")));
+        assert!(!any.is_match(&request("www.eu.example.com")));
+        assert!(!any.is_match(&request("api.example.com")));
+
+        assert!(match_host("*").is_match(&request("localhost")));
+    }
+
+    #[test]
+    fn params() {
+        let host = match_host("*.:tenant.:region.example.com");
+        let params = host.captures(&request("www.acme.eu.example.com")).unwrap();
+        assert_eq!(params.subdomain(), Some("www"));
+        assert_eq!(params.get("tenant"), Some("acme"));
+        assert_eq!(params.get("region"), Some("eu"));
+        assert_eq!(
+            params.iter().collect::<Vec<_>>(),
+            [("tenant", "acme"), ("region", "eu")]
+        );
+
+        let host = match_host(":tenant.example.com");
+        assert!(host.captures(&request("example.com")).is_none());
+        assert!(host.captures(&request("a.b.example.com")).is_none());
+        assert_eq!(
+            host.captures(&request("a.example.com"))
+                .unwrap()
+                .subdomain(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn virtual_hosts() {
+        let work = handler(async || "main")
+            .with_host("example.com")
+            .or(handler(async |params: HostParams| {
+                format!("tenant {}", params.subdomain().unwrap())
+            })
+            .with_host("*.example.com"));
+
+        let resp = work.call(&(), request("example.com")).await.unwrap();
+        assert_eq!(to_bytes(resp.into_body()).await.unwrap(), "main");
+
+        let resp = work.call(&(), request("acme.example.com")).await.unwrap();
+        assert_eq!(to_bytes(resp.into_body()).await.unwrap(), "tenant acme");
+
+        let err = work.call(&(), request("example.org")).await.unwrap_err();
+        assert_eq!(err.status(), http::StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn chained_hosts() {
+        let work = handler(async || "a")
+            .with_host("a.example.com")
+            .or(handler(async || "b").with_host("b.example.com"))
+            .or(handler(async || "c").with_host("c.example.com"));
+
+        for host in ["a", "b", "c"] {
+            let req = request(&format!("{host}.example.com"));
+            let resp = work.call(&(), req).await.unwrap();
+            assert_eq!(to_bytes(resp.into_body()).await.unwrap(), host);
+        }
+
+        let err = work.call(&(), request("d.example.com")).await.unwrap_err();
+        assert_eq!(err.status(), http::StatusCode::NOT_FOUND);
+    }
+}
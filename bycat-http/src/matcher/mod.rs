@@ -7,6 +7,11 @@ use pin_project_lite::pin_project;
 
 use crate::{Error, IntoResponse};
 
+mod host;
+mod request;
+
+pub use self::{host::*, request::*};
+
 pub trait FilteredWork<C, B>: Work<C, Request<B>> {
     fn can_handle(&self, ctx: &C, req: &Request<B>) -> bool;
 }
@@ -93,6 +98,20 @@ where
     }
 }
 
+impl<T1, T2, C, B> FilteredWork<C, B> for Or<T1, T2>
+where
+    T1: FilteredWork<C, B>,
+    T1::Output: IntoResponse<B>,
+    T1::Error: Into<Error>,
+    T2: FilteredWork<C, B>,
+    T2::Output: IntoResponse<B>,
+    T2::Error: Into<Error>,
+{
+    fn can_handle(&self, ctx: &C, req: &Request<B>) -> bool {
+        self.0.can_handle(ctx, req) || self.1.can_handle(ctx, req)
+    }
+}
+
 pin_project! {
     #[project = OrFutureProj]
     enum OrFutureState<T1, T2> {
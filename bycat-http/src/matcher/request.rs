@@ -0,0 +1,188 @@
+use alloc::boxed::Box;
+use bycat::Matcher;
+use http::{HeaderName, HeaderValue, Request, header::CONTENT_TYPE};
+
+/// Matches requests with a header, optionally with a given value.
+#[derive(Debug, Clone)]
+pub struct HeaderMatcher {
+    name: HeaderName,
+    value: Option<HeaderValue>,
+}
+
+impl<B> Matcher<Request<B>> for HeaderMatcher {
+    fn is_match(&self, req: &Request<B>) -> bool {
+        let mut values = req.headers().get_all(&self.name).iter();
+        match &self.value {
+            Some(expected) => values.any(|value| value == expected),
+            None => values.next().is_some(),
+        }
+    }
+}
+
+pub fn match_header(name: HeaderName, value: HeaderValue) -> HeaderMatcher {
+    HeaderMatcher {
+        name,
+        value: Some(value),
+    }
+}
+
+pub fn match_header_exists(name: HeaderName) -> HeaderMatcher {
+    HeaderMatcher { name, value: None }
+}
+
+/// Matches requests on the essence of their content type, so parameters like
+/// `charset` are ignored. `type/*` matches any subtype.
+#[derive(Debug, Clone)]
+pub struct ContentTypeMatcher(Box<str>);
+
+impl<B> Matcher<Request<B>> for ContentTypeMatcher {
+    fn is_match(&self, req: &Request<B>) -> bool {
+        let Some(content_type) = req
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+        else {
+            return false;
+        };
+
+        let essence = content_type.split(';').next().unwrap_or_default().trim();
+
+        match self.0.strip_suffix("/*") {
+            Some(ty) => essence
+                .split_once('/')
+                .is_some_and(|(found, _)| found.eq_ignore_ascii_case(ty)),
+            None => essence.eq_ignore_ascii_case(&self.0),
+        }
+    }
+}
+
+pub fn match_content_type(content_type: &str) -> ContentTypeMatcher {
+    ContentTypeMatcher(content_type.trim().into())
+}
+
+/// Matches requests with a query parameter, optionally with a given value.
+/// Parameters are compared after percent-decoding.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone)]
+pub struct QueryMatcher {
+    name: Box<str>,
+    value: Option<Box<str>>,
+}
+
+#[cfg(feature = "std")]
+impl<B> Matcher<Request<B>> for QueryMatcher {
+    fn is_match(&self, req: &Request<B>) -> bool {
+        let Some(query) = req.uri().query() else {
+            return false;
+        };
+
+        form_urlencoded::parse(query.as_bytes()).any(|(name, value)| {
+            *name == *self.name
+                && self
+                    .value
+                    .as_deref()
+                    .is_none_or(|expected| value == expected)
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+pub fn match_query(name: &str, value: &str) -> QueryMatcher {
+    QueryMatcher {
+        name: name.into(),
+        value: Some(value.into()),
+    }
+}
+
+#[cfg(feature = "std")]
+pub fn match_query_exists(name: &str) -> QueryMatcher {
+    QueryMatcher {
+        name: name.into(),
+        value: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        body::{Body, to_bytes},
+        handler::handler,
+        prelude::HttpWorkExt,
+    };
+    use bycat::Work;
+    use http::StatusCode;
+
+    fn request(uri: &str, headers: &[(&str, &str)]) -> Request<Body> {
+        let mut builder = Request::builder().uri(uri);
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        builder.body(Body::empty()).unwrap()
+    }
+
+    #[test]
+    fn header() {
+        let version = match_header(
+            HeaderName::from_static("x-api-version"),
+            HeaderValue::from_static("2"),
+        );
+        assert!(version.is_match(&request("/", &[("x-api-version", "2")])));
+        assert!(version.is_match(&request(
+            "/",
+            &[("x-api-version", "1"), ("x-api-version", "2")]
+        )));
+        assert!(!version.is_match(&request("/", &[("x-api-version", "1")])));
+        assert!(!version.is_match(&request("/", &[])));
+
+        let exists = match_header_exists(HeaderName::from_static("x-api-version"));
+        assert!(exists.is_match(&request("/", &[("x-api-version", "1")])));
+        assert!(!exists.is_match(&request("/", &[])));
+    }
+
+    #[test]
+    fn content_type() {
+        let json = match_content_type("application/json");
+        assert!(json.is_match(&request("/", &[("content-type", "application/json")])));
+        assert!(json.is_match(&request(
+            "/",
+            &[("content-type", "Application/JSON; charset=utf-8")]
+        )));
+        assert!(!json.is_match(&request("/", &[("content-type", "text/plain")])));
+        assert!(!json.is_match(&request("/", &[])));
+
+        let images = match_content_type("image/*");
+        assert!(images.is_match(&request("/", &[("content-type", "image/png")])));
+        assert!(!images.is_match(&request("/", &[("content-type", "imagery/png")])));
+    }
+
+    #[test]
+    fn query() {
+        let format = match_query("format", "csv file");
+        assert!(format.is_match(&request("/?format=csv%20file", &[])));
+        assert!(format.is_match(&request("/?page=2&format=csv+file", &[])));
+        assert!(!format.is_match(&request("/?format=json", &[])));
+        assert!(!format.is_match(&request("/", &[])));
+
+        let preview = match_query_exists("preview");
+        assert!(preview.is_match(&request("/?preview", &[])));
+        assert!(!preview.is_match(&request("/?page=2", &[])));
+    }
+
+    #[tokio::test]
+    async fn compose() {
+        let work = handler(async || "json")
+            .with_filter(match_content_type("application/json"))
+            .or(handler(async || "csv").with_filter(match_query("format", "csv")));
+
+        let req = request("/", &[("content-type", "application/json")]);
+        let resp = work.call(&(), req).await.unwrap();
+        assert_eq!(to_bytes(resp.into_body()).await.unwrap(), "json");
+
+        let resp = work.call(&(), request("/?format=csv", &[])).await.unwrap();
+        assert_eq!(to_bytes(resp.into_body()).await.unwrap(), "csv");
+
+        let err = work.call(&(), request("/", &[])).await.unwrap_err();
+        assert_eq!(err.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,171 @@
+use super::spool::{Spool, TempDir};
+use alloc::{string::String, sync::Arc, vec::Vec};
+use bycat::{Middleware, Work};
+use bycat_package::mime::{self, Mime};
+use http::Request;
+
+#[derive(Clone)]
+struct Options {
+    spool: Arc<dyn Spool>,
+    text_limit: u64,
+    file_limit: u64,
+    total_limit: u64,
+    field_limits: Vec<(String, u64)>,
+    allowed: Vec<Mime>,
+}
+
+/// The limits and spool of a [`MultipartForm`](super::MultipartForm).
+///
+/// Used as a middleware, it sets the config of the forms extracted by the
+/// handlers it wraps. Forms fall back to [`MultipartConfig::default`]
+/// otherwise.
+#[derive(Clone)]
+pub struct MultipartConfig {
+    opts: Arc<Options>,
+}
+
+impl Default for MultipartConfig {
+    fn default() -> Self {
+        MultipartConfig {
+            opts: Arc::new(Options {
+                spool: Arc::new(TempDir::default()),
+                text_limit: 64 * 1024,
+                file_limit: 10 * 1024 * 1024,
+                total_limit: 32 * 1024 * 1024,
+                field_limits: Vec::new(),
+                allowed: Vec::new(),
+            }),
+        }
+    }
+}
+
+impl core::fmt::Debug for MultipartConfig {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("MultipartConfig")
+            .field("text_limit", &self.opts.text_limit)
+            .field("file_limit", &self.opts.file_limit)
+            .field("total_limit", &self.opts.total_limit)
+            .field("field_limits", &self.opts.field_limits)
+            .field("allowed", &self.opts.allowed)
+            .finish_non_exhaustive()
+    }
+}
+
+impl MultipartConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Where file fields are written. Defaults to [`TempDir`].
+    pub fn spool<S: Spool>(mut self, spool: S) -> Self {
+        Arc::make_mut(&mut self.opts).spool = Arc::new(spool);
+        self
+    }
+
+    /// The size limit of each text field. Defaults to 64KiB.
+    pub fn text_limit(mut self, limit: u64) -> Self {
+        Arc::make_mut(&mut self.opts).text_limit = limit;
+        self
+    }
+
+    /// The size limit of each file field. Defaults to 10MiB.
+    pub fn file_limit(mut self, limit: u64) -> Self {
+        Arc::make_mut(&mut self.opts).file_limit = limit;
+        self
+    }
+
+    /// The size limit of the whole body. Defaults to 32MiB.
+    pub fn total_limit(mut self, limit: u64) -> Self {
+        Arc::make_mut(&mut self.opts).total_limit = limit;
+        self
+    }
+
+    /// The size limit of the field `name`, overriding the text and file
+    /// limits.
+    pub fn field_limit(mut self, name: impl Into<String>, limit: u64) -> Self {
+        let name = name.into();
+        let opts = Arc::make_mut(&mut self.opts);
+        opts.field_limits.retain(|(field, _)| *field != name);
+        opts.field_limits.push((name, limit));
+        self
+    }
+
+    /// Accept files of `mime`, which may be a range like `image/*`.
+    ///
+    /// Files of any type are accepted until a mime type is allowed.
+    pub fn allow_mime(mut self, mime: Mime) -> Self {
+        Arc::make_mut(&mut self.opts).allowed.push(mime);
+        self
+    }
+
+    pub(crate) fn spooler(&self) -> &dyn Spool {
+        &*self.opts.spool
+    }
+
+    pub(crate) fn total(&self) -> u64 {
+        self.opts.total_limit
+    }
+
+    /// The size limit of a field, given whether it is a file.
+    pub(crate) fn limit(&self, name: &str, file: bool) -> u64 {
+        self.opts
+            .field_limits
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, limit)| *limit)
+            .unwrap_or(if file {
+                self.opts.file_limit
+            } else {
+                self.opts.text_limit
+            })
+    }
+
+    pub(crate) fn is_allowed(&self, mime: &Mime) -> bool {
+        self.opts.allowed.is_empty()
+            || self.opts.allowed.iter().any(|allowed| {
+                allowed.type_() == mime::STAR
+                    || (allowed.type_() == mime.type_()
+                        && (allowed.subtype() == mime::STAR || allowed.subtype() == mime.subtype()))
+            })
+    }
+}
+
+impl<C, B, T> Middleware<C, Request<B>, T> for MultipartConfig
+where
+    T: Work<C, Request<B>>,
+{
+    type Work = MultipartConfigWork<T>;
+
+    fn wrap(&self, handle: T) -> Self::Work {
+        MultipartConfigWork {
+            work: handle,
+            config: self.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MultipartConfigWork<T> {
+    work: T,
+    config: MultipartConfig,
+}
+
+impl<T, C, B> Work<C, Request<B>> for MultipartConfigWork<T>
+where
+    T: Work<C, Request<B>>,
+{
+    type Output = T::Output;
+
+    type Error = T::Error;
+
+    type Future<'a>
+        = T::Future<'a>
+    where
+        Self: 'a,
+        C: 'a;
+
+    fn call<'a>(&'a self, context: &'a C, mut req: Request<B>) -> Self::Future<'a> {
+        req.extensions_mut().insert(self.config.clone());
+        self.work.call(context, req)
+    }
+}
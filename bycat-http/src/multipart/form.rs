@@ -0,0 +1,485 @@
+use super::{MultipartConfig, boundary, rejection};
+use crate::{
+    Error, FromRequest,
+    error::BoxError,
+    rejection::{FormRejection, MultipartRejection},
+};
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use bycat_fs::mime_guess;
+use bycat_package::{Mime, Package, mime};
+use bytes::{Bytes, BytesMut};
+use futures::{StreamExt, future::BoxFuture};
+use http::Request;
+use multer::{Constraints, Field, Multipart, SizeLimit};
+use relative_path::RelativePathBuf;
+use serde::de::DeserializeOwned;
+use std::sync::Mutex;
+
+/// How much of a file is read to sniff its type.
+const SNIFF_LEN: usize = 512;
+
+/// A multipart form, with the text fields deserialized into `T` and the
+/// file fields spooled as packages.
+///
+/// The spool and limits are set by a [`MultipartConfig`] middleware.
+#[derive(Debug)]
+pub struct MultipartForm<T> {
+    pub fields: T,
+    pub files: Files,
+}
+
+/// The file fields of a [`MultipartForm`], in the order they were sent.
+#[derive(Debug, Default)]
+pub struct Files {
+    files: Vec<(String, Package<bycat_fs::Body>)>,
+}
+
+impl Files {
+    /// The first file of the field `name`.
+    pub fn get(&self, name: &str) -> Option<&Package<bycat_fs::Body>> {
+        self.get_all(name).next()
+    }
+
+    pub fn get_all<'a>(
+        &'a self,
+        name: &'a str,
+    ) -> impl Iterator<Item = &'a Package<bycat_fs::Body>> + 'a {
+        self.files
+            .iter()
+            .filter(move |(field, _)| field == name)
+            .map(|(_, file)| file)
+    }
+
+    /// Remove the first file of the field `name`.
+    pub fn take(&mut self, name: &str) -> Option<Package<bycat_fs::Body>> {
+        let idx = self.files.iter().position(|(field, _)| field == name)?;
+        Some(self.files.remove(idx).1)
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Package<bycat_fs::Body>)> {
+        self.files
+            .iter()
+            .map(|(field, file)| (field.as_str(), file))
+    }
+}
+
+impl IntoIterator for Files {
+    type Item = (String, Package<bycat_fs::Body>);
+    type IntoIter = alloc::vec::IntoIter<Self::Item>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.files.into_iter()
+    }
+}
+
+impl<C, B, T> FromRequest<C, B> for MultipartForm<T>
+where
+    B: http_body::Body<Data = Bytes> + Send + 'static,
+    B::Error: Into<BoxError>,
+    T: DeserializeOwned + Send,
+{
+    type Future<'a>
+        = BoxFuture<'a, Result<Self, Error>>
+    where
+        C: 'a;
+
+    fn from_request<'a>(req: Request<B>, _state: &'a C) -> Self::Future<'a> {
+        Box::pin(async move {
+            let config = req
+                .extensions()
+                .get::<MultipartConfig>()
+                .cloned()
+                .unwrap_or_default();
+
+            let boundary = boundary(req.headers())?;
+            let stream = http_body_util::BodyDataStream::new(req.into_body());
+            let constraints =
+                Constraints::new().size_limit(SizeLimit::new().whole_stream(config.total()));
+            let multipart = Multipart::with_constraints(stream, boundary, constraints);
+
+            let mut files = Files::default();
+            match read(&config, multipart, &mut files).await {
+                Ok(fields) => Ok(MultipartForm { fields, files }),
+                Err(err) => {
+                    for (_, file) in files {
+                        let discard = config.spooler().discard(&file);
+                        discard.await;
+                    }
+                    Err(err)
+                }
+            }
+        })
+    }
+}
+
+async fn read<T: DeserializeOwned>(
+    config: &MultipartConfig,
+    mut multipart: Multipart<'static>,
+    files: &mut Files,
+) -> Result<T, Error> {
+    let mut pairs = Vec::new();
+
+    while let Some(mut field) = multipart.next_field().await.map_err(rejection)? {
+        let name = field.name().unwrap_or_default().to_string();
+
+        match field.file_name() {
+            Some("") => continue,
+            Some(file_name) => {
+                let file_name = sanitize(file_name);
+                let limit = config.limit(&name, true);
+                let head = head(&mut field, &name, limit).await?;
+
+                let mime = detect(&field, &file_name, &head);
+                if !config.is_allowed(&mime) {
+                    return Err(MultipartRejection::MimeNotAllowed {
+                        field: name,
+                        mime: mime.to_string(),
+                    }
+                    .into());
+                }
+
+                let file = spool(config, field, head, &name, limit, file_name, mime).await?;
+                files.files.push((name, file));
+            }
+            None => {
+                let limit = config.limit(&name, false);
+                let text = text(field, &name, limit).await?;
+                pairs.push((name, text));
+            }
+        }
+    }
+
+    let query = serde_urlencoded::to_string(&pairs).map_err(Error::custom)?;
+    serde_urlencoded::from_str(&query).map_err(|err| FormRejection::from(err).into())
+}
+
+/// The last component of a client supplied file name.
+fn sanitize(file_name: &str) -> RelativePathBuf {
+    let name = file_name
+        .rsplit(['/', '\\'])
+        .find(|part| !part.is_empty() && *part != "." && *part != "..")
+        .unwrap_or("upload");
+    RelativePathBuf::from(name)
+}
+
+/// The start of a file field, read to sniff its type.
+async fn head(field: &mut Field<'static>, name: &str, limit: u64) -> Result<Bytes, Error> {
+    let mut head = BytesMut::new();
+    while head.len() < SNIFF_LEN {
+        let Some(chunk) = field.chunk().await.map_err(rejection)? else {
+            break;
+        };
+        if (head.len() + chunk.len()) as u64 > limit {
+            return Err(too_large(name, limit));
+        }
+        head.extend_from_slice(&chunk);
+    }
+
+    Ok(head.freeze())
+}
+
+/// The mime of a file field, sniffed from the magic bytes at its `head`.
+///
+/// Content without magic bytes, like text, has the type the client sent,
+/// unless that's the generic `application/octet-stream`, or the type guessed
+/// from its name. A type that would have been sniffed, like `image/png`, is
+/// not taken from the client.
+fn detect(field: &Field<'_>, file_name: &RelativePathBuf, head: &[u8]) -> Mime {
+    if let Some(mime) = infer::get(head).and_then(|ty| ty.mime_type().parse().ok()) {
+        return mime;
+    }
+
+    field
+        .content_type()
+        .filter(|ty| **ty != mime::APPLICATION_OCTET_STREAM)
+        .cloned()
+        .or_else(|| mime_guess::from_path(file_name.as_str()).first())
+        .filter(|ty| !infer::is_mime_supported(ty.essence_str()))
+        .unwrap_or(mime::APPLICATION_OCTET_STREAM)
+}
+
+fn too_large(name: &str, limit: u64) -> Error {
+    MultipartRejection::FieldTooLarge {
+        field: name.into(),
+        limit,
+    }
+    .into()
+}
+
+async fn text(mut field: Field<'static>, name: &str, limit: u64) -> Result<String, Error> {
+    let mut text = Vec::new();
+    while let Some(chunk) = field.chunk().await.map_err(rejection)? {
+        if (text.len() + chunk.len()) as u64 > limit {
+            return Err(too_large(name, limit));
+        }
+        text.extend_from_slice(&chunk);
+    }
+
+    String::from_utf8(text).map_err(|_| {
+        MultipartRejection::Malformed(alloc::format!("field `{name}` is not valid utf-8")).into()
+    })
+}
+
+async fn spool(
+    config: &MultipartConfig,
+    field: Field<'static>,
+    head: Bytes,
+    name: &str,
+    limit: u64,
+    file_name: RelativePathBuf,
+    mime: Mime,
+) -> Result<Package<bycat_fs::Body>, Error> {
+    // The spool only sees a boxed error, so the rejection is kept aside
+    let failure = Arc::new(Mutex::new(None::<Error>));
+
+    let slot = failure.clone();
+    let field_name = String::from(name);
+    let mut size = head.len() as u64;
+    let content = futures::stream::once(async move { Ok(head) })
+        .chain(field.map(move |chunk| {
+            let chunk = chunk.map_err(rejection).and_then(|chunk| {
+                size += chunk.len() as u64;
+                if size > limit {
+                    Err(too_large(&field_name, limit))
+                } else {
+                    Ok(chunk)
+                }
+            });
+
+            chunk.map_err(|err| {
+                let message: BoxError = err.to_string().into();
+                *slot.lock().unwrap() = Some(err);
+                message
+            })
+        }))
+        .boxed();
+
+    let result = config.spooler().spool(file_name, mime, content).await;
+    let failure = failure.lock().unwrap().take();
+
+    match (result, failure) {
+        (Ok(file), None) => Ok(file),
+        (Ok(file), Some(err)) => {
+            let discard = config.spooler().discard(&file);
+            discard.await;
+            Err(err)
+        }
+        (Err(_), Some(err)) => Err(err),
+        (Err(err), None) => Err(Error::custom(err)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::multipart::{TempDir, TempUpload};
+    use http::{StatusCode, header::CONTENT_TYPE};
+    use http_body_util::Full;
+
+    #[derive(Debug, serde::Deserialize)]
+    struct Upload {
+        title: String,
+        count: u32,
+    }
+
+    const BODY: &str = "--XX\r\n\
+        Content-Disposition: form-data; name=\"title\"\r\n\r\n\
+        Holiday\r\n\
+        --XX\r\n\
+        Content-Disposition: form-data; name=\"count\"\r\n\r\n\
+        2\r\n\
+        --XX\r\n\
+        Content-Disposition: form-data; name=\"photo\"; filename=\"../../beach.png\"\r\n\
+        Content-Type: application/octet-stream\r\n\r\n\
+        not really a png\r\n\
+        --XX--\r\n";
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+
+    /// A form like `BODY`, with a photo of `content_type` and `content`.
+    fn upload(content_type: &str, content: &[u8]) -> Vec<u8> {
+        let mut body = alloc::format!(
+            "--XX\r\n\
+            Content-Disposition: form-data; name=\"title\"\r\n\r\n\
+            Holiday\r\n\
+            --XX\r\n\
+            Content-Disposition: form-data; name=\"count\"\r\n\r\n\
+            2\r\n\
+            --XX\r\n\
+            Content-Disposition: form-data; name=\"photo\"; filename=\"beach.png\"\r\n\
+            Content-Type: {content_type}\r\n\r\n"
+        )
+        .into_bytes();
+        body.extend_from_slice(content);
+        body.extend_from_slice(b"\r\n--XX--\r\n");
+        body
+    }
+
+    fn request(config: Option<MultipartConfig>, body: impl Into<Bytes>) -> Request<Full<Bytes>> {
+        let mut req = Request::builder()
+            .header(CONTENT_TYPE, "multipart/form-data; boundary=XX")
+            .body(Full::new(body.into()))
+            .unwrap();
+        if let Some(config) = config {
+            req.extensions_mut().insert(config);
+        }
+        req
+    }
+
+    fn dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(alloc::format!(
+            "bycat-multipart-{name}-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    async fn extract(req: Request<Full<Bytes>>) -> Result<MultipartForm<Upload>, Error> {
+        <MultipartForm<Upload> as FromRequest<(), _>>::from_request(req, &()).await
+    }
+
+    #[tokio::test]
+    async fn fields_and_files() {
+        let dir = dir("form");
+        let config = MultipartConfig::new().spool(TempDir(dir.clone()));
+
+        let mut form = extract(request(Some(config), BODY)).await.unwrap();
+        assert_eq!(form.fields.title, "Holiday");
+        assert_eq!(form.fields.count, 2);
+        assert_eq!(form.files.len(), 1);
+
+        let photo = form.files.take("photo").unwrap();
+        assert_eq!(photo.name(), "beach.png");
+        // Neither the content type nor the name is trusted for sniffable types
+        assert_eq!(photo.mime(), &mime::APPLICATION_OCTET_STREAM);
+
+        let bycat_fs::Body::Path(path) = photo.content() else {
+            panic!("expected a spooled file");
+        };
+        let path = path.clone();
+        assert!(path.starts_with(&dir));
+        assert_eq!(std::fs::read(&path).unwrap(), b"not really a png");
+        assert_eq!(photo.meta().get::<TempUpload>().unwrap().path(), path);
+
+        // Removed with the package
+        drop(photo);
+        assert!(!path.exists());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn persist() {
+        let dir = dir("persist");
+        let config = MultipartConfig::new().spool(TempDir(dir.clone()));
+
+        let mut form = extract(request(Some(config), BODY)).await.unwrap();
+        let photo = form.files.take("photo").unwrap();
+        let upload = photo.meta().get::<TempUpload>().unwrap().clone();
+        upload.persist();
+        assert!(upload.is_persisted());
+
+        drop((form, photo, upload));
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn limits() {
+        let dir = dir("limit");
+        let config = MultipartConfig::new()
+            .spool(TempDir(dir.clone()))
+            .field_limit("photo", 4);
+
+        let err = extract(request(Some(config), BODY)).await.unwrap_err();
+        assert_eq!(err.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+
+        let config = MultipartConfig::new().text_limit(3);
+        let err = extract(request(Some(config), BODY)).await.unwrap_err();
+        assert_eq!(err.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn mime_not_allowed() {
+        let dir = dir("mime");
+        let config = MultipartConfig::new()
+            .spool(TempDir(dir.clone()))
+            .allow_mime("image/jpeg".parse().unwrap());
+
+        let err = extract(request(Some(config), BODY)).await.unwrap_err();
+        assert_eq!(err.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert_eq!(err.rejection().unwrap().kind(), "unsupported-media-type");
+
+        let config = MultipartConfig::new()
+            .spool(TempDir(dir.clone()))
+            .allow_mime("image/*".parse().unwrap());
+        let body = upload("application/octet-stream", PNG);
+        let form = extract(request(Some(config), body)).await.unwrap();
+        assert_eq!(form.files.get("photo").unwrap().mime(), &mime::IMAGE_PNG);
+
+        drop(form);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn sniffed_mime() {
+        let dir = dir("sniff");
+        let config = MultipartConfig::new()
+            .spool(TempDir(dir.clone()))
+            .allow_mime("image/*".parse().unwrap())
+            .allow_mime("text/csv".parse().unwrap());
+
+        // Named and sent as a png, without being one
+        let body = upload("image/png", b"not really a png");
+        let err = extract(request(Some(config.clone()), body))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        let body = upload("image/png", b"%PDF-1.7\n");
+        let err = extract(request(Some(config.clone()), body))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+
+        // Text can't be sniffed, so its content type is used
+        let body = upload("text/csv", b"a,b\n1,2");
+        let form = extract(request(Some(config), body)).await.unwrap();
+        let csv = form.files.get("photo").unwrap();
+        assert_eq!(csv.mime().essence_str(), "text/csv");
+
+        drop(form);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn missing_boundary() {
+        let req = Request::builder()
+            .header(CONTENT_TYPE, "multipart/form-data")
+            .body(Full::new(Bytes::new()))
+            .unwrap();
+
+        let err = extract(req).await.unwrap_err();
+        assert_eq!(err.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(err.rejection().unwrap().kind(), "multipart-boundary");
+    }
+}
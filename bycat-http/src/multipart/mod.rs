@@ -1,8 +1,27 @@
-use bycat_error::{BoxError, Error};
+#[cfg(feature = "multipart-form")]
+mod config;
+#[cfg(feature = "multipart-form")]
+mod form;
+#[cfg(feature = "multipart-form")]
+mod spool;
+
+use crate::{
+    Error,
+    error::BoxError,
+    rejection::{MultipartRejection, PayloadTooLarge, UnsupportedMediaType},
+};
+use alloc::string::String;
 use bytes::Bytes;
-use http::header::CONTENT_TYPE;
+use http::{HeaderMap, header::CONTENT_TYPE};
 pub use multer::{Field, Multipart};
 
+#[cfg(feature = "multipart-form")]
+pub use self::{
+    config::{MultipartConfig, MultipartConfigWork},
+    form::{Files, MultipartForm},
+    spool::{Spool, StoredPath, TempDir, TempUpload, VfsSpool},
+};
+
 use crate::FromRequest;
 
 impl<'ctx, C, B> FromRequest<C, B> for Multipart<'ctx>
@@ -16,13 +35,9 @@ where
         C: 'a;
 
     fn from_request<'a>(req: http::Request<B>, _state: &'a C) -> Self::Future<'a> {
-        let Some(boundary) = req
-            .headers()
-            .get(CONTENT_TYPE)
-            .and_then(|ct| ct.to_str().ok())
-            .and_then(|ct| multer::parse_boundary(ct).ok())
-        else {
-            return core::future::ready(Err(Error::new("Bad request")));
+        let boundary = match boundary(req.headers()) {
+            Ok(boundary) => boundary,
+            Err(err) => return core::future::ready(Err(err)),
         };
 
         let stream = http_body_util::BodyDataStream::new(req.into_body());
@@ -31,3 +46,45 @@ where
         core::future::ready(Ok(multipart))
     }
 }
+
+/// The boundary of a multipart request with `headers`.
+pub(crate) fn boundary(headers: &HeaderMap) -> Result<String, Error> {
+    let content_type = headers.get(CONTENT_TYPE).and_then(|ct| ct.to_str().ok());
+
+    let Some(content_type) = content_type else {
+        return Err(UnsupportedMediaType {
+            expected: "multipart/form-data",
+            found: None,
+        }
+        .into());
+    };
+
+    multer::parse_boundary(content_type).map_err(|err| match err {
+        multer::Error::NoMultipart => UnsupportedMediaType {
+            expected: "multipart/form-data",
+            found: Some(content_type.into()),
+        }
+        .into(),
+        multer::Error::NoBoundary => MultipartRejection::MissingBoundary.into(),
+        err => rejection(err),
+    })
+}
+
+/// The rejection for an error reading a multipart body.
+pub(crate) fn rejection(err: multer::Error) -> Error {
+    use alloc::string::ToString;
+
+    match err {
+        multer::Error::FieldSizeExceeded { limit, field_name } => {
+            MultipartRejection::FieldTooLarge {
+                field: field_name.unwrap_or_default(),
+                limit,
+            }
+            .into()
+        }
+        multer::Error::StreamSizeExceeded { limit } => {
+            PayloadTooLarge { limit: Some(limit) }.into()
+        }
+        err => MultipartRejection::Malformed(err.to_string()).into(),
+    }
+}
@@ -0,0 +1,215 @@
+use crate::error::BoxError;
+use alloc::{
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    sync::Arc,
+};
+use bycat_fs::{Body, RemovableFS, VirtualFS};
+use bycat_package::{Mime, Package};
+use bytes::Bytes;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use futures::{StreamExt, future::BoxFuture, stream::BoxStream};
+use relative_path::RelativePathBuf;
+use std::{
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::io::AsyncWriteExt;
+
+/// Where the file fields of a [`MultipartForm`](super::MultipartForm) are
+/// written while the request is read.
+pub trait Spool: Send + Sync + 'static {
+    /// Write the content of a file called `name`, returning it as a package.
+    fn spool(
+        &self,
+        name: RelativePathBuf,
+        mime: Mime,
+        content: BoxStream<'static, Result<Bytes, BoxError>>,
+    ) -> BoxFuture<'_, Result<Package<Body>, BoxError>>;
+
+    /// Remove a package spooled for a request that was rejected later on.
+    fn discard(&self, _package: &Package<Body>) -> BoxFuture<'_, ()> {
+        Box::pin(async {})
+    }
+}
+
+/// A file name unique to this process, keeping the extension of `name`.
+fn unique(name: &RelativePathBuf) -> RelativePathBuf {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_nanos())
+        .unwrap_or_default();
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+
+    let mut unique = RelativePathBuf::from(format!("upload-{nanos:x}-{count:x}"));
+    if let Some(extension) = name.extension() {
+        unique.set_extension(extension);
+    }
+    unique
+}
+
+/// Spools files to a directory on disk, [`std::env::temp_dir`] by default.
+///
+/// The packages point at the written files, which are removed once the
+/// package and its clones are dropped. A handler keeping a file calls
+/// [`TempUpload::persist`] before moving it.
+#[derive(Debug, Clone)]
+pub struct TempDir(pub PathBuf);
+
+impl Default for TempDir {
+    fn default() -> Self {
+        TempDir(std::env::temp_dir())
+    }
+}
+
+impl Spool for TempDir {
+    fn spool(
+        &self,
+        name: RelativePathBuf,
+        mime: Mime,
+        mut content: BoxStream<'static, Result<Bytes, BoxError>>,
+    ) -> BoxFuture<'_, Result<Package<Body>, BoxError>> {
+        Box::pin(async move {
+            let path = unique(&name).to_logical_path(&self.0);
+
+            let written = async {
+                let mut file = tokio::fs::File::create(&path).await?;
+                while let Some(chunk) = content.next().await {
+                    file.write_all(&chunk?).await?;
+                }
+                file.flush().await?;
+                Ok::<_, BoxError>(())
+            };
+
+            if let Err(err) = written.await {
+                let _ = tokio::fs::remove_file(&path).await;
+                return Err(err);
+            }
+
+            let upload = TempUpload::new(path.clone());
+            let mut package = Package::new(name, mime, Body::Path(path));
+            package.meta_mut().insert(upload);
+            Ok(package)
+        })
+    }
+
+    fn discard(&self, package: &Package<Body>) -> BoxFuture<'_, ()> {
+        let path = match package.content() {
+            Body::Path(path) => Some(path.clone()),
+            _ => None,
+        };
+
+        Box::pin(async move {
+            if let Some(path) = path {
+                let _ = tokio::fs::remove_file(path).await;
+            }
+        })
+    }
+}
+
+/// A file spooled by [`TempDir`], in the meta of its package.
+///
+/// The file is removed when the last package holding it is dropped, like a
+/// `tempfile::NamedTempFile`, unless it's persisted.
+///
+/// ```ignore
+/// let photo = form.files.take("photo").unwrap();
+/// if let Some(upload) = photo.meta().get::<TempUpload>() {
+///     upload.persist();
+///     tokio::fs::rename(upload.path(), "photos/beach.png").await?;
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct TempUpload(Arc<TempFile>);
+
+#[derive(Debug)]
+struct TempFile {
+    path: PathBuf,
+    keep: AtomicBool,
+}
+
+impl TempUpload {
+    fn new(path: PathBuf) -> TempUpload {
+        TempUpload(Arc::new(TempFile {
+            path,
+            keep: AtomicBool::new(false),
+        }))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0.path
+    }
+
+    /// Keep the file when the package is dropped.
+    pub fn persist(&self) {
+        self.0.keep.store(true, Ordering::Release);
+    }
+
+    pub fn is_persisted(&self) -> bool {
+        self.0.keep.load(Ordering::Acquire)
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if !*self.keep.get_mut() {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Spools files to a [`VirtualFS`], under a unique name in its root.
+///
+/// The packages are read back from the file system once written, and
+/// removed from it when discarded, so it must be [`RemovableFS`].
+#[derive(Debug, Clone)]
+pub struct VfsSpool<V>(pub V);
+
+impl<V> Spool for VfsSpool<V>
+where
+    V: RemovableFS<Body = Body> + Send + Sync + 'static,
+    V::Error: core::error::Error + Send + Sync + 'static,
+    for<'a> V::Read<'a>: Send,
+    for<'a> V::Write<'a>: Send,
+    for<'a> V::Remove<'a>: Send,
+{
+    fn spool(
+        &self,
+        name: RelativePathBuf,
+        mime: Mime,
+        content: BoxStream<'static, Result<Bytes, BoxError>>,
+    ) -> BoxFuture<'_, Result<Package<Body>, BoxError>> {
+        Box::pin(async move {
+            let path = unique(&name);
+            let package = Package::new(path.clone(), mime.clone(), Body::Stream(content));
+            self.0.write(package).await?;
+
+            let mut package = self.0.read(&path).await?;
+            // Keep the name and mime of the upload, rather than the stored file
+            package.parts.mime = mime;
+            package.parts.meta.insert(StoredPath(path.to_string()));
+            package.set_path(name);
+            Ok(package)
+        })
+    }
+
+    fn discard(&self, package: &Package<Body>) -> BoxFuture<'_, ()> {
+        let path = package.meta().get::<StoredPath>().cloned();
+
+        Box::pin(async move {
+            if let Some(StoredPath(path)) = path
+                && let Err(err) = self.0.remove(&path).await
+            {
+                tracing::warn!("failed to remove discarded upload {path}: {err}");
+            }
+        })
+    }
+}
+
+/// The path a [`VfsSpool`] stored an upload under, in the meta of its
+/// package.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredPath(pub String);
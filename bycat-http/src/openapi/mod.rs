@@ -0,0 +1,625 @@
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+use bycat::Work;
+use bytes::Bytes;
+use http::{HeaderValue, Request, Response, header::CONTENT_TYPE};
+use schemars::{JsonSchema, Schema, SchemaGenerator, generate::SchemaSettings};
+use serde_json::{Map, Value, json};
+
+use crate::{Error, body::HttpBody, router::RouteInfo};
+
+/// Generates the schema of a type, registering its definitions in the generator.
+pub type SchemaFn = fn(&mut SchemaGenerator) -> Schema;
+
+pub(crate) fn subschema<T: JsonSchema>(generator: &mut SchemaGenerator) -> Schema {
+    generator.subschema_for::<T>()
+}
+
+/// Generator for OpenAPI 3.1 documents.
+///
+/// ```ignore
+/// let api = SendRouterBuilder::new()
+///     .with_documented_route(
+///         MethodFilter::GET,
+///         "/users/:id",
+///         RouteMeta::new().summary("Get user"),
+///         get_user,
+///     )?
+///     .build();
+///
+/// let document = OpenApi::new("Users", "1.0.0").generate(api.route_info())?;
+///
+/// let mut router = SendRouterBuilder::new()
+///     .with_get("/openapi.json", document)?
+///     .with_get("/docs", DocsUi::swagger("/openapi.json"))?;
+/// router.merge(api)?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct OpenApi {
+    title: String,
+    version: String,
+    description: Option<String>,
+    servers: Vec<String>,
+}
+
+impl OpenApi {
+    pub fn new(title: impl Into<String>, version: impl Into<String>) -> OpenApi {
+        OpenApi {
+            title: title.into(),
+            version: version.into(),
+            description: None,
+            servers: Vec::default(),
+        }
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    pub fn server(mut self, url: impl Into<String>) -> Self {
+        self.servers.push(url.into());
+        self
+    }
+
+    pub fn generate<'a>(
+        &self,
+        routes: impl IntoIterator<Item = &'a RouteInfo>,
+    ) -> Result<Document, serde_json::Error> {
+        let mut generator = SchemaSettings::draft2020_12()
+            .with(|settings| settings.definitions_path = "/components/schemas".into())
+            .into_generator();
+
+        let mut paths = BTreeMap::<String, Map<String, Value>>::new();
+
+        for route in routes {
+            let path = template(&route.path);
+            let operation = operation(&mut generator, route);
+
+            let item = paths.entry(path).or_default();
+            for method in route.method.iter() {
+                item.insert(method.to_string().to_ascii_lowercase(), operation.clone());
+            }
+        }
+
+        let mut info = json!({
+            "title": self.title,
+            "version": self.version,
+        });
+
+        if let Some(description) = &self.description {
+            info["description"] = description.as_str().into();
+        }
+
+        let mut document = json!({
+            "openapi": "3.1.0",
+            "info": info,
+            "paths": paths,
+        });
+
+        if !self.servers.is_empty() {
+            document["servers"] = self
+                .servers
+                .iter()
+                .map(|url| json!({ "url": url }))
+                .collect();
+        }
+
+        let schemas = generator.take_definitions(true);
+        if !schemas.is_empty() {
+            document["components"] = json!({ "schemas": schemas });
+        }
+
+        Document::new(document)
+    }
+}
+
+fn operation(generator: &mut SchemaGenerator, route: &RouteInfo) -> Value {
+    let meta = &route.meta;
+    let mut operation = Map::new();
+
+    if let Some(summary) = &meta.summary {
+        operation.insert("summary".into(), summary.as_str().into());
+    }
+
+    if let Some(description) = &meta.description {
+        operation.insert("description".into(), description.as_str().into());
+    }
+
+    if let Some(id) = &meta.operation_id {
+        operation.insert("operationId".into(), id.as_str().into());
+    }
+
+    if !meta.tags.is_empty() {
+        operation.insert("tags".into(), meta.tags.clone().into());
+    }
+
+    if meta.deprecated {
+        operation.insert("deprecated".into(), true.into());
+    }
+
+    let parameters = params(&route.path)
+        .map(|name| {
+            json!({
+                "name": name,
+                "in": "path",
+                "required": true,
+                "schema": { "type": "string" },
+            })
+        })
+        .collect::<Vec<_>>();
+
+    if !parameters.is_empty() {
+        operation.insert("parameters".into(), parameters.into());
+    }
+
+    if let Some(request) = meta.request {
+        operation.insert(
+            "requestBody".into(),
+            json!({
+                "required": true,
+                "content": {
+                    "application/json": { "schema": request(generator) },
+                },
+            }),
+        );
+    }
+
+    let mut responses = Map::new();
+
+    for response in &meta.responses {
+        let mut output = json!({ "description": response.description });
+
+        if let Some(schema) = response.schema {
+            output["content"] = json!({
+                "application/json": { "schema": schema(generator) },
+            });
+        }
+
+        responses.insert(response.status.as_u16().to_string(), output);
+    }
+
+    if responses.is_empty() {
+        responses.insert("default".into(), json!({ "description": "" }));
+    }
+
+    operation.insert("responses".into(), responses.into());
+
+    operation.into()
+}
+
+fn params(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter_map(|segment| {
+        segment
+            .strip_prefix(':')
+            .or_else(|| segment.strip_prefix('*'))
+    })
+}
+
+/// Converts a route path, eg. `/users/:id`, to an OpenAPI path template, eg. `/users/{id}`.
+fn template(path: &str) -> String {
+    let mut output = String::with_capacity(path.len());
+
+    for (idx, segment) in path.split('/').enumerate() {
+        if idx > 0 {
+            output.push('/');
+        }
+
+        match segment
+            .strip_prefix(':')
+            .or_else(|| segment.strip_prefix('*'))
+        {
+            Some(name) => {
+                output.push('{');
+                output.push_str(name);
+                output.push('}');
+            }
+            None => output.push_str(segment),
+        }
+    }
+
+    output
+}
+
+/// A generated OpenAPI document. Serves itself as `application/json` when used as a route.
+#[derive(Debug, Clone)]
+pub struct Document {
+    value: Value,
+    json: Bytes,
+}
+
+impl Document {
+    fn new(value: Value) -> Result<Document, serde_json::Error> {
+        let json = serde_json::to_vec(&value)?;
+        Ok(Document {
+            value,
+            json: json.into(),
+        })
+    }
+
+    pub fn as_value(&self) -> &Value {
+        &self.value
+    }
+
+    pub fn as_bytes(&self) -> &Bytes {
+        &self.json
+    }
+}
+
+impl<C, B: HttpBody> Work<C, Request<B>> for Document {
+    type Output = Response<B>;
+    type Error = Error;
+    type Future<'a>
+        = core::future::Ready<Result<Response<B>, Error>>
+    where
+        Self: 'a,
+        C: 'a;
+
+    fn call<'a>(&'a self, _context: &'a C, _req: Request<B>) -> Self::Future<'a> {
+        core::future::ready(Ok(respond(self.json.clone(), "application/json")))
+    }
+}
+
+/// A file served by a [`DocsUi`] page itself, at `?asset=<name>`.
+#[derive(Clone, Copy)]
+struct Embedded {
+    name: &'static str,
+    content_type: &'static str,
+    content: &'static [u8],
+}
+
+impl core::fmt::Debug for Embedded {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Embedded")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Swagger UI 5.17.14.
+#[cfg(feature = "openapi-ui")]
+const SWAGGER_UI: &[Embedded] = &[
+    Embedded {
+        name: "swagger-ui-bundle.js",
+        content_type: "text/javascript; charset=utf-8",
+        content: include_bytes!("assets/swagger-ui-bundle.js"),
+    },
+    Embedded {
+        name: "swagger-ui.css",
+        content_type: "text/css; charset=utf-8",
+        content: include_bytes!("assets/swagger-ui.css"),
+    },
+];
+
+/// Redoc 2.0.0-rc.72.
+#[cfg(feature = "openapi-ui")]
+const REDOC: &[Embedded] = &[Embedded {
+    name: "redoc.standalone.js",
+    content_type: "text/javascript; charset=utf-8",
+    content: include_bytes!("assets/redoc.standalone.js"),
+}];
+
+/// Where the scripts and stylesheets of a [`DocsUi`] are loaded from.
+#[derive(Debug, Clone)]
+pub struct Assets {
+    url: String,
+    integrity: BTreeMap<String, String>,
+}
+
+impl Assets {
+    /// Assets served from `url`, eg. `/docs/assets` when serving the files of
+    /// the `swagger-ui-dist` or `redoc` packages yourself, or a CDN like
+    /// `https://unpkg.com/swagger-ui-dist@5.17.14`.
+    pub fn new(url: impl Into<String>) -> Assets {
+        Assets {
+            url: url.into(),
+            integrity: BTreeMap::default(),
+        }
+    }
+
+    /// Sets the subresource integrity hash of a file, eg.
+    /// `("swagger-ui-bundle.js", "sha384-...")`. Browsers refuse files that
+    /// don't match.
+    pub fn integrity(mut self, file: impl Into<String>, hash: impl Into<String>) -> Self {
+        self.integrity.insert(file.into(), hash.into());
+        self
+    }
+
+    fn file(&self, file: &str) -> String {
+        let mut output = format!(
+            "\"{}/{}\"",
+            attribute(self.url.trim_end_matches('/')),
+            attribute(file)
+        );
+
+        if let Some(hash) = self.integrity.get(file) {
+            output.push_str(" integrity=\"");
+            output.push_str(&attribute(hash));
+            output.push_str("\" crossorigin=\"anonymous\"");
+        }
+
+        output
+    }
+}
+
+impl From<&str> for Assets {
+    fn from(url: &str) -> Self {
+        Assets::new(url)
+    }
+}
+
+impl From<String> for Assets {
+    fn from(url: String) -> Self {
+        Assets::new(url)
+    }
+}
+
+/// A documentation page for an OpenAPI document.
+///
+/// With the `openapi-ui` feature, [`DocsUi::swagger`] and [`DocsUi::redoc`]
+/// embed pinned releases of their scripts and stylesheets, which the page
+/// serves itself at `?asset=<file>`. To load them from elsewhere, eg. a CDN,
+/// use [`DocsUi::swagger_with_assets`] or [`DocsUi::redoc_with_assets`].
+#[derive(Debug, Clone)]
+pub struct DocsUi {
+    page: Bytes,
+    assets: &'static [Embedded],
+}
+
+impl DocsUi {
+    #[cfg(feature = "openapi-ui")]
+    pub fn swagger(spec_url: &str) -> DocsUi {
+        DocsUi::render(
+            include_str!("swagger.html"),
+            spec_url,
+            &[
+                ("{{stylesheet}}", embedded("swagger-ui.css")),
+                ("{{script}}", embedded("swagger-ui-bundle.js")),
+            ],
+            SWAGGER_UI,
+        )
+    }
+
+    pub fn swagger_with_assets(spec_url: &str, assets: impl Into<Assets>) -> DocsUi {
+        let assets = assets.into();
+        DocsUi::render(
+            include_str!("swagger.html"),
+            spec_url,
+            &[
+                ("{{stylesheet}}", assets.file("swagger-ui.css")),
+                ("{{script}}", assets.file("swagger-ui-bundle.js")),
+            ],
+            &[],
+        )
+    }
+
+    #[cfg(feature = "openapi-ui")]
+    pub fn redoc(spec_url: &str) -> DocsUi {
+        DocsUi::render(
+            include_str!("redoc.html"),
+            spec_url,
+            &[("{{script}}", embedded("redoc.standalone.js"))],
+            REDOC,
+        )
+    }
+
+    pub fn redoc_with_assets(spec_url: &str, assets: impl Into<Assets>) -> DocsUi {
+        let assets = assets.into();
+        DocsUi::render(
+            include_str!("redoc.html"),
+            spec_url,
+            &[("{{script}}", assets.file("redoc.standalone.js"))],
+            &[],
+        )
+    }
+
+    fn render(
+        template: &str,
+        spec_url: &str,
+        files: &[(&str, String)],
+        assets: &'static [Embedded],
+    ) -> DocsUi {
+        let mut page = template.replace("{{spec_url}}", &script_string(spec_url));
+        for (placeholder, file) in files {
+            page = page.replace(placeholder, file);
+        }
+
+        DocsUi {
+            page: page.into(),
+            assets,
+        }
+    }
+}
+
+/// A quoted url to an embedded file, relative to the page.
+#[cfg(feature = "openapi-ui")]
+fn embedded(file: &str) -> String {
+    format!("\"?asset={}\"", attribute(file))
+}
+
+impl<C, B: HttpBody> Work<C, Request<B>> for DocsUi {
+    type Output = Response<B>;
+    type Error = Error;
+    type Future<'a>
+        = core::future::Ready<Result<Response<B>, Error>>
+    where
+        Self: 'a,
+        C: 'a;
+
+    fn call<'a>(&'a self, _context: &'a C, req: Request<B>) -> Self::Future<'a> {
+        let Some(name) = req.uri().query().and_then(|q| q.strip_prefix("asset=")) else {
+            return core::future::ready(Ok(respond(self.page.clone(), "text/html; charset=utf-8")));
+        };
+
+        core::future::ready(match self.assets.iter().find(|asset| asset.name == name) {
+            Some(asset) => Ok(respond(
+                Bytes::from_static(asset.content),
+                asset.content_type,
+            )),
+            None => Err(Error::not_found()),
+        })
+    }
+}
+
+/// A javascript string literal, safe to embed in a `<script>` element.
+fn script_string(value: &str) -> String {
+    let mut output = String::with_capacity(value.len() + 2);
+    output.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '<' | '>' | '&' | '\u{2028}' | '\u{2029}' => {
+                output.push_str(&format!("\\u{:04x}", c as u32))
+            }
+            c if c.is_control() => output.push_str(&format!("\\u{:04x}", c as u32)),
+            c => output.push(c),
+        }
+    }
+    output.push('"');
+    output
+}
+
+/// Escapes a value for a double-quoted html attribute.
+fn attribute(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn respond<B: HttpBody>(body: Bytes, content_type: &'static str) -> Response<B> {
+    let mut resp = Response::new(B::from_bytes(body));
+    resp.headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+    resp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        body::Body,
+        handler,
+        router::{MethodFilter, RouteMeta, SendRouterBuilder},
+    };
+    use http::StatusCode;
+
+    #[derive(JsonSchema)]
+    #[allow(dead_code)]
+    struct User {
+        name: String,
+    }
+
+    #[test]
+    fn generate() {
+        let users = SendRouterBuilder::<(), Body>::new()
+            .with_documented_route(
+                MethodFilter::GET,
+                "/:id",
+                RouteMeta::new()
+                    .summary("Get user")
+                    .tag("users")
+                    .response_with::<User>(StatusCode::OK, "The user"),
+                handler(async || "user"),
+            )
+            .unwrap()
+            .with_documented_route(
+                MethodFilter::POST,
+                "/",
+                RouteMeta::new()
+                    .request::<User>()
+                    .response(StatusCode::CREATED, "Created"),
+                handler(async || "created"),
+            )
+            .unwrap();
+
+        let mut router = SendRouterBuilder::<(), Body>::new()
+            .with_get("/health", handler(async || "ok"))
+            .unwrap();
+        router.mount("/users", users).unwrap();
+
+        let document = OpenApi::new("Users", "1.0.0")
+            .generate(router.build().route_info())
+            .unwrap();
+        let value = document.as_value();
+
+        assert_eq!(value["openapi"], "3.1.0");
+        assert_eq!(value["info"]["title"], "Users");
+
+        let get = &value["paths"]["/users/{id}"]["get"];
+        assert_eq!(get["summary"], "Get user");
+        assert_eq!(get["tags"], json!(["users"]));
+        assert_eq!(get["parameters"][0]["name"], "id");
+        assert_eq!(get["parameters"][0]["in"], "path");
+        assert_eq!(
+            get["responses"]["200"]["content"]["application/json"]["schema"]["$ref"],
+            "#/components/schemas/User"
+        );
+
+        let post = &value["paths"]["/users"]["post"];
+        assert_eq!(
+            post["requestBody"]["content"]["application/json"]["schema"]["$ref"],
+            "#/components/schemas/User"
+        );
+        assert_eq!(post["responses"]["201"]["description"], "Created");
+
+        assert!(value["paths"]["/health"]["get"].is_object());
+        assert!(value["components"]["schemas"]["User"].is_object());
+    }
+
+    #[test]
+    fn template_path() {
+        assert_eq!(
+            template("/users/:id/files/*path"),
+            "/users/{id}/files/{path}"
+        );
+        assert_eq!(template("/"), "/");
+    }
+
+    #[test]
+    fn docs_ui() {
+        let cdn = "https://unpkg.com/swagger-ui-dist@5.17.14";
+        let page = DocsUi::swagger_with_assets("/openapi.json", cdn).page;
+        let page = core::str::from_utf8(&page).unwrap();
+        assert!(
+            page.contains("src=\"https://unpkg.com/swagger-ui-dist@5.17.14/swagger-ui-bundle.js\"")
+        );
+        assert!(page.contains("url: \"/openapi.json\","));
+
+        let assets = Assets::new("/docs/assets/").integrity("redoc.standalone.js", "sha384-abc");
+        let page = DocsUi::redoc_with_assets("</script>", assets).page;
+        let page = core::str::from_utf8(&page).unwrap();
+        assert!(page.contains(
+            "src=\"/docs/assets/redoc.standalone.js\" integrity=\"sha384-abc\" crossorigin=\"anonymous\""
+        ));
+        assert!(page.contains("Redoc.init(\"\\u003c/script\\u003e\""));
+    }
+
+    #[cfg(feature = "openapi-ui")]
+    #[tokio::test]
+    async fn embedded_assets() {
+        use crate::body::{Body, to_bytes};
+
+        let docs = DocsUi::swagger("/openapi.json");
+        let page = core::str::from_utf8(&docs.page).unwrap();
+        assert!(page.contains("src=\"?asset=swagger-ui-bundle.js\""));
+        assert!(page.contains("href=\"?asset=swagger-ui.css\""));
+
+        let get = |uri: &str| {
+            let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+            Work::<(), _>::call(&docs, &(), req)
+        };
+
+        let resp = get("/docs?asset=swagger-ui.css").await.unwrap();
+        assert_eq!(resp.headers()[CONTENT_TYPE], "text/css; charset=utf-8");
+        let body = to_bytes(resp.into_body()).await.unwrap();
+        assert_eq!(body.len(), SWAGGER_UI[1].content.len());
+
+        let resp = get("/docs").await.unwrap();
+        assert_eq!(resp.headers()[CONTENT_TYPE], "text/html; charset=utf-8");
+
+        let err = get("/docs?asset=redoc.standalone.js").await.unwrap_err();
+        assert_eq!(err.status(), http::StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,45 @@
+use crate::{Error, error::BoxError};
+use core::fmt;
+use http::StatusCode;
+
+/// A [`Proxy`](super::Proxy) couldn't get a response from an upstream.
+#[derive(Debug)]
+pub enum ProxyError {
+    /// Every upstream is failing its health checks.
+    NoUpstream,
+    /// The request to the upstream failed.
+    Unavailable(BoxError),
+}
+
+impl ProxyError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ProxyError::NoUpstream => StatusCode::SERVICE_UNAVAILABLE,
+            ProxyError::Unavailable(_) => StatusCode::BAD_GATEWAY,
+        }
+    }
+}
+
+impl fmt::Display for ProxyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProxyError::NoUpstream => write!(f, "No upstream available"),
+            ProxyError::Unavailable(_) => write!(f, "Upstream unavailable"),
+        }
+    }
+}
+
+impl core::error::Error for ProxyError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            ProxyError::NoUpstream => None,
+            ProxyError::Unavailable(err) => Some(&**err),
+        }
+    }
+}
+
+impl From<ProxyError> for Error {
+    fn from(value: ProxyError) -> Self {
+        Error::custom_with_status(value.status(), value)
+    }
+}
@@ -0,0 +1,151 @@
+use alloc::{format, vec::Vec};
+use core::net::IpAddr;
+use http::{
+    HeaderMap, HeaderName, HeaderValue,
+    header::{
+        CONNECTION, FORWARDED, PROXY_AUTHENTICATE, PROXY_AUTHORIZATION, TE, TRAILER,
+        TRANSFER_ENCODING, UPGRADE,
+    },
+};
+
+const HOP_BY_HOP: [HeaderName; 8] = [
+    CONNECTION,
+    HeaderName::from_static("keep-alive"),
+    PROXY_AUTHENTICATE,
+    PROXY_AUTHORIZATION,
+    TE,
+    TRAILER,
+    TRANSFER_ENCODING,
+    UPGRADE,
+];
+
+const X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+const X_FORWARDED_HOST: HeaderName = HeaderName::from_static("x-forwarded-host");
+const X_FORWARDED_PROTO: HeaderName = HeaderName::from_static("x-forwarded-proto");
+
+/// Remove the headers only meant for a single connection, including those
+/// listed in `Connection`. With `upgrade` the protocol upgrade headers are
+/// kept, so it can be passed on.
+pub(crate) fn strip_hop_by_hop(headers: &mut HeaderMap, upgrade: bool) {
+    let protocol = headers.get(UPGRADE).cloned();
+
+    let listed = headers
+        .get_all(CONNECTION)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|name| HeaderName::try_from(name.trim()).ok())
+        .collect::<Vec<_>>();
+
+    for name in listed.iter().chain(&HOP_BY_HOP) {
+        headers.remove(name);
+    }
+
+    if upgrade && let Some(protocol) = protocol {
+        headers.insert(CONNECTION, HeaderValue::from_static("upgrade"));
+        headers.insert(UPGRADE, protocol);
+    }
+}
+
+/// Record the client, and the host and protocol it asked for, in the
+/// `Forwarded` and `X-Forwarded-*` headers.
+pub(crate) fn forwarded(
+    headers: &mut HeaderMap,
+    client: Option<IpAddr>,
+    host: Option<HeaderValue>,
+    proto: &str,
+) {
+    let mut element = Vec::new();
+
+    if let Some(client) = client {
+        element.push(match client {
+            IpAddr::V4(ip) => format!("for={ip}"),
+            IpAddr::V6(ip) => format!("for=\"[{ip}]\""),
+        });
+
+        let value = match headers.get(X_FORWARDED_FOR).and_then(|v| v.to_str().ok()) {
+            Some(existing) => format!("{existing}, {client}"),
+            None => format!("{client}"),
+        };
+        if let Ok(value) = HeaderValue::try_from(value) {
+            headers.insert(X_FORWARDED_FOR, value);
+        }
+    }
+
+    if let Some(host) = host {
+        if let Ok(value) = host.to_str() {
+            element.push(format!("host=\"{value}\""));
+        }
+        headers.insert(X_FORWARDED_HOST, host);
+    }
+
+    element.push(format!("proto={proto}"));
+    if let Ok(value) = HeaderValue::try_from(proto) {
+        headers.insert(X_FORWARDED_PROTO, value);
+    }
+
+    let element = element.join(";");
+    let value = match headers.get(FORWARDED).and_then(|v| v.to_str().ok()) {
+        Some(existing) => format!("{existing}, {element}"),
+        None => element,
+    };
+    if let Ok(value) = HeaderValue::try_from(value) {
+        headers.insert(FORWARDED, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hop_by_hop() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONNECTION, "keep-alive, x-secret".parse().unwrap());
+        headers.insert("keep-alive", "timeout=5".parse().unwrap());
+        headers.insert("x-secret", "1".parse().unwrap());
+        headers.insert(TRANSFER_ENCODING, "chunked".parse().unwrap());
+        headers.insert("x-kept", "1".parse().unwrap());
+
+        strip_hop_by_hop(&mut headers, false);
+        assert_eq!(headers.len(), 1);
+        assert_eq!(headers["x-kept"], "1");
+    }
+
+    #[test]
+    fn upgrade() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONNECTION, "keep-alive, Upgrade".parse().unwrap());
+        headers.insert(UPGRADE, "websocket".parse().unwrap());
+
+        let mut stripped = headers.clone();
+        strip_hop_by_hop(&mut stripped, false);
+        assert!(stripped.is_empty());
+
+        strip_hop_by_hop(&mut headers, true);
+        assert_eq!(headers[CONNECTION], "upgrade");
+        assert_eq!(headers[UPGRADE], "websocket");
+    }
+
+    #[test]
+    fn forwarded_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert(FORWARDED, "for=10.0.0.1".parse().unwrap());
+        headers.insert(X_FORWARDED_FOR, "10.0.0.1".parse().unwrap());
+
+        forwarded(
+            &mut headers,
+            Some("::1".parse().unwrap()),
+            Some(HeaderValue::from_static("example.com")),
+            "https",
+        );
+
+        assert_eq!(
+            headers[FORWARDED],
+            "for=10.0.0.1, for=\"[::1]\";host=\"example.com\";proto=https"
+        );
+        assert_eq!(headers[X_FORWARDED_FOR], "10.0.0.1, ::1");
+        assert_eq!(headers[X_FORWARDED_HOST], "example.com");
+        assert_eq!(headers[X_FORWARDED_PROTO], "https");
+    }
+}
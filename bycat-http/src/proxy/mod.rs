@@ -0,0 +1,417 @@
+mod error;
+mod headers;
+mod upstream;
+
+pub use self::{
+    error::ProxyError,
+    upstream::{Balance, InvalidUpstream, Upstreams},
+};
+
+use self::upstream::{ActiveGuard, Upstream};
+use crate::{
+    Error, Runtime,
+    body::Body,
+    client::HyperClient,
+    extract::{ClientAddr, PeerAddr},
+    serve::FuturesIo,
+    util::header_contains,
+};
+use alloc::{sync::Arc, sync::Weak};
+use bycat::Work;
+use core::{
+    pin::Pin,
+    sync::atomic::{AtomicBool, Ordering},
+    task::{Context, Poll},
+    time::Duration,
+};
+use futures::{AsyncReadExt, FutureExt, future::BoxFuture};
+use http::{
+    HeaderValue, Method, Request, Response, StatusCode, Version,
+    header::{CONNECTION, HOST},
+    uri::Scheme,
+};
+use hyper::upgrade::OnUpgrade;
+use hyper_util::client::legacy::connect::Connect;
+use pin_project_lite::pin_project;
+
+#[cfg(feature = "serve-tokio")]
+use hyper_util::client::legacy::connect::HttpConnector;
+
+#[cfg(feature = "serve-smol")]
+use crate::client::SmolConnector;
+
+/// A reverse proxy, forwarding requests to one of its [`Upstreams`].
+///
+/// The path of a request is appended to the path of the upstream, and
+/// `Forwarded` and `X-Forwarded-*` headers record the client. Bodies are
+/// streamed both ways, and websocket upgrades are tunneled through.
+///
+/// ```ignore
+/// let upstreams = Upstreams::new(["http://10.0.0.1:8080", "http://10.0.0.2:8080"])?
+///     .balance(Balance::LeastConnections)
+///     .health_check(PathAndQuery::from_static("/healthz"), Duration::from_secs(5));
+///
+/// router.route("/api/*rest", Proxy::tokio(upstreams));
+/// ```
+pub struct Proxy<C> {
+    inner: Arc<Inner<C>>,
+    preserve_host: bool,
+}
+
+struct Inner<C> {
+    client: HyperClient<C>,
+    upstreams: Upstreams,
+    runtime: Runtime,
+    health_started: AtomicBool,
+}
+
+impl<C> Clone for Proxy<C> {
+    fn clone(&self) -> Self {
+        Proxy {
+            inner: self.inner.clone(),
+            preserve_host: self.preserve_host,
+        }
+    }
+}
+
+#[cfg(feature = "serve-tokio")]
+impl Proxy<HttpConnector> {
+    pub fn tokio(upstreams: Upstreams) -> Proxy<HttpConnector> {
+        Proxy::new(HyperClient::tokio(), upstreams, Runtime::tokio())
+    }
+}
+
+#[cfg(feature = "serve-smol")]
+impl Proxy<SmolConnector> {
+    pub fn smol(upstreams: Upstreams) -> Proxy<SmolConnector> {
+        Proxy::new(HyperClient::smol(), upstreams, Runtime::smol())
+    }
+}
+
+impl<C> Proxy<C> {
+    pub fn new(client: HyperClient<C>, upstreams: Upstreams, runtime: Runtime) -> Proxy<C> {
+        Proxy {
+            inner: Arc::new(Inner {
+                client,
+                upstreams,
+                runtime,
+                health_started: AtomicBool::new(false),
+            }),
+            preserve_host: false,
+        }
+    }
+
+    /// Send the `Host` of the client to the upstream, instead of the
+    /// authority of the upstream.
+    pub fn preserve_host(mut self, preserve: bool) -> Self {
+        self.preserve_host = preserve;
+        self
+    }
+
+    pub fn upstreams(&self) -> &Upstreams {
+        &self.inner.upstreams
+    }
+}
+
+impl<C> Proxy<C>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+{
+    /// Start checking the upstreams, once. The checks stop when the proxy
+    /// is dropped.
+    fn start_health_checks(&self) {
+        let Some(health) = self.inner.upstreams.health.clone() else {
+            return;
+        };
+
+        if self.inner.health_started.swap(true, Ordering::AcqRel) {
+            return;
+        }
+
+        let weak = Arc::downgrade(&self.inner);
+        let runtime = self.inner.runtime;
+        runtime.spawn(async move {
+            while let Some(inner) = Weak::upgrade(&weak) {
+                for upstream in inner.upstreams.iter() {
+                    let healthy = check(&inner, upstream, &health.path, health.interval).await;
+                    upstream.set_healthy(healthy);
+                }
+                drop(inner);
+                runtime.sleep(health.interval).await;
+            }
+        });
+    }
+
+    async fn forward(self, mut req: Request<Body>) -> Result<Response<Body>, Error> {
+        let inner = &self.inner;
+        let upstream = inner.upstreams.select().ok_or(ProxyError::NoUpstream)?;
+        let guard = upstream.acquire();
+
+        let upgrade = header_contains(req.headers(), CONNECTION, "upgrade");
+        let on_upgrade = if upgrade {
+            req.extensions_mut().remove::<OnUpgrade>()
+        } else {
+            None
+        };
+
+        let client = req
+            .extensions()
+            .get::<ClientAddr>()
+            .map(|addr| addr.0)
+            .or_else(|| req.extensions().get::<PeerAddr>().map(|addr| addr.0.ip()));
+        let host = req
+            .headers()
+            .get(HOST)
+            .cloned()
+            .or_else(|| req.uri().authority().and_then(|a| a.as_str().parse().ok()));
+        let proto = match req.uri().scheme() {
+            Some(scheme) if *scheme == Scheme::HTTPS => "https",
+            _ => "http",
+        };
+
+        let (mut parts, body) = req.into_parts();
+        let headers = &mut parts.headers;
+        headers::strip_hop_by_hop(headers, on_upgrade.is_some());
+        headers::forwarded(headers, client, host, proto);
+        if !self.preserve_host {
+            headers.insert(
+                HOST,
+                HeaderValue::try_from(upstream.authority()).map_err(Error::custom)?,
+            );
+        }
+        parts.uri = upstream.uri(parts.uri.path_and_query());
+        // Upstreams are spoken to over http/1, whatever the client used
+        parts.version = Version::HTTP_11;
+
+        let mut resp = match inner.client.request(Request::from_parts(parts, body)).await {
+            Ok(resp) => resp,
+            Err(err) => {
+                // Checks bring it back, without them it would stay out for good
+                if inner.upstreams.health.is_some() {
+                    upstream.set_healthy(false);
+                }
+                return Err(ProxyError::Unavailable(err.into()).into());
+            }
+        };
+
+        if resp.status() == StatusCode::SWITCHING_PROTOCOLS
+            && let Some(on_upgrade) = on_upgrade
+        {
+            let upstream_upgrade = hyper::upgrade::on(&mut resp);
+            inner
+                .runtime
+                .spawn(tunnel(on_upgrade, upstream_upgrade, guard));
+
+            headers::strip_hop_by_hop(resp.headers_mut(), true);
+            return Ok(resp.map(|_| Body::empty()));
+        }
+
+        headers::strip_hop_by_hop(resp.headers_mut(), false);
+        Ok(resp.map(|body| Body::from_streaming(TrackedBody { body, guard })))
+    }
+}
+
+impl<T, C> Work<T, Request<Body>> for Proxy<C>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+{
+    type Output = Response<Body>;
+
+    type Error = Error;
+
+    type Future<'a>
+        = BoxFuture<'a, Result<Response<Body>, Error>>
+    where
+        Self: 'a,
+        T: 'a;
+
+    fn call<'a>(&'a self, _context: &'a T, req: Request<Body>) -> Self::Future<'a> {
+        self.start_health_checks();
+        self.clone().forward(req).boxed()
+    }
+}
+
+/// Whether `upstream` answers a request for `path` with a success status
+/// within `timeout`.
+async fn check<C>(
+    inner: &Inner<C>,
+    upstream: &Upstream,
+    path: &http::uri::PathAndQuery,
+    timeout: Duration,
+) -> bool
+where
+    C: Connect + Clone + Send + Sync + 'static,
+{
+    let req = Request::builder()
+        .method(Method::GET)
+        .uri(upstream.uri(Some(path)))
+        .body(Body::empty())
+        .expect("valid request");
+
+    let resp = inner.client.request(req);
+    let timeout = inner.runtime.sleep(timeout);
+
+    match futures::future::select(resp, timeout).await {
+        futures::future::Either::Left((Ok(resp), _)) => resp.status().is_success(),
+        _ => false,
+    }
+}
+
+/// Copy bytes between the upgraded client and upstream connections until
+/// either side closes.
+async fn tunnel(client: OnUpgrade, upstream: OnUpgrade, _guard: ActiveGuard) {
+    let Ok((client, upstream)) = futures::future::try_join(client, upstream).await else {
+        return;
+    };
+
+    let (client_read, mut client_write) = FuturesIo::new(client).split();
+    let (upstream_read, mut upstream_write) = FuturesIo::new(upstream).split();
+
+    let _ = futures::future::try_join(
+        futures::io::copy(client_read, &mut upstream_write),
+        futures::io::copy(upstream_read, &mut client_write),
+    )
+    .await;
+}
+
+pin_project! {
+    /// A response body counting as a request in flight until it's dropped.
+    struct TrackedBody {
+        #[pin]
+        body: Body,
+        guard: ActiveGuard,
+    }
+}
+
+impl http_body::Body for TrackedBody {
+    type Data = bytes::Bytes;
+
+    type Error = Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<http_body::Frame<Self::Data>, Self::Error>>> {
+        self.project().body.poll_frame(cx)
+    }
+
+    fn size_hint(&self) -> http_body::SizeHint {
+        self.body.size_hint()
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.body.is_end_stream()
+    }
+}
+
+#[cfg(all(test, feature = "serve-tokio"))]
+mod tests {
+    use super::*;
+    use crate::{
+        body::to_bytes,
+        test_util::{head_complete, serve},
+    };
+    use alloc::{string::String, vec::Vec};
+    use tokio::{net::TcpListener, sync::mpsc::UnboundedReceiver};
+
+    /// Answer each request with `body`, sending the raw requests over the channel.
+    async fn upstream(body: &'static str) -> (String, UnboundedReceiver<String>) {
+        let response = format!(
+            "HTTP/1.1 200 OK\r\ncontent-length: {}\r\nkeep-alive: timeout=5\r\nconnection: close\r\n\r\n{body}",
+            body.len()
+        );
+        let (addr, requests) = serve(response, head_complete).await;
+        (format!("http://{addr}"), requests)
+    }
+
+    fn request(path: &str) -> Request<Body> {
+        let mut req = Request::builder()
+            .uri(path)
+            .header(HOST, "example.com")
+            .header(CONNECTION, "keep-alive, x-secret")
+            .header("x-secret", "1")
+            .body(Body::empty())
+            .unwrap();
+        req.extensions_mut()
+            .insert(ClientAddr("10.0.0.7".parse().unwrap()));
+        req
+    }
+
+    #[tokio::test]
+    async fn forward() {
+        let (url, mut requests) = upstream("hello").await;
+        let proxy = Proxy::tokio(Upstreams::new([format!("{url}/api")]).unwrap());
+
+        let resp = proxy.call(&(), request("/users?page=2")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(resp.headers().get("keep-alive").is_none());
+        assert_eq!(to_bytes(resp.into_body()).await.unwrap(), "hello");
+
+        let raw = requests.recv().await.unwrap().to_ascii_lowercase();
+        assert!(raw.starts_with("get /api/users?page=2 http/1.1"));
+        assert!(raw.contains(&format!("host: {}", url.trim_start_matches("http://"))));
+        assert!(raw.contains("x-forwarded-for: 10.0.0.7"));
+        assert!(raw.contains("x-forwarded-host: example.com"));
+        assert!(raw.contains("forwarded: for=10.0.0.7;host=\"example.com\";proto=http"));
+        assert!(!raw.contains("x-secret"));
+
+        let proxy = proxy.preserve_host(true);
+        proxy.call(&(), request("/")).await.unwrap();
+        let raw = requests.recv().await.unwrap().to_ascii_lowercase();
+        assert!(raw.contains("host: example.com"));
+    }
+
+    #[tokio::test]
+    async fn http2_client() {
+        let (url, mut requests) = upstream("hello").await;
+        let proxy = Proxy::tokio(Upstreams::new([url]).unwrap());
+
+        let mut req = request("/");
+        *req.version_mut() = Version::HTTP_2;
+        let resp = proxy.call(&(), req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(to_bytes(resp.into_body()).await.unwrap(), "hello");
+
+        let raw = requests.recv().await.unwrap().to_ascii_lowercase();
+        assert!(raw.starts_with("get / http/1.1"));
+    }
+
+    #[tokio::test]
+    async fn round_robin() {
+        let (a, _a) = upstream("a").await;
+        let (b, _b) = upstream("b").await;
+        let proxy = Proxy::tokio(Upstreams::new([a, b]).unwrap());
+
+        let mut bodies = Vec::new();
+        for _ in 0..4 {
+            let resp = proxy.call(&(), request("/")).await.unwrap();
+            bodies.push(to_bytes(resp.into_body()).await.unwrap());
+        }
+        assert_eq!(bodies, ["a", "b", "a", "b"]);
+    }
+
+    #[tokio::test]
+    async fn unavailable() {
+        // Nothing listens on the port once the listener is dropped
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let proxy = Proxy::tokio(
+            Upstreams::new([format!("http://{addr}")])
+                .unwrap()
+                .health_check(
+                    http::uri::PathAndQuery::from_static("/healthz"),
+                    Duration::from_secs(60),
+                ),
+        );
+
+        let err = proxy.call(&(), request("/")).await.unwrap_err();
+        assert_eq!(err.status(), StatusCode::BAD_GATEWAY);
+        assert!(err.rejection().is_none());
+
+        let err = proxy.call(&(), request("/")).await.unwrap_err();
+        assert_eq!(err.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+}
@@ -0,0 +1,261 @@
+use alloc::{string::String, sync::Arc, vec::Vec};
+use core::{
+    fmt,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    time::Duration,
+};
+use http::{
+    Uri,
+    uri::{PathAndQuery, Scheme},
+};
+
+/// How requests are spread over the upstreams.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Balance {
+    /// Take turns.
+    #[default]
+    RoundRobin,
+    /// Pick the upstream with the fewest requests in flight.
+    LeastConnections,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct HealthCheck {
+    pub(crate) path: PathAndQuery,
+    pub(crate) interval: Duration,
+}
+
+/// The servers a [`Proxy`](super::Proxy) forwards to.
+pub struct Upstreams {
+    upstreams: Vec<Arc<Upstream>>,
+    balance: Balance,
+    next: AtomicUsize,
+    pub(crate) health: Option<HealthCheck>,
+}
+
+impl fmt::Debug for Upstreams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Upstreams")
+            .field("upstreams", &self.upstreams)
+            .field("balance", &self.balance)
+            .finish()
+    }
+}
+
+impl Upstreams {
+    /// Upstreams from urls like `http://10.0.0.1:8080`. A path is prepended to
+    /// the path of forwarded requests.
+    ///
+    /// Only plain `http` upstreams are supported, the proxy doesn't connect
+    /// over TLS.
+    pub fn new<I>(uris: I) -> Result<Upstreams, InvalidUpstream>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        let upstreams = uris
+            .into_iter()
+            .map(|uri| Upstream::parse(uri.as_ref()).map(Arc::new))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if upstreams.is_empty() {
+            return Err(InvalidUpstream(String::from("no upstreams")));
+        }
+
+        Ok(Upstreams {
+            upstreams,
+            balance: Balance::RoundRobin,
+            next: AtomicUsize::new(0),
+            health: None,
+        })
+    }
+
+    pub fn balance(mut self, balance: Balance) -> Self {
+        self.balance = balance;
+        self
+    }
+
+    /// Request `path` on every upstream each `interval`, and only forward to
+    /// those answering with a success status. Upstreams that can't be
+    /// reached are skipped until they pass a check again.
+    pub fn health_check(mut self, path: PathAndQuery, interval: Duration) -> Self {
+        self.health = Some(HealthCheck { path, interval });
+        self
+    }
+
+    /// The upstreams, and whether they are considered healthy.
+    pub fn status(&self) -> Vec<(Uri, bool)> {
+        self.upstreams
+            .iter()
+            .map(|upstream| (upstream.base.clone(), upstream.is_healthy()))
+            .collect()
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &Arc<Upstream>> {
+        self.upstreams.iter()
+    }
+
+    /// Pick a healthy upstream.
+    pub(crate) fn select(&self) -> Option<Arc<Upstream>> {
+        let len = self.upstreams.len();
+        let start = self.next.fetch_add(1, Ordering::Relaxed) % len;
+        let healthy = (0..len)
+            .map(|i| &self.upstreams[(start + i) % len])
+            .filter(|upstream| upstream.is_healthy());
+
+        match self.balance {
+            Balance::RoundRobin => healthy.into_iter().next(),
+            Balance::LeastConnections => healthy.min_by_key(|upstream| upstream.active()),
+        }
+        .cloned()
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Upstream {
+    base: Uri,
+    healthy: AtomicBool,
+    active: AtomicUsize,
+}
+
+impl Upstream {
+    fn parse(uri: &str) -> Result<Upstream, InvalidUpstream> {
+        let base: Uri = uri
+            .parse()
+            .map_err(|_| InvalidUpstream(String::from(uri)))?;
+
+        if base.scheme() != Some(&Scheme::HTTP) || base.authority().is_none() {
+            return Err(InvalidUpstream(String::from(uri)));
+        }
+
+        Ok(Upstream {
+            base,
+            healthy: AtomicBool::new(true),
+            active: AtomicUsize::new(0),
+        })
+    }
+
+    /// The uri of `path` on this upstream.
+    pub(crate) fn uri(&self, path: Option<&PathAndQuery>) -> Uri {
+        let prefix = self.base.path().trim_end_matches('/');
+        let path = path.map(PathAndQuery::as_str).unwrap_or("/");
+
+        Uri::builder()
+            .scheme(self.base.scheme().cloned().expect("scheme"))
+            .authority(self.base.authority().cloned().expect("authority"))
+            .path_and_query(alloc::format!("{prefix}{path}"))
+            .build()
+            .expect("valid uri")
+    }
+
+    pub(crate) fn authority(&self) -> &str {
+        self.base
+            .authority()
+            .map(|a| a.as_str())
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn set_healthy(&self, healthy: bool) {
+        self.healthy.store(healthy, Ordering::Relaxed);
+    }
+
+    fn active(&self) -> usize {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    /// Count a request as in flight until the guard is dropped.
+    pub(crate) fn acquire(self: &Arc<Self>) -> ActiveGuard {
+        self.active.fetch_add(1, Ordering::Relaxed);
+        ActiveGuard(self.clone())
+    }
+}
+
+pub(crate) struct ActiveGuard(Arc<Upstream>);
+
+impl Drop for ActiveGuard {
+    fn drop(&mut self) {
+        self.0.active.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// An upstream url that isn't an absolute `http` url.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidUpstream(String);
+
+impl fmt::Display for InvalidUpstream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid upstream: {}", self.0)
+    }
+}
+
+impl core::error::Error for InvalidUpstream {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn upstreams(balance: Balance) -> Upstreams {
+        Upstreams::new(["http://a:80", "http://b:80/api/", "http://c:80"])
+            .unwrap()
+            .balance(balance)
+    }
+
+    fn pick(upstreams: &Upstreams) -> String {
+        String::from(upstreams.select().unwrap().authority())
+    }
+
+    #[test]
+    fn parse() {
+        assert!(Upstreams::new(["/relative"]).is_err());
+        assert!(Upstreams::new(["https://a:443"]).is_err());
+        assert!(Upstreams::new(["ftp://a:21"]).is_err());
+        assert!(Upstreams::new(Vec::<&str>::new()).is_err());
+
+        let upstreams = upstreams(Balance::RoundRobin);
+        let b = &upstreams.upstreams[1];
+        assert_eq!(
+            b.uri(Some(&PathAndQuery::from_static("/users?page=2"))),
+            "http://b:80/api/users?page=2"
+        );
+        assert_eq!(b.uri(None), "http://b:80/api/");
+    }
+
+    #[test]
+    fn round_robin() {
+        let upstreams = upstreams(Balance::RoundRobin);
+        assert_eq!(pick(&upstreams), "a:80");
+        assert_eq!(pick(&upstreams), "b:80");
+        assert_eq!(pick(&upstreams), "c:80");
+        assert_eq!(pick(&upstreams), "a:80");
+
+        // Unhealthy upstreams are skipped
+        upstreams.upstreams[1].set_healthy(false);
+        assert_eq!(pick(&upstreams), "c:80");
+        assert_eq!(pick(&upstreams), "c:80");
+
+        for upstream in upstreams.iter() {
+            upstream.set_healthy(false);
+        }
+        assert!(upstreams.select().is_none());
+    }
+
+    #[test]
+    fn least_connections() {
+        let upstreams = upstreams(Balance::LeastConnections);
+
+        let a = upstreams.select().unwrap().acquire();
+        let b = upstreams.select().unwrap().acquire();
+        assert_eq!(pick(&upstreams), "c:80");
+
+        drop(b);
+        let _c = upstreams.upstreams[2].acquire();
+        assert_eq!(pick(&upstreams), "b:80");
+
+        drop(a);
+        assert_eq!(upstreams.upstreams[0].active(), 0);
+    }
+}
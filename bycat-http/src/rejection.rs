@@ -0,0 +1,543 @@
+use crate::{Error, error::BoxError};
+use alloc::{collections::BTreeMap, string::String};
+use core::fmt;
+use http::{HeaderMap, HeaderName, StatusCode};
+
+/// Additional members attached to a rejection when it's rendered,
+/// eg. as problem details.
+pub type Details = BTreeMap<&'static str, DetailValue>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DetailValue {
+    String(String),
+    Number(u64),
+}
+
+impl From<String> for DetailValue {
+    fn from(value: String) -> Self {
+        DetailValue::String(value)
+    }
+}
+
+impl From<&str> for DetailValue {
+    fn from(value: &str) -> Self {
+        DetailValue::String(value.into())
+    }
+}
+
+impl From<u64> for DetailValue {
+    fn from(value: u64) -> Self {
+        DetailValue::Number(value)
+    }
+}
+
+impl From<usize> for DetailValue {
+    fn from(value: usize) -> Self {
+        DetailValue::Number(value as u64)
+    }
+}
+
+/// An error caused by the client, eg. an extractor failing on a malformed request.
+///
+/// Rejections are carried by [`Error`] and keep their status code, so they can be
+/// rendered by an [`ErrorHandler`](crate::error_handler::ErrorHandler).
+pub trait Rejection: core::error::Error + Send + Sync + 'static {
+    fn status(&self) -> StatusCode;
+
+    /// A short, stable identifier for the kind of rejection, eg. `missing-header`.
+    fn kind(&self) -> &'static str;
+
+    fn details(&self, _details: &mut Details) {}
+
+    /// Headers to send along with the response, eg. `WWW-Authenticate`.
+    fn headers(&self, _headers: &mut HeaderMap) {}
+}
+
+/// A required header was not present on the request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingHeader(pub HeaderName);
+
+impl fmt::Display for MissingHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Missing request header `{}`", self.0)
+    }
+}
+
+impl core::error::Error for MissingHeader {}
+
+impl Rejection for MissingHeader {
+    fn status(&self) -> StatusCode {
+        StatusCode::BAD_REQUEST
+    }
+
+    fn kind(&self) -> &'static str {
+        "missing-header"
+    }
+
+    fn details(&self, details: &mut Details) {
+        details.insert("header", self.0.as_str().into());
+    }
+}
+
+/// A header was present, but the value was invalid.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidHeader(pub HeaderName);
+
+impl fmt::Display for InvalidHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid request header `{}`", self.0)
+    }
+}
+
+impl core::error::Error for InvalidHeader {}
+
+impl Rejection for InvalidHeader {
+    fn status(&self) -> StatusCode {
+        StatusCode::BAD_REQUEST
+    }
+
+    fn kind(&self) -> &'static str {
+        "invalid-header"
+    }
+
+    fn details(&self, details: &mut Details) {
+        details.insert("header", self.0.as_str().into());
+    }
+}
+
+/// The request body exceeded the configured limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PayloadTooLarge {
+    pub limit: Option<u64>,
+}
+
+impl fmt::Display for PayloadTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.limit {
+            Some(limit) => write!(f, "Payload exceeds the limit of {limit} bytes"),
+            None => write!(f, "Payload too large"),
+        }
+    }
+}
+
+impl core::error::Error for PayloadTooLarge {}
+
+impl Rejection for PayloadTooLarge {
+    fn status(&self) -> StatusCode {
+        StatusCode::PAYLOAD_TOO_LARGE
+    }
+
+    fn kind(&self) -> &'static str {
+        "payload-too-large"
+    }
+
+    fn details(&self, details: &mut Details) {
+        if let Some(limit) = self.limit {
+            details.insert("limit", limit.into());
+        }
+    }
+}
+
+/// The request `Content-Type` is not supported by the extractor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsupportedMediaType {
+    pub expected: &'static str,
+    pub found: Option<String>,
+}
+
+impl fmt::Display for UnsupportedMediaType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.found {
+            Some(found) => write!(
+                f,
+                "Unsupported media type `{found}`. Expected `{}`",
+                self.expected
+            ),
+            None => write!(f, "Missing content type. Expected `{}`", self.expected),
+        }
+    }
+}
+
+impl core::error::Error for UnsupportedMediaType {}
+
+impl Rejection for UnsupportedMediaType {
+    fn status(&self) -> StatusCode {
+        StatusCode::UNSUPPORTED_MEDIA_TYPE
+    }
+
+    fn kind(&self) -> &'static str {
+        "unsupported-media-type"
+    }
+
+    fn details(&self, details: &mut Details) {
+        details.insert("expected", self.expected.into());
+        if let Some(found) = &self.found {
+            details.insert("found", found.as_str().into());
+        }
+    }
+}
+
+/// The request body could not be parsed as json.
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JsonRejection {
+    /// The body is not syntactically valid json.
+    Syntax {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    /// The body is valid json, but does not match the expected type.
+    Data {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+}
+
+#[cfg(feature = "serde")]
+impl From<serde_json::Error> for JsonRejection {
+    fn from(value: serde_json::Error) -> Self {
+        use alloc::string::ToString;
+
+        let line = value.line();
+        let column = value.column();
+        let message = value.to_string();
+
+        match value.classify() {
+            serde_json::error::Category::Data => JsonRejection::Data {
+                line,
+                column,
+                message,
+            },
+            _ => JsonRejection::Syntax {
+                line,
+                column,
+                message,
+            },
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl fmt::Display for JsonRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonRejection::Syntax { message, .. } => write!(f, "Invalid json: {message}"),
+            JsonRejection::Data { message, .. } => {
+                write!(f, "Failed to deserialize json: {message}")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl core::error::Error for JsonRejection {}
+
+#[cfg(feature = "serde")]
+impl Rejection for JsonRejection {
+    fn status(&self) -> StatusCode {
+        match self {
+            JsonRejection::Syntax { .. } => StatusCode::BAD_REQUEST,
+            JsonRejection::Data { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            JsonRejection::Syntax { .. } => "json-syntax",
+            JsonRejection::Data { .. } => "json-data",
+        }
+    }
+
+    fn details(&self, details: &mut Details) {
+        let (JsonRejection::Syntax { line, column, .. } | JsonRejection::Data { line, column, .. }) =
+            self;
+        details.insert("line", (*line).into());
+        details.insert("column", (*column).into());
+    }
+}
+
+/// The request body could not be parsed as an urlencoded form.
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormRejection {
+    pub message: String,
+}
+
+#[cfg(feature = "serde")]
+impl From<serde_urlencoded::de::Error> for FormRejection {
+    fn from(value: serde_urlencoded::de::Error) -> Self {
+        use alloc::string::ToString;
+
+        FormRejection {
+            message: value.to_string(),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl fmt::Display for FormRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Failed to deserialize form: {}", self.message)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl core::error::Error for FormRejection {}
+
+#[cfg(feature = "serde")]
+impl Rejection for FormRejection {
+    fn status(&self) -> StatusCode {
+        StatusCode::UNPROCESSABLE_ENTITY
+    }
+
+    fn kind(&self) -> &'static str {
+        "form-data"
+    }
+}
+
+/// A generic bad request, created through [`Error::bad_request`].
+#[derive(Debug)]
+pub struct BadRequest(pub BoxError);
+
+impl fmt::Display for BadRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl core::error::Error for BadRequest {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        Some(&*self.0)
+    }
+}
+
+impl Rejection for BadRequest {
+    fn status(&self) -> StatusCode {
+        StatusCode::BAD_REQUEST
+    }
+
+    fn kind(&self) -> &'static str {
+        "bad-request"
+    }
+}
+
+/// The CSRF token of an unsafe request was missing, or didn't match the one
+/// issued to the client.
+#[cfg(feature = "csrf")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsrfRejection {
+    Missing,
+    Mismatch,
+}
+
+#[cfg(feature = "csrf")]
+impl fmt::Display for CsrfRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CsrfRejection::Missing => write!(f, "Missing CSRF token"),
+            CsrfRejection::Mismatch => write!(f, "Invalid CSRF token"),
+        }
+    }
+}
+
+#[cfg(feature = "csrf")]
+impl core::error::Error for CsrfRejection {}
+
+#[cfg(feature = "csrf")]
+impl Rejection for CsrfRejection {
+    fn status(&self) -> StatusCode {
+        StatusCode::FORBIDDEN
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            CsrfRejection::Missing => "csrf-missing",
+            CsrfRejection::Mismatch => "csrf-mismatch",
+        }
+    }
+}
+
+/// The request lacked valid credentials.
+///
+/// Sent with a `WWW-Authenticate` header when there's a challenge.
+#[cfg(feature = "auth")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unauthorized {
+    pub challenge: Option<http::HeaderValue>,
+    pub reason: Option<alloc::borrow::Cow<'static, str>>,
+}
+
+#[cfg(feature = "auth")]
+impl Unauthorized {
+    pub fn new(challenge: Option<http::HeaderValue>) -> Unauthorized {
+        Unauthorized {
+            challenge,
+            reason: None,
+        }
+    }
+
+    pub fn reason(mut self, reason: impl Into<alloc::borrow::Cow<'static, str>>) -> Self {
+        self.reason = Some(reason.into());
+        self
+    }
+}
+
+#[cfg(feature = "auth")]
+impl fmt::Display for Unauthorized {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.reason {
+            Some(reason) => write!(f, "Unauthorized: {reason}"),
+            None => write!(f, "Unauthorized"),
+        }
+    }
+}
+
+#[cfg(feature = "auth")]
+impl core::error::Error for Unauthorized {}
+
+#[cfg(feature = "auth")]
+impl Rejection for Unauthorized {
+    fn status(&self) -> StatusCode {
+        StatusCode::UNAUTHORIZED
+    }
+
+    fn kind(&self) -> &'static str {
+        "unauthorized"
+    }
+
+    fn headers(&self, headers: &mut HeaderMap) {
+        if let Some(challenge) = &self.challenge {
+            headers.insert(http::header::WWW_AUTHENTICATE, challenge.clone());
+        }
+    }
+}
+
+/// The request was authenticated, but isn't allowed to access the resource.
+#[cfg(feature = "auth")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Forbidden;
+
+#[cfg(feature = "auth")]
+impl fmt::Display for Forbidden {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Forbidden")
+    }
+}
+
+#[cfg(feature = "auth")]
+impl core::error::Error for Forbidden {}
+
+#[cfg(feature = "auth")]
+impl Rejection for Forbidden {
+    fn status(&self) -> StatusCode {
+        StatusCode::FORBIDDEN
+    }
+
+    fn kind(&self) -> &'static str {
+        "forbidden"
+    }
+}
+
+/// A multipart body could not be read, or broke the limits of the form.
+#[cfg(feature = "multipart")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MultipartRejection {
+    /// The `Content-Type` has no boundary.
+    MissingBoundary,
+    /// The body is not valid multipart.
+    Malformed(String),
+    /// A field exceeded its size limit.
+    FieldTooLarge { field: String, limit: u64 },
+    /// A file field has a mime type the form doesn't accept.
+    MimeNotAllowed { field: String, mime: String },
+}
+
+#[cfg(feature = "multipart")]
+impl fmt::Display for MultipartRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MultipartRejection::MissingBoundary => write!(f, "Missing multipart boundary"),
+            MultipartRejection::Malformed(message) => {
+                write!(f, "Malformed multipart body: {message}")
+            }
+            MultipartRejection::FieldTooLarge { field, limit } => {
+                write!(f, "Field `{field}` exceeds the limit of {limit} bytes")
+            }
+            MultipartRejection::MimeNotAllowed { field, mime } => {
+                write!(f, "Field `{field}` has unsupported media type `{mime}`")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "multipart")]
+impl core::error::Error for MultipartRejection {}
+
+#[cfg(feature = "multipart")]
+impl Rejection for MultipartRejection {
+    fn status(&self) -> StatusCode {
+        match self {
+            MultipartRejection::MissingBoundary | MultipartRejection::Malformed(_) => {
+                StatusCode::BAD_REQUEST
+            }
+            MultipartRejection::FieldTooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+            MultipartRejection::MimeNotAllowed { .. } => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            MultipartRejection::MissingBoundary => "multipart-boundary",
+            MultipartRejection::Malformed(_) => "multipart",
+            MultipartRejection::FieldTooLarge { .. } => "payload-too-large",
+            MultipartRejection::MimeNotAllowed { .. } => "unsupported-media-type",
+        }
+    }
+
+    fn details(&self, details: &mut Details) {
+        match self {
+            MultipartRejection::FieldTooLarge { field, limit } => {
+                details.insert("field", field.as_str().into());
+                details.insert("limit", (*limit).into());
+            }
+            MultipartRejection::MimeNotAllowed { field, mime } => {
+                details.insert("field", field.as_str().into());
+                details.insert("found", mime.as_str().into());
+            }
+            _ => {}
+        }
+    }
+}
+
+macro_rules! into_error {
+    ($($ty: ty),*) => {
+        $(
+            impl From<$ty> for Error {
+                fn from(value: $ty) -> Self {
+                    Error::reject(value)
+                }
+            }
+        )*
+    };
+}
+
+into_error!(
+    MissingHeader,
+    InvalidHeader,
+    PayloadTooLarge,
+    UnsupportedMediaType
+);
+
+#[cfg(feature = "serde")]
+into_error!(JsonRejection, FormRejection);
+
+#[cfg(feature = "csrf")]
+into_error!(CsrfRejection);
+
+#[cfg(feature = "auth")]
+into_error!(Unauthorized, Forbidden);
+
+#[cfg(feature = "multipart")]
+into_error!(MultipartRejection);
@@ -0,0 +1,301 @@
+use crate::{Error, FromRequestParts, IntoResponse};
+use bycat::{Middleware, Work};
+use core::{
+    future::{Ready, ready},
+    marker::PhantomData,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use http::{HeaderName, HeaderValue, Request, Response, request::Parts};
+use pin_project_lite::pin_project;
+
+pub const X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+/// The longest incoming request id that is propagated.
+const MAX_LEN: usize = 128;
+
+/// The id of a request, set by the [`SetRequestId`] middleware.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RequestId(HeaderValue);
+
+impl RequestId {
+    pub fn new(id: HeaderValue) -> RequestId {
+        RequestId(id)
+    }
+
+    pub fn as_str(&self) -> &str {
+        // Only visible ascii ids are accepted
+        self.0.to_str().unwrap_or_default()
+    }
+
+    pub fn header_value(&self) -> &HeaderValue {
+        &self.0
+    }
+}
+
+impl core::fmt::Display for RequestId {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl<C> FromRequestParts<C> for RequestId {
+    type Future<'a>
+        = Ready<Result<Self, Error>>
+    where
+        C: 'a;
+
+    fn from_request_parts<'a>(parts: &'a mut Parts, _state: &'a C) -> Self::Future<'a> {
+        ready(
+            parts
+                .extensions
+                .get::<RequestId>()
+                .cloned()
+                .ok_or_else(|| Error::custom("Missing request id")),
+        )
+    }
+}
+
+fn uuid() -> HeaderValue {
+    let id = uuid::Uuid::new_v4();
+    HeaderValue::from_str(
+        id.hyphenated()
+            .encode_lower(&mut uuid::Uuid::encode_buffer()),
+    )
+    .expect("uuid is a valid header value")
+}
+
+/// Gives every request an id, and echoes it in the response.
+///
+/// The id of the incoming `X-Request-Id` header is kept, if it's at most
+/// 128 visible ascii characters, so ids propagate through proxies. Other
+/// requests get a random uuid.
+#[derive(Debug, Clone)]
+pub struct SetRequestId {
+    header: HeaderName,
+    trust_incoming: bool,
+    generate: fn() -> HeaderValue,
+}
+
+impl Default for SetRequestId {
+    fn default() -> Self {
+        SetRequestId {
+            header: X_REQUEST_ID,
+            trust_incoming: true,
+            generate: uuid,
+        }
+    }
+}
+
+impl SetRequestId {
+    pub fn new() -> SetRequestId {
+        SetRequestId::default()
+    }
+
+    /// The header carrying the id. Defaults to `X-Request-Id`.
+    pub fn header(mut self, header: HeaderName) -> Self {
+        self.header = header;
+        self
+    }
+
+    /// Whether to keep the ids sent by clients. Turn it off when the server
+    /// isn't behind a proxy setting the header.
+    pub fn trust_incoming(mut self, trust: bool) -> Self {
+        self.trust_incoming = trust;
+        self
+    }
+
+    /// How new ids are made. Defaults to random uuids.
+    pub fn generator(mut self, generate: fn() -> HeaderValue) -> Self {
+        self.generate = generate;
+        self
+    }
+
+    fn id<B>(&self, req: &Request<B>) -> RequestId {
+        let incoming = req
+            .headers()
+            .get(&self.header)
+            .filter(|_| self.trust_incoming)
+            .filter(|id| {
+                !id.is_empty()
+                    && id.len() <= MAX_LEN
+                    && id.as_bytes().iter().all(|b| b.is_ascii_graphic())
+            });
+
+        match incoming {
+            Some(id) => RequestId(id.clone()),
+            None => RequestId((self.generate)()),
+        }
+    }
+}
+
+impl<C, B, T> Middleware<C, Request<B>, T> for SetRequestId
+where
+    T: Work<C, Request<B>>,
+    T::Output: IntoResponse<B>,
+{
+    type Work = SetRequestIdWork<T, B>;
+
+    fn wrap(&self, handle: T) -> Self::Work {
+        SetRequestIdWork {
+            work: handle,
+            options: self.clone(),
+            body: PhantomData,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SetRequestIdWork<T, B> {
+    work: T,
+    options: SetRequestId,
+    body: PhantomData<fn(B)>,
+}
+
+impl<T: Clone, B> Clone for SetRequestIdWork<T, B> {
+    fn clone(&self) -> Self {
+        SetRequestIdWork {
+            work: self.work.clone(),
+            options: self.options.clone(),
+            body: PhantomData,
+        }
+    }
+}
+
+impl<T, C, B> Work<C, Request<B>> for SetRequestIdWork<T, B>
+where
+    T: Work<C, Request<B>>,
+    T::Output: IntoResponse<B>,
+{
+    type Output = Response<B>;
+
+    type Error = T::Error;
+
+    type Future<'a>
+        = SetRequestIdFuture<'a, T, C, B>
+    where
+        Self: 'a,
+        C: 'a;
+
+    fn call<'a>(&'a self, context: &'a C, mut req: Request<B>) -> Self::Future<'a> {
+        let id = self.options.id(&req);
+        req.headers_mut()
+            .insert(self.options.header.clone(), id.0.clone());
+        req.extensions_mut().insert(id.clone());
+
+        SetRequestIdFuture {
+            future: self.work.call(context, req),
+            header: &self.options.header,
+            id: Some(id),
+        }
+    }
+}
+
+pin_project! {
+    pub struct SetRequestIdFuture<'a, T, C, B>
+    where
+        T: Work<C, Request<B>>,
+        T: 'a,
+        C: 'a,
+    {
+        #[pin]
+        future: T::Future<'a>,
+        header: &'a HeaderName,
+        id: Option<RequestId>,
+    }
+}
+
+impl<'a, T, C, B> Future for SetRequestIdFuture<'a, T, C, B>
+where
+    T: Work<C, Request<B>>,
+    T::Output: IntoResponse<B>,
+{
+    type Output = Result<Response<B>, T::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let output = core::task::ready!(this.future.poll(cx))?;
+
+        let mut resp = output.into_response();
+        if let Some(id) = this.id.take() {
+            resp.headers_mut().insert(this.header.clone(), id.0);
+        }
+
+        Poll::Ready(Ok(resp))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::Body;
+
+    struct Echo;
+
+    impl Work<(), Request<Body>> for Echo {
+        type Output = Response<Body>;
+        type Error = Error;
+        type Future<'a>
+            = Ready<Result<Response<Body>, Error>>
+        where
+            Self: 'a;
+
+        fn call<'a>(&'a self, _context: &'a (), req: Request<Body>) -> Self::Future<'a> {
+            let id = req.extensions().get::<RequestId>().unwrap();
+            ready(Ok(Response::new(Body::from(alloc::format!("{id}")))))
+        }
+    }
+
+    #[tokio::test]
+    async fn generates() {
+        let work = Middleware::<(), Request<Body>, _>::wrap(&SetRequestId::new(), Echo);
+
+        let resp = work.call(&(), Request::new(Body::empty())).await.unwrap();
+        let id = resp.headers()[X_REQUEST_ID].to_str().unwrap().to_owned();
+        assert_eq!(id.len(), 36);
+
+        let body = crate::body::to_bytes(resp.into_body()).await.unwrap();
+        assert_eq!(body, id.as_bytes());
+    }
+
+    #[tokio::test]
+    async fn propagates() {
+        let work = Middleware::<(), Request<Body>, _>::wrap(&SetRequestId::new(), Echo);
+
+        let req = Request::builder()
+            .header(X_REQUEST_ID, "abc-123")
+            .body(Body::empty())
+            .unwrap();
+        let resp = work.call(&(), req).await.unwrap();
+        assert_eq!(resp.headers()[X_REQUEST_ID], "abc-123");
+
+        let req = Request::builder()
+            .header(X_REQUEST_ID, "has spaces")
+            .body(Body::empty())
+            .unwrap();
+        let resp = work.call(&(), req).await.unwrap();
+        assert_ne!(resp.headers()[X_REQUEST_ID], "has spaces");
+
+        let untrusted = SetRequestId::new().trust_incoming(false);
+        let work = Middleware::<(), Request<Body>, _>::wrap(&untrusted, Echo);
+        let req = Request::builder()
+            .header(X_REQUEST_ID, "abc-123")
+            .body(Body::empty())
+            .unwrap();
+        let resp = work.call(&(), req).await.unwrap();
+        assert_ne!(resp.headers()[X_REQUEST_ID], "abc-123");
+    }
+
+    #[test]
+    fn extractor() {
+        let (mut parts, _) = Request::new(()).into_parts();
+        let id = RequestId::from_request_parts(&mut parts, &()).into_inner();
+        assert!(id.is_err());
+
+        parts
+            .extensions
+            .insert(RequestId::new(HeaderValue::from_static("abc")));
+        let id = RequestId::from_request_parts(&mut parts, &()).into_inner();
+        assert_eq!(id.unwrap().as_str(), "abc");
+    }
+}
@@ -0,0 +1,110 @@
+use alloc::{string::String, vec::Vec};
+use http::StatusCode;
+use routing::router::MethodFilter;
+
+#[cfg(feature = "openapi")]
+use crate::openapi::{SchemaFn, subschema};
+
+/// Documentation attached to a route, eg. for generating an OpenAPI document.
+#[derive(Debug, Clone, Default)]
+pub struct RouteMeta {
+    pub summary: Option<String>,
+    pub description: Option<String>,
+    pub operation_id: Option<String>,
+    pub tags: Vec<String>,
+    pub deprecated: bool,
+    #[cfg(feature = "openapi")]
+    pub request: Option<SchemaFn>,
+    pub responses: Vec<ResponseMeta>,
+}
+
+impl RouteMeta {
+    pub fn new() -> RouteMeta {
+        RouteMeta::default()
+    }
+
+    pub fn summary(mut self, summary: impl Into<String>) -> Self {
+        self.summary = Some(summary.into());
+        self
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    pub fn operation_id(mut self, id: impl Into<String>) -> Self {
+        self.operation_id = Some(id.into());
+        self
+    }
+
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.tags.push(tag.into());
+        self
+    }
+
+    pub fn deprecated(mut self) -> Self {
+        self.deprecated = true;
+        self
+    }
+
+    pub fn response(mut self, status: StatusCode, description: impl Into<String>) -> Self {
+        self.responses.push(ResponseMeta {
+            status,
+            description: description.into(),
+            #[cfg(feature = "openapi")]
+            schema: None,
+        });
+        self
+    }
+
+    /// The json request body of the route.
+    #[cfg(feature = "openapi")]
+    pub fn request<T: schemars::JsonSchema>(mut self) -> Self {
+        self.request = Some(subschema::<T>);
+        self
+    }
+
+    /// A response with a json body.
+    #[cfg(feature = "openapi")]
+    pub fn response_with<T: schemars::JsonSchema>(
+        mut self,
+        status: StatusCode,
+        description: impl Into<String>,
+    ) -> Self {
+        self.responses.push(ResponseMeta {
+            status,
+            description: description.into(),
+            schema: Some(subschema::<T>),
+        });
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ResponseMeta {
+    pub status: StatusCode,
+    pub description: String,
+    #[cfg(feature = "openapi")]
+    pub schema: Option<SchemaFn>,
+}
+
+/// A registered route and its documentation.
+#[derive(Debug, Clone)]
+pub struct RouteInfo {
+    pub method: MethodFilter,
+    pub path: String,
+    pub meta: RouteMeta,
+}
+
+pub(crate) fn join_path(prefix: &str, path: &str) -> String {
+    let prefix = prefix.trim_matches('/');
+    let path = path.trim_start_matches('/');
+
+    match (prefix.is_empty(), path.is_empty()) {
+        (true, true) => String::from("/"),
+        (true, false) => format!("/{path}"),
+        (false, true) => format!("/{prefix}"),
+        (false, false) => format!("/{prefix}/{path}"),
+    }
+}
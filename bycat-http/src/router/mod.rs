@@ -1,13 +1,20 @@
 mod error;
+mod meta;
 mod params;
+#[cfg(feature = "serde")]
+mod path;
 mod router;
 mod send;
 
 pub use self::{
     error::*,
+    meta::{ResponseMeta, RouteInfo, RouteMeta},
     params::*,
     router::*,
     send::{SendRouter, SendRouterBuilder, SendWork},
 };
 
+#[cfg(feature = "serde")]
+pub use self::path::{Path, PathError, PathErrorKind};
+
 pub use routing::router::MethodFilter;
@@ -4,7 +4,8 @@ use routing::Params;
 #[derive(Debug, Clone, Default)]
 pub struct UrlParams {
     pub(crate) inner: BTreeMap<Arc<str>, Arc<str>>,
-    pub(crate) order: Vec<Arc<str>>,
+    pub(crate) order: Vec<(Arc<str>, Arc<str>)>,
+    pub(crate) prefix: Option<Arc<str>>,
 }
 
 impl UrlParams {
@@ -13,14 +14,46 @@ impl UrlParams {
     }
 
     pub fn get_at(&self, idx: usize) -> Option<&Arc<str>> {
-        self.order.get(idx)
+        self.order.get(idx).map(|(_, value)| value)
+    }
+
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Arc<str>, &Arc<str>)> {
+        self.order.iter().map(|(key, value)| (key, value))
+    }
+
+    /// The part of the request path matched by the mount point of the router,
+    /// if the route was mounted.
+    pub fn prefix(&self) -> Option<&str> {
+        self.prefix.as_deref()
+    }
+
+    /// Strips the mount point from `path`, returning the path as seen by the mounted router.
+    pub fn strip_prefix<'a>(&self, path: &'a str) -> &'a str {
+        let Some(prefix) = self.prefix.as_deref() else {
+            return path;
+        };
+
+        match path.strip_prefix(prefix.trim_end_matches('/')) {
+            Some("") => "/",
+            Some(rest) if rest.starts_with('/') => rest,
+            _ => path,
+        }
     }
 }
 
 impl Params for UrlParams {
     fn set(&mut self, key: alloc::borrow::Cow<'_, str>, value: alloc::borrow::Cow<'_, str>) {
+        let key: Arc<str> = Arc::from(key.as_ref());
         let value: Arc<str> = Arc::from(value.as_ref());
-        self.order.push(value.clone());
-        self.inner.insert(Arc::from(key.as_ref()), value);
+        self.order.push((key.clone(), value.clone()));
+        self.inner.insert(key, value);
     }
 }
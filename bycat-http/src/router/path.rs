@@ -0,0 +1,614 @@
+use crate::{
+    Error, FromRequestParts, IntoResponse,
+    body::HttpBody,
+    rejection::{Details, Rejection},
+    router::UrlParams,
+};
+use alloc::{
+    string::{String, ToString},
+    sync::Arc,
+};
+use bytes::Bytes;
+use core::{
+    fmt,
+    future::{self, Ready},
+    ops::{Deref, DerefMut},
+};
+use http::{Response, StatusCode, request::Parts};
+use serde::{
+    de::{self, DeserializeOwned, Deserializer as _, IntoDeserializer},
+    forward_to_deserialize_any,
+};
+
+/// Extract typed values from the parameters of the matched route.
+///
+/// Parameters can be deserialized into a single value, a tuple (in the order
+/// they appear in the route) or a struct (by name).
+///
+/// ```ignore
+/// #[derive(serde::Deserialize)]
+/// struct UserRoute {
+///     id: u64,
+///     tab: String,
+/// }
+///
+/// router.get("/users/:id/:tab", handler(async |Path(route): Path<UserRoute>| {
+///     format!("{} {}", route.id, route.tab)
+/// }))?;
+///
+/// router.get("/posts/:id/:slug", handler(async |Path((id, slug)): Path<(u64, String)>| {
+///     format!("{id} {slug}")
+/// }))?;
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(transparent)]
+pub struct Path<T>(pub T);
+
+impl<T> Path<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for Path<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Path<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<C, T> FromRequestParts<C> for Path<T>
+where
+    T: DeserializeOwned,
+{
+    type Future<'a>
+        = Ready<Result<Self, Error>>
+    where
+        C: 'a;
+
+    fn from_request_parts<'a>(parts: &'a mut Parts, state: &'a C) -> Self::Future<'a> {
+        let params = UrlParams::from_request_parts(parts, state).into_inner();
+        future::ready(params.and_then(|params| Path::from_params(&params).map_err(Into::into)))
+    }
+}
+
+impl<T> Path<T>
+where
+    T: DeserializeOwned,
+{
+    pub fn from_params(params: &UrlParams) -> Result<Path<T>, PathError> {
+        T::deserialize(PathDeserializer { params }).map(Path)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathErrorKind {
+    /// The number of parameters did not match the target type.
+    WrongNumberOfParameters {
+        got: usize,
+        expected: usize,
+    },
+    /// A parameter could not be parsed as the expected type.
+    InvalidValue {
+        value: Arc<str>,
+        expected: &'static str,
+    },
+    /// The target type cannot be deserialized from a path parameter.
+    UnsupportedType(&'static str),
+    Message(String),
+}
+
+/// Rejection returned by [`Path`] when the parameters can not be deserialized.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathError {
+    param: Option<Arc<str>>,
+    kind: PathErrorKind,
+}
+
+impl PathError {
+    fn new(kind: PathErrorKind) -> PathError {
+        PathError { param: None, kind }
+    }
+
+    fn with_param(mut self, param: &Arc<str>) -> PathError {
+        if self.param.is_none() {
+            self.param = Some(param.clone());
+        }
+        self
+    }
+
+    /// The name of the parameter which failed, if known.
+    pub fn param(&self) -> Option<&str> {
+        self.param.as_deref()
+    }
+
+    pub fn kind(&self) -> &PathErrorKind {
+        &self.kind
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self.kind {
+            PathErrorKind::UnsupportedType(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            PathErrorKind::WrongNumberOfParameters { got, expected } => {
+                write!(
+                    f,
+                    "Wrong number of path parameters. Expected {expected} but got {got}"
+                )
+            }
+            PathErrorKind::InvalidValue { value, expected } => match &self.param {
+                Some(param) => write!(
+                    f,
+                    "Cannot parse parameter `{param}` with value `{value}` as `{expected}`"
+                ),
+                None => write!(f, "Cannot parse `{value}` as `{expected}`"),
+            },
+            PathErrorKind::UnsupportedType(name) => {
+                write!(f, "Unsupported type `{name}` for path parameters")
+            }
+            PathErrorKind::Message(msg) => match &self.param {
+                Some(param) => write!(f, "Invalid parameter `{param}`: {msg}"),
+                None => write!(f, "{msg}"),
+            },
+        }
+    }
+}
+
+impl core::error::Error for PathError {}
+
+impl de::Error for PathError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        PathError::new(PathErrorKind::Message(msg.to_string()))
+    }
+}
+
+impl Rejection for PathError {
+    fn status(&self) -> StatusCode {
+        PathError::status(self)
+    }
+
+    fn kind(&self) -> &'static str {
+        "invalid-path-parameter"
+    }
+
+    fn details(&self, details: &mut Details) {
+        if let Some(param) = &self.param {
+            details.insert("parameter", (&**param).into());
+        }
+    }
+}
+
+impl From<PathError> for Error {
+    fn from(value: PathError) -> Self {
+        if value.status() == StatusCode::BAD_REQUEST {
+            Error::reject(value)
+        } else {
+            Error::custom(value)
+        }
+    }
+}
+
+impl<B: HttpBody> IntoResponse<B> for PathError {
+    fn into_response(self) -> Response<B> {
+        let mut resp = Response::new(B::from_bytes(Bytes::from(self.to_string())));
+        *resp.status_mut() = self.status();
+        resp
+    }
+}
+
+struct PathDeserializer<'de> {
+    params: &'de UrlParams,
+}
+
+impl<'de> PathDeserializer<'de> {
+    fn single(&self) -> Result<ParamDeserializer<'de>, PathError> {
+        if self.params.len() != 1 {
+            return Err(PathError::new(PathErrorKind::WrongNumberOfParameters {
+                got: self.params.len(),
+                expected: 1,
+            }));
+        }
+
+        let (key, value) = &self.params.order[0];
+
+        Ok(ParamDeserializer { key, value })
+    }
+}
+
+macro_rules! single {
+    ($($method: ident)*) => {
+        $(
+            fn $method<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+                self.single()?.$method(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for PathDeserializer<'de> {
+    type Error = PathError;
+
+    fn deserialize_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_map(visitor)
+    }
+
+    single!(
+        deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64 deserialize_f32
+        deserialize_f64 deserialize_char deserialize_str deserialize_string
+        deserialize_option
+    );
+
+    fn deserialize_enum<V: de::Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.single()?.deserialize_enum(name, variants, visitor)
+    }
+
+    fn deserialize_unit<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_seq(ParamsSeq {
+            iter: self.params.order.iter(),
+        })
+    }
+
+    fn deserialize_tuple<V: de::Visitor<'de>>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        if self.params.len() != len {
+            return Err(PathError::new(PathErrorKind::WrongNumberOfParameters {
+                got: self.params.len(),
+                expected: len,
+            }));
+        }
+
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_map<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_map(ParamsMap {
+            iter: self.params.order.iter(),
+            value: None,
+        })
+    }
+
+    fn deserialize_struct<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_bytes<V: de::Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+        Err(PathError::new(PathErrorKind::UnsupportedType("bytes")))
+    }
+
+    fn deserialize_byte_buf<V: de::Visitor<'de>>(
+        self,
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        Err(PathError::new(PathErrorKind::UnsupportedType("bytes")))
+    }
+
+    fn deserialize_identifier<V: de::Visitor<'de>>(
+        self,
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        Err(PathError::new(PathErrorKind::UnsupportedType("identifier")))
+    }
+
+    fn deserialize_ignored_any<V: de::Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+}
+
+struct ParamsSeq<'de> {
+    iter: core::slice::Iter<'de, (Arc<str>, Arc<str>)>,
+}
+
+impl<'de> de::SeqAccess<'de> for ParamsSeq<'de> {
+    type Error = PathError;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        match self.iter.next() {
+            Some((key, value)) => seed
+                .deserialize(ParamDeserializer { key, value })
+                .map(Some)
+                .map_err(|err| err.with_param(key)),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct ParamsMap<'de> {
+    iter: core::slice::Iter<'de, (Arc<str>, Arc<str>)>,
+    value: Option<&'de (Arc<str>, Arc<str>)>,
+}
+
+impl<'de> de::MapAccess<'de> for ParamsMap<'de> {
+    type Error = PathError;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+    where
+        K: de::DeserializeSeed<'de>,
+    {
+        match self.iter.next() {
+            Some(entry) => {
+                self.value = Some(entry);
+                seed.deserialize((&*entry.0).into_deserializer()).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        let Some((key, value)) = self.value.take() else {
+            return Err(de::Error::custom("value is missing"));
+        };
+
+        seed.deserialize(ParamDeserializer { key, value })
+            .map_err(|err| err.with_param(key))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct ParamDeserializer<'de> {
+    key: &'de Arc<str>,
+    value: &'de Arc<str>,
+}
+
+macro_rules! parse_value {
+    ($($method: ident => $visit: ident $ty: ty,)*) => {
+        $(
+            fn $method<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+                match self.value.parse::<$ty>() {
+                    Ok(value) => visitor.$visit(value),
+                    Err(_) => Err(PathError {
+                        param: Some(self.key.clone()),
+                        kind: PathErrorKind::InvalidValue {
+                            value: self.value.clone(),
+                            expected: stringify!($ty),
+                        },
+                    }),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for ParamDeserializer<'de> {
+    type Error = PathError;
+
+    fn deserialize_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_borrowed_str(self.value)
+    }
+
+    parse_value!(
+        deserialize_bool => visit_bool bool,
+        deserialize_i8 => visit_i8 i8,
+        deserialize_i16 => visit_i16 i16,
+        deserialize_i32 => visit_i32 i32,
+        deserialize_i64 => visit_i64 i64,
+        deserialize_i128 => visit_i128 i128,
+        deserialize_u8 => visit_u8 u8,
+        deserialize_u16 => visit_u16 u16,
+        deserialize_u32 => visit_u32 u32,
+        deserialize_u64 => visit_u64 u64,
+        deserialize_u128 => visit_u128 u128,
+        deserialize_f32 => visit_f32 f32,
+        deserialize_f64 => visit_f64 f64,
+        deserialize_char => visit_char char,
+    );
+
+    fn deserialize_option<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_enum(de::value::BorrowedStrDeserializer::<PathError>::new(
+            self.value,
+        ))
+    }
+
+    fn deserialize_seq<V: de::Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+        Err(PathError::new(PathErrorKind::UnsupportedType("sequence")))
+    }
+
+    fn deserialize_map<V: de::Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+        Err(PathError::new(PathErrorKind::UnsupportedType("map")))
+    }
+
+    forward_to_deserialize_any! {
+        str string bytes byte_buf unit unit_struct tuple
+        tuple_struct struct identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use routing::Params;
+    use serde::Deserialize;
+
+    fn params(values: &[(&str, &str)]) -> UrlParams {
+        let mut params = UrlParams::default();
+        for (key, value) in values {
+            params.set((*key).into(), (*value).into());
+        }
+        params
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct UserRoute {
+        id: u64,
+        tab: String,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(rename_all = "lowercase")]
+    enum Kind {
+        Post,
+        Page,
+    }
+
+    #[test]
+    fn single_value() {
+        let Path(id) = Path::<u64>::from_params(&params(&[("id", "42")])).unwrap();
+        assert_eq!(id, 42);
+    }
+
+    #[test]
+    fn tuple() {
+        let Path((id, slug)) =
+            Path::<(u64, String)>::from_params(&params(&[("id", "7"), ("slug", "hello")])).unwrap();
+        assert_eq!(id, 7);
+        assert_eq!(slug, "hello");
+    }
+
+    #[test]
+    fn structure() {
+        let Path(route) =
+            Path::<UserRoute>::from_params(&params(&[("tab", "settings"), ("id", "1")])).unwrap();
+        assert_eq!(
+            route,
+            UserRoute {
+                id: 1,
+                tab: "settings".into()
+            }
+        );
+    }
+
+    #[test]
+    fn unit_enum() {
+        let Path(kind) = Path::<Kind>::from_params(&params(&[("kind", "page")])).unwrap();
+        assert_eq!(kind, Kind::Page);
+    }
+
+    #[test]
+    fn invalid_value_names_param() {
+        let err = Path::<(u64, String)>::from_params(&params(&[("id", "abc"), ("slug", "a")]))
+            .unwrap_err();
+
+        assert_eq!(err.param(), Some("id"));
+        assert_eq!(err.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            err.kind(),
+            &PathErrorKind::InvalidValue {
+                value: "abc".into(),
+                expected: "u64"
+            }
+        );
+    }
+
+    #[test]
+    fn struct_field_error_names_param() {
+        let err =
+            Path::<UserRoute>::from_params(&params(&[("id", "-1"), ("tab", "a")])).unwrap_err();
+        assert_eq!(err.param(), Some("id"));
+    }
+
+    #[test]
+    fn wrong_number_of_params() {
+        let err = Path::<(u64, u64)>::from_params(&params(&[("id", "1")])).unwrap_err();
+        assert_eq!(
+            err.kind(),
+            &PathErrorKind::WrongNumberOfParameters {
+                got: 1,
+                expected: 2
+            }
+        );
+    }
+
+    #[test]
+    fn extractor() {
+        let (mut parts, _) = http::Request::new(()).into_parts();
+        let err = Path::<u64>::from_request_parts(&mut parts, &())
+            .into_inner()
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        parts.extensions.insert(params(&[("id", "42")]));
+        let Path(id) = Path::<u64>::from_request_parts(&mut parts, &())
+            .into_inner()
+            .unwrap();
+        assert_eq!(id, 42);
+    }
+}
@@ -1,10 +1,16 @@
 use crate::{
     body::HttpBody,
-    router::{RouteError, UrlParams},
+    router::{
+        RouteError, UrlParams,
+        meta::{RouteInfo, RouteMeta, join_path},
+    },
 };
-use alloc::{collections::BTreeMap, string::String, vec::Vec};
+use alloc::{collections::BTreeMap, string::String, sync::Arc, vec::Vec};
 use bycat::{Middleware, Work};
-use core::{marker::PhantomData, task::Poll};
+use core::{
+    marker::PhantomData,
+    task::{Poll, ready},
+};
 use http::{HeaderValue, Method, Request, Response, StatusCode, header::ALLOW};
 use pin_project_lite::pin_project;
 use routing::{Params, Segments, router::MethodFilter};
@@ -14,12 +20,34 @@ use std::fmt::Write as _;
 pub struct Entry<T> {
     pub handler: T,
     pub name: Option<String>,
+    /// The full path of the route, including the mount points.
+    pub(crate) path: Arc<str>,
+    /// The path the route's router is mounted at, if any.
+    pub(crate) prefix: Option<Arc<str>>,
+}
+
+impl<T> Entry<T> {
+    fn map<U>(self, func: impl FnOnce(T) -> U) -> Entry<U> {
+        Entry {
+            handler: func(self.handler),
+            name: self.name,
+            path: self.path,
+            prefix: self.prefix,
+        }
+    }
 }
 
+/// Collects routes and middleware for a [`Router`].
+///
+/// Errors returned by routes are passed on as `T::Error`, which isn't
+/// necessarily an [`Error`](crate::Error), so there is no error handler hook
+/// here. Wrap the built router in [`HandleError`](crate::error_handler::HandleError),
+/// or use [`SendRouterBuilder::error_handler`](crate::router::SendRouterBuilder::error_handler).
 pub struct Builder<T, M, C, B> {
     pub routes: routing::router::Router<Entry<T>>,
     pub middleware: Vec<M>,
     pub middleware_path: BTreeMap<String, Vec<M>>,
+    info: Vec<RouteInfo>,
     context: PhantomData<fn() -> (C, B)>,
 }
 
@@ -33,6 +61,7 @@ where
             routes: self.routes.clone(),
             middleware: self.middleware.clone(),
             middleware_path: self.middleware_path.clone(),
+            info: self.info.clone(),
             context: PhantomData,
         }
     }
@@ -44,6 +73,7 @@ impl<T, M, C, B> Default for Builder<T, M, C, B> {
             routes: routing::router::Router::new(),
             middleware: Vec::default(),
             middleware_path: Default::default(),
+            info: Vec::default(),
             context: PhantomData,
         }
     }
@@ -55,6 +85,16 @@ impl<T, M, C, B> Builder<T, M, C, B> {
         method: MethodFilter,
         path: impl AsRef<str>,
         handler: T,
+    ) -> Result<&mut Self, RouteError> {
+        self.add_route_with_meta(method, path, RouteMeta::default(), handler)
+    }
+
+    pub fn add_route_with_meta(
+        &mut self,
+        method: MethodFilter,
+        path: impl AsRef<str>,
+        meta: RouteMeta,
+        handler: T,
     ) -> Result<&mut Self, RouteError> {
         self.routes.route(
             method,
@@ -62,8 +102,15 @@ impl<T, M, C, B> Builder<T, M, C, B> {
             Entry {
                 handler,
                 name: None,
+                path: join_path("", path.as_ref()).into(),
+                prefix: None,
             },
         )?;
+        self.info.push(RouteInfo {
+            method,
+            path: join_path("", path.as_ref()),
+            meta,
+        });
         Ok(self)
     }
 
@@ -72,8 +119,23 @@ impl<T, M, C, B> Builder<T, M, C, B> {
         self
     }
 
+    /// Add a middleware to the routes at or below `path`, eg. a mounted router.
+    /// The scope may contain parameters, eg. `/users/:id`.
+    ///
+    /// Scoped middleware wraps the middleware added with [`Builder::middleware`],
+    /// so it runs first.
+    pub fn scoped_middleware(&mut self, path: &str, middleware: M) -> &mut Self {
+        self.middleware_path
+            .entry(join_path("", path))
+            .or_default()
+            .push(middleware);
+        self
+    }
+
     pub fn merge(&mut self, router: impl Into<Router<T, C, B>>) -> Result<&mut Self, RouteError> {
-        self.routes.merge(router.into().routes)?;
+        let router = router.into();
+        self.routes.merge(router.routes)?;
+        self.info.extend(router.info);
         Ok(self)
     }
 
@@ -82,7 +144,17 @@ impl<T, M, C, B> Builder<T, M, C, B> {
         path: &str,
         router: impl Into<Router<T, C, B>>,
     ) -> Result<&mut Self, RouteError> {
-        self.routes.mount(path, router.into().routes)?;
+        let router = router.into();
+        let routes = router.routes.map(|entry, _| Entry {
+            path: join_path(path, &entry.path).into(),
+            prefix: Some(join_path(path, entry.prefix.as_deref().unwrap_or("/")).into()),
+            ..entry
+        });
+        self.routes.mount(path, routes)?;
+        self.info.extend(router.info.into_iter().map(|info| RouteInfo {
+            path: join_path(path, &info.path),
+            ..info
+        }));
         Ok(self)
     }
 }
@@ -103,7 +175,7 @@ where
             };
 
             for (p, ms) in self.middleware_path.iter() {
-                if routing::match_path(&segments, &p, &mut ()) {
+                if in_scope(&segments, p, &route.path) {
                     for m in ms.iter().rev() {
                         route.handler = m.wrap(route.handler);
                     }
@@ -116,6 +188,7 @@ where
         Router {
             routes,
             fallback: None,
+            info: self.info,
             context: PhantomData,
         }
     }
@@ -134,6 +207,7 @@ where
 pub struct Router<T, C, B> {
     routes: routing::router::Router<Entry<T>>,
     fallback: Option<T>,
+    info: Vec<RouteInfo>,
     context: PhantomData<fn() -> (C, B)>,
 }
 
@@ -145,6 +219,7 @@ where
         Self {
             routes: self.routes.clone(),
             fallback: self.fallback.clone(),
+            info: self.info.clone(),
             context: PhantomData,
         }
     }
@@ -156,11 +231,22 @@ impl<T, C, B> Default for Router<T, C, B> {
             routes: routing::router::Router::new(),
             context: PhantomData,
             fallback: None,
+            info: Vec::default(),
         }
     }
 }
 
 impl<T, C, B> Router<T, C, B> {
+    /// Maps the handlers of the router, eg. to run them in another context.
+    pub fn map<U, C2>(self, func: impl Fn(T) -> U) -> Router<U, C2, B> {
+        Router {
+            routes: self.routes.map(|entry, _| entry.map(&func)),
+            fallback: self.fallback.map(&func),
+            info: self.info,
+            context: PhantomData,
+        }
+    }
+
     pub fn get_match<P: Params>(
         &self,
         method: MethodFilter,
@@ -175,6 +261,99 @@ impl<T, C, B> Router<T, C, B> {
     pub fn iter(&self) -> impl Iterator<Item = (&Segments<'_>, &routing::router::Route<Entry<T>>)> {
         self.routes.iter()
     }
+
+    /// The registered routes and their documentation, in registration order.
+    pub fn route_info(&self) -> &[RouteInfo] {
+        &self.info
+    }
+
+    /// Returns the methods routed for `path`, or `None` if no route matches the path.
+    ///
+    /// `HEAD` is included when `GET` is, and `OPTIONS` is always included,
+    /// since both are answered by the router.
+    pub fn allowed_methods(&self, path: &str) -> Option<MethodFilter> {
+        let mut allowed = MethodFilter::empty();
+
+        for (_, method) in self.routes.match_routes(path, MethodFilter::all(), &mut ()) {
+            allowed |= method;
+        }
+
+        if allowed.is_empty() {
+            return None;
+        }
+
+        if allowed.contains(MethodFilter::GET) {
+            allowed |= MethodFilter::HEAD;
+        }
+
+        Some(allowed | MethodFilter::OPTIONS)
+    }
+}
+
+fn path_segments(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|segment| !segment.is_empty())
+}
+
+/// Whether the route with `segments` and `path` is at or below `scope`.
+fn in_scope(segments: &Segments<'_>, scope: &str, path: &str) -> bool {
+    if routing::match_path(segments, scope, &mut ()) {
+        return true;
+    }
+
+    // Routes below the scope match it, followed by the rest of their own path
+    let rest = path_segments(path)
+        .skip(path_segments(scope).count())
+        .collect::<Vec<_>>();
+    if rest.is_empty() {
+        return false;
+    }
+
+    routing::match_path(segments, &join_path(scope, &rest.join("/")), &mut ())
+}
+
+/// The part of the request path matched by the mount point `prefix` of the
+/// route with the full path `route`.
+fn matched_prefix(prefix: &str, route: &str, path: &str) -> Arc<str> {
+    let count = if path_segments(prefix).any(|segment| segment.starts_with('*')) {
+        // A wildcard in the mount point matches any number of segments,
+        // so count the segments of the mounted route from the end instead
+        let mounted = path_segments(route)
+            .count()
+            .saturating_sub(path_segments(prefix).count());
+        path_segments(path).count().saturating_sub(mounted)
+    } else {
+        path_segments(prefix).count()
+    };
+
+    let mut output = String::new();
+    for segment in path_segments(path).take(count) {
+        output.push('/');
+        output.push_str(segment);
+    }
+
+    if output.is_empty() {
+        output.push('/');
+    }
+
+    output.into()
+}
+
+fn allow_response<B: HttpBody>(status: StatusCode, allowed: MethodFilter) -> Response<B> {
+    let mut output = String::new();
+
+    for (idx, method) in allowed.iter().enumerate() {
+        if idx > 0 {
+            output.push_str(", ");
+        }
+
+        write!(&mut output, "{}", method).expect("Write");
+    }
+
+    let mut resp = Response::new(B::empty());
+    *resp.status_mut() = status;
+    resp.headers_mut()
+        .insert(ALLOW, HeaderValue::from_str(&output).expect("HeaderValue"));
+    resp
 }
 
 impl<T, C, B> Work<C, Request<B>> for Router<T, C, B>
@@ -214,7 +393,8 @@ pin_project! {
         },
         Future {
             #[pin]
-            future: T::Future<'a>
+            future: T::Future<'a>,
+            head: bool,
         }
     }
 }
@@ -248,35 +428,34 @@ where
                 StateProj::Init { context, req } => {
                     let mut req = req.take().unwrap();
                     let context = context.take().unwrap();
+                    let head = req.method() == Method::HEAD;
+                    let path = req.uri().path();
+
                     let mut params = UrlParams::default();
-                    let found = if let Some(found) = this.router.get_match(
-                        req.method().clone().into(),
-                        req.uri().path(),
-                        &mut params,
-                    ) {
+                    let mut found = this
+                        .router
+                        .get_match(req.method().clone().into(), path, &mut params);
+
+                    if found.is_none() && head {
+                        // Answer HEAD with the GET route, the body is stripped when done
+                        params = UrlParams::default();
+                        found = this.router.get_match(MethodFilter::GET, path, &mut params);
+                    }
+
+                    let found = if let Some(found) = found {
+                        params.prefix = found
+                            .prefix
+                            .as_ref()
+                            .map(|prefix| matched_prefix(prefix, &found.path, path));
                         &found.handler
-                    } else if req.method() == Method::OPTIONS {
-                        let mut output = String::new();
-
-                        for (idx, method) in this
-                            .router
-                            .routes
-                            .match_routes(req.uri().path(), MethodFilter::all(), &mut ())
-                            .map(|(_, method)| method)
-                            .enumerate()
-                        {
-                            if idx > 0 {
-                                output.push(',');
-                            }
-
-                            write!(&mut output, "{}", method).expect("Write");
-                        }
+                    } else if let Some(allowed) = this.router.allowed_methods(path) {
+                        let status = if req.method() == Method::OPTIONS {
+                            StatusCode::NO_CONTENT
+                        } else {
+                            StatusCode::METHOD_NOT_ALLOWED
+                        };
 
-                        let mut resp = Response::new(B::empty());
-                        *resp.status_mut() = StatusCode::NO_CONTENT;
-                        resp.headers_mut()
-                            .insert(ALLOW, HeaderValue::from_str(&output).expect("HeaderValue"));
-                        return Poll::Ready(Ok(resp));
+                        return Poll::Ready(Ok(allow_response(status, allowed)));
                     } else if let Some(fallback) = &this.router.fallback {
                         fallback
                     } else {
@@ -287,10 +466,318 @@ where
 
                     req.extensions_mut().insert(params);
                     let future = found.call(context, req);
-                    this.state.set(State::Future { future });
+                    this.state.set(State::Future { future, head });
+                }
+                StateProj::Future { future, head } => {
+                    let resp = ready!(future.poll(cx))?;
+
+                    if !*head {
+                        return Poll::Ready(Ok(resp));
+                    }
+
+                    let (parts, _) = resp.into_parts();
+                    return Poll::Ready(Ok(Response::from_parts(parts, B::empty())));
                 }
-                StateProj::Future { future } => return future.poll(cx),
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        body::{Body, to_bytes},
+        cors::Cors,
+        handler,
+        router::{SendRouter, SendRouterBuilder, SendWork},
+    };
+    use bycat::{middleware, work_fn};
+    use http::header::{ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_REQUEST_METHOD, ORIGIN};
+
+    #[tokio::test]
+    async fn generic_router_error_handler() {
+        use crate::error_handler::{HandleError, ProblemDetails};
+
+        let router: Router<SendWork<(), Body>, (), Body> = SendRouterBuilder::new()
+            .with_get(
+                "/fail",
+                work_fn(|_: (), _req: Request<Body>| async {
+                    Err::<&'static str, _>(crate::Error::not_found())
+                }),
+            )
+            .unwrap()
+            .into();
+
+        let work = Middleware::<(), Request<Body>, _>::wrap(
+            &HandleError::new(ProblemDetails::new()),
+            router,
+        );
+
+        let resp = work.call(&(), request(Method::GET, "/fail")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            resp.headers()[http::header::CONTENT_TYPE],
+            "application/problem+json"
+        );
+    }
+
+    fn router() -> SendRouter<(), Body> {
+        SendRouterBuilder::new()
+            .with_get("/items", handler(async || "items"))
+            .unwrap()
+            .with_post("/items", handler(async || "created"))
+            .unwrap()
+            .build()
+    }
+
+    fn request(method: Method, path: &str) -> Request<Body> {
+        Request::builder()
+            .method(method)
+            .uri(path)
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    fn allowed(resp: &Response<Body>, header: http::HeaderName) -> Vec<Method> {
+        resp.headers()[header]
+            .to_str()
+            .unwrap()
+            .split(',')
+            .map(|method| Method::from_bytes(method.trim().as_bytes()).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn method_not_allowed() {
+        let resp = router()
+            .call(&(), request(Method::DELETE, "/items"))
+            .await
+            .unwrap();
+
+        assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+
+        let allow = allowed(&resp, ALLOW);
+        assert_eq!(allow.len(), 4);
+        for method in [Method::GET, Method::HEAD, Method::POST, Method::OPTIONS] {
+            assert!(allow.contains(&method), "missing {method}");
+        }
+    }
+
+    #[tokio::test]
+    async fn not_found() {
+        let resp = router()
+            .call(&(), request(Method::DELETE, "/other"))
+            .await
+            .unwrap();
+
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        assert!(resp.headers().get(ALLOW).is_none());
+    }
+
+    #[tokio::test]
+    async fn head_falls_back_to_get() {
+        let resp = router()
+            .call(&(), request(Method::HEAD, "/items"))
+            .await
+            .unwrap();
+
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()[http::header::CONTENT_TYPE], "text/plain");
+        assert!(to_bytes(resp.into_body()).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn options() {
+        let resp = router()
+            .call(&(), request(Method::OPTIONS, "/items"))
+            .await
+            .unwrap();
+
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        assert_eq!(allowed(&resp, ALLOW).len(), 4);
+    }
+
+    #[tokio::test]
+    async fn options_with_cors() {
+        let work = Middleware::<(), Request<Body>, _>::wrap(&Cors::new(), router());
+
+        let mut req = request(Method::OPTIONS, "/items");
+        req.headers_mut()
+            .insert(ORIGIN, HeaderValue::from_static("https://example.com"));
+        req.headers_mut().insert(
+            ACCESS_CONTROL_REQUEST_METHOD,
+            HeaderValue::from_static("POST"),
+        );
+
+        let resp = work.call(&(), req).await.unwrap();
+
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+        let methods = allowed(&resp, ACCESS_CONTROL_ALLOW_METHODS);
+        assert_eq!(methods.len(), 2);
+        assert!(methods.contains(&Method::GET));
+        assert!(methods.contains(&Method::POST));
+    }
+
+    async fn text(resp: Response<Body>) -> String {
+        let bytes = to_bytes(resp.into_body()).await.unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn mount_prefix() {
+        let users = SendRouterBuilder::<(), Body>::new()
+            .with_get(
+                "/:id",
+                handler(async |params: UrlParams| {
+                    format!("{} {}", params.prefix().unwrap(), params.get("id").unwrap())
+                }),
+            )
+            .unwrap();
+
+        let router = SendRouterBuilder::<(), Body>::new()
+            .with_mount("/users", users)
+            .unwrap()
+            .build();
+
+        let resp = router
+            .call(&(), request(Method::GET, "/users/42"))
+            .await
+            .unwrap();
+
+        assert_eq!(text(resp).await, "/users 42");
+    }
+
+    #[tokio::test]
+    async fn mount_context() {
+        let admin = SendRouterBuilder::<usize, Body>::new()
+            .with_get(
+                "/len",
+                work_fn(|len: usize, _req: Request<Body>| async move {
+                    Ok::<_, crate::Error>(len.to_string())
+                }),
+            )
+            .unwrap();
+
+        let router = SendRouterBuilder::<String, Body>::new()
+            .with_mount_context("/admin", admin, |state: &String| state.len())
+            .unwrap()
+            .build();
+
+        let resp = router
+            .call(&String::from("bycat"), request(Method::GET, "/admin/len"))
+            .await
+            .unwrap();
+
+        assert_eq!(text(resp).await, "5");
+    }
+
+    #[tokio::test]
+    async fn scoped_middleware() {
+        let admin = SendRouterBuilder::<(), Body>::new()
+            .with_get("/dashboard", handler(async || "dashboard"))
+            .unwrap();
+
+        let router = SendRouterBuilder::<(), Body>::new()
+            .with_get("/public", handler(async || "public"))
+            .unwrap()
+            .with_mount("/admin", admin)
+            .unwrap()
+            .with_scoped_middleware(
+                "/admin",
+                middleware(|task: SendWork<(), Body>| {
+                    work_fn(move |ctx: (), req| {
+                        let task = task.clone();
+                        async move {
+                            let mut resp = task.call(&ctx, req).await?;
+                            resp.headers_mut()
+                                .insert("x-scope", HeaderValue::from_static("admin"));
+                            Ok::<_, crate::Error>(resp)
+                        }
+                    })
+                }),
+            )
+            .build();
+
+        let resp = router
+            .call(&(), request(Method::GET, "/admin/dashboard"))
+            .await
+            .unwrap();
+        assert_eq!(resp.headers()["x-scope"], "admin");
+
+        let resp = router
+            .call(&(), request(Method::GET, "/public"))
+            .await
+            .unwrap();
+        assert!(resp.headers().get("x-scope").is_none());
+    }
+
+    fn tag(name: &'static str) -> impl Fn(SendWork<(), Body>) -> SendWork<(), Body> + Send + Sync {
+        move |task: SendWork<(), Body>| {
+            SendWork::new(work_fn(move |ctx: (), req: Request<Body>| {
+                let task = task.clone();
+                async move {
+                    let mut resp = task.call(&ctx, req).await?;
+                    resp.headers_mut()
+                        .append("x-order", HeaderValue::from_static(name));
+                    Ok::<_, crate::Error>(resp)
+                }
+            }))
+        }
+    }
+
+    #[tokio::test]
+    async fn scoped_middleware_order() {
+        let router = SendRouterBuilder::<(), Body>::new()
+            .with_get("/admin/users", handler(async || "users"))
+            .unwrap()
+            .with_middleware(middleware(tag("global")))
+            .with_scoped_middleware("/admin", middleware(tag("scoped")))
+            .build();
+
+        let resp = router
+            .call(&(), request(Method::GET, "/admin/users"))
+            .await
+            .unwrap();
+
+        // Scoped middleware wraps the global middleware, so it sees the response last
+        let order = resp.headers().get_all("x-order").iter().collect::<Vec<_>>();
+        assert_eq!(order, ["global", "scoped"]);
+    }
+
+    #[tokio::test]
+    async fn parameterized_scope() {
+        let router = SendRouterBuilder::<(), Body>::new()
+            .with_get("/users/:user_id/posts", handler(async || "posts"))
+            .unwrap()
+            .with_get("/users", handler(async || "users"))
+            .unwrap()
+            .with_scoped_middleware("/users/:id", middleware(tag("user")))
+            .build();
+
+        let resp = router
+            .call(&(), request(Method::GET, "/users/42/posts"))
+            .await
+            .unwrap();
+        assert_eq!(resp.headers()["x-order"], "user");
+
+        let resp = router
+            .call(&(), request(Method::GET, "/users"))
+            .await
+            .unwrap();
+        assert!(resp.headers().get("x-order").is_none());
+    }
+
+    #[test]
+    fn prefix() {
+        assert_eq!(
+            &*matched_prefix("/users", "/users/:id", "/users/42"),
+            "/users"
+        );
+        assert_eq!(
+            &*matched_prefix("/files/*path", "/files/*path/meta", "/files/a/b/meta"),
+            "/files/a/b"
+        );
+    }
+}
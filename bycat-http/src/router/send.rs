@@ -7,20 +7,46 @@ use routing::router::MethodFilter;
 use crate::{
     Error, IntoResponse,
     body::HttpBody,
-    router::{Builder, RouteError, Router, RouterFuture},
+    error_handler::{ErrorContext, ErrorHandler, HandleErrorFuture},
+    router::{Builder, RouteError, RouteInfo, RouteMeta, Router, RouterFuture},
 };
 
+type DynErrorHandler<B> = Arc<dyn ErrorHandler<B> + Send + Sync>;
+
 pub struct SendRouterBuilder<C, B> {
     builder: Builder<SendWork<C, B>, SendMiddleware<C, B>, C, B>,
+    error_handler: Option<DynErrorHandler<B>>,
 }
 
 impl<C: Send + Sync, B: Send + 'static> SendRouterBuilder<C, B> {
     pub fn new() -> Self {
         Self {
             builder: Builder::default(),
+            error_handler: None,
         }
     }
 
+    /// Render errors returned from routes with the given handler,
+    /// eg. [`ProblemDetails`](crate::error_handler::ProblemDetails).
+    ///
+    /// Only the send router has this hook, since its routes all fail with
+    /// [`Error`]. See [`Builder`] for the generic router.
+    pub fn error_handler<T>(&mut self, handler: T) -> &mut Self
+    where
+        T: ErrorHandler<B> + Send + Sync + 'static,
+    {
+        self.error_handler = Some(Arc::new(handler));
+        self
+    }
+
+    pub fn with_error_handler<T>(mut self, handler: T) -> Self
+    where
+        T: ErrorHandler<B> + Send + Sync + 'static,
+    {
+        self.error_handler(handler);
+        self
+    }
+
     pub fn get<T>(&mut self, path: &str, worker: T) -> Result<&mut Self, RouteError>
     where
         T: Work<C, Request<B>> + Send + Sync + 'static,
@@ -88,6 +114,26 @@ impl<C: Send + Sync, B: Send + 'static> SendRouterBuilder<C, B> {
         Ok(self)
     }
 
+    /// Add a route with documentation, see [`SendRouter::route_info`].
+    pub fn documented_route<T>(
+        &mut self,
+        method: MethodFilter,
+        path: &str,
+        meta: RouteMeta,
+        worker: T,
+    ) -> Result<&mut Self, RouteError>
+    where
+        T: Work<C, Request<B>> + Send + Sync + 'static,
+        T::Error: Into<Error>,
+        for<'a> T::Future<'a>: Send + 'a,
+        T::Output: IntoResponse<B>,
+    {
+        let send_worker = SendWork::new(worker);
+        self.builder
+            .add_route_with_meta(method, path, meta, send_worker)?;
+        Ok(self)
+    }
+
     pub fn middleware<T>(&mut self, middleware: T) -> &mut Self
     where
         T: Middleware<C, Request<B>, SendWork<C, B>> + Send + Sync + 'static,
@@ -101,6 +147,20 @@ impl<C: Send + Sync, B: Send + 'static> SendRouterBuilder<C, B> {
         self
     }
 
+    /// Add a middleware to the routes at or below `path`, eg. a mounted router.
+    pub fn scoped_middleware<T>(&mut self, path: &str, middleware: T) -> &mut Self
+    where
+        T: Middleware<C, Request<B>, SendWork<C, B>> + Send + Sync + 'static,
+        T::Work: Work<C, Request<B>> + Send + Sync + 'static,
+        <T::Work as Work<C, Request<B>>>::Error: Into<Error>,
+        <T::Work as Work<C, Request<B>>>::Output: IntoResponse<B>,
+        for<'a> <T::Work as Work<C, Request<B>>>::Future<'a>: Send + 'a,
+    {
+        let send_middleware = SendMiddleware::new(middleware);
+        self.builder.scoped_middleware(path, send_middleware);
+        self
+    }
+
     pub fn mount<T>(&mut self, path: &str, router: T) -> Result<&mut Self, RouteError>
     where
         T: Into<Router<SendWork<C, B>, C, B>>,
@@ -109,6 +169,36 @@ impl<C: Send + Sync, B: Send + 'static> SendRouterBuilder<C, B> {
         Ok(self)
     }
 
+    /// Mount a router running in another context, which is derived from this
+    /// router's context for each request.
+    ///
+    /// ```ignore
+    /// let admin = SendRouterBuilder::<AdminState, Body>::new()
+    ///     .with_get("/users", list_users)?;
+    ///
+    /// let app = SendRouterBuilder::<AppState, Body>::new()
+    ///     .with_mount_context("/admin", admin, |state: &AppState| state.admin.clone())?;
+    /// ```
+    pub fn mount_context<T, S, F>(
+        &mut self,
+        path: &str,
+        router: T,
+        map: F,
+    ) -> Result<&mut Self, RouteError>
+    where
+        T: Into<Router<SendWork<S, B>, S, B>>,
+        S: Send + Sync + 'static,
+        F: Fn(&C) -> S + Send + Sync + 'static,
+        C: 'static,
+    {
+        let map = Arc::new(map);
+        let router = router
+            .into()
+            .map(|work: SendWork<S, B>| work.map_context(map.clone()));
+        self.builder.mount(path, router)?;
+        Ok(self)
+    }
+
     pub fn merge<T>(&mut self, router: T) -> Result<&mut Self, RouteError>
     where
         T: Into<Router<SendWork<C, B>, C, B>>,
@@ -172,6 +262,59 @@ impl<C: Send + Sync, B: Send + 'static> SendRouterBuilder<C, B> {
         Ok(self)
     }
 
+    pub fn with_documented_route<T>(
+        mut self,
+        method: MethodFilter,
+        path: &str,
+        meta: RouteMeta,
+        worker: T,
+    ) -> Result<Self, RouteError>
+    where
+        T: Work<C, Request<B>> + Send + Sync + 'static,
+        T::Error: Into<Error>,
+        for<'a> T::Future<'a>: Send + 'a,
+        T::Output: IntoResponse<B>,
+    {
+        self.documented_route(method, path, meta, worker)?;
+        Ok(self)
+    }
+
+    pub fn with_mount<T>(mut self, path: &str, router: T) -> Result<Self, RouteError>
+    where
+        T: Into<Router<SendWork<C, B>, C, B>>,
+    {
+        self.mount(path, router)?;
+        Ok(self)
+    }
+
+    pub fn with_mount_context<T, S, F>(
+        mut self,
+        path: &str,
+        router: T,
+        map: F,
+    ) -> Result<Self, RouteError>
+    where
+        T: Into<Router<SendWork<S, B>, S, B>>,
+        S: Send + Sync + 'static,
+        F: Fn(&C) -> S + Send + Sync + 'static,
+        C: 'static,
+    {
+        self.mount_context(path, router, map)?;
+        Ok(self)
+    }
+
+    pub fn with_scoped_middleware<T>(mut self, path: &str, middleware: T) -> Self
+    where
+        T: Middleware<C, Request<B>, SendWork<C, B>> + Send + Sync + 'static,
+        T::Work: Work<C, Request<B>> + Send + Sync + 'static,
+        <T::Work as Work<C, Request<B>>>::Error: Into<Error>,
+        <T::Work as Work<C, Request<B>>>::Output: IntoResponse<B>,
+        for<'a> <T::Work as Work<C, Request<B>>>::Future<'a>: Send + 'a,
+    {
+        self.scoped_middleware(path, middleware);
+        self
+    }
+
     pub fn with_middleware<T>(mut self, middleware: T) -> Self
     where
         T: Middleware<C, Request<B>, SendWork<C, B>> + Send + Sync + 'static,
@@ -187,6 +330,7 @@ impl<C: Send + Sync, B: Send + 'static> SendRouterBuilder<C, B> {
     pub fn build(self) -> SendRouter<C, B> {
         SendRouter {
             router: Arc::new(self.builder.build()),
+            error_handler: self.error_handler,
         }
     }
 }
@@ -213,12 +357,20 @@ impl<C: Send + Sync, B: Send + 'static> From<SendRouter<C, B>> for Router<SendWo
 
 pub struct SendRouter<C, B> {
     router: Arc<Router<SendWork<C, B>, C, B>>,
+    error_handler: Option<DynErrorHandler<B>>,
+}
+
+impl<C, B> SendRouter<C, B> {
+    pub fn route_info(&self) -> &[RouteInfo] {
+        self.router.route_info()
+    }
 }
 
 impl<C, B> Clone for SendRouter<C, B> {
     fn clone(&self) -> Self {
         Self {
             router: self.router.clone(),
+            error_handler: self.error_handler.clone(),
         }
     }
 }
@@ -227,13 +379,24 @@ impl<C, B: HttpBody> Work<C, Request<B>> for SendRouter<C, B> {
     type Error = Error;
     type Output = http::Response<B>;
     type Future<'a>
-        = RouterFuture<'a, SendWork<C, B>, C, B>
+        = HandleErrorFuture<
+        'a,
+        RouterFuture<'a, SendWork<C, B>, C, B>,
+        dyn ErrorHandler<B> + Send + Sync,
+        B,
+    >
     where
         Self: 'a,
         C: 'a;
 
     fn call<'a>(&'a self, context: &'a C, req: Request<B>) -> Self::Future<'a> {
-        self.router.call(context, req)
+        let handler = self.error_handler.as_deref();
+        HandleErrorFuture {
+            ctx: handler.map(|_| ErrorContext::from_request(&req)),
+            future: self.router.call(context, req),
+            handler,
+            body: core::marker::PhantomData,
+        }
     }
 }
 
@@ -284,6 +447,39 @@ impl<C: Send + Sync, B: Send + 'static> SendWork<C, B> {
             inner: Arc::from(Wrapper(worker)) as Arc<dyn Worker<C, B> + Send + Sync>,
         }
     }
+
+    /// Run the work in a context derived from the caller's context.
+    pub fn map_context<P, F>(self, map: Arc<F>) -> SendWork<P, B>
+    where
+        C: 'static,
+        P: Send + Sync + 'static,
+        F: Fn(&P) -> C + Send + Sync + 'static,
+    {
+        struct MapContext<C, B, F> {
+            work: SendWork<C, B>,
+            map: Arc<F>,
+        }
+
+        impl<P, C, B, F> Worker<P, B> for MapContext<C, B, F>
+        where
+            C: Send + Sync,
+            B: Send + 'static,
+            F: Fn(&P) -> C + Send + Sync,
+        {
+            fn call<'a>(
+                &'a self,
+                context: &'a P,
+                req: http::Request<B>,
+            ) -> BoxFuture<'a, Result<http::Response<B>, Error>> {
+                let context = (self.map)(context);
+                Box::pin(async move { self.work.call(&context, req).await })
+            }
+        }
+
+        SendWork {
+            inner: Arc::new(MapContext { work: self, map }) as Arc<dyn Worker<P, B> + Send + Sync>,
+        }
+    }
 }
 
 impl<C, B> Work<C, Request<B>> for SendWork<C, B> {
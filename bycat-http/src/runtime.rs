@@ -0,0 +1,54 @@
+use alloc::boxed::Box;
+use core::time::Duration;
+use futures::future::BoxFuture;
+
+/// How work is spawned in the background, and timers wait, for the proxy,
+/// response cache refreshes, server-sent event keep-alives and health check
+/// timeouts.
+#[derive(Debug, Clone, Copy)]
+pub struct Runtime {
+    spawn: fn(BoxFuture<'static, ()>),
+    sleep: fn(Duration) -> BoxFuture<'static, ()>,
+}
+
+impl Runtime {
+    pub fn new(
+        spawn: fn(BoxFuture<'static, ()>),
+        sleep: fn(Duration) -> BoxFuture<'static, ()>,
+    ) -> Runtime {
+        Runtime { spawn, sleep }
+    }
+
+    #[cfg(feature = "serve-tokio")]
+    pub fn tokio() -> Runtime {
+        Runtime {
+            spawn: |future| {
+                tokio::spawn(future);
+            },
+            sleep: |duration| Box::pin(tokio::time::sleep(duration)),
+        }
+    }
+
+    #[cfg(feature = "serve-smol")]
+    pub fn smol() -> Runtime {
+        Runtime {
+            spawn: |future| smol::spawn(future).detach(),
+            sleep: |duration| {
+                Box::pin(async move {
+                    smol::Timer::after(duration).await;
+                })
+            },
+        }
+    }
+
+    pub fn spawn<F>(&self, future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        (self.spawn)(Box::pin(future))
+    }
+
+    pub fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        (self.sleep)(duration)
+    }
+}
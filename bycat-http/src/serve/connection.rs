@@ -1,15 +1,31 @@
-use alloc::boxed::Box;
-use bycat_service::{GracefulShutdown, Shutdown};
-use core::error::Error as StdError;
+use alloc::{boxed::Box, sync::Arc};
+use bycat_service::{GracefulShutdown, Shutdown, WatchError};
+use core::{
+    error::Error as StdError,
+    pin::Pin,
+    task::{Context, Poll},
+};
 use http::{Request, Response};
 use hyper::{
     body::{Body, Incoming},
+    rt::Sleep,
     server::conn::http1::Builder,
     service::{HttpService, Service},
 };
 use pin_project_lite::pin_project;
 
-use super::{Listener, listener::Socket};
+use super::{
+    Listener,
+    limits::{ConnState, ConnectionGuard, Counted, Limits, RequestFuture},
+    listener::Socket,
+};
+use crate::error::BoxError;
+
+#[cfg(feature = "http2")]
+use {
+    super::Http2Config,
+    hyper_util::server::conn::auto::{self, HttpServerConnExec},
+};
 
 // #[derive(Debug, Clone, Default)]
 // pub struct LocalTokioExecutor;
@@ -31,9 +47,12 @@ where
     shutdown: Shutdown,
     socket: L::Io,
     local_address: L::Addr,
-    // Should be used for http2
-    #[allow(unused)]
+    #[cfg_attr(not(feature = "http2"), allow(unused))]
     executor: E,
+    limits: Arc<Limits>,
+    guard: ConnectionGuard,
+    #[cfg(feature = "http2")]
+    http2: Arc<Http2Config>,
 }
 
 impl<L, E> Connection<L, E>
@@ -46,6 +65,8 @@ where
         shutdown: Shutdown,
         socket: L::Io,
         local_address: L::Addr,
+        limits: Arc<Limits>,
+        guard: ConnectionGuard,
     ) -> Connection<L, E> {
         Connection {
             builder,
@@ -53,8 +74,18 @@ where
             shutdown,
             socket,
             executor,
+            limits,
+            guard,
+            #[cfg(feature = "http2")]
+            http2: Arc::default(),
         }
     }
+
+    #[cfg(feature = "http2")]
+    pub(crate) fn with_http2(mut self, http2: Arc<Http2Config>) -> Self {
+        self.http2 = http2;
+        self
+    }
 }
 
 impl<L, E> Connection<L, E>
@@ -69,94 +100,266 @@ where
         &self.socket
     }
 
-    pub async fn serve_connection<S, B>(
-        self,
-        service: S,
-    ) -> Result<(), Box<dyn StdError + Send + Sync + 'static>>
+    pub async fn serve_connection<S, B>(self, service: S) -> Result<(), BoxError>
     where
         S: Service<Request<Incoming>, Response = Response<B>>,
-        S::Error: Into<Box<dyn StdError + Send + Sync>>,
+        S::Error: Into<BoxError>,
         B: Body + 'static,
-        B::Error: Into<Box<dyn StdError + Send + Sync>>,
+        B::Error: Into<BoxError>,
         L::Io: 'static,
     {
-        let conn = self.builder.serve_connection(self.socket, service);
-        self.shutdown
-            .watch(HyperConn { conn })
-            .await
-            .map_err(|e| Box::new(e) as _)
+        let state = Arc::new(ConnState::default());
+        let mut builder = self.builder;
+        self.limits.configure_http1(&mut builder);
+
+        let conn = builder.serve_connection(self.socket, Counted::new(service, state.clone()));
+        let result = self
+            .shutdown
+            .try_watch(HyperConn::new(conn, state, self.limits))
+            .await;
+
+        drop(self.guard);
+        result.map_err(watch_error)
     }
 
-    pub async fn serve_connection_with_upgrades<S, B>(
-        self,
-        service: S,
-    ) -> Result<(), Box<dyn StdError + Send + Sync + 'static>>
+    pub async fn serve_connection_with_upgrades<S, B>(self, service: S) -> Result<(), BoxError>
     where
         S: Service<Request<Incoming>, Response = Response<B>>,
-        S::Error: Into<Box<dyn StdError + Send + Sync>>,
+        S::Error: Into<BoxError>,
         B: Body + 'static,
-        B::Error: Into<Box<dyn StdError + Send + Sync>>,
+        B::Error: Into<BoxError>,
         L::Io: Send + 'static,
-        // I: Socket + Unpin + Send + 'static,
     {
-        let conn = self
-            .builder
-            .serve_connection(self.socket, service)
+        let state = Arc::new(ConnState::default());
+        let mut builder = self.builder;
+        self.limits.configure_http1(&mut builder);
+
+        let conn = builder
+            .serve_connection(self.socket, Counted::new(service, state.clone()))
             .with_upgrades();
-        self.shutdown
-            .watch(HyperConn { conn })
-            .await
-            .map_err(|e| Box::new(e) as _)
+        let result = self
+            .shutdown
+            .try_watch(HyperConn::new(conn, state, self.limits))
+            .await;
+
+        drop(self.guard);
+        result.map_err(watch_error)
+    }
+
+    /// Serve HTTP/1.1 and HTTP/2 as configured by the [`Http2Config`].
+    /// Connections limited to HTTP/1.1 are served like [`serve_connection`](Self::serve_connection).
+    #[cfg(feature = "http2")]
+    pub async fn serve_connection_auto<S, B>(self, service: S) -> Result<(), BoxError>
+    where
+        S: Service<Request<Incoming>, Response = Response<B>>,
+        S::Future: 'static,
+        S::Error: Into<BoxError>,
+        B: Body + 'static,
+        B::Error: Into<BoxError>,
+        L::Io: 'static,
+        E: HttpServerConnExec<RequestFuture<S::Future>, B>,
+    {
+        if self.http2.is_http1_only() {
+            return self.serve_connection(service).await;
+        }
+
+        let state = Arc::new(ConnState::default());
+        let mut builder = self
+            .http2
+            .builder(self.executor, self.limits.timer.as_ref());
+        self.limits.configure_auto(&mut builder);
+
+        let conn = builder.serve_connection(self.socket, Counted::new(service, state.clone()));
+        let result = self
+            .shutdown
+            .try_watch(HyperConn::new(conn, state, self.limits))
+            .await;
+
+        drop(self.guard);
+        result.map_err(watch_error)
+    }
+
+    #[cfg(feature = "http2")]
+    pub async fn serve_connection_auto_with_upgrades<S, B>(self, service: S) -> Result<(), BoxError>
+    where
+        S: Service<Request<Incoming>, Response = Response<B>>,
+        S::Future: 'static,
+        S::Error: Into<BoxError>,
+        B: Body + 'static,
+        B::Error: Into<BoxError>,
+        L::Io: Send + 'static,
+        E: HttpServerConnExec<RequestFuture<S::Future>, B>,
+    {
+        if self.http2.is_http1_only() {
+            return self.serve_connection_with_upgrades(service).await;
+        }
+
+        let state = Arc::new(ConnState::default());
+        let mut builder = self
+            .http2
+            .builder(self.executor, self.limits.timer.as_ref());
+        self.limits.configure_auto(&mut builder);
+
+        let conn = builder
+            .serve_connection_with_upgrades(self.socket, Counted::new(service, state.clone()));
+        let result = self
+            .shutdown
+            .try_watch(HyperConn::new(conn, state, self.limits))
+            .await;
+
+        drop(self.guard);
+        result.map_err(watch_error)
+    }
+}
+
+fn watch_error<E: Into<BoxError>>(error: WatchError<E>) -> BoxError {
+    match error {
+        WatchError::Connection(err) => err.into(),
+        WatchError::Aborted => Box::new(std::io::Error::new(
+            std::io::ErrorKind::ConnectionAborted,
+            "connection aborted by shutdown",
+        )),
     }
 }
 
+/// Hyper connections that can be closed gracefully.
+trait Graceful {
+    fn graceful(self: Pin<&mut Self>);
+}
+
 pin_project! {
-    struct HyperConn<T: ?Sized> {
+    /// A hyper connection enforcing the idle timeout and request limit.
+    struct HyperConn<T> {
        #[pin]
        conn: T,
+       state: Arc<ConnState>,
+       limits: Arc<Limits>,
+       idle: Option<(usize, Pin<Box<dyn Sleep>>)>,
+       closing: bool,
+    }
+}
+
+impl<T> HyperConn<T> {
+    fn new(conn: T, state: Arc<ConnState>, limits: Arc<Limits>) -> HyperConn<T> {
+        HyperConn {
+            conn,
+            state,
+            limits,
+            idle: None,
+            closing: false,
+        }
     }
 }
 
 impl<T> Future for HyperConn<T>
 where
-    T: Future,
+    T: Future + Graceful,
 {
     type Output = T::Output;
 
-    fn poll(
-        self: core::pin::Pin<&mut Self>,
-        cx: &mut core::task::Context<'_>,
-    ) -> core::task::Poll<Self::Output> {
-        self.project().conn.poll(cx)
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut this = self.project();
+
+        if !*this.closing {
+            this.state.register(cx);
+
+            let requests = this.state.requests();
+            let mut close = this.limits.max_requests.is_some_and(|max| requests >= max);
+
+            if let (Some(timeout), Some(timer)) = (this.limits.idle_timeout, &this.limits.timer) {
+                if close || this.state.active() > 0 {
+                    *this.idle = None;
+                } else {
+                    // Restart the timer when requests were served since it was set.
+                    if !matches!(this.idle, Some((seen, _)) if *seen == requests) {
+                        *this.idle = Some((requests, timer.sleep(timeout)));
+                    }
+
+                    if let Some((_, sleep)) = this.idle.as_mut() {
+                        close = sleep.as_mut().poll(cx).is_ready();
+                    }
+                }
+            }
+
+            if close {
+                *this.closing = true;
+                this.conn.as_mut().graceful();
+            }
+        }
+
+        this.conn.poll(cx)
     }
 }
 
-impl<I, B, S> GracefulShutdown for HyperConn<hyper::server::conn::http1::Connection<I, S>>
+impl<T, E> GracefulShutdown for HyperConn<T>
+where
+    T: Future<Output = Result<(), E>> + Graceful,
+{
+    type Error = E;
+
+    fn graceful_shutdown(self: Pin<&mut Self>) {
+        let this = self.project();
+        if !*this.closing {
+            *this.closing = true;
+            this.conn.graceful();
+        }
+    }
+}
+
+impl<I, B, S> Graceful for hyper::server::conn::http1::Connection<I, S>
 where
     S: HttpService<Incoming, ResBody = B>,
-    S::Error: Into<Box<dyn StdError + Send + Sync>>,
+    S::Error: Into<BoxError>,
     I: Socket + Unpin + 'static,
     B: Body + 'static,
-    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+    B::Error: Into<BoxError>,
 {
-    type Error = hyper::Error;
-    fn graceful_shutdown(self: core::pin::Pin<&mut Self>) {
-        hyper::server::conn::http1::Connection::graceful_shutdown(self.project().conn);
+    fn graceful(self: Pin<&mut Self>) {
+        hyper::server::conn::http1::Connection::graceful_shutdown(self);
     }
 }
 
-impl<I, B, S> GracefulShutdown
-    for HyperConn<hyper::server::conn::http1::UpgradeableConnection<I, S>>
+impl<I, B, S> Graceful for hyper::server::conn::http1::UpgradeableConnection<I, S>
 where
     S: HttpService<Incoming, ResBody = B>,
-    S::Error: Into<Box<dyn StdError + Send + Sync>>,
+    S::Error: Into<BoxError>,
     I: Socket + Send + Unpin + 'static,
     B: Body + 'static,
-    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+    B::Error: Into<BoxError>,
 {
-    type Error = hyper::Error;
+    fn graceful(self: Pin<&mut Self>) {
+        hyper::server::conn::http1::UpgradeableConnection::graceful_shutdown(self);
+    }
+}
 
-    fn graceful_shutdown(self: core::pin::Pin<&mut Self>) {
-        hyper::server::conn::http1::UpgradeableConnection::graceful_shutdown(self.project().conn);
+#[cfg(feature = "http2")]
+impl<I, B, S, E> Graceful for auto::Connection<'_, I, S, E>
+where
+    S: HttpService<Incoming, ResBody = B>,
+    S::Error: Into<BoxError>,
+    S::Future: 'static,
+    I: Socket + Unpin + 'static,
+    B: Body + 'static,
+    B::Error: Into<BoxError>,
+    E: HttpServerConnExec<S::Future, B>,
+{
+    fn graceful(self: Pin<&mut Self>) {
+        auto::Connection::graceful_shutdown(self);
+    }
+}
+
+#[cfg(feature = "http2")]
+impl<I, B, S, E> Graceful for auto::UpgradeableConnection<'_, I, S, E>
+where
+    S: HttpService<Incoming, ResBody = B>,
+    S::Error: Into<BoxError>,
+    S::Future: 'static,
+    I: Socket + Send + Unpin + 'static,
+    B: Body + 'static,
+    B::Error: Into<BoxError>,
+    E: HttpServerConnExec<S::Future, B>,
+{
+    fn graceful(self: Pin<&mut Self>) {
+        auto::UpgradeableConnection::graceful_shutdown(self);
     }
 }
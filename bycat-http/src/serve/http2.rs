@@ -0,0 +1,300 @@
+use alloc::time::Duration;
+use hyper_util::server::conn::auto;
+
+use super::limits::SharedTimer;
+
+/// The HTTP versions a [`Server`](super::Server) accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Protocol {
+    /// HTTP/1.1 only.
+    Http1,
+    /// HTTP/2 only. Without TLS this is h2c with prior knowledge.
+    Http2,
+    /// Detect the version from the connection preface. This covers h2c with
+    /// prior knowledge, and h2 negotiated through ALPN by a
+    /// [`TlsListener`](super::TlsListener).
+    #[default]
+    Auto,
+}
+
+/// HTTP/2 configuration of a [`Server`](super::Server).
+///
+/// Options left unset use hyper's defaults.
+#[derive(Debug, Clone)]
+pub struct Http2Config {
+    protocol: Protocol,
+    max_concurrent_streams: Option<u32>,
+    initial_stream_window_size: Option<u32>,
+    initial_connection_window_size: Option<u32>,
+    adaptive_window: bool,
+    max_frame_size: Option<u32>,
+    keep_alive_interval: Option<Duration>,
+    keep_alive_timeout: Option<Duration>,
+}
+
+impl Default for Http2Config {
+    fn default() -> Self {
+        Http2Config {
+            protocol: Protocol::default(),
+            max_concurrent_streams: None,
+            initial_stream_window_size: None,
+            initial_connection_window_size: None,
+            adaptive_window: false,
+            max_frame_size: None,
+            keep_alive_interval: None,
+            keep_alive_timeout: None,
+        }
+    }
+}
+
+impl Http2Config {
+    pub fn new() -> Http2Config {
+        Http2Config::default()
+    }
+
+    pub fn protocol(mut self, protocol: Protocol) -> Self {
+        self.protocol = protocol;
+        self
+    }
+
+    pub fn max_concurrent_streams(mut self, max: u32) -> Self {
+        self.max_concurrent_streams = Some(max);
+        self
+    }
+
+    pub fn initial_stream_window_size(mut self, size: u32) -> Self {
+        self.initial_stream_window_size = Some(size);
+        self
+    }
+
+    pub fn initial_connection_window_size(mut self, size: u32) -> Self {
+        self.initial_connection_window_size = Some(size);
+        self
+    }
+
+    /// Use BDP based flow control, overriding the window sizes.
+    pub fn adaptive_window(mut self, enabled: bool) -> Self {
+        self.adaptive_window = enabled;
+        self
+    }
+
+    pub fn max_frame_size(mut self, size: u32) -> Self {
+        self.max_frame_size = Some(size);
+        self
+    }
+
+    /// Send a PING frame at the given interval, closing the connection
+    /// if it's not acknowledged within `timeout`.
+    ///
+    /// Uses the timer of the server, which is set by default with the
+    /// `serve-tokio` feature.
+    pub fn keep_alive(mut self, interval: Duration, timeout: Duration) -> Self {
+        self.keep_alive_interval = Some(interval);
+        self.keep_alive_timeout = Some(timeout);
+        self
+    }
+
+    pub(crate) fn is_http1_only(&self) -> bool {
+        self.protocol == Protocol::Http1
+    }
+
+    pub(crate) fn builder<E>(&self, executor: E, timer: Option<&SharedTimer>) -> auto::Builder<E> {
+        let mut builder = auto::Builder::new(executor);
+
+        builder = match self.protocol {
+            Protocol::Http1 => builder.http1_only(),
+            Protocol::Http2 => builder.http2_only(),
+            Protocol::Auto => builder,
+        };
+
+        let mut http2 = builder.http2();
+
+        http2
+            .max_concurrent_streams(self.max_concurrent_streams)
+            .initial_stream_window_size(self.initial_stream_window_size)
+            .initial_connection_window_size(self.initial_connection_window_size)
+            .adaptive_window(self.adaptive_window)
+            .max_frame_size(self.max_frame_size);
+
+        if let Some(timer) = timer {
+            http2.timer(timer.clone());
+
+            if let Some(interval) = self.keep_alive_interval {
+                http2.keep_alive_interval(interval);
+            }
+
+            if let Some(timeout) = self.keep_alive_timeout {
+                http2.keep_alive_timeout(timeout);
+            }
+        } else if self.keep_alive_interval.is_some() {
+            tracing::warn!("http2 keep-alive requires a timer, ignoring");
+        }
+
+        builder
+    }
+}
+
+#[cfg(all(test, feature = "serve-tokio"))]
+mod tests {
+    use super::*;
+    use crate::{
+        handler,
+        serve::{Server, Shutdown, TokioExecutor, TokioServer},
+    };
+    use bytes::Bytes;
+    use http::{Request, Response, Version};
+    use http_body_util::{BodyExt, Empty};
+    use hyper::body::Incoming;
+    use hyper_util::rt::TokioIo;
+    use tokio::net::{TcpListener, TcpStream};
+
+    async fn start(config: Http2Config) -> (std::net::SocketAddr, Shutdown) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let shutdown = Shutdown::new();
+        let server = Server::new(
+            TokioExecutor::new(),
+            TokioServer(handler(async || "hello"), ()),
+        )
+        .http2(config);
+
+        let wait = shutdown.clone();
+        tokio::spawn(async move { server.serve(listener, &wait).await });
+
+        (addr, shutdown)
+    }
+
+    fn request() -> Request<Empty<Bytes>> {
+        Request::get("http://localhost/")
+            .body(Empty::new())
+            .unwrap()
+    }
+
+    async fn text(resp: Response<Incoming>) -> Bytes {
+        resp.into_body().collect().await.unwrap().to_bytes()
+    }
+
+    async fn h2c(addr: std::net::SocketAddr) -> hyper::Result<Response<Incoming>> {
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let (mut sender, conn) =
+            hyper::client::conn::http2::handshake(TokioExecutor::new(), TokioIo::new(stream))
+                .await?;
+        tokio::spawn(conn);
+        sender.send_request(request()).await
+    }
+
+    async fn http1(addr: std::net::SocketAddr) -> hyper::Result<Response<Incoming>> {
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let (mut sender, conn) =
+            hyper::client::conn::http1::handshake(TokioIo::new(stream)).await?;
+        tokio::spawn(conn);
+        sender.send_request(request()).await
+    }
+
+    #[tokio::test]
+    async fn auto_detect() {
+        let (addr, shutdown) = start(Http2Config::new()).await;
+
+        let resp = h2c(addr).await.unwrap();
+        assert_eq!(resp.version(), Version::HTTP_2);
+        assert_eq!(text(resp).await, "hello");
+
+        let resp = http1(addr).await.unwrap();
+        assert_eq!(resp.version(), Version::HTTP_11);
+        assert_eq!(text(resp).await, "hello");
+
+        shutdown.shutdown();
+    }
+
+    #[tokio::test]
+    async fn http2_only() {
+        let config = Http2Config::new()
+            .protocol(Protocol::Http2)
+            .max_concurrent_streams(10)
+            .initial_stream_window_size(1 << 20)
+            .initial_connection_window_size(1 << 21)
+            .keep_alive(Duration::from_secs(10), Duration::from_secs(5));
+        let (addr, shutdown) = start(config).await;
+
+        let resp = h2c(addr).await.unwrap();
+        assert_eq!(resp.version(), Version::HTTP_2);
+        assert_eq!(text(resp).await, "hello");
+
+        assert!(http1(addr).await.is_err());
+
+        shutdown.shutdown();
+    }
+
+    #[tokio::test]
+    async fn http1_only() {
+        let (addr, shutdown) = start(Http2Config::new().protocol(Protocol::Http1)).await;
+
+        assert!(h2c(addr).await.is_err());
+
+        let resp = http1(addr).await.unwrap();
+        assert_eq!(text(resp).await, "hello");
+
+        shutdown.shutdown();
+    }
+
+    #[cfg(feature = "tls")]
+    #[tokio::test]
+    async fn alpn_h2() {
+        use crate::serve::{TlsConfig, TlsListener};
+        use alloc::sync::Arc;
+        use tokio_rustls::{
+            TlsConnector,
+            rustls::{self, ClientConfig, RootCertStore, pki_types::ServerName},
+        };
+
+        let rcgen::CertifiedKey { cert, key_pair } =
+            rcgen::generate_simple_self_signed(vec!["localhost".into()]).unwrap();
+
+        let tcp = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = tcp.local_addr().unwrap();
+        let listener = TlsListener::new(
+            tcp,
+            TlsConfig::from_pem(cert.pem(), key_pair.serialize_pem())
+                .alpn_protocols(vec![b"h2".to_vec(), b"http/1.1".to_vec()]),
+        )
+        .unwrap();
+
+        let shutdown = Shutdown::new();
+        let server = Server::new(
+            TokioExecutor::new(),
+            TokioServer(handler(async || "hello"), ()),
+        );
+        let wait = shutdown.clone();
+        tokio::spawn(async move { server.serve(listener, &wait).await });
+
+        let mut roots = RootCertStore::empty();
+        roots.add(cert.der().clone()).unwrap();
+        let mut config =
+            ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+                .with_safe_default_protocol_versions()
+                .unwrap()
+                .with_root_certificates(roots)
+                .with_no_client_auth();
+        config.alpn_protocols = vec![b"h2".to_vec()];
+
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let stream = TlsConnector::from(Arc::new(config))
+            .connect(ServerName::try_from("localhost").unwrap(), stream)
+            .await
+            .unwrap();
+        assert_eq!(stream.get_ref().1.alpn_protocol(), Some(&b"h2"[..]));
+
+        let (mut sender, conn) =
+            hyper::client::conn::http2::handshake(TokioExecutor::new(), TokioIo::new(stream))
+                .await
+                .unwrap();
+        tokio::spawn(conn);
+
+        let resp = sender.send_request(request()).await.unwrap();
+        assert_eq!(resp.version(), Version::HTTP_2);
+        assert_eq!(text(resp).await, "hello");
+
+        shutdown.shutdown();
+    }
+}
@@ -0,0 +1,396 @@
+use alloc::{boxed::Box, pin::Pin, sync::Arc, time::Duration};
+use core::{
+    sync::atomic::{AtomicUsize, Ordering},
+    task::{Context, Poll},
+};
+use futures::task::AtomicWaker;
+use hyper::{
+    rt::{Sleep, Timer},
+    service::Service,
+};
+use pin_project_lite::pin_project;
+use std::time::Instant;
+
+#[derive(Clone)]
+pub(crate) struct SharedTimer(Arc<dyn Timer + Send + Sync>);
+
+impl SharedTimer {
+    pub(crate) fn new<T>(timer: T) -> SharedTimer
+    where
+        T: Timer + Send + Sync + 'static,
+    {
+        SharedTimer(Arc::new(timer))
+    }
+}
+
+impl Timer for SharedTimer {
+    fn sleep(&self, duration: Duration) -> Pin<Box<dyn Sleep>> {
+        self.0.sleep(duration)
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> Pin<Box<dyn Sleep>> {
+        self.0.sleep_until(deadline)
+    }
+
+    fn reset(&self, sleep: &mut Pin<Box<dyn Sleep>>, new_deadline: Instant) {
+        self.0.reset(sleep, new_deadline)
+    }
+}
+
+impl core::fmt::Debug for SharedTimer {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("Timer")
+    }
+}
+
+pub(crate) fn default_timer() -> Option<SharedTimer> {
+    #[cfg(feature = "serve-tokio")]
+    return Some(SharedTimer::new(hyper_util::rt::TokioTimer::new()));
+    #[cfg(not(feature = "serve-tokio"))]
+    return None;
+}
+
+/// Per connection limits of a [`Server`](super::Server).
+#[derive(Debug, Clone)]
+pub(crate) struct Limits {
+    pub header_read_timeout: Option<Duration>,
+    pub idle_timeout: Option<Duration>,
+    pub max_requests: Option<usize>,
+    pub timer: Option<SharedTimer>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            header_read_timeout: None,
+            idle_timeout: None,
+            max_requests: None,
+            timer: default_timer(),
+        }
+    }
+}
+
+impl Limits {
+    pub fn configure_http1(&self, builder: &mut hyper::server::conn::http1::Builder) {
+        if let Some(timeout) = self.header_read_timeout {
+            match &self.timer {
+                Some(timer) => {
+                    builder.timer(timer.clone()).header_read_timeout(timeout);
+                }
+                None => tracing::warn!("header read timeout requires a timer, ignoring"),
+            }
+        }
+    }
+
+    #[cfg(feature = "http2")]
+    pub fn configure_auto<E>(&self, builder: &mut hyper_util::server::conn::auto::Builder<E>) {
+        if let Some(timeout) = self.header_read_timeout {
+            match &self.timer {
+                Some(timer) => {
+                    builder
+                        .http1()
+                        .timer(timer.clone())
+                        .header_read_timeout(timeout);
+                }
+                None => tracing::warn!("header read timeout requires a timer, ignoring"),
+            }
+        }
+    }
+}
+
+/// Counts open connections, so the accept loop can wait for a free slot.
+#[derive(Debug, Default)]
+pub(crate) struct ConnectionCounter {
+    active: AtomicUsize,
+    waker: AtomicWaker,
+}
+
+impl ConnectionCounter {
+    pub fn acquire(self: &Arc<Self>) -> ConnectionGuard {
+        self.active.fetch_add(1, Ordering::AcqRel);
+        ConnectionGuard {
+            counter: self.clone(),
+        }
+    }
+
+    pub fn available(&self, max: Option<usize>) -> impl Future<Output = ()> + '_ {
+        core::future::poll_fn(move |cx| {
+            let Some(max) = max else {
+                return Poll::Ready(());
+            };
+
+            if self.active.load(Ordering::Acquire) < max {
+                return Poll::Ready(());
+            }
+
+            self.waker.register(cx.waker());
+
+            if self.active.load(Ordering::Acquire) < max {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct ConnectionGuard {
+    counter: Arc<ConnectionCounter>,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.counter.active.fetch_sub(1, Ordering::AcqRel);
+        self.counter.waker.wake();
+    }
+}
+
+/// Request bookkeeping of a single connection.
+#[derive(Debug, Default)]
+pub(crate) struct ConnState {
+    requests: AtomicUsize,
+    active: AtomicUsize,
+    waker: AtomicWaker,
+}
+
+impl ConnState {
+    pub fn requests(&self) -> usize {
+        self.requests.load(Ordering::Acquire)
+    }
+
+    pub fn active(&self) -> usize {
+        self.active.load(Ordering::Acquire)
+    }
+
+    pub fn register(&self, cx: &Context<'_>) {
+        self.waker.register(cx.waker());
+    }
+}
+
+/// Service counting the requests of a connection.
+#[derive(Debug)]
+pub(crate) struct Counted<S> {
+    service: S,
+    state: Arc<ConnState>,
+}
+
+impl<S> Counted<S> {
+    pub fn new(service: S, state: Arc<ConnState>) -> Counted<S> {
+        Counted { service, state }
+    }
+}
+
+impl<S, R> Service<R> for Counted<S>
+where
+    S: Service<R>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = RequestFuture<S::Future>;
+
+    fn call(&self, req: R) -> Self::Future {
+        self.state.requests.fetch_add(1, Ordering::AcqRel);
+        self.state.active.fetch_add(1, Ordering::AcqRel);
+        self.state.waker.wake();
+
+        RequestFuture {
+            future: self.service.call(req),
+            guard: ActiveGuard {
+                state: self.state.clone(),
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ActiveGuard {
+    state: Arc<ConnState>,
+}
+
+impl Drop for ActiveGuard {
+    fn drop(&mut self) {
+        self.state.active.fetch_sub(1, Ordering::AcqRel);
+        self.state.waker.wake();
+    }
+}
+
+pin_project! {
+    /// The response future of a request served by a [`Server`](super::Server).
+    pub struct RequestFuture<F> {
+        #[pin]
+        future: F,
+        guard: ActiveGuard,
+    }
+}
+
+impl<F> Future for RequestFuture<F>
+where
+    F: Future,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.project().future.poll(cx)
+    }
+}
+
+#[cfg(all(test, feature = "serve-tokio"))]
+mod tests {
+    use crate::{
+        Error,
+        body::Body,
+        handler,
+        serve::{Server, Shutdown, TokioExecutor, TokioServer},
+    };
+    use alloc::time::Duration;
+    use bycat::Work;
+    use bytes::Bytes;
+    use http::{Request, Response, Uri};
+    use http_body_util::Empty;
+    use hyper::client::conn::http1::SendRequest;
+    use hyper_util::rt::TokioIo;
+    use tokio::{
+        net::{TcpListener, TcpStream},
+        task::JoinHandle,
+        time::{sleep, timeout},
+    };
+
+    async fn hello(uri: Uri) -> &'static str {
+        if uri.path() == "/slow" {
+            sleep(Duration::from_secs(10)).await;
+        }
+        "hello"
+    }
+
+    async fn start<W>(
+        server: Server<TokioServer<W, ()>, TokioExecutor>,
+    ) -> (std::net::SocketAddr, Shutdown)
+    where
+        W: Work<(), Request<Body>, Output = Response<Body>, Error = Error>
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+        for<'a> W::Future<'a>: Send,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let shutdown = Shutdown::new();
+        let wait = shutdown.clone();
+        tokio::spawn(async move { server.serve(listener, &wait).await });
+
+        (addr, shutdown)
+    }
+
+    async fn connect(
+        addr: std::net::SocketAddr,
+    ) -> (SendRequest<Empty<Bytes>>, JoinHandle<hyper::Result<()>>) {
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let (sender, conn) = hyper::client::conn::http1::handshake(TokioIo::new(stream))
+            .await
+            .unwrap();
+        (sender, tokio::spawn(conn))
+    }
+
+    fn request(path: &str) -> Request<Empty<Bytes>> {
+        Request::get(format!("http://localhost{path}"))
+            .body(Empty::new())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn max_requests_per_connection() {
+        let server = Server::new(TokioExecutor::new(), TokioServer(handler(hello), ()))
+            .max_requests_per_connection(2);
+        let (addr, shutdown) = start(server).await;
+
+        let (mut sender, conn) = connect(addr).await;
+        assert!(sender.send_request(request("/")).await.is_ok());
+        assert!(sender.send_request(request("/")).await.is_ok());
+
+        timeout(Duration::from_secs(1), conn)
+            .await
+            .expect("connection closed")
+            .unwrap()
+            .unwrap();
+
+        shutdown.shutdown();
+    }
+
+    #[tokio::test]
+    async fn idle_timeout() {
+        let server = Server::new(TokioExecutor::new(), TokioServer(handler(hello), ()))
+            .idle_timeout(Duration::from_millis(50));
+        let (addr, shutdown) = start(server).await;
+
+        let (mut sender, conn) = connect(addr).await;
+        assert!(sender.send_request(request("/")).await.is_ok());
+
+        timeout(Duration::from_secs(1), conn)
+            .await
+            .expect("connection closed")
+            .unwrap()
+            .unwrap();
+
+        shutdown.shutdown();
+    }
+
+    #[tokio::test]
+    async fn max_connections() {
+        let server =
+            Server::new(TokioExecutor::new(), TokioServer(handler(hello), ())).max_connections(1);
+        let (addr, shutdown) = start(server).await;
+
+        let (mut first, _) = connect(addr).await;
+        assert!(first.send_request(request("/")).await.is_ok());
+
+        // Accepted by the kernel, but not served until a slot is free.
+        let (mut second, _) = connect(addr).await;
+        let pending = tokio::spawn(async move { second.send_request(request("/")).await });
+        sleep(Duration::from_millis(50)).await;
+        assert!(!pending.is_finished());
+
+        drop(first);
+        let resp = timeout(Duration::from_secs(1), pending).await.unwrap();
+        assert!(resp.unwrap().is_ok());
+
+        shutdown.shutdown();
+    }
+
+    #[tokio::test]
+    async fn drain_aborts_slow_requests() {
+        let server = Server::new(TokioExecutor::new(), TokioServer(handler(hello), ()));
+        let (addr, shutdown) = start(server).await;
+
+        let (mut sender, _) = connect(addr).await;
+        let pending = tokio::spawn(async move { sender.send_request(request("/slow")).await });
+        sleep(Duration::from_millis(50)).await;
+
+        let report = shutdown.drain(sleep(Duration::from_millis(50))).await;
+        assert_eq!(report.connections, 1);
+        assert_eq!(report.aborted, 1);
+
+        let resp = timeout(Duration::from_secs(1), pending).await.unwrap();
+        assert!(resp.unwrap().is_err());
+    }
+
+    #[tokio::test]
+    async fn drain_completes() {
+        let server = Server::new(TokioExecutor::new(), TokioServer(handler(hello), ()));
+        let (addr, shutdown) = start(server).await;
+
+        let (mut sender, _) = connect(addr).await;
+        assert!(sender.send_request(request("/")).await.is_ok());
+
+        let report = timeout(
+            Duration::from_secs(1),
+            shutdown.drain(sleep(Duration::from_secs(10))),
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.connections, 1);
+        assert_eq!(report.aborted, 0);
+    }
+}
@@ -92,6 +92,28 @@ fn is_connection_error(e: &io::Error) -> bool {
     )
 }
 
+/// Listeners accepting tokio sockets, which can be wrapped by eg. `TlsListener`
+/// or `ProxyProtocolListener`.
+#[cfg(feature = "serve-tokio")]
+pub trait TokioListener: Listener {
+    type Stream: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static;
+
+    fn into_stream(io: Self::Io) -> Self::Stream;
+}
+
+#[cfg(feature = "serve-tokio")]
+impl<L, S> TokioListener for L
+where
+    L: Listener<Io = hyper_util::rt::TokioIo<S>>,
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static,
+{
+    type Stream = S;
+
+    fn into_stream(io: Self::Io) -> Self::Stream {
+        io.into_inner()
+    }
+}
+
 #[cfg(feature = "serve-tokio")]
 #[derive(Debug, Clone, Default)]
 pub struct LocalTokioExecutor;
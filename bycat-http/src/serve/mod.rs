@@ -1,16 +1,32 @@
 mod connection;
 mod futures;
+#[cfg(feature = "http2")]
+mod http2;
+mod limits;
 mod listener;
+#[cfg(feature = "proxy-protocol")]
+mod proxy_protocol;
 mod server;
+#[cfg(feature = "tls")]
+mod tls;
 
 use self::connection::Connection;
-pub use self::{futures::FuturesIo, listener::*, server::*};
+pub use self::{futures::FuturesIo, limits::RequestFuture, listener::*, server::*};
 
-pub use bycat_service::Shutdown;
+#[cfg(feature = "http2")]
+pub use self::http2::{Http2Config, Protocol};
+
+#[cfg(feature = "proxy-protocol")]
+pub use self::proxy_protocol::{ProxyProtocolListener, read_proxy_header};
+
+#[cfg(feature = "tls")]
+pub use self::tls::{TlsConfig, TlsError, TlsListener, TlsReloader};
+
+pub use bycat_service::{DrainReport, Shutdown};
 pub use hyper::rt::Executor;
 
 #[cfg(feature = "serve-tokio")]
-use crate::body::Body;
+use crate::{body::Body, extract::PeerAddr};
 #[cfg(feature = "serve-tokio")]
 use ::{bycat::Work, http_body_util::BodyExt};
 
@@ -52,7 +68,7 @@ where
         + 'static,
     C: Clone + 'static,
 {
-    let server = Server::new(TokioExecutor::default(), LocalTokioServer(service, context));
+    let server = Server::new(LocalTokioExecutor, LocalTokioServer(service, context));
 
     let listener = tokio::net::TcpListener::bind(addr).await?;
 
@@ -69,7 +85,7 @@ impl<T, C, L> Servable<TokioExecutor, L> for TokioServer<T, C>
 where
     L: Listener + 'static,
     L::Io: Send,
-    L::Addr: Send,
+    L::Addr: Send + 'static,
     T: Work<
             C,
             http::Request<crate::body::Body>,
@@ -117,7 +133,7 @@ impl<L, T, C> Future for TokioServerFuture<L, T, C>
 where
     L: Listener + 'static,
     L::Io: Send,
-    L::Addr: Send,
+    L::Addr: Send + 'static,
     T: Work<
             C,
             http::Request<crate::body::Body>,
@@ -147,16 +163,21 @@ where
                 let work = work.take().unwrap();
                 let conn = conn.take().unwrap();
                 let context = context.take().unwrap();
+                let peer = PeerAddr::from_addr(conn.remote_address());
 
                 tokio::spawn(async move {
                     let svc = hyper::service::service_fn(move |req| {
                         let work = work.clone();
                         let context = context.clone();
                         async move {
-                            let req = req.map(|body: hyper::body::Incoming| {
+                            let mut req = req.map(|body: hyper::body::Incoming| {
                                 Body::from_streaming(body.map_err(crate::Error::custom))
                             });
 
+                            if let Some(peer) = peer {
+                                req.extensions_mut().insert(peer);
+                            }
+
                             match work.call(&context, req).await {
                                 Ok(ret) => Ok(ret),
                                 Err(err) => {
@@ -167,7 +188,12 @@ where
                         }
                     });
 
-                    if let Err(err) = conn.serve_connection(svc).await {
+                    #[cfg(feature = "http2")]
+                    let result = conn.serve_connection_auto(svc).await;
+                    #[cfg(not(feature = "http2"))]
+                    let result = conn.serve_connection(svc).await;
+
+                    if let Err(err) = result {
                         alloc::eprintln!("server error: {}", err);
                     }
                 });
@@ -185,11 +211,11 @@ where
 pub struct LocalTokioServer<T, C>(T, C);
 
 #[cfg(feature = "serve-tokio")]
-impl<T, C, L> Servable<TokioExecutor, L> for LocalTokioServer<T, C>
+impl<T, C, L> Servable<LocalTokioExecutor, L> for LocalTokioServer<T, C>
 where
     L: Listener + 'static,
     L::Io: Send,
-    L::Addr: Send,
+    L::Addr: Send + 'static,
     T: Work<
             C,
             http::Request<crate::body::Body>,
@@ -204,7 +230,7 @@ where
     where
         Self: 'a;
 
-    fn call(&self, conn: Conn<L, TokioExecutor>) -> Self::Future<'_> {
+    fn call(&self, conn: Conn<L, LocalTokioExecutor>) -> Self::Future<'_> {
         LocalTokioServerFuture::Init {
             work: Some(self.0.clone()),
             conn: Some(conn),
@@ -222,7 +248,7 @@ pin_project_lite::pin_project! {
     {
        Init {
         work: Option<T>,
-        conn: Option<Conn<L, TokioExecutor>>,
+        conn: Option<Conn<L, LocalTokioExecutor>>,
         context: Option<C>
 
        },
@@ -234,7 +260,7 @@ impl<L, T, C> Future for LocalTokioServerFuture<L, T, C>
 where
     L: Listener + 'static,
     L::Io: Send,
-    L::Addr: Send,
+    L::Addr: Send + 'static,
     T: Work<
             C,
             http::Request<crate::body::Body>,
@@ -261,16 +287,21 @@ where
                 let work = work.take().unwrap();
                 let conn = conn.take().unwrap();
                 let context = context.take().unwrap();
+                let peer = PeerAddr::from_addr(conn.remote_address());
 
                 tokio::task::spawn_local(async move {
                     let svc = hyper::service::service_fn(move |req| {
                         let work = work.clone();
                         let context = context.clone();
                         async move {
-                            let req = req.map(|body: hyper::body::Incoming| {
+                            let mut req = req.map(|body: hyper::body::Incoming| {
                                 Body::from_streaming(body.map_err(crate::Error::custom))
                             });
 
+                            if let Some(peer) = peer {
+                                req.extensions_mut().insert(peer);
+                            }
+
                             match work.call(&context, req).await {
                                 Ok(ret) => Ok(ret),
                                 Err(err) => {
@@ -281,7 +312,12 @@ where
                         }
                     });
 
-                    if let Err(err) = conn.serve_connection(svc).await {
+                    #[cfg(feature = "http2")]
+                    let result = conn.serve_connection_auto(svc).await;
+                    #[cfg(not(feature = "http2"))]
+                    let result = conn.serve_connection(svc).await;
+
+                    if let Err(err) = result {
                         alloc::eprintln!("server error: {}", err);
                     }
                 });
@@ -0,0 +1,348 @@
+use alloc::{boxed::Box, io, net::SocketAddr, time::Duration, vec, vec::Vec};
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use futures::{FutureExt, StreamExt, future::BoxFuture, stream::FuturesUnordered};
+use hyper_util::rt::TokioIo;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use super::{Listener, TokioListener};
+
+const V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+
+/// The longest v1 header, including the trailing CRLF.
+const V1_MAX_LEN: usize = 107;
+
+fn invalid(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Reads a PROXY protocol v1 or v2 header.
+///
+/// Returns the source address of the proxied connection, or `None` for
+/// health checks from the proxy itself (`LOCAL` and `UNKNOWN`).
+/// Exactly the header is consumed, so the stream is positioned at the payload.
+pub async fn read_proxy_header<S>(stream: &mut S) -> io::Result<Option<SocketAddr>>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut start = [0u8; 12];
+    stream.read_exact(&mut start).await?;
+
+    if start == V2_SIGNATURE {
+        read_v2(stream).await
+    } else if start.starts_with(b"PROXY ") {
+        read_v1(stream, &start).await
+    } else {
+        Err(invalid("missing proxy protocol header"))
+    }
+}
+
+async fn read_v1<S>(stream: &mut S, start: &[u8]) -> io::Result<Option<SocketAddr>>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut line = Vec::with_capacity(V1_MAX_LEN);
+    line.extend_from_slice(start);
+
+    // The header has no length prefix, so read byte by byte to not consume the payload.
+    while !line.ends_with(b"\r\n") {
+        if line.len() >= V1_MAX_LEN {
+            return Err(invalid("proxy protocol header too long"));
+        }
+        line.push(stream.read_u8().await?);
+    }
+
+    let line = core::str::from_utf8(&line[..line.len() - 2])
+        .map_err(|_| invalid("invalid proxy protocol header"))?;
+
+    parse_v1(line)
+}
+
+fn parse_v1(line: &str) -> io::Result<Option<SocketAddr>> {
+    let mut parts = line.split(' ').skip(1);
+
+    match parts.next() {
+        Some("UNKNOWN") => return Ok(None),
+        Some("TCP4") | Some("TCP6") => {}
+        _ => return Err(invalid("unsupported proxy protocol family")),
+    }
+
+    let (Some(src), Some(_dst), Some(port), Some(_dst_port), None) = (
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+    ) else {
+        return Err(invalid("invalid proxy protocol header"));
+    };
+
+    let ip: IpAddr = src
+        .parse()
+        .map_err(|_| invalid("invalid proxy protocol address"))?;
+    let port: u16 = port
+        .parse()
+        .map_err(|_| invalid("invalid proxy protocol port"))?;
+
+    Ok(Some(SocketAddr::new(ip, port)))
+}
+
+async fn read_v2<S>(stream: &mut S) -> io::Result<Option<SocketAddr>>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut header = [0u8; 4];
+    stream.read_exact(&mut header).await?;
+
+    let [version_command, family, len @ ..] = header;
+
+    if version_command >> 4 != 2 {
+        return Err(invalid("unsupported proxy protocol version"));
+    }
+
+    let mut payload = vec![0u8; u16::from_be_bytes(len) as usize];
+    stream.read_exact(&mut payload).await?;
+
+    match version_command & 0x0f {
+        // LOCAL
+        0x0 => return Ok(None),
+        // PROXY
+        0x1 => {}
+        _ => return Err(invalid("unsupported proxy protocol command")),
+    }
+
+    parse_v2(family, &payload)
+}
+
+fn parse_v2(family: u8, payload: &[u8]) -> io::Result<Option<SocketAddr>> {
+    let short = || invalid("proxy protocol address too short");
+
+    match family >> 4 {
+        // AF_INET
+        0x1 => {
+            let addr: &[u8; 12] = payload.get(..12).ok_or_else(short)?.try_into().unwrap();
+            let ip = Ipv4Addr::from(<[u8; 4]>::try_from(&addr[..4]).unwrap());
+            let port = u16::from_be_bytes([addr[8], addr[9]]);
+            Ok(Some(SocketAddr::new(ip.into(), port)))
+        }
+        // AF_INET6
+        0x2 => {
+            let addr: &[u8; 36] = payload.get(..36).ok_or_else(short)?.try_into().unwrap();
+            let ip = Ipv6Addr::from(<[u8; 16]>::try_from(&addr[..16]).unwrap());
+            let port = u16::from_be_bytes([addr[32], addr[33]]);
+            Ok(Some(SocketAddr::new(ip.into(), port)))
+        }
+        // AF_UNSPEC and AF_UNIX
+        _ => Ok(None),
+    }
+}
+
+type Handshake<S> = BoxFuture<'static, io::Result<(TokioIo<S>, SocketAddr)>>;
+
+/// A listener reading the HAProxy PROXY protocol header of the connections
+/// accepted by the wrapped listener.
+///
+/// The address of the accepted connections is the client address sent by
+/// the proxy, so it's available to handlers as [`PeerAddr`](crate::extract::PeerAddr)
+/// and [`ClientAddr`](crate::extract::ClientAddr). Connections without a valid
+/// header are dropped.
+///
+/// ```ignore
+/// let listener = TcpListener::bind("0.0.0.0:8080").await?;
+/// server.serve(ProxyProtocolListener::new(listener), &shutdown).await;
+/// ```
+pub struct ProxyProtocolListener<L>
+where
+    L: TokioListener,
+{
+    inner: L,
+    timeout: Duration,
+    handshakes: FuturesUnordered<Handshake<L::Stream>>,
+}
+
+impl<L> ProxyProtocolListener<L>
+where
+    L: TokioListener,
+{
+    pub fn new(inner: L) -> ProxyProtocolListener<L> {
+        ProxyProtocolListener {
+            inner,
+            timeout: Duration::from_secs(5),
+            handshakes: FuturesUnordered::new(),
+        }
+    }
+
+    /// How long to wait for the header. Defaults to 5 seconds.
+    pub fn header_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn get_ref(&self) -> &L {
+        &self.inner
+    }
+}
+
+enum Event<S> {
+    Accept(S, SocketAddr),
+    Handshake(io::Result<(TokioIo<S>, SocketAddr)>),
+}
+
+impl<L> Listener for ProxyProtocolListener<L>
+where
+    L: TokioListener<Addr = SocketAddr> + Send,
+{
+    type Io = TokioIo<L::Stream>;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        loop {
+            // Headers are read concurrently, so slow clients do not block new connections
+            let event = if self.handshakes.is_empty() {
+                let (io, addr) = self.inner.accept().await;
+                Event::Accept(L::into_stream(io), addr)
+            } else {
+                futures::select_biased! {
+                    ret = self.handshakes.select_next_some() => Event::Handshake(ret),
+                    (io, addr) = self.inner.accept().fuse() => Event::Accept(L::into_stream(io), addr),
+                }
+            };
+
+            match event {
+                Event::Accept(mut stream, peer) => {
+                    let timeout = self.timeout;
+
+                    self.handshakes.push(Box::pin(async move {
+                        match tokio::time::timeout(timeout, read_proxy_header(&mut stream)).await {
+                            Ok(Ok(addr)) => Ok((TokioIo::new(stream), addr.unwrap_or(peer))),
+                            Ok(Err(err)) => Err(err),
+                            Err(_) => Err(io::ErrorKind::TimedOut.into()),
+                        }
+                    }));
+                }
+                Event::Handshake(Ok(ret)) => return ret,
+                Event::Handshake(Err(err)) => {
+                    tracing::debug!("proxy protocol header failed: {err}");
+                }
+            }
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        self.inner.local_addr()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::{
+        io::AsyncWriteExt,
+        net::{TcpListener, TcpStream},
+    };
+
+    async fn read(header: &[u8]) -> io::Result<(Option<SocketAddr>, Vec<u8>)> {
+        let mut input = header;
+        let addr = read_proxy_header(&mut input).await?;
+        Ok((addr, input.to_vec()))
+    }
+
+    fn v2(command: u8, family: u8, payload: &[u8]) -> Vec<u8> {
+        let mut header = V2_SIGNATURE.to_vec();
+        header.push(0x20 | command);
+        header.push(family);
+        header.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+        header.extend_from_slice(payload);
+        header
+    }
+
+    #[tokio::test]
+    async fn v1() {
+        let (addr, rest) = read(b"PROXY TCP4 192.168.0.1 192.168.0.11 56324 443\r\nGET /")
+            .await
+            .unwrap();
+        assert_eq!(addr, Some("192.168.0.1:56324".parse().unwrap()));
+        assert_eq!(rest, b"GET /");
+
+        let (addr, _) = read(b"PROXY TCP6 2001:db8::1 2001:db8::2 4711 443\r\n")
+            .await
+            .unwrap();
+        assert_eq!(addr, Some("[2001:db8::1]:4711".parse().unwrap()));
+
+        let (addr, rest) = read(b"PROXY UNKNOWN\r\nGET /").await.unwrap();
+        assert_eq!(addr, None);
+        assert_eq!(rest, b"GET /");
+
+        assert!(read(b"PROXY TCP4 192.168.0.1\r\n").await.is_err());
+        assert!(read(b"GET / HTTP/1.1\r\n\r\n").await.is_err());
+        assert!(
+            read(&[b"PROXY TCP4 ".as_slice(), &[b'1'; 200]].concat())
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn v2_inet() {
+        let mut payload = vec![192, 168, 0, 1, 192, 168, 0, 11];
+        payload.extend_from_slice(&56324u16.to_be_bytes());
+        payload.extend_from_slice(&443u16.to_be_bytes());
+        // A TLV, which is skipped
+        payload.extend_from_slice(&[0x04, 0x00, 0x01, 0xff]);
+
+        let mut input = v2(0x1, 0x11, &payload);
+        input.extend_from_slice(b"GET /");
+
+        let (addr, rest) = read(&input).await.unwrap();
+        assert_eq!(addr, Some("192.168.0.1:56324".parse().unwrap()));
+        assert_eq!(rest, b"GET /");
+    }
+
+    #[tokio::test]
+    async fn v2_inet6() {
+        let src: Ipv6Addr = "2001:db8::1".parse().unwrap();
+        let dst: Ipv6Addr = "2001:db8::2".parse().unwrap();
+
+        let mut payload = src.octets().to_vec();
+        payload.extend_from_slice(&dst.octets());
+        payload.extend_from_slice(&4711u16.to_be_bytes());
+        payload.extend_from_slice(&443u16.to_be_bytes());
+
+        let (addr, _) = read(&v2(0x1, 0x21, &payload)).await.unwrap();
+        assert_eq!(addr, Some("[2001:db8::1]:4711".parse().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn v2_local() {
+        let (addr, rest) = read(&[v2(0x0, 0x00, &[]), b"GET /".to_vec()].concat())
+            .await
+            .unwrap();
+        assert_eq!(addr, None);
+        assert_eq!(rest, b"GET /");
+
+        assert!(read(&v2(0x1, 0x11, &[1, 2, 3])).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn listener() {
+        let tcp = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = tcp.local_addr().unwrap();
+        let mut listener = ProxyProtocolListener::new(tcp);
+
+        // A connection without a header is dropped
+        let mut bad = TcpStream::connect(addr).await.unwrap();
+        bad.write_all(b"GET / HTTP/1.1\r\n\r\n").await.unwrap();
+
+        let mut good = TcpStream::connect(addr).await.unwrap();
+        good.write_all(b"PROXY TCP4 10.1.2.3 10.0.0.1 1234 80\r\nhello")
+            .await
+            .unwrap();
+
+        let (io, peer) = Listener::accept(&mut listener).await;
+        assert_eq!(peer, "10.1.2.3:1234".parse().unwrap());
+
+        let mut stream = io.into_inner();
+        let mut buf = [0u8; 5];
+        stream.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"hello");
+    }
+}
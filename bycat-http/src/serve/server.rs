@@ -1,12 +1,23 @@
+use alloc::{sync::Arc, time::Duration};
 use bycat_service::Shutdown;
 use futures::FutureExt;
 use http::{Request, Response};
 use http_body::Body;
-use hyper::{body::Incoming, server::conn::http1::Builder, service::Service};
+use hyper::{body::Incoming, rt::Timer, server::conn::http1::Builder, service::Service};
 
 use crate::error::BoxError;
 
-use super::{Connection, listener::Listener};
+use super::{
+    Connection,
+    limits::{ConnectionCounter, Limits, SharedTimer},
+    listener::Listener,
+};
+
+#[cfg(feature = "http2")]
+use {
+    super::{Http2Config, RequestFuture},
+    hyper_util::server::conn::auto::HttpServerConnExec,
+};
 
 pub struct Conn<L, E>
 where
@@ -20,6 +31,11 @@ impl<L, E> Conn<L, E>
 where
     L: Listener,
 {
+    /// The address of the peer, as returned by the listener.
+    pub fn remote_address(&self) -> &L::Addr {
+        self.conn.local_address()
+    }
+
     pub async fn serve_connection<S, B>(self, service: S) -> Result<(), BoxError>
     where
         S: Service<Request<Incoming>, Response = Response<B>>,
@@ -34,6 +50,26 @@ where
             self.conn.serve_connection(service).await
         }
     }
+
+    /// Like [`serve_connection`](Self::serve_connection), but accepting the
+    /// protocols configured with [`Server::http2`].
+    #[cfg(feature = "http2")]
+    pub async fn serve_connection_auto<S, B>(self, service: S) -> Result<(), BoxError>
+    where
+        S: Service<Request<Incoming>, Response = Response<B>>,
+        S::Future: 'static,
+        S::Error: Into<BoxError>,
+        B: Body + 'static,
+        B::Error: Into<BoxError>,
+        L::Io: Send + 'static,
+        E: HttpServerConnExec<RequestFuture<S::Future>, B>,
+    {
+        if self.with_upgrade {
+            self.conn.serve_connection_auto_with_upgrades(service).await
+        } else {
+            self.conn.serve_connection_auto(service).await
+        }
+    }
 }
 
 pub trait Servable<E, L>
@@ -52,6 +88,10 @@ pub struct Server<T, E> {
     builder: Builder,
     executor: E,
     with_upgrade: bool,
+    max_connections: Option<usize>,
+    limits: Arc<Limits>,
+    #[cfg(feature = "http2")]
+    http2: Arc<Http2Config>,
 }
 
 impl<T, E> Server<T, E> {
@@ -61,6 +101,10 @@ impl<T, E> Server<T, E> {
             builder: Builder::new(),
             executor,
             with_upgrade: false,
+            max_connections: None,
+            limits: Arc::default(),
+            #[cfg(feature = "http2")]
+            http2: Arc::default(),
         }
     }
 
@@ -68,34 +112,96 @@ impl<T, E> Server<T, E> {
         self.with_upgrade = with_upgrade;
         self
     }
+
+    /// Stop accepting connections while `max` connections are open.
+    pub fn max_connections(mut self, max: usize) -> Self {
+        self.max_connections = Some(max);
+        self
+    }
+
+    /// Close connections that don't send the request headers within `timeout`.
+    ///
+    /// Requires a timer, which is set by default with the `serve-tokio` feature.
+    pub fn header_read_timeout(mut self, timeout: Duration) -> Self {
+        Arc::make_mut(&mut self.limits).header_read_timeout = Some(timeout);
+        self
+    }
+
+    /// Close connections that have been without requests in flight for `timeout`.
+    ///
+    /// Requires a timer, which is set by default with the `serve-tokio` feature.
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        Arc::make_mut(&mut self.limits).idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Close connections gracefully after serving `max` requests.
+    pub fn max_requests_per_connection(mut self, max: usize) -> Self {
+        Arc::make_mut(&mut self.limits).max_requests = Some(max);
+        self
+    }
+
+    pub fn timer<M>(mut self, timer: M) -> Self
+    where
+        M: Timer + Send + Sync + 'static,
+    {
+        Arc::make_mut(&mut self.limits).timer = Some(SharedTimer::new(timer));
+        self
+    }
+
+    /// Configure the accepted protocols and HTTP/2 options.
+    /// By default both HTTP/1.1 and HTTP/2 are accepted.
+    #[cfg(feature = "http2")]
+    pub fn http2(mut self, config: Http2Config) -> Self {
+        self.http2 = Arc::new(config);
+        self
+    }
 }
 
 impl<T, E> Server<T, E>
 where
     E: Clone,
 {
+    /// Accept connections from `listener` until `shutdown` is triggered.
+    ///
+    /// Open connections are watched by `shutdown`, so [`Shutdown::drain`] can
+    /// wait for them to finish.
     pub async fn serve<L>(&self, mut listener: L, shutdown: &Shutdown)
     where
         L: Listener,
         T: Servable<E, L>,
     {
-        let inner = Shutdown::new();
+        let counter = Arc::new(ConnectionCounter::default());
 
         let mut wait = shutdown.wait().fuse();
 
         loop {
-            futures::select_biased! {
-                (stream, address) = listener.accept().fuse() => {
-
-                    let conn = Connection::new(self.executor.clone(),self.builder.clone(), inner.clone(), stream, address);
+            let accept = async {
+                counter.available(self.max_connections).await;
+                listener.accept().await
+            };
 
-                    self.service.call(Conn { conn, with_upgrade: self.with_upgrade }).await;
-                }
+            futures::select_biased! {
                 _ = &mut wait => {
-                    inner.shutdown();
                     break;
                 }
+                (stream, address) = accept.fuse() => {
+
+                    let conn = Connection::new(
+                        self.executor.clone(),
+                        self.builder.clone(),
+                        shutdown.clone(),
+                        stream,
+                        address,
+                        self.limits.clone(),
+                        counter.acquire(),
+                    );
+
+                    #[cfg(feature = "http2")]
+                    let conn = conn.with_http2(self.http2.clone());
 
+                    self.service.call(Conn { conn, with_upgrade: self.with_upgrade }).await;
+                }
             };
         }
     }
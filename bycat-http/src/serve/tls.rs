@@ -0,0 +1,556 @@
+use alloc::{
+    boxed::Box,
+    collections::BTreeMap,
+    fmt, io,
+    path::PathBuf,
+    string::String,
+    sync::Arc,
+    time::{Duration, SystemTime},
+    vec::Vec,
+};
+use arc_swap::ArcSwap;
+use futures::{FutureExt, StreamExt, future::BoxFuture, stream::FuturesUnordered};
+use hyper_util::rt::TokioIo;
+use tokio_rustls::{
+    TlsAcceptor,
+    rustls::{
+        self, ServerConfig,
+        crypto::CryptoProvider,
+        pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject},
+        server::{ClientHello, ResolvesServerCert},
+        sign::CertifiedKey,
+    },
+    server::TlsStream,
+};
+
+use super::{Listener, TokioListener};
+
+#[derive(Debug)]
+pub enum TlsError {
+    Io(io::Error),
+    Pem(rustls::pki_types::pem::Error),
+    Rustls(rustls::Error),
+    NoCertificate,
+}
+
+impl fmt::Display for TlsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TlsError::Io(err) => write!(f, "IO error: {err}"),
+            TlsError::Pem(err) => write!(f, "Invalid pem: {err}"),
+            TlsError::Rustls(err) => write!(f, "TLS error: {err}"),
+            TlsError::NoCertificate => write!(f, "No certificates configured"),
+        }
+    }
+}
+
+impl core::error::Error for TlsError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            TlsError::Io(err) => Some(err),
+            TlsError::Pem(err) => Some(err),
+            TlsError::Rustls(err) => Some(err),
+            TlsError::NoCertificate => None,
+        }
+    }
+}
+
+impl From<io::Error> for TlsError {
+    fn from(value: io::Error) -> Self {
+        TlsError::Io(value)
+    }
+}
+
+impl From<rustls::pki_types::pem::Error> for TlsError {
+    fn from(value: rustls::pki_types::pem::Error) -> Self {
+        TlsError::Pem(value)
+    }
+}
+
+impl From<rustls::Error> for TlsError {
+    fn from(value: rustls::Error) -> Self {
+        TlsError::Rustls(value)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum CertSource {
+    Files { cert: PathBuf, key: PathBuf },
+    Pem { cert: Vec<u8>, key: Vec<u8> },
+}
+
+impl CertSource {
+    fn load(&self, provider: &CryptoProvider) -> Result<Arc<CertifiedKey>, TlsError> {
+        let (cert, key) = match self {
+            CertSource::Files { cert, key } => (std::fs::read(cert)?, std::fs::read(key)?),
+            CertSource::Pem { cert, key } => (cert.clone(), key.clone()),
+        };
+
+        let certs = CertificateDer::pem_slice_iter(&cert).collect::<Result<Vec<_>, _>>()?;
+        if certs.is_empty() {
+            return Err(TlsError::NoCertificate);
+        }
+
+        let key = PrivateKeyDer::from_pem_slice(&key)?;
+        let key = provider.key_provider.load_private_key(key)?;
+
+        Ok(Arc::new(CertifiedKey::new(certs, key)))
+    }
+
+    fn modified(&self) -> Option<SystemTime> {
+        let CertSource::Files { cert, key } = self else {
+            return None;
+        };
+
+        let cert = std::fs::metadata(cert).and_then(|m| m.modified()).ok()?;
+        let key = std::fs::metadata(key).and_then(|m| m.modified()).ok()?;
+
+        Some(cert.max(key))
+    }
+}
+
+/// Certificates and protocols for a [`TlsListener`].
+#[derive(Debug, Clone)]
+pub struct TlsConfig {
+    default: Option<CertSource>,
+    sni: BTreeMap<String, CertSource>,
+    alpn: Vec<Vec<u8>>,
+    handshake_timeout: Duration,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        let alpn = if cfg!(feature = "http2") {
+            vec![b"h2".to_vec(), b"http/1.1".to_vec()]
+        } else {
+            vec![b"http/1.1".to_vec()]
+        };
+
+        TlsConfig {
+            default: None,
+            sni: BTreeMap::default(),
+            alpn,
+            handshake_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+impl TlsConfig {
+    /// Use the pem encoded certificate chain and private key at the given paths.
+    pub fn from_files(cert: impl Into<PathBuf>, key: impl Into<PathBuf>) -> TlsConfig {
+        TlsConfig::default().cert_files(cert, key)
+    }
+
+    pub fn from_pem(cert: impl Into<Vec<u8>>, key: impl Into<Vec<u8>>) -> TlsConfig {
+        TlsConfig::default().cert_pem(cert, key)
+    }
+
+    /// The certificate used when no SNI certificate matches the client.
+    pub fn cert_files(mut self, cert: impl Into<PathBuf>, key: impl Into<PathBuf>) -> Self {
+        self.default = Some(CertSource::Files {
+            cert: cert.into(),
+            key: key.into(),
+        });
+        self
+    }
+
+    pub fn cert_pem(mut self, cert: impl Into<Vec<u8>>, key: impl Into<Vec<u8>>) -> Self {
+        self.default = Some(CertSource::Pem {
+            cert: cert.into(),
+            key: key.into(),
+        });
+        self
+    }
+
+    /// Use a certificate for clients requesting `host` through SNI.
+    pub fn sni_files(
+        mut self,
+        host: impl Into<String>,
+        cert: impl Into<PathBuf>,
+        key: impl Into<PathBuf>,
+    ) -> Self {
+        self.sni.insert(
+            host.into().to_ascii_lowercase(),
+            CertSource::Files {
+                cert: cert.into(),
+                key: key.into(),
+            },
+        );
+        self
+    }
+
+    pub fn sni_pem(
+        mut self,
+        host: impl Into<String>,
+        cert: impl Into<Vec<u8>>,
+        key: impl Into<Vec<u8>>,
+    ) -> Self {
+        self.sni.insert(
+            host.into().to_ascii_lowercase(),
+            CertSource::Pem {
+                cert: cert.into(),
+                key: key.into(),
+            },
+        );
+        self
+    }
+
+    /// The ALPN protocols, in order of preference.
+    /// Defaults to `h2` and `http/1.1` with the `http2` feature, otherwise `http/1.1`.
+    pub fn alpn_protocols(mut self, protocols: Vec<Vec<u8>>) -> Self {
+        self.alpn = protocols;
+        self
+    }
+
+    pub fn handshake_timeout(mut self, timeout: Duration) -> Self {
+        self.handshake_timeout = timeout;
+        self
+    }
+
+    fn load(&self, provider: &CryptoProvider) -> Result<Certificates, TlsError> {
+        let default = self
+            .default
+            .as_ref()
+            .map(|source| source.load(provider))
+            .transpose()?;
+
+        let sni = self
+            .sni
+            .iter()
+            .map(|(host, source)| Ok((host.clone(), source.load(provider)?)))
+            .collect::<Result<BTreeMap<_, _>, TlsError>>()?;
+
+        if default.is_none() && sni.is_empty() {
+            return Err(TlsError::NoCertificate);
+        }
+
+        Ok(Certificates { default, sni })
+    }
+
+    fn modified(&self) -> Option<SystemTime> {
+        self.default
+            .iter()
+            .chain(self.sni.values())
+            .filter_map(CertSource::modified)
+            .max()
+    }
+}
+
+#[derive(Debug)]
+struct Certificates {
+    default: Option<Arc<CertifiedKey>>,
+    sni: BTreeMap<String, Arc<CertifiedKey>>,
+}
+
+#[derive(Debug)]
+struct Resolver {
+    certs: ArcSwap<Certificates>,
+}
+
+impl ResolvesServerCert for Resolver {
+    fn resolve(&self, hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        let certs = self.certs.load();
+
+        hello
+            .server_name()
+            .and_then(|name| certs.sni.get(&name.to_ascii_lowercase()))
+            .or(certs.default.as_ref())
+            .cloned()
+    }
+}
+
+/// Reloads the certificates of a [`TlsListener`] from disk.
+#[derive(Debug, Clone)]
+pub struct TlsReloader {
+    config: Arc<TlsConfig>,
+    resolver: Arc<Resolver>,
+    provider: Arc<CryptoProvider>,
+}
+
+impl TlsReloader {
+    /// Reload the certificates. New connections will use the new certificates,
+    /// while established connections are unaffected.
+    pub fn reload(&self) -> Result<(), TlsError> {
+        let certs = self.config.load(&self.provider)?;
+        self.resolver.certs.store(Arc::new(certs));
+        Ok(())
+    }
+
+    /// Poll the certificate files for changes, reloading them when modified.
+    pub async fn watch(self, interval: Duration) {
+        let mut last = self.config.modified();
+
+        loop {
+            tokio::time::sleep(interval).await;
+
+            let modified = self.config.modified();
+            if modified == last {
+                continue;
+            }
+
+            match self.reload() {
+                Ok(()) => {
+                    tracing::info!("reloaded tls certificates");
+                    last = modified;
+                }
+                // The files might be halfway written, so try again on next tick
+                Err(err) => tracing::warn!("could not reload tls certificates: {err}"),
+            }
+        }
+    }
+}
+
+type Handshake<S, A> = BoxFuture<'static, Result<(TokioIo<TlsStream<S>>, A), io::Error>>;
+
+/// A listener terminating TLS on the connections accepted by the wrapped listener.
+///
+/// ```ignore
+/// let listener = TcpListener::bind("0.0.0.0:443").await?;
+/// let listener = TlsListener::new(listener, TlsConfig::from_files("cert.pem", "key.pem"))?;
+///
+/// tokio::spawn(listener.reloader().watch(Duration::from_secs(60)));
+///
+/// server.serve(listener, &shutdown).await;
+/// ```
+pub struct TlsListener<L>
+where
+    L: TokioListener,
+{
+    inner: L,
+    acceptor: TlsAcceptor,
+    reloader: TlsReloader,
+    handshakes: FuturesUnordered<Handshake<L::Stream, L::Addr>>,
+}
+
+impl<L> TlsListener<L>
+where
+    L: TokioListener,
+{
+    pub fn new(inner: L, config: TlsConfig) -> Result<Self, TlsError> {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let certs = config.load(&provider)?;
+
+        let resolver = Arc::new(Resolver {
+            certs: ArcSwap::from_pointee(certs),
+        });
+
+        let mut server = ServerConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()?
+            .with_no_client_auth()
+            .with_cert_resolver(resolver.clone());
+        server.alpn_protocols = config.alpn.clone();
+
+        Ok(TlsListener {
+            inner,
+            acceptor: TlsAcceptor::from(Arc::new(server)),
+            reloader: TlsReloader {
+                config: Arc::new(config),
+                resolver,
+                provider,
+            },
+            handshakes: FuturesUnordered::new(),
+        })
+    }
+
+    pub fn reloader(&self) -> TlsReloader {
+        self.reloader.clone()
+    }
+
+    pub fn get_ref(&self) -> &L {
+        &self.inner
+    }
+}
+
+enum Event<S, A> {
+    Accept(S, A),
+    Handshake(Result<(TokioIo<TlsStream<S>>, A), io::Error>),
+}
+
+impl<L> Listener for TlsListener<L>
+where
+    L: TokioListener + Send,
+    L::Addr: Send + 'static,
+{
+    type Io = TokioIo<TlsStream<L::Stream>>;
+    type Addr = L::Addr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        loop {
+            // Handshakes run concurrently, so slow clients do not block new connections
+            let event = if self.handshakes.is_empty() {
+                let (io, addr) = self.inner.accept().await;
+                Event::Accept(L::into_stream(io), addr)
+            } else {
+                futures::select_biased! {
+                    ret = self.handshakes.select_next_some() => Event::Handshake(ret),
+                    (io, addr) = self.inner.accept().fuse() => Event::Accept(L::into_stream(io), addr),
+                }
+            };
+
+            match event {
+                Event::Accept(io, addr) => {
+                    let accept = self.acceptor.accept(io);
+                    let timeout = self.reloader.config.handshake_timeout;
+
+                    self.handshakes.push(Box::pin(async move {
+                        match tokio::time::timeout(timeout, accept).await {
+                            Ok(Ok(stream)) => Ok((TokioIo::new(stream), addr)),
+                            Ok(Err(err)) => Err(err),
+                            Err(_) => Err(io::ErrorKind::TimedOut.into()),
+                        }
+                    }));
+                }
+                Event::Handshake(Ok(ret)) => return ret,
+                Event::Handshake(Err(err)) => {
+                    tracing::debug!("tls handshake failed: {err}");
+                }
+            }
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        self.inner.local_addr()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio_rustls::{
+        TlsConnector,
+        rustls::{ClientConfig, RootCertStore, pki_types::ServerName},
+    };
+
+    struct SelfSigned {
+        cert: String,
+        key: String,
+        der: CertificateDer<'static>,
+    }
+
+    fn self_signed(host: &str) -> SelfSigned {
+        let rcgen::CertifiedKey { cert, key_pair } =
+            rcgen::generate_simple_self_signed(vec![host.into()]).unwrap();
+
+        SelfSigned {
+            cert: cert.pem(),
+            key: key_pair.serialize_pem(),
+            der: cert.der().clone(),
+        }
+    }
+
+    async fn connect(
+        addr: std::net::SocketAddr,
+        host: &'static str,
+        roots: &[&SelfSigned],
+    ) -> tokio_rustls::client::TlsStream<TcpStream> {
+        let mut store = RootCertStore::empty();
+        for root in roots {
+            store.add(root.der.clone()).unwrap();
+        }
+
+        let mut config =
+            ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+                .with_safe_default_protocol_versions()
+                .unwrap()
+                .with_root_certificates(store)
+                .with_no_client_auth();
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+        let tcp = TcpStream::connect(addr).await.unwrap();
+        TlsConnector::from(Arc::new(config))
+            .connect(ServerName::try_from(host).unwrap(), tcp)
+            .await
+            .unwrap()
+    }
+
+    fn peer_certificate(
+        stream: &tokio_rustls::client::TlsStream<TcpStream>,
+    ) -> CertificateDer<'static> {
+        stream.get_ref().1.peer_certificates().unwrap()[0]
+            .clone()
+            .into_owned()
+    }
+
+    #[tokio::test]
+    async fn handshake_with_alpn() {
+        let cert = self_signed("localhost");
+
+        let tcp = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = tcp.local_addr().unwrap();
+        let mut listener = TlsListener::new(
+            tcp,
+            TlsConfig::from_pem(cert.cert.as_bytes(), cert.key.as_bytes())
+                .alpn_protocols(vec![b"h2".to_vec(), b"http/1.1".to_vec()]),
+        )
+        .unwrap();
+
+        let (client, (server, _)) = tokio::join!(
+            connect(addr, "localhost", &[&cert]),
+            Listener::accept(&mut listener)
+        );
+
+        assert_eq!(peer_certificate(&client), cert.der);
+        assert_eq!(client.get_ref().1.alpn_protocol(), Some(&b"h2"[..]));
+        assert_eq!(server.inner().get_ref().1.alpn_protocol(), Some(&b"h2"[..]));
+    }
+
+    #[tokio::test]
+    async fn sni() {
+        let default = self_signed("localhost");
+        let api = self_signed("api.test");
+
+        let tcp = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = tcp.local_addr().unwrap();
+        let mut listener = TlsListener::new(
+            tcp,
+            TlsConfig::from_pem(default.cert.as_bytes(), default.key.as_bytes()).sni_pem(
+                "API.test",
+                api.cert.as_bytes(),
+                api.key.as_bytes(),
+            ),
+        )
+        .unwrap();
+
+        let (client, _) = tokio::join!(
+            connect(addr, "api.test", &[&default, &api]),
+            Listener::accept(&mut listener)
+        );
+        assert_eq!(peer_certificate(&client), api.der);
+
+        let (client, _) = tokio::join!(
+            connect(addr, "localhost", &[&default, &api]),
+            Listener::accept(&mut listener)
+        );
+        assert_eq!(peer_certificate(&client), default.der);
+    }
+
+    #[tokio::test]
+    async fn reload() {
+        let dir = std::env::temp_dir().join(format!("bycat-tls-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (cert_path, key_path) = (dir.join("cert.pem"), dir.join("key.pem"));
+
+        let first = self_signed("localhost");
+        std::fs::write(&cert_path, &first.cert).unwrap();
+        std::fs::write(&key_path, &first.key).unwrap();
+
+        let tcp = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = tcp.local_addr().unwrap();
+        let mut listener =
+            TlsListener::new(tcp, TlsConfig::from_files(&cert_path, &key_path)).unwrap();
+
+        let second = self_signed("localhost");
+        std::fs::write(&cert_path, &second.cert).unwrap();
+        std::fs::write(&key_path, &second.key).unwrap();
+        listener.reloader().reload().unwrap();
+
+        let (client, _) = tokio::join!(
+            connect(addr, "localhost", &[&first, &second]),
+            Listener::accept(&mut listener)
+        );
+        assert_eq!(peer_certificate(&client), second.der);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
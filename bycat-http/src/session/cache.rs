@@ -0,0 +1,130 @@
+use crate::Error;
+use alloc::{format, vec::Vec};
+use bycat_cache::CacheStore;
+use bycat_value::Map;
+use core::time::Duration;
+use uuid::Uuid;
+
+use super::{SessionId, Store, store::record};
+
+/// A session store on top of a [`CacheStore`].
+///
+/// The expiry is stored with the session, since caches have no notion of it.
+/// A removed session is overwritten with an empty value, which loads as an
+/// empty session, so reaping is left to the cache's own eviction.
+#[derive(Debug, Clone)]
+pub struct CacheSessionStore<T> {
+    cache: T,
+    prefix: &'static str,
+    ttl: Duration,
+}
+
+impl<T> CacheSessionStore<T> {
+    pub fn new(cache: T) -> CacheSessionStore<T> {
+        CacheSessionStore {
+            cache,
+            prefix: "session:",
+            ttl: Duration::from_secs(60 * 60 * 24),
+        }
+    }
+
+    /// Prefix of the cache keys. Defaults to `session:`.
+    pub fn prefix(mut self, prefix: &'static str) -> Self {
+        self.prefix = prefix;
+        self
+    }
+
+    /// How long sessions are kept after they were last saved. Defaults to a day.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    fn key(&self, id: Uuid) -> Vec<u8> {
+        format!("{}{}", self.prefix, id.hyphenated()).into_bytes()
+    }
+}
+
+impl<T> Store for CacheSessionStore<T>
+where
+    T: CacheStore + Send + Sync,
+    for<'a> T::GetFuture<'a>: Send,
+    for<'a> T::SetFuture<'a>: Send,
+{
+    async fn save<'a>(&'a self, id: SessionId, session: &'a Map) -> Result<(), Error> {
+        let Some(id) = id.id() else {
+            return Ok(());
+        };
+
+        let data = record::encode(record::expires(self.ttl), session)?;
+        self.cache
+            .set(&self.key(id), &data)
+            .await
+            .map_err(Error::custom)
+    }
+
+    async fn load<'a>(&'a self, id: SessionId) -> Result<Map, Error> {
+        let Some(id) = id.id() else {
+            return Ok(Map::default());
+        };
+
+        let data = self.cache.get(&self.key(id)).await.map_err(Error::custom)?;
+
+        if data.is_empty() {
+            return Ok(Map::default());
+        }
+
+        Ok(record::decode(&data)?.unwrap_or_default())
+    }
+
+    async fn remove<'a>(&'a self, id: SessionId) -> Result<(), Error> {
+        let Some(id) = id.id() else {
+            return Ok(());
+        };
+
+        self.cache
+            .set(&self.key(id), &[])
+            .await
+            .map_err(Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::MemoryCache;
+
+    fn session() -> Map {
+        let mut map = Map::default();
+        map.insert("user", "rasmus");
+        map
+    }
+
+    #[tokio::test]
+    async fn save_load_remove() {
+        let store = CacheSessionStore::new(MemoryCache::default());
+        let id = SessionId::new(Uuid::new_v4());
+
+        assert!(store.load(id.clone()).await.unwrap().is_empty());
+
+        store.save(id.clone(), &session()).await.unwrap();
+        assert_eq!(store.load(id.clone()).await.unwrap(), session());
+        assert!(
+            store
+                .cache
+                .contains(format!("session:{}", id.id().unwrap()).as_bytes())
+        );
+
+        store.remove(id.clone()).await.unwrap();
+        assert!(store.load(id).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn expiry() {
+        let store = CacheSessionStore::new(MemoryCache::default()).ttl(Duration::ZERO);
+        let id = SessionId::new(Uuid::new_v4());
+
+        store.save(id.clone(), &session()).await.unwrap();
+        assert!(store.load(id).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,250 @@
+use crate::cookies::CookieJar;
+use alloc::{borrow::Cow, format};
+use cookie::{Cookie, Key, SameSite};
+use core::time::Duration;
+use std::time::{SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+/// Milliseconds since the unix epoch.
+pub(super) fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// The contents of the session cookie.
+///
+/// Encoded as `{id}.{created}.{seen}`. The cookie is signed or encrypted, so
+/// the timestamps can be trusted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct SessionCookie {
+    pub id: Uuid,
+    pub created: u64,
+    pub seen: u64,
+}
+
+impl SessionCookie {
+    pub fn new(id: Uuid, now: u64) -> SessionCookie {
+        SessionCookie {
+            id,
+            created: now,
+            seen: now,
+        }
+    }
+
+    fn parse(value: &str, now: u64) -> Option<SessionCookie> {
+        let mut parts = value.split('.');
+        let id = Uuid::parse_str(parts.next()?).ok()?;
+
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(created), Some(seen), None) => Some(SessionCookie {
+                id,
+                created: created.parse().ok()?,
+                seen: seen.parse().ok()?,
+            }),
+            // Cookies issued before timestamps were added
+            (None, _, _) => Some(SessionCookie::new(id, now)),
+            _ => None,
+        }
+    }
+
+    fn encode(&self) -> alloc::string::String {
+        format!("{}.{}.{}", self.id.hyphenated(), self.created, self.seen)
+    }
+}
+
+#[derive(Clone)]
+pub(super) struct Config {
+    pub name: Cow<'static, str>,
+    pub key: Key,
+    pub private: bool,
+    pub same_site: SameSite,
+    pub domain: Option<Cow<'static, str>>,
+    pub path: Cow<'static, str>,
+    pub secure: bool,
+    pub http_only: bool,
+    pub idle_timeout: Option<Duration>,
+    pub absolute_timeout: Option<Duration>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            name: Cow::Borrowed("sess_id"),
+            key: Key::generate(),
+            private: false,
+            same_site: SameSite::Lax,
+            domain: None,
+            path: Cow::Borrowed("/"),
+            secure: true,
+            http_only: true,
+            idle_timeout: None,
+            absolute_timeout: None,
+        }
+    }
+}
+
+impl Config {
+    /// Read the session cookie. Cookies failing verification are ignored.
+    pub fn read(&self, jar: &CookieJar, now: u64) -> Option<SessionCookie> {
+        let cookie = if self.private {
+            jar.private(&self.key).get(&self.name)
+        } else {
+            jar.signed(&self.key).get(&self.name)
+        }?;
+
+        SessionCookie::parse(cookie.value(), now)
+    }
+
+    pub fn write(&self, jar: &CookieJar, session: SessionCookie, now: u64) {
+        let mut cookie = self.cookie(session.encode());
+
+        if let Some(max_age) = self.max_age(&session, now) {
+            cookie.set_max_age(cookie::time::Duration::milliseconds(max_age as i64));
+        }
+
+        if self.private {
+            jar.private(&self.key).add(cookie);
+        } else {
+            jar.signed(&self.key).add(cookie);
+        }
+    }
+
+    pub fn clear(&self, jar: &CookieJar) {
+        jar.remove(self.cookie(""));
+    }
+
+    pub fn is_expired(&self, session: &SessionCookie, now: u64) -> bool {
+        let elapsed = |since: u64, timeout: Duration| {
+            u128::from(now.saturating_sub(since)) >= timeout.as_millis()
+        };
+
+        self.idle_timeout
+            .is_some_and(|timeout| elapsed(session.seen, timeout))
+            || self
+                .absolute_timeout
+                .is_some_and(|timeout| elapsed(session.created, timeout))
+    }
+
+    /// The cookie only needs refreshing on every request when it carries an
+    /// idle deadline.
+    pub fn touches(&self) -> bool {
+        self.idle_timeout.is_some()
+    }
+
+    /// Milliseconds until the session expires, if it expires at all.
+    fn max_age(&self, session: &SessionCookie, now: u64) -> Option<u64> {
+        let remaining = |since: u64, timeout: Duration| {
+            (since.saturating_add(timeout.as_millis() as u64)).saturating_sub(now)
+        };
+
+        let idle = self
+            .idle_timeout
+            .map(|timeout| remaining(session.seen, timeout));
+        let absolute = self
+            .absolute_timeout
+            .map(|timeout| remaining(session.created, timeout));
+
+        match (idle, absolute) {
+            (Some(idle), Some(absolute)) => Some(idle.min(absolute)),
+            (idle, absolute) => idle.or(absolute),
+        }
+    }
+
+    fn cookie(&self, value: impl Into<Cow<'static, str>>) -> Cookie<'static> {
+        let mut cookie = Cookie::new(self.name.clone(), value);
+        cookie.set_secure(self.secure);
+        cookie.set_http_only(self.http_only);
+        cookie.set_same_site(self.same_site);
+        cookie.set_path(self.path.clone());
+        if let Some(domain) = &self.domain {
+            cookie.set_domain(domain.clone());
+        }
+        cookie
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_parse() {
+        let cookie = SessionCookie {
+            id: Uuid::new_v4(),
+            created: 1_000,
+            seen: 2_000,
+        };
+
+        assert_eq!(SessionCookie::parse(&cookie.encode(), 5), Some(cookie));
+        assert_eq!(
+            SessionCookie::parse(&cookie.id.hyphenated().to_string(), 5),
+            Some(SessionCookie::new(cookie.id, 5))
+        );
+        assert_eq!(SessionCookie::parse("not-a-uuid.1.2", 5), None);
+        assert_eq!(SessionCookie::parse(&format!("{}.1", cookie.id), 5), None);
+    }
+
+    #[test]
+    fn expiry() {
+        let config = Config {
+            idle_timeout: Some(Duration::from_secs(60)),
+            absolute_timeout: Some(Duration::from_secs(3600)),
+            ..Default::default()
+        };
+
+        let session = SessionCookie {
+            id: Uuid::new_v4(),
+            created: 0,
+            seen: 3_000_000,
+        };
+
+        assert!(!config.is_expired(&session, 3_030_000));
+        assert_eq!(config.max_age(&session, 3_030_000), Some(30_000));
+
+        // Idle
+        assert!(config.is_expired(&session, 3_060_000));
+
+        // Absolute, even when recently seen
+        let session = SessionCookie {
+            seen: 3_590_000,
+            ..session
+        };
+        assert!(!config.is_expired(&session, 3_599_000));
+        assert_eq!(config.max_age(&session, 3_599_000), Some(1_000));
+        assert!(config.is_expired(&session, 3_600_000));
+    }
+
+    #[test]
+    fn private_and_signed() {
+        let session = SessionCookie::new(Uuid::new_v4(), now());
+
+        for private in [false, true] {
+            let config = Config {
+                private,
+                ..Default::default()
+            };
+
+            let jar = CookieJar::from_headers(&Default::default());
+            config.write(&jar, session, session.created);
+
+            let value = jar.get("sess_id").unwrap().value().to_string();
+            assert_eq!(private, !value.contains(&session.id.to_string()));
+
+            let mut headers = http::HeaderMap::new();
+            headers.insert(
+                http::header::COOKIE,
+                format!("sess_id={value}").parse().unwrap(),
+            );
+            let jar = CookieJar::from_headers(&headers);
+            assert_eq!(config.read(&jar, now()), Some(session));
+
+            let other = Config {
+                private,
+                ..Default::default()
+            };
+            assert_eq!(other.read(&jar, now()), None);
+        }
+    }
+}
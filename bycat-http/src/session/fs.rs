@@ -0,0 +1,186 @@
+use crate::Error;
+use alloc::{format, path::PathBuf};
+use bycat_value::Map;
+use core::time::Duration;
+use std::io::ErrorKind;
+use uuid::Uuid;
+
+use super::{SessionId, Store, store::record};
+
+/// A session store keeping one file per session in a directory.
+///
+/// Files are written to a temporary file and renamed into place, so a
+/// session is never read half written.
+#[derive(Debug, Clone)]
+pub struct FileStore {
+    dir: PathBuf,
+    ttl: Duration,
+}
+
+impl FileStore {
+    pub fn new(dir: impl Into<PathBuf>) -> FileStore {
+        FileStore {
+            dir: dir.into(),
+            ttl: Duration::from_secs(60 * 60 * 24),
+        }
+    }
+
+    /// How long sessions are kept after they were last saved. Defaults to a day.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    fn path(&self, id: Uuid) -> PathBuf {
+        self.dir.join(format!("{}.json", id.hyphenated()))
+    }
+}
+
+impl Store for FileStore {
+    async fn save<'a>(&'a self, id: SessionId, session: &'a Map) -> Result<(), Error> {
+        let Some(id) = id.id() else {
+            return Ok(());
+        };
+
+        let data = record::encode(record::expires(self.ttl), session)?;
+
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .map_err(Error::custom)?;
+
+        let tmp = self.dir.join(format!(
+            ".{}.{}.tmp",
+            id.hyphenated(),
+            Uuid::new_v4().simple()
+        ));
+
+        if let Err(err) = tokio::fs::write(&tmp, data).await {
+            tokio::fs::remove_file(&tmp).await.ok();
+            return Err(Error::custom(err));
+        }
+
+        tokio::fs::rename(&tmp, self.path(id))
+            .await
+            .map_err(Error::custom)
+    }
+
+    async fn load<'a>(&'a self, id: SessionId) -> Result<Map, Error> {
+        let Some(id) = id.id() else {
+            return Ok(Map::default());
+        };
+
+        let path = self.path(id);
+
+        let data = match tokio::fs::read(&path).await {
+            Ok(data) => data,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Map::default()),
+            Err(err) => return Err(Error::custom(err)),
+        };
+
+        match record::decode(&data)? {
+            Some(session) => Ok(session),
+            None => {
+                tokio::fs::remove_file(&path).await.ok();
+                Ok(Map::default())
+            }
+        }
+    }
+
+    async fn remove<'a>(&'a self, id: SessionId) -> Result<(), Error> {
+        let Some(id) = id.id() else {
+            return Ok(());
+        };
+
+        match tokio::fs::remove_file(self.path(id)).await {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(Error::custom(err)),
+            _ => Ok(()),
+        }
+    }
+
+    async fn reap(&self) -> Result<usize, Error> {
+        let mut entries = match tokio::fs::read_dir(&self.dir).await {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(0),
+            Err(err) => return Err(Error::custom(err)),
+        };
+
+        let mut count = 0;
+
+        while let Some(entry) = entries.next_entry().await.map_err(Error::custom)? {
+            let path = entry.path();
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+
+            let Ok(data) = tokio::fs::read(&path).await else {
+                continue;
+            };
+
+            if record::is_expired(&data) && tokio::fs::remove_file(&path).await.is_ok() {
+                count += 1;
+            }
+        }
+
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("bycat-sessions-{name}-{}", Uuid::new_v4().simple()))
+    }
+
+    fn session() -> Map {
+        let mut map = Map::default();
+        map.insert("user", "rasmus");
+        map
+    }
+
+    #[tokio::test]
+    async fn save_load_remove() {
+        let dir = dir("fs");
+        let store = FileStore::new(&dir);
+        let id = SessionId::new(Uuid::new_v4());
+
+        assert!(store.load(id.clone()).await.unwrap().is_empty());
+
+        store.save(id.clone(), &session()).await.unwrap();
+        assert_eq!(store.load(id.clone()).await.unwrap(), session());
+
+        // Survives a new store over the same directory
+        assert_eq!(
+            FileStore::new(&dir).load(id.clone()).await.unwrap(),
+            session()
+        );
+
+        store.remove(id.clone()).await.unwrap();
+        assert!(store.load(id.clone()).await.unwrap().is_empty());
+        store.remove(id).await.unwrap();
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn expiry() {
+        let dir = dir("fs-expiry");
+        let store = FileStore::new(&dir).ttl(Duration::ZERO);
+
+        let expired = SessionId::new(Uuid::new_v4());
+        store.save(expired.clone(), &session()).await.unwrap();
+
+        let live = SessionId::new(Uuid::new_v4());
+        FileStore::new(&dir)
+            .save(live.clone(), &session())
+            .await
+            .unwrap();
+
+        assert_eq!(store.reap().await.unwrap(), 1);
+        assert!(store.load(expired).await.unwrap().is_empty());
+        assert_eq!(store.load(live).await.unwrap(), session());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
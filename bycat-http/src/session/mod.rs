@@ -1,35 +1,64 @@
+#[cfg(feature = "session-cache")]
+mod cache;
+mod config;
+#[cfg(feature = "session-fs")]
+mod fs;
 mod session;
+#[cfg(feature = "session-sqlite")]
+mod sqlite;
 mod store;
 
 use crate::{Error, error::BoxError};
 use crate::{
     cookies::CookieJar,
     session::{
+        config::{Config, SessionCookie},
         session::State,
-        store::{DynStoreImpl, SessionStore},
+        store::DynStoreImpl,
     },
 };
-use alloc::{borrow::Cow, string::ToString, sync::Arc};
+use alloc::{borrow::Cow, sync::Arc};
 use bycat::{Middleware, Work};
-use cookie::Cookie;
+use cookie::{Key, SameSite};
 use core::{
     marker::PhantomData,
     task::{Poll, ready},
+    time::Duration,
 };
+use futures::future::BoxFuture;
 use http::Request;
 use pin_project_lite::pin_project;
-use uuid::Uuid;
 
 pub use self::{
     session::{Session, SessionId},
     store::{MemoryStore, Store},
 };
 
+pub(crate) use self::store::SessionStore;
+
+#[cfg(any(
+    feature = "session-fs",
+    feature = "session-sqlite",
+    feature = "session-cache"
+))]
+pub use self::store::reaper;
+
+#[cfg(feature = "session-cache")]
+pub use self::cache::CacheSessionStore;
+#[cfg(feature = "session-fs")]
+pub use self::fs::FileStore;
+#[cfg(feature = "session-sqlite")]
+pub use self::sqlite::SqliteStore;
+
+/// Middleware loading the session identified by a signed, or optionally
+/// encrypted, cookie.
+///
+/// The key is generated per process by default, so sessions don't survive a
+/// restart unless one is provided with [`Sessions::key`].
 #[derive(Clone)]
 pub struct Sessions {
     store: SessionStore,
-    cookie_name: Cow<'static, str>,
-    cookie_key: cookie::Key,
+    config: Config,
 }
 
 impl Sessions {
@@ -39,8 +68,7 @@ impl Sessions {
     {
         Sessions {
             store: Arc::new(DynStoreImpl(store)),
-            cookie_name: Cow::Borrowed("sess_id"),
-            cookie_key: cookie::Key::generate(),
+            config: Config::default(),
         }
     }
 
@@ -48,7 +76,68 @@ impl Sessions {
     where
         T: Into<Cow<'static, str>>,
     {
-        self.cookie_name = cookie.into();
+        self.config.name = cookie.into();
+        self
+    }
+
+    /// The key used to sign or encrypt the session cookie.
+    pub fn key(mut self, key: Key) -> Self {
+        self.config.key = key;
+        self
+    }
+
+    /// Encrypt the session cookie instead of only signing it.
+    pub fn private(mut self, private: bool) -> Self {
+        self.config.private = private;
+        self
+    }
+
+    /// Defaults to [`SameSite::Lax`].
+    pub fn same_site(mut self, same_site: SameSite) -> Self {
+        self.config.same_site = same_site;
+        self
+    }
+
+    pub fn domain<T>(mut self, domain: T) -> Self
+    where
+        T: Into<Cow<'static, str>>,
+    {
+        self.config.domain = Some(domain.into());
+        self
+    }
+
+    /// Defaults to `/`.
+    pub fn path<T>(mut self, path: T) -> Self
+    where
+        T: Into<Cow<'static, str>>,
+    {
+        self.config.path = path.into();
+        self
+    }
+
+    /// Defaults to `true`.
+    pub fn secure(mut self, secure: bool) -> Self {
+        self.config.secure = secure;
+        self
+    }
+
+    /// Defaults to `true`.
+    pub fn http_only(mut self, http_only: bool) -> Self {
+        self.config.http_only = http_only;
+        self
+    }
+
+    /// Expire sessions not used for `timeout`. The cookie is refreshed on
+    /// every request.
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.config.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Expire sessions `timeout` after they were created, however active they
+    /// are. Regenerating the session id doesn't reset it.
+    pub fn absolute_timeout(mut self, timeout: Duration) -> Self {
+        self.config.absolute_timeout = Some(timeout);
         self
     }
 }
@@ -64,8 +153,7 @@ where
         SessionsWork {
             work: handler,
             store: self.store.clone(),
-            cookie_name: self.cookie_name.clone(),
-            cookie_key: self.cookie_key.clone(),
+            config: Arc::new(self.config.clone()),
             req: PhantomData,
         }
     }
@@ -74,8 +162,7 @@ where
 pub struct SessionsWork<C, B, T> {
     work: T,
     store: SessionStore,
-    cookie_name: Cow<'static, str>,
-    cookie_key: cookie::Key,
+    config: Arc<Config>,
     req: PhantomData<(C, B)>,
 }
 
@@ -84,8 +171,7 @@ impl<C, B, T: Clone> Clone for SessionsWork<C, B, T> {
         Self {
             work: self.work.clone(),
             store: self.store.clone(),
-            cookie_name: self.cookie_name.clone(),
-            cookie_key: self.cookie_key.clone(),
+            config: self.config.clone(),
             req: self.req.clone(),
         }
     }
@@ -113,9 +199,13 @@ where
                 context,
                 work: &self.work,
             },
-            cookie_name: &self.cookie_name,
-            cookie_key: &self.cookie_key,
+            config: &self.config,
             store: &self.store,
+            cookies: None,
+            id: None,
+            session: None,
+            expired: false,
+            now: 0,
         }
     }
 }
@@ -131,11 +221,16 @@ pin_project! {
             context: &'a C,
             work: &'a T
         },
+        Expire {
+            #[pin]
+            future: BoxFuture<'a, Result<(), Error>>,
+            req: Option<Request<B>>,
+            context: &'a C,
+            work: &'a T
+        },
         Future {
             #[pin]
             future: T::Future<'a>,
-            cookies: CookieJar,
-            id: SessionId
         }
     }
 }
@@ -147,10 +242,13 @@ pin_project! {
     {
         #[pin]
         state: SessionWorkFutureState<'a, C, B, T>,
-        cookie_name: &'a Cow<'static,str>,
-        cookie_key: &'a cookie::Key,
+        config: &'a Config,
         store: &'a SessionStore,
-
+        cookies: Option<CookieJar>,
+        id: Option<SessionId>,
+        session: Option<SessionCookie>,
+        expired: bool,
+        now: u64,
     }
 }
 
@@ -171,18 +269,25 @@ where
             match this.state.as_mut().project() {
                 SessionFutureStateProj::Init { req, context, work } => {
                     let mut req = req.take().unwrap();
+                    let (context, work) = (*context, *work);
 
                     let cookies = CookieJar::from_request(&req)?;
+                    let now = config::now();
+                    let mut expired = None;
+
                     let id = if let Some(id) = req.extensions().get::<SessionId>() {
                         id.clone()
                     } else {
-                        let id = if let Some(id) =
-                            cookies.signed(&this.cookie_key).get(&this.cookie_name)
-                        {
-                            let id = Uuid::parse_str(id.value()).map_err(Error::custom)?;
-                            SessionId::new(id)
-                        } else {
-                            SessionId::default()
+                        let id = match this.config.read(&cookies, now) {
+                            Some(session) if this.config.is_expired(&session, now) => {
+                                expired = Some(session.id);
+                                SessionId::default()
+                            }
+                            Some(session) => {
+                                *this.session = Some(session);
+                                SessionId::new(session.id)
+                            }
+                            None => SessionId::default(),
                         };
 
                         req.extensions_mut().insert(id.clone());
@@ -192,37 +297,76 @@ where
 
                     req.extensions_mut().insert(this.store.clone());
 
-                    let future = work.call(*context, req);
+                    *this.cookies = Some(cookies);
+                    *this.id = Some(id);
+                    *this.now = now;
 
-                    this.state.set(SessionWorkFutureState::Future {
-                        future,
-                        cookies,
-                        id,
-                    });
+                    if let Some(expired) = expired {
+                        *this.expired = true;
+                        let store: &'a SessionStore = *this.store;
+                        let future = store.remove(SessionId::new(expired));
+                        this.state.set(SessionWorkFutureState::Expire {
+                            future,
+                            req: Some(req),
+                            context,
+                            work,
+                        });
+                    } else {
+                        let future = work.call(context, req);
+                        this.state.set(SessionWorkFutureState::Future { future });
+                    }
                 }
-                SessionFutureStateProj::Future {
+                SessionFutureStateProj::Expire {
                     future,
-                    cookies,
-                    id,
-                } => match ready!(future.poll(cx)) {
+                    req,
+                    context,
+                    work,
+                } => {
+                    if let Err(err) = ready!(future.poll(cx)) {
+                        tracing::warn!("failed to remove expired session: {err}");
+                    }
+
+                    let future = work.call(*context, req.take().unwrap());
+                    this.state.set(SessionWorkFutureState::Future { future });
+                }
+                SessionFutureStateProj::Future { future } => match ready!(future.poll(cx)) {
                     Ok(ret) => {
-                        match id.state() {
-                            State::Set(uuid) => {
-                                let mut cookie = Cookie::new(
-                                    this.cookie_name.clone(),
-                                    uuid.hyphenated().to_string(),
-                                );
+                        let cookies = this.cookies.take().unwrap();
+                        let id = this.id.take().unwrap();
+                        let now = *this.now;
 
-                                cookie.set_secure(true);
-                                cookie.set_http_only(true);
+                        // Keep the creation time when the id is regenerated,
+                        // so the absolute timeout still applies
+                        let created = this.session.map_or(now, |session| session.created);
 
-                                cookies.signed(&this.cookie_key).add(cookie);
+                        match id.state() {
+                            State::Set(id) => {
+                                this.config.write(
+                                    &cookies,
+                                    SessionCookie {
+                                        id,
+                                        created,
+                                        seen: now,
+                                    },
+                                    now,
+                                );
+                            }
+                            State::Init(id) if this.config.touches() => {
+                                this.config.write(
+                                    &cookies,
+                                    SessionCookie {
+                                        id,
+                                        created,
+                                        seen: now,
+                                    },
+                                    now,
+                                );
+                            }
+                            State::Remove(_) => {
+                                this.config.clear(&cookies);
                             }
-                            State::Remove(uuid) => {
-                                cookies.signed(&this.cookie_key).remove(Cookie::new(
-                                    this.cookie_name.clone(),
-                                    uuid.hyphenated().to_string(),
-                                ));
+                            State::Noop if *this.expired => {
+                                this.config.clear(&cookies);
                             }
                             _ => {}
                         }
@@ -235,3 +379,153 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{body::Body, cookies::Cookies, handler};
+    use alloc::string::{String, ToString};
+    use bycat::prelude::WorkExt;
+    use bycat_value::Map;
+    use cookie::Cookie;
+    use http::{
+        HeaderMap, Response,
+        header::{COOKIE, SET_COOKIE},
+    };
+    use uuid::Uuid;
+
+    async fn request<W>(work: &W, cookie: Option<String>) -> Response<Body>
+    where
+        W: Work<(), Request<Body>, Output = Response<Body>>,
+        W::Error: core::fmt::Debug,
+    {
+        let mut req = Request::builder();
+        if let Some(cookie) = cookie {
+            req = req.header(COOKIE, cookie);
+        }
+        work.call(&(), req.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+    }
+
+    fn set_cookie(resp: &Response<Body>) -> Option<Cookie<'static>> {
+        let header = resp.headers().get(SET_COOKIE)?.to_str().unwrap();
+        Some(Cookie::parse_encoded(header.to_string()).unwrap())
+    }
+
+    /// The `Cookie` header a browser would send for `session`.
+    fn cookie_header(config: &Config, session: SessionCookie) -> String {
+        let jar = CookieJar::from_headers(&HeaderMap::new());
+        config.write(&jar, session, session.seen);
+        jar.get(&config.name)
+            .unwrap()
+            .encoded()
+            .stripped()
+            .to_string()
+    }
+
+    fn read(config: &Config, cookie: &Cookie<'static>) -> Option<SessionCookie> {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            COOKIE,
+            cookie.encoded().stripped().to_string().parse().unwrap(),
+        );
+        config.read(&CookieJar::from_headers(&headers), config::now())
+    }
+
+    async fn stored(store: &MemoryStore, id: Uuid) -> Map {
+        store.load(SessionId::new(id)).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn idle_timeout() {
+        let store = Arc::new(MemoryStore::default());
+        let sessions = Sessions::new(store.clone()).idle_timeout(Duration::from_secs(60));
+        let config = sessions.config.clone();
+
+        let work = handler(|_session: Session| async move { "ok" })
+            .wrap(sessions)
+            .wrap(Cookies);
+
+        let id = Uuid::new_v4();
+        let mut session = Map::default();
+        session.insert("user", "rasmus");
+        store.save(SessionId::new(id), &session).await.unwrap();
+
+        // Active sessions are refreshed
+        let now = config::now();
+        let resp = request(
+            &work,
+            Some(cookie_header(
+                &config,
+                SessionCookie {
+                    id,
+                    created: now - 120_000,
+                    seen: now - 30_000,
+                },
+            )),
+        )
+        .await;
+
+        let cookie = set_cookie(&resp).unwrap();
+        assert_eq!(cookie.max_age(), Some(cookie::time::Duration::seconds(60)));
+        assert_eq!(cookie.same_site(), Some(SameSite::Lax));
+        assert_eq!(cookie.path(), Some("/"));
+
+        let refreshed = read(&config, &cookie).unwrap();
+        assert_eq!(refreshed.id, id);
+        assert_eq!(refreshed.created, now - 120_000);
+        assert!(refreshed.seen >= now);
+
+        // Idle sessions are removed
+        let resp = request(
+            &work,
+            Some(cookie_header(
+                &config,
+                SessionCookie {
+                    id,
+                    created: now - 120_000,
+                    seen: now - 60_000,
+                },
+            )),
+        )
+        .await;
+
+        let cookie = set_cookie(&resp).unwrap();
+        assert_eq!(cookie.max_age(), Some(cookie::time::Duration::ZERO));
+        assert!(stored(&store, id).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn regenerate_keeps_creation_time() {
+        let store = Arc::new(MemoryStore::default());
+        let sessions = Sessions::new(store.clone())
+            .private(true)
+            .absolute_timeout(Duration::from_secs(3600));
+        let config = sessions.config.clone();
+
+        let work =
+            handler(|mut session: Session| async move { session.regenerate().await.map(|_| "ok") })
+                .wrap(sessions)
+                .wrap(Cookies);
+
+        let id = Uuid::new_v4();
+        let mut session = Map::default();
+        session.insert("user", "rasmus");
+        store.save(SessionId::new(id), &session).await.unwrap();
+
+        let created = config::now() - 1_000_000;
+        let resp = request(
+            &work,
+            Some(cookie_header(&config, SessionCookie::new(id, created))),
+        )
+        .await;
+
+        let rotated = read(&config, &set_cookie(&resp).unwrap()).unwrap();
+        assert_ne!(rotated.id, id);
+        assert_eq!(rotated.created, created);
+
+        assert!(stored(&store, id).await.is_empty());
+        assert_eq!(stored(&store, rotated.id).await, session);
+    }
+}
@@ -60,7 +60,7 @@ impl SessionId {
         }
     }
 
-    fn generate(&self) {
+    pub(crate) fn generate(&self) {
         self.0.store(State::Set(Uuid::new_v4()).into());
     }
 }
@@ -94,13 +94,22 @@ impl Session {
         self.value.remove(name);
     }
 
-    pub async fn regenerate_id(&mut self) -> Result<(), Error> {
+    /// Move the session to a new id, keeping its data.
+    ///
+    /// Call this whenever the privileges of the session change, like on
+    /// login, to prevent session fixation.
+    pub async fn regenerate(&mut self) -> Result<(), Error> {
         self.store.remove(self.id.clone()).await?;
         self.id.generate();
         self.save().await?;
         Ok(())
     }
 
+    #[deprecated(note = "use `Session::regenerate`")]
+    pub async fn regenerate_id(&mut self) -> Result<(), Error> {
+        self.regenerate().await
+    }
+
     pub async fn save(&mut self) -> Result<(), Error> {
         if self.id.state().id().is_none() {
             self.id.generate();
@@ -0,0 +1,221 @@
+use crate::Error;
+use alloc::{string::String, sync::Arc, vec::Vec};
+use bycat_value::Map;
+use core::time::Duration;
+use rusqlite::{Connection, OptionalExtension, params};
+use std::{path::Path, sync::Mutex};
+
+use super::{SessionId, Store, store::record};
+
+/// A session store backed by an embedded SQLite database.
+///
+/// Queries run on tokio's blocking thread pool.
+#[derive(Debug, Clone)]
+pub struct SqliteStore {
+    conn: Arc<Mutex<Connection>>,
+    table: Arc<str>,
+    ttl: Duration,
+}
+
+impl SqliteStore {
+    pub fn open(path: impl AsRef<Path>) -> Result<SqliteStore, Error> {
+        let conn = Connection::open(path).map_err(Error::custom)?;
+        conn.pragma_update(None, "journal_mode", "WAL")
+            .map_err(Error::custom)?;
+        SqliteStore::from_connection(conn)
+    }
+
+    pub fn memory() -> Result<SqliteStore, Error> {
+        SqliteStore::from_connection(Connection::open_in_memory().map_err(Error::custom)?)
+    }
+
+    pub fn from_connection(conn: Connection) -> Result<SqliteStore, Error> {
+        SqliteStore::with_table(conn, "sessions")
+    }
+
+    /// Use `table` for the sessions, creating it if it doesn't exist.
+    pub fn with_table(conn: Connection, table: &str) -> Result<SqliteStore, Error> {
+        if table.is_empty()
+            || !table
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'_')
+        {
+            return Err(Error::custom("invalid session table name"));
+        }
+
+        conn.execute_batch(&alloc::format!(
+            "CREATE TABLE IF NOT EXISTS {table} (
+                id TEXT PRIMARY KEY NOT NULL,
+                data BLOB NOT NULL,
+                expires INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS {table}_expires ON {table} (expires);"
+        ))
+        .map_err(Error::custom)?;
+
+        Ok(SqliteStore {
+            conn: Arc::new(Mutex::new(conn)),
+            table: table.into(),
+            ttl: Duration::from_secs(60 * 60 * 24),
+        })
+    }
+
+    /// How long sessions are kept after they were last saved. Defaults to a day.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    async fn run<T, F>(&self, func: F) -> Result<T, Error>
+    where
+        F: FnOnce(&Connection, &str) -> rusqlite::Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let conn = self.conn.clone();
+        let table = self.table.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap_or_else(|err| err.into_inner());
+            func(&conn, &table)
+        })
+        .await
+        .map_err(Error::custom)?
+        .map_err(Error::custom)
+    }
+}
+
+impl Store for SqliteStore {
+    async fn save<'a>(&'a self, id: SessionId, session: &'a Map) -> Result<(), Error> {
+        let Some(id) = id.id() else {
+            return Ok(());
+        };
+
+        let expires = record::expires(self.ttl) as i64;
+        let data = serde_json::to_vec(session).map_err(Error::custom)?;
+        let id = id.hyphenated().to_string();
+
+        self.run(move |conn, table| {
+            conn.execute(
+                &alloc::format!(
+                    "INSERT INTO {table} (id, data, expires) VALUES (?1, ?2, ?3)
+                     ON CONFLICT(id) DO UPDATE SET data = excluded.data, expires = excluded.expires"
+                ),
+                params![id, data, expires],
+            )
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    async fn load<'a>(&'a self, id: SessionId) -> Result<Map, Error> {
+        let Some(id) = id.id() else {
+            return Ok(Map::default());
+        };
+
+        let id = id.hyphenated().to_string();
+        let now = record::now() as i64;
+
+        let data = self
+            .run(move |conn, table| {
+                conn.query_row(
+                    &alloc::format!("SELECT data FROM {table} WHERE id = ?1 AND expires > ?2"),
+                    params![id, now],
+                    |row| row.get::<_, Vec<u8>>(0),
+                )
+                .optional()
+            })
+            .await?;
+
+        match data {
+            Some(data) => serde_json::from_slice(&data).map_err(Error::custom),
+            None => Ok(Map::default()),
+        }
+    }
+
+    async fn remove<'a>(&'a self, id: SessionId) -> Result<(), Error> {
+        let Some(id) = id.id() else {
+            return Ok(());
+        };
+
+        let id: String = id.hyphenated().to_string();
+
+        self.run(move |conn, table| {
+            conn.execute(
+                &alloc::format!("DELETE FROM {table} WHERE id = ?1"),
+                params![id],
+            )
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    async fn reap(&self) -> Result<usize, Error> {
+        let now = record::now() as i64;
+
+        self.run(move |conn, table| {
+            conn.execute(
+                &alloc::format!("DELETE FROM {table} WHERE expires <= ?1"),
+                params![now],
+            )
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn session() -> Map {
+        let mut map = Map::default();
+        map.insert("user", "rasmus");
+        map
+    }
+
+    #[tokio::test]
+    async fn save_load_remove() {
+        let store = SqliteStore::memory().unwrap();
+        let id = SessionId::new(Uuid::new_v4());
+
+        assert!(store.load(id.clone()).await.unwrap().is_empty());
+
+        store.save(id.clone(), &session()).await.unwrap();
+        assert_eq!(store.load(id.clone()).await.unwrap(), session());
+
+        let mut updated = session();
+        updated.insert("theme", "dark");
+        store.save(id.clone(), &updated).await.unwrap();
+        assert_eq!(store.load(id.clone()).await.unwrap(), updated);
+
+        store.remove(id.clone()).await.unwrap();
+        assert!(store.load(id).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn expiry() {
+        let store = SqliteStore::memory().unwrap();
+
+        let live = SessionId::new(Uuid::new_v4());
+        store.save(live.clone(), &session()).await.unwrap();
+
+        let expired_store = store.clone().ttl(Duration::ZERO);
+        let expired = SessionId::new(Uuid::new_v4());
+        expired_store
+            .save(expired.clone(), &session())
+            .await
+            .unwrap();
+
+        assert!(store.load(expired).await.unwrap().is_empty());
+        assert_eq!(store.reap().await.unwrap(), 1);
+        assert_eq!(store.load(live).await.unwrap(), session());
+    }
+
+    #[test]
+    fn table_name() {
+        let conn = Connection::open_in_memory().unwrap();
+        assert!(SqliteStore::with_table(conn, "sessions; DROP TABLE users").is_err());
+    }
+}
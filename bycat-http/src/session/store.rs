@@ -14,6 +14,101 @@ pub trait Store {
     ) -> impl Future<Output = Result<(), Error>> + Send;
     fn load<'a>(&'a self, id: SessionId) -> impl Future<Output = Result<Map, Error>> + Send;
     fn remove<'a>(&'a self, id: SessionId) -> impl Future<Output = Result<(), Error>> + Send;
+
+    /// Remove expired sessions, returning how many were removed.
+    fn reap(&self) -> impl Future<Output = Result<usize, Error>> + Send {
+        async { Ok(0) }
+    }
+}
+
+impl<S> Store for Arc<S>
+where
+    S: Store + Send + Sync,
+{
+    fn save<'a>(
+        &'a self,
+        id: SessionId,
+        session: &'a Map,
+    ) -> impl Future<Output = Result<(), Error>> + Send {
+        (**self).save(id, session)
+    }
+
+    fn load<'a>(&'a self, id: SessionId) -> impl Future<Output = Result<Map, Error>> + Send {
+        (**self).load(id)
+    }
+
+    fn remove<'a>(&'a self, id: SessionId) -> impl Future<Output = Result<(), Error>> + Send {
+        (**self).remove(id)
+    }
+
+    fn reap(&self) -> impl Future<Output = Result<usize, Error>> + Send {
+        (**self).reap()
+    }
+}
+
+/// Periodically remove expired sessions from `store`.
+///
+/// ```ignore
+/// let store = Arc::new(FileStore::new("sessions"));
+/// tokio::spawn(reaper(store.clone(), Duration::from_secs(60)));
+/// let sessions = Sessions::new(store);
+/// ```
+#[cfg(any(
+    feature = "session-fs",
+    feature = "session-sqlite",
+    feature = "session-cache"
+))]
+pub async fn reaper<S: Store>(store: S, interval: core::time::Duration) {
+    loop {
+        tokio::time::sleep(interval).await;
+        match store.reap().await {
+            Ok(0) => {}
+            Ok(count) => tracing::debug!("reaped {count} expired sessions"),
+            Err(err) => tracing::warn!("failed to reap sessions: {err}"),
+        }
+    }
+}
+
+/// Serialized session data, stored with its expiry as seconds since the unix epoch.
+#[cfg(any(
+    feature = "session-fs",
+    feature = "session-sqlite",
+    feature = "session-cache"
+))]
+pub(super) mod record {
+    use crate::Error;
+    use alloc::vec::Vec;
+    use bycat_value::Map;
+    use core::time::Duration;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    pub fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or_default()
+    }
+
+    pub fn expires(ttl: Duration) -> u64 {
+        now().saturating_add(ttl.as_secs())
+    }
+
+    pub fn encode(expires: u64, session: &Map) -> Result<Vec<u8>, Error> {
+        serde_json::to_vec(&(expires, session)).map_err(Error::custom)
+    }
+
+    /// Returns the session, or `None` when it has expired.
+    pub fn decode(bytes: &[u8]) -> Result<Option<Map>, Error> {
+        let (expires, session): (u64, Map) =
+            serde_json::from_slice(bytes).map_err(Error::custom)?;
+        Ok((expires > now()).then_some(session))
+    }
+
+    pub fn is_expired(bytes: &[u8]) -> bool {
+        serde_json::from_slice::<(u64, serde::de::IgnoredAny)>(bytes)
+            .map(|(expires, _)| expires <= now())
+            .unwrap_or(true)
+    }
 }
 
 #[derive(Default)]
@@ -55,13 +150,13 @@ impl Store for MemoryStore {
     }
 }
 
-pub(super) trait DynStore: Send + Sync {
+pub(crate) trait DynStore: Send + Sync {
     fn save<'a>(&'a self, id: SessionId, session: &'a Map) -> BoxFuture<'a, Result<(), Error>>;
     fn load<'a>(&'a self, id: SessionId) -> BoxFuture<'a, Result<Map, Error>>;
     fn remove<'a>(&'a self, id: SessionId) -> BoxFuture<'a, Result<(), Error>>;
 }
 
-pub(super) type SessionStore = Arc<dyn DynStore>;
+pub(crate) type SessionStore = Arc<dyn DynStore>;
 
 pub(super) struct DynStoreImpl<T>(pub T);
 
@@ -0,0 +1,449 @@
+use crate::{
+    Error, FromRequestParts, IntoResponse, Runtime, body::HttpBody, error::BoxError,
+    rejection::InvalidHeader,
+};
+use alloc::{borrow::Cow, boxed::Box, string::String};
+use bytes::Bytes;
+use core::{
+    fmt::Write as _,
+    future::{self, Ready},
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use futures::{Stream, future::BoxFuture};
+use http::{
+    HeaderName, HeaderValue, Response,
+    header::{CACHE_CONTROL, CONTENT_TYPE},
+    request::Parts,
+};
+use sync_wrapper::SyncWrapper;
+
+const LAST_EVENT_ID: HeaderName = HeaderName::from_static("last-event-id");
+
+/// A response streaming server-sent events.
+///
+/// ```ignore
+/// async fn progress(LastEventId(last): LastEventId) -> Sse<impl Stream<Item = Result<Event, Error>>> {
+///     let events = job.progress(last).map(|step| {
+///         Ok(Event::default().id(step.id.to_string()).event("step").data(step.message))
+///     });
+///     Sse::new(events).keep_alive(KeepAlive::new(Runtime::tokio()))
+/// }
+/// ```
+pub struct Sse<S> {
+    stream: S,
+    keep_alive: Option<KeepAlive>,
+}
+
+impl<S> Sse<S> {
+    pub fn new(stream: S) -> Sse<S> {
+        Sse {
+            stream,
+            keep_alive: None,
+        }
+    }
+
+    /// Send a comment when no event was sent for a while, so proxies don't
+    /// close the connection.
+    pub fn keep_alive(mut self, keep_alive: KeepAlive) -> Self {
+        self.keep_alive = Some(keep_alive);
+        self
+    }
+}
+
+impl<S, E, B> IntoResponse<B> for Sse<S>
+where
+    S: Stream<Item = Result<Event, E>> + Send + 'static,
+    E: Into<BoxError>,
+    B: HttpBody,
+{
+    fn into_response(self) -> Response<B> {
+        let keep_alive = self.keep_alive.map(|keep_alive| {
+            let sleep = keep_alive.runtime.sleep(keep_alive.interval);
+            (keep_alive, SyncWrapper::new(sleep))
+        });
+
+        let mut resp = Response::new(B::from_streaming(SseBody {
+            stream: SyncWrapper::new(Box::pin(self.stream)),
+            keep_alive,
+            done: false,
+        }));
+        let headers = resp.headers_mut();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/event-stream"));
+        headers.insert(CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+        resp
+    }
+}
+
+/// How often, and with what comment, an idle [`Sse`] stream is kept alive.
+#[derive(Debug, Clone)]
+pub struct KeepAlive {
+    interval: Duration,
+    comment: Cow<'static, str>,
+    runtime: Runtime,
+}
+
+impl KeepAlive {
+    /// Keep alive every 15 seconds, waiting on the timers of `runtime`.
+    pub fn new(runtime: Runtime) -> KeepAlive {
+        KeepAlive {
+            interval: Duration::from_secs(15),
+            comment: Cow::Borrowed(""),
+            runtime,
+        }
+    }
+
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// The text of the comment. Empty by default.
+    ///
+    /// # Panics
+    ///
+    /// If `comment` contains a newline.
+    pub fn comment(mut self, comment: impl Into<Cow<'static, str>>) -> Self {
+        let comment = comment.into();
+        assert_single_line(&comment);
+        self.comment = comment;
+        self
+    }
+}
+
+/// A server-sent event.
+///
+/// Multiline data is sent as several `data` fields, which the client joins
+/// again. Lines may end with `\r\n`, `\r` or `\n`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Event {
+    id: Option<String>,
+    event: Option<String>,
+    data: Option<String>,
+    retry: Option<Duration>,
+    comment: Option<String>,
+}
+
+impl Event {
+    /// The id the client sends back in `Last-Event-ID` when it reconnects.
+    ///
+    /// # Panics
+    ///
+    /// If `id` contains a newline or a null character.
+    pub fn id(mut self, id: impl Into<String>) -> Self {
+        let id = id.into();
+        assert_single_line(&id);
+        assert!(!id.contains('\0'), "event id contains a null character");
+        self.id = Some(id);
+        self
+    }
+
+    /// The type of the event, `message` if not set.
+    ///
+    /// # Panics
+    ///
+    /// If `event` contains a newline.
+    pub fn event(mut self, event: impl Into<String>) -> Self {
+        let event = event.into();
+        assert_single_line(&event);
+        self.event = Some(event);
+        self
+    }
+
+    pub fn data(mut self, data: impl Into<String>) -> Self {
+        self.data = Some(data.into());
+        self
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn json_data<T: serde::Serialize>(self, data: &T) -> Result<Self, serde_json::Error> {
+        Ok(self.data(serde_json::to_string(data)?))
+    }
+
+    /// How long the client waits before reconnecting.
+    pub fn retry(mut self, retry: Duration) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// A comment, ignored by the client.
+    ///
+    /// # Panics
+    ///
+    /// If `comment` contains a newline.
+    pub fn comment(mut self, comment: impl Into<String>) -> Self {
+        let comment = comment.into();
+        assert_single_line(&comment);
+        self.comment = Some(comment);
+        self
+    }
+
+    fn encode(&self) -> Bytes {
+        let mut out = String::new();
+
+        if let Some(comment) = &self.comment {
+            let _ = writeln!(out, ":{comment}");
+        }
+        if let Some(event) = &self.event {
+            let _ = writeln!(out, "event: {event}");
+        }
+        if let Some(id) = &self.id {
+            let _ = writeln!(out, "id: {id}");
+        }
+        if let Some(retry) = self.retry {
+            let _ = writeln!(out, "retry: {}", retry.as_millis());
+        }
+        if let Some(data) = &self.data {
+            for line in lines(data) {
+                let _ = writeln!(out, "data: {line}");
+            }
+        }
+        out.push('\n');
+
+        Bytes::from(out)
+    }
+}
+
+/// The lines of `data`, split on any of the line endings of the event
+/// stream format.
+fn lines(data: &str) -> impl Iterator<Item = &str> {
+    let mut rest = Some(data);
+    core::iter::from_fn(move || {
+        let current = rest?;
+        match current.find(['\r', '\n']) {
+            Some(end) => {
+                let next = if current[end..].starts_with("\r\n") {
+                    end + 2
+                } else {
+                    end + 1
+                };
+                rest = Some(&current[next..]);
+                Some(&current[..end])
+            }
+            None => {
+                rest = None;
+                Some(current)
+            }
+        }
+    })
+}
+
+fn assert_single_line(value: &str) {
+    assert!(
+        !value.contains(['\n', '\r']),
+        "server-sent event field contains a newline"
+    );
+}
+
+/// The body of an [`Sse`] response.
+///
+/// The stream and timer are only polled through `&mut self`, so they're
+/// wrapped to make the body `Sync` without requiring it of them.
+struct SseBody<S> {
+    stream: SyncWrapper<Pin<Box<S>>>,
+    keep_alive: Option<(KeepAlive, SyncWrapper<BoxFuture<'static, ()>>)>,
+    done: bool,
+}
+
+impl<S, E> http_body::Body for SseBody<S>
+where
+    S: Stream<Item = Result<Event, E>>,
+    E: Into<BoxError>,
+{
+    type Data = Bytes;
+
+    type Error = Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<http_body::Frame<Self::Data>, Self::Error>>> {
+        let this = &mut *self;
+        if this.done {
+            return Poll::Ready(None);
+        }
+
+        match this.stream.get_mut().as_mut().poll_next(cx) {
+            Poll::Ready(Some(Ok(event))) => {
+                if let Some((keep_alive, sleep)) = &mut this.keep_alive {
+                    *sleep.get_mut() = keep_alive.runtime.sleep(keep_alive.interval);
+                }
+                return Poll::Ready(Some(Ok(http_body::Frame::data(event.encode()))));
+            }
+            Poll::Ready(Some(Err(err))) => {
+                this.done = true;
+                return Poll::Ready(Some(Err(Error::custom(err))));
+            }
+            Poll::Ready(None) => {
+                this.done = true;
+                return Poll::Ready(None);
+            }
+            Poll::Pending => {}
+        }
+
+        if let Some((keep_alive, sleep)) = &mut this.keep_alive
+            && sleep.get_mut().as_mut().poll(cx).is_ready()
+        {
+            *sleep.get_mut() = keep_alive.runtime.sleep(keep_alive.interval);
+            let comment = Bytes::from(alloc::format!(":{}\n\n", keep_alive.comment));
+            return Poll::Ready(Some(Ok(http_body::Frame::data(comment))));
+        }
+
+        Poll::Pending
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.done
+    }
+}
+
+/// The `Last-Event-ID` a reconnecting client sends, to resume after the
+/// last event it received.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LastEventId(pub Option<String>);
+
+impl<C> FromRequestParts<C> for LastEventId {
+    type Future<'a>
+        = Ready<Result<Self, Error>>
+    where
+        C: 'a;
+
+    fn from_request_parts<'a>(parts: &'a mut Parts, _state: &'a C) -> Self::Future<'a> {
+        let id = match parts.headers.get(LAST_EVENT_ID) {
+            Some(value) => match value.to_str() {
+                Ok(value) => Ok(LastEventId(Some(String::from(value)))),
+                Err(_) => Err(InvalidHeader(LAST_EVENT_ID).into()),
+            },
+            None => Ok(LastEventId(None)),
+        };
+
+        future::ready(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::{Body, to_bytes};
+    use alloc::vec::Vec;
+    use futures::StreamExt;
+    use http::Request;
+    use http_body_util::BodyExt;
+
+    #[test]
+    fn encode() {
+        let event = Event::default()
+            .id("42")
+            .event("progress")
+            .data("line one\r\nline two")
+            .retry(Duration::from_secs(3))
+            .comment("hi");
+
+        assert_eq!(
+            event.encode(),
+            ":hi\nevent: progress\nid: 42\nretry: 3000\ndata: line one\ndata: line two\n\n"
+        );
+        assert_eq!(Event::default().data("").encode(), "data: \n\n");
+        assert_eq!(
+            Event::default().data("a\rb\r\nc\n\nd\r").encode(),
+            "data: a\ndata: b\ndata: c\ndata: \ndata: d\ndata: \n\n"
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn newline_in_id() {
+        let _ = Event::default().id("1\n2");
+    }
+
+    #[test]
+    #[should_panic]
+    fn carriage_return_in_event() {
+        let _ = Event::default().event("a\rb");
+    }
+
+    #[test]
+    fn body_is_sync() {
+        fn assert_sync<T: Sync>(_: &T) {}
+
+        // Neither the stream nor the timer is `Sync`
+        let stream = futures::stream::iter([core::cell::Cell::new(1)])
+            .map(|n| Ok::<_, Error>(Event::default().data(n.get().to_string())));
+        let keep_alive = KeepAlive::new(Runtime::new(|_| {}, |_| Box::pin(future::pending())));
+        let sleep = keep_alive.runtime.sleep(keep_alive.interval);
+
+        assert_sync(&SseBody {
+            stream: SyncWrapper::new(Box::pin(stream)),
+            keep_alive: Some((keep_alive, SyncWrapper::new(sleep))),
+            done: false,
+        });
+    }
+
+    #[tokio::test]
+    async fn response() {
+        let events = futures::stream::iter([
+            Ok::<_, Error>(Event::default().data("one")),
+            Ok(Event::default().event("done").data("two")),
+        ]);
+        let resp: Response<Body> = Sse::new(events).into_response();
+
+        assert_eq!(resp.headers()[CONTENT_TYPE], "text/event-stream");
+        assert_eq!(resp.headers()[CACHE_CONTROL], "no-cache");
+        assert_eq!(
+            to_bytes(resp.into_body()).await.unwrap(),
+            "data: one\n\nevent: done\ndata: two\n\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn error() {
+        let events = futures::stream::iter([Ok(Event::default().data("one")), Err("failed")]);
+        let mut body: Body = Sse::new(events).into_response().into_body();
+
+        assert!(body.frame().await.unwrap().is_ok());
+        assert!(body.frame().await.unwrap().is_err());
+        assert!(body.frame().await.is_none());
+    }
+
+    #[cfg(feature = "serve-tokio")]
+    #[tokio::test]
+    async fn keep_alive() {
+        let events = futures::stream::iter([Ok::<_, Error>(Event::default().data("one"))])
+            .chain(futures::stream::pending());
+        let resp: Response<Body> = Sse::new(events)
+            .keep_alive(
+                KeepAlive::new(Runtime::tokio())
+                    .interval(Duration::from_millis(10))
+                    .comment("ping"),
+            )
+            .into_response();
+        let mut body = resp.into_body();
+
+        let mut frames = Vec::new();
+        for _ in 0..3 {
+            let frame = body.frame().await.unwrap().unwrap();
+            frames.push(frame.into_data().unwrap());
+        }
+        assert_eq!(frames, ["data: one\n\n", ":ping\n\n", ":ping\n\n"]);
+    }
+
+    #[tokio::test]
+    async fn last_event_id() {
+        let (mut parts, _) = Request::builder()
+            .header(LAST_EVENT_ID, "41")
+            .body(())
+            .unwrap()
+            .into_parts();
+        let id = LastEventId::from_request_parts(&mut parts, &())
+            .await
+            .unwrap();
+        assert_eq!(id, LastEventId(Some(String::from("41"))));
+
+        let (mut parts, _) = Request::new(()).into_parts();
+        let id = LastEventId::from_request_parts(&mut parts, &())
+            .await
+            .unwrap();
+        assert_eq!(id, LastEventId(None));
+    }
+}
@@ -0,0 +1,428 @@
+use crate::{Error, IntoResponse, body::HttpBody, error::BoxError, into_response::Html};
+use alloc::{
+    borrow::Cow,
+    boxed::Box,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use bycat::{Middleware, Work};
+use bycat_fs::VirtualFS;
+use bycat_package::Content;
+use bycat_source::Source;
+use bycat_value::Value;
+use core::marker::PhantomData;
+use futures::{TryStreamExt, future::BoxFuture};
+use http::{Request, Response, StatusCode};
+use minijinja::Environment;
+use parking_lot::RwLock;
+
+type Load = dyn Fn() -> BoxFuture<'static, Result<Vec<(String, String)>, BoxError>> + Send + Sync;
+
+type Configure = dyn Fn(&mut Environment<'static>) + Send + Sync;
+
+#[derive(Clone)]
+struct Options {
+    load: Arc<Load>,
+    configure: Option<Arc<Configure>>,
+    auto_reload: bool,
+}
+
+/// Minijinja templates loaded from a [`VirtualFS`].
+///
+/// Every text file in the file system is a template, named by its path.
+/// They're loaded on the first render, and on every render when auto reload
+/// is on, which it is in debug builds.
+///
+/// Used as a middleware, it renders the [`Template`]s returned by the
+/// handlers it wraps.
+#[derive(Clone)]
+pub struct Templates {
+    opts: Arc<Options>,
+    env: Arc<RwLock<Option<Arc<Environment<'static>>>>>,
+}
+
+impl core::fmt::Debug for Templates {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Templates")
+            .field("auto_reload", &self.opts.auto_reload)
+            .field("loaded", &self.env.read().is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Templates {
+    pub fn new<V>(fs: V) -> Templates
+    where
+        V: VirtualFS + Send + Sync + 'static,
+        V::Error: Into<BoxError>,
+        V::Body: Content + Send,
+        <V::Body as Content>::Error: Into<BoxError>,
+        V::Walk: Send,
+        for<'a> <V::Walk as Source<()>>::Stream<'a>: Send,
+    {
+        let fs = Arc::new(fs);
+        let load = move || {
+            let fs = fs.clone();
+            Box::pin(async move { sources(&*fs).await }) as BoxFuture<'static, _>
+        };
+
+        Templates {
+            opts: Arc::new(Options {
+                load: Arc::new(load),
+                configure: None,
+                auto_reload: cfg!(debug_assertions),
+            }),
+            env: Arc::default(),
+        }
+    }
+
+    /// Reload the templates before every render. Defaults to on in debug
+    /// builds.
+    pub fn auto_reload(mut self, on: bool) -> Self {
+        Arc::make_mut(&mut self.opts).auto_reload = on;
+        self
+    }
+
+    /// Set up the environment, eg. with filters and globals, every time the
+    /// templates are loaded.
+    pub fn configure<F>(mut self, configure: F) -> Self
+    where
+        F: Fn(&mut Environment<'static>) + Send + Sync + 'static,
+    {
+        Arc::make_mut(&mut self.opts).configure = Some(Arc::new(configure));
+        self.env = Arc::default();
+        self
+    }
+
+    /// Load the templates again.
+    pub async fn reload(&self) -> Result<(), Error> {
+        self.load().await.map(|_| ())
+    }
+
+    /// Render the template `name` with `context`.
+    pub async fn render(&self, name: &str, context: &Value) -> Result<String, Error> {
+        let env = self.environment().await?;
+
+        env.get_template(name)
+            .and_then(|template| template.render(context))
+            .map_err(|err| {
+                tracing::error!("failed to render template {name}: {err:#}");
+                Error::custom(err)
+            })
+    }
+
+    async fn environment(&self) -> Result<Arc<Environment<'static>>, Error> {
+        let loaded = self.env.read().clone();
+        match loaded {
+            Some(env) if !self.opts.auto_reload => Ok(env),
+            _ => self.load().await,
+        }
+    }
+
+    async fn load(&self) -> Result<Arc<Environment<'static>>, Error> {
+        let sources = (self.opts.load)().await.map_err(Error::custom)?;
+
+        let mut env = Environment::new();
+        for (name, source) in sources {
+            env.add_template_owned(name, source).map_err(|err| {
+                tracing::error!("failed to load template: {err:#}");
+                Error::custom(err)
+            })?;
+        }
+
+        if let Some(configure) = &self.opts.configure {
+            configure(&mut env);
+        }
+
+        let env = Arc::new(env);
+        *self.env.write() = Some(env.clone());
+        Ok(env)
+    }
+}
+
+async fn sources<V>(fs: &V) -> Result<Vec<(String, String)>, BoxError>
+where
+    V: VirtualFS,
+    V::Error: Into<BoxError>,
+    V::Body: Content,
+    <V::Body as Content>::Error: Into<BoxError>,
+{
+    let mut stream = Box::pin(fs.walk().create_stream(&()));
+
+    let mut sources = Vec::new();
+    while let Some(mut package) = stream.try_next().await.map_err(Into::<BoxError>::into)? {
+        let bytes = package
+            .content
+            .bytes()
+            .await
+            .map_err(Into::<BoxError>::into)?;
+        // Files next to the templates, like images, aren't templates
+        if let Ok(source) = String::from_utf8(bytes.to_vec()) {
+            sources.push((package.path().as_str().to_string(), source));
+        }
+    }
+
+    Ok(sources)
+}
+
+/// A response rendered from a template by the [`Templates`] middleware.
+#[derive(Debug, Clone)]
+pub struct Template {
+    name: Cow<'static, str>,
+    context: Value,
+    status: StatusCode,
+}
+
+impl Template {
+    pub fn new(name: impl Into<Cow<'static, str>>, context: impl Into<Value>) -> Template {
+        Template {
+            name: name.into(),
+            context: context.into(),
+            status: StatusCode::OK,
+        }
+    }
+
+    pub fn status(mut self, status: StatusCode) -> Self {
+        self.status = status;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn context(&self) -> &Value {
+        &self.context
+    }
+}
+
+impl<B: HttpBody> IntoResponse<B> for Template {
+    /// An empty response, carrying the template for the middleware to render.
+    ///
+    /// The status is only set once rendered, so a template returned without
+    /// the [`Templates`] middleware is an internal server error rather than
+    /// an empty page.
+    fn into_response(self) -> Response<B> {
+        let mut resp = Response::new(B::empty());
+        *resp.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+        resp.extensions_mut().insert(self);
+        resp
+    }
+}
+
+impl<C, B, T> Middleware<C, Request<B>, T> for Templates
+where
+    T: Work<C, Request<B>> + Sync,
+    for<'a> T::Future<'a>: Send,
+    T::Output: IntoResponse<B>,
+    T::Error: Into<Error>,
+    C: Sync,
+    B: HttpBody + From<String> + Send,
+{
+    type Work = TemplatesWork<C, B, T>;
+
+    fn wrap(&self, handler: T) -> Self::Work {
+        TemplatesWork {
+            templates: self.clone(),
+            work: handler,
+            req: PhantomData,
+        }
+    }
+}
+
+pub struct TemplatesWork<C, B, T> {
+    templates: Templates,
+    work: T,
+    req: PhantomData<fn(&C, B)>,
+}
+
+impl<C, B, T: Clone> Clone for TemplatesWork<C, B, T> {
+    fn clone(&self) -> Self {
+        TemplatesWork {
+            templates: self.templates.clone(),
+            work: self.work.clone(),
+            req: PhantomData,
+        }
+    }
+}
+
+impl<C, B, T> Work<C, Request<B>> for TemplatesWork<C, B, T>
+where
+    T: Work<C, Request<B>> + Sync,
+    for<'a> T::Future<'a>: Send,
+    T::Output: IntoResponse<B>,
+    T::Error: Into<Error>,
+    C: Sync,
+    B: HttpBody + From<String> + Send,
+{
+    type Output = Response<B>;
+
+    type Error = Error;
+
+    type Future<'a>
+        = BoxFuture<'a, Result<Response<B>, Error>>
+    where
+        Self: 'a,
+        C: 'a;
+
+    fn call<'a>(&'a self, context: &'a C, req: Request<B>) -> Self::Future<'a> {
+        Box::pin(async move {
+            let mut resp = self
+                .work
+                .call(context, req)
+                .await
+                .map_err(Into::into)?
+                .into_response();
+
+            let Some(template) = resp.extensions_mut().remove::<Template>() else {
+                return Ok(resp);
+            };
+
+            let html = self
+                .templates
+                .render(&template.name, &template.context)
+                .await?;
+
+            let (mut parts, _) = resp.into_parts();
+            let (html, body) = IntoResponse::<B>::into_response(Html(html)).into_parts();
+            parts.status = template.status;
+            parts.headers.extend(html.headers);
+
+            Ok(Response::from_parts(parts, body))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::{Body, to_bytes};
+    use bycat_fs::Fs;
+    use bytes::Bytes;
+    use core::future::{Ready, ready};
+    use http::header::CONTENT_TYPE;
+
+    struct Page(&'static str);
+
+    impl Work<(), Request<Body>> for Page {
+        type Output = Template;
+        type Error = Error;
+        type Future<'a>
+            = Ready<Result<Template, Error>>
+        where
+            Self: 'a;
+
+        fn call<'a>(&'a self, _context: &'a (), _req: Request<Body>) -> Self::Future<'a> {
+            let context = bycat_value::value!({ "name": "World" });
+            ready(Ok(
+                Template::new(self.0, context).status(StatusCode::CREATED)
+            ))
+        }
+    }
+
+    fn dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(alloc::format!(
+            "bycat-templates-{name}-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("base.html"),
+            "<h1>{% block title %}{% endblock %}</h1>",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("index.html"),
+            "{% extends \"base.html\" %}{% block title %}Hello {{ name }}{% endblock %}",
+        )
+        .unwrap();
+        dir
+    }
+
+    async fn body(resp: Response<Body>) -> Bytes {
+        to_bytes(resp.into_body()).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn render() {
+        let dir = dir("render");
+        let templates = Templates::new(Fs::new(&dir)).auto_reload(false);
+        let work = Middleware::<(), Request<Body>, _>::wrap(&templates, Page("index.html"));
+
+        let resp = work.call(&(), Request::new(Body::empty())).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        assert_eq!(resp.headers()[CONTENT_TYPE], "text/html");
+        assert_eq!(body(resp).await, "<h1>Hello World</h1>");
+
+        // Without auto reload, changes aren't picked up until a reload
+        std::fs::write(
+            dir.join("base.html"),
+            "<h2>{% block title %}{% endblock %}</h2>",
+        )
+        .unwrap();
+        let resp = work.call(&(), Request::new(Body::empty())).await.unwrap();
+        assert_eq!(body(resp).await, "<h1>Hello World</h1>");
+
+        templates.reload().await.unwrap();
+        let resp = work.call(&(), Request::new(Body::empty())).await.unwrap();
+        assert_eq!(body(resp).await, "<h2>Hello World</h2>");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn without_middleware() {
+        let resp = Page("index.html")
+            .call(&(), Request::new(Body::empty()))
+            .await
+            .unwrap();
+        let resp: Response<Body> = resp.into_response();
+        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(resp.extensions().get::<Template>().is_some());
+    }
+
+    #[tokio::test]
+    async fn auto_reload() {
+        let dir = dir("reload");
+        let templates = Templates::new(Fs::new(&dir)).auto_reload(true);
+        let context = bycat_value::value!({ "name": "World" });
+
+        assert_eq!(
+            templates.render("index.html", &context).await.unwrap(),
+            "<h1>Hello World</h1>"
+        );
+
+        std::fs::write(dir.join("index.html"), "Bye {{ name }}").unwrap();
+        assert_eq!(
+            templates.render("index.html", &context).await.unwrap(),
+            "Bye World"
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn errors() {
+        let dir = dir("errors");
+        std::fs::write(dir.join("broken.html"), "{{ name | nope }}").unwrap();
+        let templates = Templates::new(Fs::new(&dir));
+
+        let work = Middleware::<(), Request<Body>, _>::wrap(&templates, Page("missing.html"));
+        let err = work
+            .call(&(), Request::new(Body::empty()))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(!err.is_public());
+
+        let work = Middleware::<(), Request<Body>, _>::wrap(&templates, Page("broken.html"));
+        let err = work
+            .call(&(), Request::new(Body::empty()))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
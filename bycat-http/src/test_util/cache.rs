@@ -0,0 +1,33 @@
+use alloc::{collections::HashMap, sync::Mutex, vec::Vec};
+use bycat_cache::{CacheStore, Error};
+use core::future::{Ready, ready};
+
+/// A [`CacheStore`] keeping its entries in memory.
+#[derive(Default)]
+pub(crate) struct MemoryCache(Mutex<HashMap<Vec<u8>, Vec<u8>>>);
+
+impl MemoryCache {
+    pub(crate) fn contains(&self, key: &[u8]) -> bool {
+        self.0.lock().unwrap().contains_key(key)
+    }
+}
+
+impl CacheStore for MemoryCache {
+    type GetFuture<'a> = Ready<Result<Vec<u8>, Error>>;
+    type SetFuture<'a> = Ready<Result<(), Error>>;
+
+    fn get<'a>(&'a self, key: &'a [u8]) -> Self::GetFuture<'a> {
+        ready(Ok(self
+            .0
+            .lock()
+            .unwrap()
+            .get(key)
+            .cloned()
+            .unwrap_or_default()))
+    }
+
+    fn set<'a>(&'a self, key: &'a [u8], value: &'a [u8]) -> Self::SetFuture<'a> {
+        self.0.lock().unwrap().insert(key.to_vec(), value.to_vec());
+        ready(Ok(()))
+    }
+}
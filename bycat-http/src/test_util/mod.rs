@@ -0,0 +1,17 @@
+//! Fixtures shared between the tests of several modules.
+
+#[cfg(any(feature = "cache", feature = "session-cache"))]
+mod cache;
+#[cfg(any(
+    feature = "client",
+    all(feature = "client-hyper", feature = "serve-tokio")
+))]
+mod server;
+
+#[cfg(any(feature = "cache", feature = "session-cache"))]
+pub(crate) use self::cache::MemoryCache;
+#[cfg(any(
+    feature = "client",
+    all(feature = "client-hyper", feature = "serve-tokio")
+))]
+pub(crate) use self::server::{head_complete, serve};
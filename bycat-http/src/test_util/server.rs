@@ -0,0 +1,46 @@
+use alloc::{net::SocketAddr, string::String, vec::Vec};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+    sync::mpsc::{UnboundedReceiver, unbounded_channel},
+};
+
+/// Whether the head of a request has been read.
+pub(crate) fn head_complete(request: &[u8]) -> bool {
+    request.windows(4).any(|w| w == b"\r\n\r\n")
+}
+
+/// A raw http/1 server answering each connection with `response` once
+/// `complete` says the request is read, sending the raw requests over the
+/// channel.
+pub(crate) async fn serve(
+    response: String,
+    complete: fn(&[u8]) -> bool,
+) -> (SocketAddr, UnboundedReceiver<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (tx, rx) = unbounded_channel();
+
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let tx = tx.clone();
+            let response = response.clone();
+            tokio::spawn(async move {
+                let mut request = Vec::new();
+                let mut buf = [0; 1024];
+                while !complete(&request) {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    if n == 0 {
+                        break;
+                    }
+                    request.extend_from_slice(&buf[..n]);
+                }
+                stream.write_all(response.as_bytes()).await.unwrap();
+                tx.send(String::from_utf8(request).unwrap()).ok();
+            });
+        }
+    });
+
+    (addr, rx)
+}
@@ -21,3 +21,9 @@ pub fn header_contains(headers: &HeaderMap, key: HeaderName, value: &'static str
         false
     }
 }
+
+/// Compare two byte strings in time depending only on their length, eg. for
+/// tokens and keys.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
@@ -0,0 +1,288 @@
+use alloc::boxed::Box;
+use core::{
+    fmt,
+    marker::PhantomData,
+    pin::Pin,
+    task::{Context, Poll, ready},
+};
+use futures::{Sink, Stream};
+use pin_project_lite::pin_project;
+use serde::{Serialize, de::DeserializeOwned};
+use tungstenite::{
+    Error as WsError, Message,
+    protocol::{CloseFrame, frame::coding::CloseCode},
+};
+
+use super::WebSocket;
+
+type BoxError = Box<dyn core::error::Error + Send + Sync>;
+
+/// Serializes values into websocket messages, and back.
+pub trait Codec {
+    fn encode<T: Serialize>(&self, value: &T) -> Result<Message, CodecError>;
+
+    fn decode<T: DeserializeOwned>(&self, message: Message) -> Result<T, CodecError>;
+}
+
+/// JSON in text messages.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Json;
+
+impl Codec for Json {
+    fn encode<T: Serialize>(&self, value: &T) -> Result<Message, CodecError> {
+        serde_json::to_string(value)
+            .map(|json| Message::Text(json.into()))
+            .map_err(|err| CodecError::Encode(Box::new(err)))
+    }
+
+    fn decode<T: DeserializeOwned>(&self, message: Message) -> Result<T, CodecError> {
+        match message {
+            Message::Text(text) => {
+                serde_json::from_str(text.as_str()).map_err(|err| CodecError::Decode(Box::new(err)))
+            }
+            _ => Err(CodecError::Unsupported),
+        }
+    }
+}
+
+/// MessagePack in binary messages.
+#[cfg(feature = "ws-msgpack")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MessagePack;
+
+#[cfg(feature = "ws-msgpack")]
+impl Codec for MessagePack {
+    fn encode<T: Serialize>(&self, value: &T) -> Result<Message, CodecError> {
+        rmp_serde::to_vec_named(value)
+            .map(|bytes| Message::Binary(bytes.into()))
+            .map_err(|err| CodecError::Encode(Box::new(err)))
+    }
+
+    fn decode<T: DeserializeOwned>(&self, message: Message) -> Result<T, CodecError> {
+        match message {
+            Message::Binary(bytes) => {
+                rmp_serde::from_slice(&bytes).map_err(|err| CodecError::Decode(Box::new(err)))
+            }
+            _ => Err(CodecError::Unsupported),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum CodecError {
+    Socket(WsError),
+    /// A kind of message the codec doesn't read, binary messages for
+    /// [`Json`] say.
+    Unsupported,
+    Decode(BoxError),
+    Encode(BoxError),
+}
+
+impl CodecError {
+    /// The close code telling the peer about the error.
+    pub fn close_code(&self) -> CloseCode {
+        match self {
+            CodecError::Socket(WsError::Capacity(_)) => CloseCode::Size,
+            CodecError::Socket(WsError::Protocol(_)) => CloseCode::Protocol,
+            CodecError::Socket(WsError::Utf8(_)) => CloseCode::Invalid,
+            CodecError::Socket(_) => CloseCode::Error,
+            CodecError::Unsupported => CloseCode::Unsupported,
+            CodecError::Decode(_) => CloseCode::Invalid,
+            CodecError::Encode(_) => CloseCode::Error,
+        }
+    }
+}
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodecError::Socket(err) => write!(f, "websocket error: {err}"),
+            CodecError::Unsupported => f.write_str("unsupported message type"),
+            CodecError::Decode(err) => write!(f, "could not decode message: {err}"),
+            CodecError::Encode(err) => write!(f, "could not encode message: {err}"),
+        }
+    }
+}
+
+impl core::error::Error for CodecError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            CodecError::Socket(err) => Some(err),
+            CodecError::Unsupported => None,
+            CodecError::Decode(err) | CodecError::Encode(err) => Some(&**err),
+        }
+    }
+}
+
+impl From<WsError> for CodecError {
+    fn from(value: WsError) -> Self {
+        CodecError::Socket(value)
+    }
+}
+
+impl WebSocket {
+    /// Receive `I` and send `O`, serialized with `codec`.
+    pub fn typed<C, I, O>(self, codec: C) -> TypedWebSocket<C, I, O>
+    where
+        C: Codec,
+    {
+        TypedWebSocket {
+            socket: self,
+            codec,
+            close: None,
+            closing: false,
+            types: PhantomData,
+        }
+    }
+}
+
+pin_project! {
+    /// A [`WebSocket`] yielding decoded messages, and encoding those sent.
+    ///
+    /// Messages that fail to decode are returned as errors, and close the
+    /// connection with the matching [`CodecError::close_code`]. Pings, pongs
+    /// and close messages are handled by the socket and skipped.
+    pub struct TypedWebSocket<C, I, O = I> {
+        #[pin]
+        socket: WebSocket,
+        codec: C,
+        close: Option<CloseFrame>,
+        closing: bool,
+        types: PhantomData<fn(O) -> I>,
+    }
+}
+
+impl<C, I, O> TypedWebSocket<C, I, O> {
+    pub fn codec(&self) -> &C {
+        &self.codec
+    }
+
+    pub fn into_inner(self) -> WebSocket {
+        self.socket
+    }
+}
+
+impl<C, I, O> Stream for TypedWebSocket<C, I, O>
+where
+    C: Codec,
+    I: DeserializeOwned,
+{
+    type Item = Result<I, CodecError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            let mut this = self.as_mut().project();
+
+            if this.close.is_some() {
+                if let Err(err) = ready!(this.socket.as_mut().poll_ready(cx)) {
+                    return Poll::Ready(Some(Err(err.into())));
+                }
+                let frame = this.close.take();
+                if let Err(err) = this.socket.as_mut().start_send(Message::Close(frame)) {
+                    return Poll::Ready(Some(Err(err.into())));
+                }
+                // Reading writes out the close frame, if it can't go now
+                let _ = this.socket.as_mut().poll_flush(cx);
+            }
+
+            let message = match ready!(this.socket.as_mut().poll_next(cx)) {
+                Some(Ok(message @ (Message::Text(_) | Message::Binary(_)))) => message,
+                Some(Ok(_)) => continue,
+                Some(Err(err)) => return Poll::Ready(Some(Err(err.into()))),
+                None => return Poll::Ready(None),
+            };
+
+            let result = this.codec.decode(message);
+            if let Err(err) = &result
+                && !*this.closing
+            {
+                *this.closing = true;
+                *this.close = Some(CloseFrame {
+                    code: err.close_code(),
+                    reason: "invalid message".into(),
+                });
+            }
+
+            return Poll::Ready(Some(result));
+        }
+    }
+}
+
+impl<C, I, O> Sink<O> for TypedWebSocket<C, I, O>
+where
+    C: Codec,
+    O: Serialize,
+{
+    type Error = CodecError;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.project().socket.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: O) -> Result<(), Self::Error> {
+        let this = self.project();
+        let message = this.codec.encode(&item)?;
+        this.socket.start_send(message).map_err(Into::into)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.project().socket.poll_flush(cx).map_err(Into::into)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.project().socket.poll_close(cx).map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{string::String, vec};
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Chat {
+        room: String,
+        text: String,
+    }
+
+    fn chat() -> Chat {
+        Chat {
+            room: String::from("lobby"),
+            text: String::from("hello"),
+        }
+    }
+
+    #[test]
+    fn json() {
+        let message = Json.encode(&chat()).unwrap();
+        assert_eq!(
+            message,
+            Message::Text(r#"{"room":"lobby","text":"hello"}"#.into())
+        );
+        assert_eq!(Json.decode::<Chat>(message).unwrap(), chat());
+
+        let err = Json
+            .decode::<Chat>(Message::Text(r#"{"room":1}"#.into()))
+            .unwrap_err();
+        assert_eq!(err.close_code(), CloseCode::Invalid);
+
+        let err = Json
+            .decode::<Chat>(Message::Binary(vec![1, 2].into()))
+            .unwrap_err();
+        assert_eq!(err.close_code(), CloseCode::Unsupported);
+    }
+
+    #[cfg(feature = "ws-msgpack")]
+    #[test]
+    fn msgpack() {
+        let message = MessagePack.encode(&chat()).unwrap();
+        assert!(message.is_binary());
+        assert_eq!(MessagePack.decode::<Chat>(message).unwrap(), chat());
+
+        let err = MessagePack
+            .decode::<Chat>(Message::Text("{}".into()))
+            .unwrap_err();
+        assert_eq!(err.close_code(), CloseCode::Unsupported);
+    }
+}
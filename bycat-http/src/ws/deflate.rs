@@ -0,0 +1,725 @@
+//! permessage-deflate (RFC 7692).
+//!
+//! Tungstenite doesn't know about compression, so [`DeflateIo`] sits between
+//! it and the connection: compressed messages from the client are inflated
+//! into plain frames before tungstenite reads them, and the data frames it
+//! writes are deflated on the way out.
+use alloc::{format, string::String, vec::Vec};
+use bytes::{Buf, BytesMut};
+use core::{
+    pin::Pin,
+    task::{Context, Poll, ready},
+};
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress, Status};
+use futures::{AsyncRead, AsyncWrite};
+use http::HeaderValue;
+use std::io;
+
+const TRAILER: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+
+/// Buffered output is written to the connection before accepting more.
+const WRITE_BUFFER: usize = 64 * 1024;
+
+/// Settings for permessage-deflate compression, see
+/// [`WebSocketUpgrade::deflate`](super::WebSocketUpgrade::deflate).
+///
+/// Window bits range from 9 to 15, and bound the memory each side needs to
+/// keep per connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Deflate {
+    level: u32,
+    server_max_window_bits: u8,
+    client_max_window_bits: u8,
+    server_no_context_takeover: bool,
+    client_no_context_takeover: bool,
+    max_message_size: usize,
+}
+
+impl Default for Deflate {
+    fn default() -> Self {
+        Deflate::new()
+    }
+}
+
+impl Deflate {
+    pub fn new() -> Deflate {
+        Deflate {
+            level: 6,
+            server_max_window_bits: 15,
+            client_max_window_bits: 15,
+            server_no_context_takeover: false,
+            client_no_context_takeover: false,
+            max_message_size: 64 << 20,
+        }
+    }
+
+    /// Compression level, from 0 to 9.
+    pub fn level(mut self, level: u32) -> Self {
+        self.level = level.min(9);
+        self
+    }
+
+    /// Window the server compresses messages with.
+    pub fn server_max_window_bits(mut self, bits: u8) -> Self {
+        self.server_max_window_bits = bits.clamp(9, 15);
+        self
+    }
+
+    /// Window the client is asked to compress messages with. Only applied
+    /// when the client offers to limit it.
+    pub fn client_max_window_bits(mut self, bits: u8) -> Self {
+        self.client_max_window_bits = bits.clamp(9, 15);
+        self
+    }
+
+    /// Reset the server compressor after each message.
+    pub fn server_no_context_takeover(mut self, enable: bool) -> Self {
+        self.server_no_context_takeover = enable;
+        self
+    }
+
+    /// Ask the client to reset its compressor after each message.
+    pub fn client_no_context_takeover(mut self, enable: bool) -> Self {
+        self.client_no_context_takeover = enable;
+        self
+    }
+
+    /// Largest message accepted, compressed or inflated.
+    pub fn max_message_size(mut self, size: usize) -> Self {
+        self.max_message_size = size;
+        self
+    }
+
+    /// Accept the first offer in the `Sec-WebSocket-Extensions` headers of the
+    /// request that these settings can satisfy.
+    pub(crate) fn negotiate<'a, I>(&self, headers: I) -> Option<(Params, HeaderValue)>
+    where
+        I: IntoIterator<Item = &'a HeaderValue>,
+    {
+        let params = headers
+            .into_iter()
+            .filter_map(|header| header.to_str().ok())
+            .flat_map(|header| header.split(','))
+            .find_map(|offer| self.accept(offer))?;
+
+        let response = HeaderValue::try_from(params.to_string())
+            .expect("extension parameters are a valid header value");
+
+        Some((params, response))
+    }
+
+    fn accept(&self, offer: &str) -> Option<Params> {
+        let mut parts = offer.split(';').map(str::trim);
+        if parts.next()? != "permessage-deflate" {
+            return None;
+        }
+
+        let mut params = Params {
+            level: self.level,
+            server_max_window_bits: (self.server_max_window_bits < 15)
+                .then_some(self.server_max_window_bits),
+            client_max_window_bits: None,
+            server_no_context_takeover: self.server_no_context_takeover,
+            client_no_context_takeover: self.client_no_context_takeover,
+            max_message_size: self.max_message_size,
+        };
+
+        let mut seen = Vec::new();
+        for param in parts {
+            let (name, value) = match param.split_once('=') {
+                Some((name, value)) => (name.trim(), Some(value.trim().trim_matches('"'))),
+                None => (param, None),
+            };
+
+            // Offers repeating a parameter are invalid, and declined
+            if seen.contains(&name) {
+                return None;
+            }
+            seen.push(name);
+
+            match (name, value) {
+                ("server_no_context_takeover", None) => params.server_no_context_takeover = true,
+                ("client_no_context_takeover", None) => {}
+                ("server_max_window_bits", Some(value)) => {
+                    let bits = window_bits(value)?;
+                    // We can't compress with less than 9 bits
+                    if bits < 9 {
+                        return None;
+                    }
+                    params.server_max_window_bits = Some(self.server_max_window_bits.min(bits));
+                }
+                ("client_max_window_bits", value) => {
+                    let bits = match value {
+                        Some(value) => window_bits(value)?,
+                        None => 15,
+                    };
+                    params.client_max_window_bits = Some(self.client_max_window_bits.min(bits));
+                }
+                _ => return None,
+            }
+        }
+
+        Some(params)
+    }
+}
+
+fn window_bits(value: &str) -> Option<u8> {
+    // Leading zeros aren't allowed
+    if value.starts_with('0') {
+        return None;
+    }
+    value.parse().ok().filter(|bits| (8..=15).contains(bits))
+}
+
+/// The negotiated extension parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Params {
+    level: u32,
+    server_max_window_bits: Option<u8>,
+    client_max_window_bits: Option<u8>,
+    server_no_context_takeover: bool,
+    client_no_context_takeover: bool,
+    max_message_size: usize,
+}
+
+impl core::fmt::Display for Params {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("permessage-deflate")?;
+        if self.server_no_context_takeover {
+            f.write_str("; server_no_context_takeover")?;
+        }
+        if self.client_no_context_takeover {
+            f.write_str("; client_no_context_takeover")?;
+        }
+        if let Some(bits) = self.server_max_window_bits {
+            write!(f, "; server_max_window_bits={bits}")?;
+        }
+        if let Some(bits) = self.client_max_window_bits {
+            write!(f, "; client_max_window_bits={bits}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Compresses and inflates the frames passing through to tungstenite.
+pub(crate) struct DeflateIo<S> {
+    io: S,
+    codec: Option<Codec>,
+    read: BytesMut,
+    inflated: BytesMut,
+    written: BytesMut,
+    deflated: BytesMut,
+}
+
+impl<S> DeflateIo<S> {
+    pub(crate) fn new(io: S, params: Option<Params>) -> DeflateIo<S> {
+        DeflateIo {
+            io,
+            codec: params.map(Codec::new),
+            read: BytesMut::new(),
+            inflated: BytesMut::new(),
+            written: BytesMut::new(),
+            deflated: BytesMut::new(),
+        }
+    }
+}
+
+impl<S: AsyncWrite + Unpin> DeflateIo<S> {
+    fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while !self.deflated.is_empty() {
+            let n = ready!(Pin::new(&mut self.io).poll_write(cx, &self.deflated))?;
+            if n == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.deflated.advance(n);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for DeflateIo<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let Some(codec) = &mut this.codec else {
+            return Pin::new(&mut this.io).poll_read(cx, buf);
+        };
+
+        loop {
+            if !this.inflated.is_empty() {
+                let n = buf.len().min(this.inflated.len());
+                buf[..n].copy_from_slice(&this.inflated[..n]);
+                this.inflated.advance(n);
+                return Poll::Ready(Ok(n));
+            }
+
+            if codec.inbound(&mut this.read, &mut this.inflated)? {
+                continue;
+            }
+
+            let mut chunk = [0; 8192];
+            let n = ready!(Pin::new(&mut this.io).poll_read(cx, &mut chunk))?;
+            if n == 0 {
+                // Hand a truncated frame to tungstenite, so it reports it
+                this.inflated.extend_from_slice(&this.read);
+                this.read.clear();
+                if this.inflated.is_empty() {
+                    return Poll::Ready(Ok(0));
+                }
+            }
+            this.read.extend_from_slice(&chunk[..n]);
+        }
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for DeflateIo<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if this.codec.is_none() {
+            return Pin::new(&mut this.io).poll_write(cx, buf);
+        }
+
+        if this.deflated.len() >= WRITE_BUFFER {
+            ready!(this.poll_drain(cx))?;
+        }
+
+        let codec = this.codec.as_mut().expect("codec");
+        this.written.extend_from_slice(buf);
+        while codec.outbound(&mut this.written, &mut this.deflated)? {}
+
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        Pin::new(&mut this.io).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        Pin::new(&mut this.io).poll_close(cx)
+    }
+}
+
+struct Codec {
+    params: Params,
+    compress: Compress,
+    decompress: Decompress,
+    /// Opcode and payload of a compressed message being received.
+    message: Option<(u8, Vec<u8>)>,
+    /// An uncompressed fragmented message is being sent.
+    fragmented: bool,
+}
+
+impl Codec {
+    fn new(params: Params) -> Codec {
+        Codec {
+            compress: Compress::new_with_window_bits(
+                Compression::new(params.level),
+                false,
+                params.server_max_window_bits.unwrap_or(15),
+            ),
+            // A full window inflates whatever the client picked
+            decompress: Decompress::new(false),
+            params,
+            message: None,
+            fragmented: false,
+        }
+    }
+
+    /// Move the next complete frame from `read` to `out`, inflating it if
+    /// it ends a compressed message. Returns `false` when `read` holds no
+    /// complete frame.
+    fn inbound(&mut self, read: &mut BytesMut, out: &mut BytesMut) -> io::Result<bool> {
+        let Some(header) = Header::parse(read, self.params.max_message_size)? else {
+            return Ok(false);
+        };
+
+        let mut frame = read.split_to(header.len + header.payload);
+        if header.is_control() {
+            out.extend_from_slice(&frame);
+            return Ok(true);
+        }
+
+        let payload = &mut frame[header.len..];
+        match (&mut self.message, header.opcode) {
+            (Some((_, message)), 0) if !header.rsv1 => {
+                header.unmask(payload);
+                message.extend_from_slice(payload);
+            }
+            (Some(_), _) => return Err(invalid("expected a continuation frame")),
+            (None, 1 | 2) if header.rsv1 => {
+                header.unmask(payload);
+                self.message = Some((header.opcode, Vec::from(&payload[..])));
+            }
+            // Uncompressed messages are passed on as they are
+            (None, _) => {
+                out.extend_from_slice(&frame);
+                return Ok(true);
+            }
+        }
+
+        if let Some((_, message)) = &self.message
+            && message.len() > self.params.max_message_size
+        {
+            return Err(invalid("message too large"));
+        }
+
+        if header.fin {
+            let (opcode, mut message) = self.message.take().expect("message");
+            message.extend_from_slice(&TRAILER);
+            let payload = self.inflate(&message)?;
+            write_frame(out, opcode, false, header.mask.is_some(), &payload);
+        }
+
+        Ok(true)
+    }
+
+    /// Move the next complete frame from `written` to `out`, compressing it
+    /// if it is an unfragmented data frame.
+    fn outbound(&mut self, written: &mut BytesMut, out: &mut BytesMut) -> io::Result<bool> {
+        let Some(header) = Header::parse(written, usize::MAX)? else {
+            return Ok(false);
+        };
+
+        let mut frame = written.split_to(header.len + header.payload);
+        let compress = !header.is_control() && !self.fragmented && header.fin;
+        if !header.is_control() {
+            self.fragmented = !header.fin;
+        }
+
+        if !compress {
+            out.extend_from_slice(&frame);
+            return Ok(true);
+        }
+
+        let payload = &mut frame[header.len..];
+        header.unmask(payload);
+        let payload = self.deflate(payload)?;
+        write_frame(out, header.opcode, true, header.mask.is_some(), &payload);
+
+        Ok(true)
+    }
+
+    fn deflate(&mut self, input: &[u8]) -> io::Result<Vec<u8>> {
+        let mut output = Vec::with_capacity(input.len() / 2 + 64);
+        let start = self.compress.total_in();
+
+        loop {
+            let consumed = (self.compress.total_in() - start) as usize;
+            output.reserve(256);
+            self.compress
+                .compress_vec(&input[consumed..], &mut output, FlushCompress::Sync)
+                .map_err(io::Error::other)?;
+
+            let consumed = (self.compress.total_in() - start) as usize;
+            if consumed == input.len() && output.len() < output.capacity() {
+                break;
+            }
+        }
+
+        if output.ends_with(&TRAILER) {
+            output.truncate(output.len() - TRAILER.len());
+        }
+
+        if self.params.server_no_context_takeover {
+            self.compress.reset();
+        }
+
+        Ok(output)
+    }
+
+    fn inflate(&mut self, input: &[u8]) -> io::Result<Vec<u8>> {
+        let mut output = Vec::with_capacity(input.len() * 2);
+        let start = self.decompress.total_in();
+
+        loop {
+            let consumed = (self.decompress.total_in() - start) as usize;
+            output.reserve(1024);
+
+            let before = self.decompress.total_out();
+            let status = self
+                .decompress
+                .decompress_vec(&input[consumed..], &mut output, FlushDecompress::Sync)
+                .map_err(|err| invalid(&format!("{err}")))?;
+
+            if output.len() > self.params.max_message_size {
+                return Err(invalid("message too large"));
+            }
+
+            let consumed = (self.decompress.total_in() - start) as usize;
+            if consumed == input.len() && output.len() < output.capacity() {
+                break;
+            }
+
+            let stalled = self.decompress.total_out() == before;
+            if status == Status::StreamEnd || (status == Status::BufError && stalled) {
+                break;
+            }
+        }
+
+        if self.params.client_no_context_takeover {
+            self.decompress.reset(false);
+        }
+
+        Ok(output)
+    }
+}
+
+struct Header {
+    fin: bool,
+    rsv1: bool,
+    opcode: u8,
+    mask: Option<[u8; 4]>,
+    /// Length of the header.
+    len: usize,
+    /// Length of the payload.
+    payload: usize,
+}
+
+impl Header {
+    fn parse(buf: &[u8], max: usize) -> io::Result<Option<Header>> {
+        if buf.len() < 2 {
+            return Ok(None);
+        }
+
+        let masked = buf[1] & 0x80 != 0;
+        let (payload, mut len) = match buf[1] & 0x7f {
+            126 if buf.len() < 4 => return Ok(None),
+            126 => (u16::from_be_bytes([buf[2], buf[3]]) as u64, 4),
+            127 if buf.len() < 10 => return Ok(None),
+            127 => (
+                u64::from_be_bytes(buf[2..10].try_into().expect("8 bytes")),
+                10,
+            ),
+            n => (n as u64, 2),
+        };
+
+        let payload = usize::try_from(payload)
+            .ok()
+            .filter(|payload| *payload <= max)
+            .ok_or_else(|| invalid("frame too large"))?;
+
+        let mask = if masked {
+            if buf.len() < len + 4 {
+                return Ok(None);
+            }
+            let mask = buf[len..len + 4].try_into().expect("4 bytes");
+            len += 4;
+            Some(mask)
+        } else {
+            None
+        };
+
+        if buf.len() < len + payload {
+            return Ok(None);
+        }
+
+        Ok(Some(Header {
+            fin: buf[0] & 0x80 != 0,
+            rsv1: buf[0] & 0x40 != 0,
+            opcode: buf[0] & 0x0f,
+            mask,
+            len,
+            payload,
+        }))
+    }
+
+    fn is_control(&self) -> bool {
+        self.opcode & 0x08 != 0
+    }
+
+    fn unmask(&self, payload: &mut [u8]) {
+        if let Some(mask) = self.mask {
+            for (i, byte) in payload.iter_mut().enumerate() {
+                *byte ^= mask[i % 4];
+            }
+        }
+    }
+}
+
+/// Write a final frame. Masked frames get an all zero key, which leaves the
+/// payload as it is.
+fn write_frame(out: &mut BytesMut, opcode: u8, rsv1: bool, masked: bool, payload: &[u8]) {
+    out.extend_from_slice(&[0x80 | if rsv1 { 0x40 } else { 0 } | opcode]);
+
+    let mask = if masked { 0x80 } else { 0 };
+    match payload.len() {
+        len @ 0..=125 => out.extend_from_slice(&[mask | len as u8]),
+        len @ 126..=0xffff => {
+            out.extend_from_slice(&[mask | 126]);
+            out.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            out.extend_from_slice(&[mask | 127]);
+            out.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+
+    if masked {
+        out.extend_from_slice(&[0; 4]);
+    }
+    out.extend_from_slice(payload);
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, String::from(msg))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn offer(header: &'static str) -> Option<String> {
+        Deflate::new()
+            .negotiate([&HeaderValue::from_static(header)])
+            .map(|(_, response)| String::from(response.to_str().unwrap()))
+    }
+
+    fn params() -> Params {
+        Deflate::new().accept("permessage-deflate").unwrap()
+    }
+
+    #[test]
+    fn negotiate() {
+        assert_eq!(
+            offer("permessage-deflate").as_deref(),
+            Some("permessage-deflate")
+        );
+        assert_eq!(
+            offer("permessage-deflate; client_max_window_bits").as_deref(),
+            Some("permessage-deflate; client_max_window_bits=15")
+        );
+        assert_eq!(
+            offer("permessage-deflate; server_max_window_bits=10; server_no_context_takeover")
+                .as_deref(),
+            Some("permessage-deflate; server_no_context_takeover; server_max_window_bits=10")
+        );
+
+        // The first acceptable offer wins
+        assert_eq!(
+            offer("x-webkit-deflate-frame, permessage-deflate; server_max_window_bits=8, permessage-deflate; client_max_window_bits=\"12\"").as_deref(),
+            Some("permessage-deflate; client_max_window_bits=12")
+        );
+
+        assert_eq!(offer("permessage-deflate; unknown"), None);
+        assert_eq!(
+            offer("permessage-deflate; server_no_context_takeover; server_no_context_takeover"),
+            None
+        );
+        assert_eq!(offer("permessage-deflate; server_max_window_bits=16"), None);
+
+        // An offered window is always answered
+        assert_eq!(
+            offer("permessage-deflate; server_max_window_bits=15").as_deref(),
+            Some("permessage-deflate; server_max_window_bits=15")
+        );
+    }
+
+    #[test]
+    fn configured() {
+        let deflate = Deflate::new()
+            .server_max_window_bits(12)
+            .client_max_window_bits(10)
+            .client_no_context_takeover(true);
+
+        let (_, response) = deflate
+            .negotiate([&HeaderValue::from_static(
+                "permessage-deflate; client_max_window_bits",
+            )])
+            .unwrap();
+
+        assert_eq!(
+            response,
+            "permessage-deflate; client_no_context_takeover; server_max_window_bits=12; client_max_window_bits=10"
+        );
+    }
+
+    #[test]
+    fn inbound() {
+        // "Hello" compressed, from RFC 7692 section 7.2.3.1, in a masked frame
+        let mut client = BytesMut::new();
+        let mut compressed = [0xf2, 0x48, 0xcd, 0xc9, 0xc9, 0x07, 0x00];
+        let mask = [1, 2, 3, 4];
+        for (i, byte) in compressed.iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        }
+        client.extend_from_slice(&[0xc1, 0x80 | 7]);
+        client.extend_from_slice(&mask);
+        client.extend_from_slice(&compressed);
+        // Followed by an uncompressed ping
+        client.extend_from_slice(&[0x89, 0x80, 0, 0, 0, 0]);
+
+        let mut codec = Codec::new(params());
+        let mut out = BytesMut::new();
+        assert!(codec.inbound(&mut client, &mut out).unwrap());
+        assert!(codec.inbound(&mut client, &mut out).unwrap());
+        assert!(!codec.inbound(&mut client, &mut out).unwrap());
+
+        assert_eq!(
+            &out[..],
+            b"\x81\x85\0\0\0\0Hello\x89\x80\0\0\0\0".as_slice()
+        );
+    }
+
+    #[test]
+    fn roundtrip() {
+        let text = "a message repeated, a message repeated, a message repeated";
+
+        let mut written = BytesMut::new();
+        write_frame(&mut written, 1, false, false, text.as_bytes());
+
+        let mut server = Codec::new(params());
+        let mut sent = BytesMut::new();
+        assert!(server.outbound(&mut written, &mut sent).unwrap());
+        assert!(sent[0] & 0x40 != 0);
+        assert!(sent.len() < text.len());
+
+        // The sent frame inflates back into the message
+        let mut client = Codec::new(params());
+        let mut out = BytesMut::new();
+        assert!(client.inbound(&mut sent, &mut out).unwrap());
+        assert_eq!(&out[2..], text.as_bytes());
+    }
+
+    #[test]
+    fn fragmented() {
+        let mut written = BytesMut::new();
+        written.extend_from_slice(&[0x01, 3]);
+        written.extend_from_slice(b"one");
+        written.extend_from_slice(&[0x80, 3]);
+        written.extend_from_slice(b"two");
+
+        let expected = written.clone();
+        let mut server = Codec::new(params());
+        let mut sent = BytesMut::new();
+        while server.outbound(&mut written, &mut sent).unwrap() {}
+
+        // Fragmented messages are sent uncompressed
+        assert_eq!(sent, expected);
+    }
+
+    #[test]
+    fn message_limit() {
+        let mut client = BytesMut::new();
+        write_frame(&mut client, 2, true, true, &[0; 32]);
+
+        let mut codec = Codec::new(
+            Deflate::new()
+                .max_message_size(16)
+                .accept("permessage-deflate")
+                .unwrap(),
+        );
+        let mut out = BytesMut::new();
+        assert!(codec.inbound(&mut client, &mut out).is_err());
+    }
+}
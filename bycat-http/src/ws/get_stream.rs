@@ -4,10 +4,7 @@ use tungstenite::{
     protocol::{Role, WebSocketConfig},
 };
 
-use crate::{
-    serve::FuturesIo,
-    ws::{compat::AllowStd, stream::WebSocketStream},
-};
+use crate::ws::{compat::AllowStd, stream::WebSocketStream};
 
 pub struct GetWebSocketStream<S> {
     role: Role,
@@ -26,7 +23,7 @@ impl<S> GetWebSocketStream<S> {
 }
 
 impl<S: Unpin> Future for GetWebSocketStream<S> {
-    type Output = WebSocketStream<FuturesIo<S>>;
+    type Output = WebSocketStream<S>;
 
     fn poll(
         self: alloc::pin::Pin<&mut Self>,
@@ -37,7 +34,7 @@ impl<S: Unpin> Future for GetWebSocketStream<S> {
         let inner_stream = this.stream.take().expect("stream");
         let config = this.config.take();
 
-        let stream = AllowStd::new(FuturesIo::new(inner_stream), cx.waker());
+        let stream = AllowStd::new(inner_stream, cx.waker());
 
         let socket = WebSocket::from_raw_socket(stream, this.role, config);
 
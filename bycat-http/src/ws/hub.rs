@@ -0,0 +1,556 @@
+use alloc::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    string::String,
+    sync::Arc,
+    vec::Vec,
+};
+use core::{
+    fmt,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use futures::{
+    SinkExt, Stream, StreamExt,
+    future::{Either, select},
+    task::AtomicWaker,
+};
+use parking_lot::{Mutex, RwLock};
+use tungstenite::{
+    Error as WsError, Message,
+    protocol::{CloseFrame, frame::coding::CloseCode},
+};
+
+use super::WebSocket;
+
+/// What to do when the outbound queue of a connection is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Overflow {
+    /// Drop the oldest queued message to make room for the new one.
+    #[default]
+    DropOldest,
+    /// Close the connection, it can't keep up.
+    Disconnect,
+}
+
+/// Identifies a connection in a [`Hub`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ConnectionId(u64);
+
+impl fmt::Display for ConnectionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Named rooms of websocket connections.
+///
+/// Each connection carries a presence value `P` (a user name, say) and gets a
+/// bounded outbound queue, so a slow client never blocks a broadcast.
+///
+/// ```ignore
+/// handler(async |State(hub): State<Hub<String>>, upgrade: WebSocketUpgrade| {
+///     let (resp, future) = upgrade.on_upgrade(async move |socket: WebSocket| {
+///         let conn = hub.connect(String::from("anonymous"));
+///         conn.join("lobby");
+///         conn.run(socket, |conn, msg| {
+///             conn.broadcast("lobby", msg);
+///         })
+///         .await
+///         .ok();
+///     });
+///     tokio::spawn(future);
+///     resp
+/// })
+/// ```
+pub struct Hub<P = ()> {
+    inner: Arc<Inner<P>>,
+}
+
+impl<P> Clone for Hub<P> {
+    fn clone(&self) -> Self {
+        Hub {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<P> Default for Hub<P> {
+    fn default() -> Self {
+        Hub::new()
+    }
+}
+
+impl<P> fmt::Debug for Hub<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.inner.state.read();
+        f.debug_struct("Hub")
+            .field("connections", &state.members.len())
+            .field("rooms", &state.rooms.len())
+            .field("capacity", &self.inner.capacity)
+            .field("overflow", &self.inner.overflow)
+            .finish()
+    }
+}
+
+struct Inner<P> {
+    state: RwLock<State<P>>,
+    capacity: usize,
+    overflow: Overflow,
+}
+
+struct State<P> {
+    next: u64,
+    members: BTreeMap<ConnectionId, Member<P>>,
+    rooms: BTreeMap<String, BTreeSet<ConnectionId>>,
+}
+
+struct Member<P> {
+    presence: P,
+    queue: Arc<Queue>,
+    rooms: BTreeSet<String>,
+}
+
+impl<P> Hub<P> {
+    /// A hub queueing up to 64 messages per connection, dropping the oldest
+    /// when full.
+    pub fn new() -> Hub<P> {
+        Hub::with_capacity(64, Overflow::DropOldest)
+    }
+
+    pub fn with_capacity(capacity: usize, overflow: Overflow) -> Hub<P> {
+        assert!(capacity > 0, "capacity must be at least 1");
+
+        Hub {
+            inner: Arc::new(Inner {
+                state: RwLock::new(State {
+                    next: 0,
+                    members: BTreeMap::new(),
+                    rooms: BTreeMap::new(),
+                }),
+                capacity,
+                overflow,
+            }),
+        }
+    }
+
+    /// Register a connection. It leaves the hub when the returned
+    /// [`Connection`] is dropped.
+    pub fn connect(&self, presence: P) -> Connection<P> {
+        let queue = Arc::new(Queue::default());
+
+        let mut state = self.inner.state.write();
+        let id = ConnectionId(state.next);
+        state.next += 1;
+        state.members.insert(
+            id,
+            Member {
+                presence,
+                queue: queue.clone(),
+                rooms: BTreeSet::new(),
+            },
+        );
+
+        Connection {
+            id,
+            hub: self.clone(),
+            queue,
+        }
+    }
+
+    /// Number of connections.
+    pub fn len(&self) -> usize {
+        self.inner.state.read().members.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Rooms with at least one member.
+    pub fn rooms(&self) -> Vec<String> {
+        self.inner.state.read().rooms.keys().cloned().collect()
+    }
+
+    /// Queue a message for a single connection. Returns `false` when the
+    /// connection is gone, or was disconnected for being too slow.
+    pub fn send(&self, id: ConnectionId, msg: Message) -> bool {
+        let state = self.inner.state.read();
+        match state.members.get(&id) {
+            Some(member) => self.push(&member.queue, msg),
+            None => false,
+        }
+    }
+
+    /// Queue a message for every member of `room`, returning how many got it.
+    pub fn broadcast(&self, room: &str, msg: Message) -> usize {
+        self.broadcast_except(room, None, msg)
+    }
+
+    /// Queue a message for every connection, returning how many got it.
+    pub fn broadcast_all(&self, msg: Message) -> usize {
+        let state = self.inner.state.read();
+        state
+            .members
+            .values()
+            .filter(|member| self.push(&member.queue, msg.clone()))
+            .count()
+    }
+
+    fn broadcast_except(&self, room: &str, except: Option<ConnectionId>, msg: Message) -> usize {
+        let state = self.inner.state.read();
+        let Some(ids) = state.rooms.get(room) else {
+            return 0;
+        };
+
+        ids.iter()
+            .filter(|id| Some(**id) != except)
+            .filter_map(|id| state.members.get(id))
+            .filter(|member| self.push(&member.queue, msg.clone()))
+            .count()
+    }
+
+    fn push(&self, queue: &Queue, msg: Message) -> bool {
+        queue.push(msg, self.inner.capacity, self.inner.overflow)
+    }
+
+    fn join(&self, id: ConnectionId, room: &str) -> bool {
+        let mut state = self.inner.state.write();
+        let Some(member) = state.members.get_mut(&id) else {
+            return false;
+        };
+
+        if !member.rooms.insert(String::from(room)) {
+            return false;
+        }
+
+        state
+            .rooms
+            .entry(String::from(room))
+            .or_default()
+            .insert(id);
+        true
+    }
+
+    fn leave(&self, id: ConnectionId, room: &str) -> bool {
+        let mut state = self.inner.state.write();
+        let Some(member) = state.members.get_mut(&id) else {
+            return false;
+        };
+
+        if !member.rooms.remove(room) {
+            return false;
+        }
+
+        state.remove_from_room(id, room);
+        true
+    }
+
+    fn disconnect(&self, id: ConnectionId) {
+        let mut state = self.inner.state.write();
+        if let Some(member) = state.members.remove(&id) {
+            for room in &member.rooms {
+                state.remove_from_room(id, room);
+            }
+        }
+    }
+}
+
+impl<P: Clone> Hub<P> {
+    /// The connections in `room`, in the order they connected.
+    pub fn presence(&self, room: &str) -> Vec<(ConnectionId, P)> {
+        let state = self.inner.state.read();
+        let Some(ids) = state.rooms.get(room) else {
+            return Vec::new();
+        };
+
+        ids.iter()
+            .filter_map(|id| {
+                state
+                    .members
+                    .get(id)
+                    .map(|member| (*id, member.presence.clone()))
+            })
+            .collect()
+    }
+
+    /// The presence value of a connection.
+    pub fn get(&self, id: ConnectionId) -> Option<P> {
+        self.inner
+            .state
+            .read()
+            .members
+            .get(&id)
+            .map(|member| member.presence.clone())
+    }
+}
+
+impl<P> State<P> {
+    fn remove_from_room(&mut self, id: ConnectionId, room: &str) {
+        if let Some(ids) = self.rooms.get_mut(room) {
+            ids.remove(&id);
+            if ids.is_empty() {
+                self.rooms.remove(room);
+            }
+        }
+    }
+}
+
+/// A connection registered in a [`Hub`].
+pub struct Connection<P = ()> {
+    id: ConnectionId,
+    hub: Hub<P>,
+    queue: Arc<Queue>,
+}
+
+impl<P> fmt::Debug for Connection<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Connection").field("id", &self.id).finish()
+    }
+}
+
+impl<P> Connection<P> {
+    pub fn id(&self) -> ConnectionId {
+        self.id
+    }
+
+    pub fn hub(&self) -> &Hub<P> {
+        &self.hub
+    }
+
+    /// Join `room`, returning `false` if already a member.
+    pub fn join(&self, room: &str) -> bool {
+        self.hub.join(self.id, room)
+    }
+
+    /// Leave `room`, returning `false` if not a member.
+    pub fn leave(&self, room: &str) -> bool {
+        self.hub.leave(self.id, room)
+    }
+
+    /// The rooms this connection is in.
+    pub fn rooms(&self) -> Vec<String> {
+        let state = self.hub.inner.state.read();
+        state
+            .members
+            .get(&self.id)
+            .map(|member| member.rooms.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Queue a message for this connection.
+    pub fn send(&self, msg: Message) -> bool {
+        self.hub.push(&self.queue, msg)
+    }
+
+    /// Queue a message for the other members of `room`.
+    pub fn broadcast(&self, room: &str, msg: Message) -> usize {
+        self.hub.broadcast_except(room, Some(self.id), msg)
+    }
+
+    /// Messages dropped from the queue of this connection because it was
+    /// full.
+    pub fn dropped(&self) -> u64 {
+        self.queue.state.lock().dropped
+    }
+
+    /// The queued outbound messages. Ends when the connection is
+    /// disconnected for being too slow.
+    pub fn outbox(&self) -> Outbox<'_> {
+        Outbox { queue: &self.queue }
+    }
+
+    /// Pump the outbound queue into `socket`, and pass incoming messages to
+    /// `on_message`, until either side closes.
+    pub async fn run<F>(self, socket: WebSocket, mut on_message: F) -> Result<(), WsError>
+    where
+        F: FnMut(&Connection<P>, Message),
+    {
+        let (mut sink, mut stream) = socket.split();
+        let mut outbox = self.outbox();
+
+        loop {
+            match select(stream.next(), outbox.next()).await {
+                Either::Left((Some(msg), _)) => on_message(&self, msg?),
+                Either::Left((None, _)) => return Ok(()),
+                Either::Right((Some(msg), _)) => sink.send(msg).await?,
+                Either::Right((None, _)) => {
+                    let frame = CloseFrame {
+                        code: CloseCode::Again,
+                        reason: "too slow".into(),
+                    };
+                    sink.send(Message::Close(Some(frame))).await?;
+                    return sink.close().await;
+                }
+            }
+        }
+    }
+}
+
+impl<P> Drop for Connection<P> {
+    fn drop(&mut self) {
+        self.hub.disconnect(self.id);
+    }
+}
+
+#[derive(Default)]
+struct Queue {
+    state: Mutex<QueueState>,
+    waker: AtomicWaker,
+}
+
+#[derive(Default)]
+struct QueueState {
+    messages: VecDeque<Message>,
+    closed: bool,
+    dropped: u64,
+}
+
+impl Queue {
+    fn push(&self, msg: Message, capacity: usize, overflow: Overflow) -> bool {
+        let mut state = self.state.lock();
+        if state.closed {
+            return false;
+        }
+
+        if state.messages.len() >= capacity {
+            match overflow {
+                Overflow::DropOldest => {
+                    state.messages.pop_front();
+                    state.dropped += 1;
+                }
+                Overflow::Disconnect => {
+                    state.messages.clear();
+                    state.closed = true;
+                    drop(state);
+                    self.waker.wake();
+                    return false;
+                }
+            }
+        }
+
+        state.messages.push_back(msg);
+        drop(state);
+        self.waker.wake();
+        true
+    }
+}
+
+/// The outbound queue of a [`Connection`].
+pub struct Outbox<'a> {
+    queue: &'a Queue,
+}
+
+impl Stream for Outbox<'_> {
+    type Item = Message;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.queue.waker.register(cx.waker());
+
+        let mut state = self.queue.state.lock();
+        if let Some(msg) = state.messages.pop_front() {
+            Poll::Ready(Some(msg))
+        } else if state.closed {
+            Poll::Ready(None)
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(msg: &str) -> Message {
+        Message::Text(msg.into())
+    }
+
+    async fn drain<P>(conn: &Connection<P>) -> Vec<Message> {
+        let mut messages = Vec::new();
+        while !conn.queue.state.lock().messages.is_empty() {
+            messages.push(conn.outbox().next().await.unwrap());
+        }
+        messages
+    }
+
+    #[tokio::test]
+    async fn rooms() {
+        let hub = Hub::new();
+        let alice = hub.connect("alice");
+        let bob = hub.connect("bob");
+        let carol = hub.connect("carol");
+
+        assert!(alice.join("chat"));
+        assert!(!alice.join("chat"));
+        assert!(bob.join("chat"));
+        assert!(carol.join("news"));
+
+        assert_eq!(hub.broadcast("chat", text("hello")), 2);
+        assert_eq!(alice.broadcast("chat", text("from alice")), 1);
+        assert_eq!(hub.broadcast("empty", text("nobody")), 0);
+        assert_eq!(hub.broadcast_all(text("everyone")), 3);
+
+        assert_eq!(drain(&alice).await, [text("hello"), text("everyone")]);
+        assert_eq!(
+            drain(&bob).await,
+            [text("hello"), text("from alice"), text("everyone")]
+        );
+        assert_eq!(drain(&carol).await, [text("everyone")]);
+
+        assert!(hub.send(carol.id(), text("direct")));
+        assert_eq!(drain(&carol).await, [text("direct")]);
+    }
+
+    #[test]
+    fn presence() {
+        let hub = Hub::new();
+        let alice = hub.connect("alice");
+        let bob = hub.connect("bob");
+
+        alice.join("chat");
+        bob.join("chat");
+        assert_eq!(
+            hub.presence("chat"),
+            [(alice.id(), "alice"), (bob.id(), "bob")]
+        );
+
+        assert!(alice.leave("chat"));
+        assert!(!alice.leave("chat"));
+        assert_eq!(hub.presence("chat"), [(bob.id(), "bob")]);
+
+        let id = bob.id();
+        drop(bob);
+        assert!(hub.presence("chat").is_empty());
+        assert!(hub.rooms().is_empty());
+        assert_eq!(hub.get(id), None);
+        assert!(!hub.send(id, text("gone")));
+        assert_eq!(hub.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn drop_oldest() {
+        let hub = Hub::<()>::with_capacity(2, Overflow::DropOldest);
+        let conn = hub.connect(());
+
+        for n in 0..4 {
+            assert!(conn.send(text(&n.to_string())));
+        }
+
+        assert_eq!(conn.dropped(), 2);
+        assert_eq!(drain(&conn).await, [text("2"), text("3")]);
+    }
+
+    #[tokio::test]
+    async fn disconnect_slow() {
+        let hub = Hub::<()>::with_capacity(2, Overflow::Disconnect);
+        let conn = hub.connect(());
+        conn.join("room");
+
+        assert_eq!(hub.broadcast("room", text("1")), 1);
+        assert_eq!(hub.broadcast("room", text("2")), 1);
+        assert_eq!(hub.broadcast("room", text("3")), 0);
+        assert!(!conn.send(text("4")));
+
+        assert_eq!(conn.outbox().next().await, None);
+    }
+}
@@ -1,8 +1,13 @@
 mod callback;
+#[cfg(feature = "serde")]
+mod codec;
 mod compat;
+#[cfg(feature = "ws-deflate")]
+mod deflate;
 mod error;
 mod get_stream;
 mod handshake;
+mod hub;
 mod stream;
 mod upgrade;
 mod websocket;
@@ -10,8 +15,18 @@ mod websocket;
 pub use self::{
     callback::Callback,
     error::WebsocketError,
+    hub::{Connection, ConnectionId, Hub, Outbox, Overflow},
     upgrade::{DefaultOnFailedUpgrade, OnFailedUpgrade, WebSocketHandlerFuture, WebSocketUpgrade},
     websocket::WebSocket,
 };
 
 pub use tungstenite::protocol::{CloseFrame, Message, frame::coding::CloseCode};
+
+#[cfg(feature = "serde")]
+pub use self::codec::{Codec, CodecError, Json, TypedWebSocket};
+
+#[cfg(feature = "ws-msgpack")]
+pub use self::codec::MessagePack;
+
+#[cfg(feature = "ws-deflate")]
+pub use self::deflate::Deflate;
@@ -4,8 +4,10 @@ use crate::{
     body::HttpBody,
     util::{header_contains, header_eq},
     ws::{
-        callback::Callback, error::WebsocketError, get_stream::GetWebSocketStream,
-        websocket::WebSocket,
+        callback::Callback,
+        error::WebsocketError,
+        get_stream::GetWebSocketStream,
+        websocket::{Extensions, Transport, WebSocket, transport},
     },
 };
 use alloc::{
@@ -16,7 +18,7 @@ use alloc::{
 use bytes::Bytes;
 use http::Request;
 use http::{HeaderValue, Method, Response, StatusCode, Version, header, request::Parts};
-use hyper::upgrade::{OnUpgrade, Upgraded};
+use hyper::upgrade::OnUpgrade;
 use pin_project_lite::pin_project;
 use sha1::{Digest, Sha1};
 use tungstenite::protocol::{Role, WebSocketConfig};
@@ -59,6 +61,12 @@ pub struct WebSocketUpgrade<F = DefaultOnFailedUpgrade> {
     on_upgrade: hyper::upgrade::OnUpgrade,
     on_failed_upgrade: F,
     sec_websocket_protocol: BTreeSet<HeaderValue>,
+    /// The `Sec-WebSocket-Extensions` offered by the client.
+    #[cfg(feature = "ws-deflate")]
+    sec_websocket_extensions: Vec<HeaderValue>,
+    /// The negotiated extensions, and the response header agreeing to them.
+    #[cfg(feature = "ws-deflate")]
+    extensions: Option<(super::deflate::Params, HeaderValue)>,
 }
 
 impl<F> WebSocketUpgrade<F> {
@@ -161,9 +169,28 @@ impl<F> WebSocketUpgrade<F> {
             on_upgrade: self.on_upgrade,
             on_failed_upgrade: callback,
             sec_websocket_protocol: self.sec_websocket_protocol,
+            #[cfg(feature = "ws-deflate")]
+            sec_websocket_extensions: self.sec_websocket_extensions,
+            #[cfg(feature = "ws-deflate")]
+            extensions: self.extensions,
         }
     }
 
+    /// Compress messages with permessage-deflate, if the client offers it.
+    ///
+    /// Replaces the settings of earlier calls.
+    #[cfg(feature = "ws-deflate")]
+    pub fn deflate(mut self, config: super::Deflate) -> Self {
+        self.extensions = config.negotiate(&self.sec_websocket_extensions);
+        self
+    }
+
+    /// Whether [`deflate()`][Self::deflate] was agreed on with the client.
+    #[cfg(feature = "ws-deflate")]
+    pub fn is_deflated(&self) -> bool {
+        self.extensions.is_some()
+    }
+
     /// Finalize upgrading the connection and call the provided callback with
     /// the stream.
     #[must_use = "to set up the WebSocket connection, this response must be returned"]
@@ -179,8 +206,17 @@ impl<F> WebSocketUpgrade<F> {
 
         let protocol = self.protocol.clone();
 
+        #[cfg(feature = "ws-deflate")]
+        let (extensions, extensions_header) = match self.extensions {
+            Some((params, header)) => (Some(params), Some(header)),
+            None => (None, None),
+        };
+        #[cfg(not(feature = "ws-deflate"))]
+        let extensions = ();
+
         let future = WebSocketHandlerFuture {
             protocol,
+            extensions: Some(extensions),
             func: Some(callback),
             config: Some(config),
             state: UpgradeFutureState::Upgrade { future: on_upgrade },
@@ -218,6 +254,13 @@ impl<F> WebSocketUpgrade<F> {
                 .insert(header::SEC_WEBSOCKET_PROTOCOL, protocol);
         }
 
+        #[cfg(feature = "ws-deflate")]
+        if let Some(extensions) = extensions_header {
+            response
+                .headers_mut()
+                .insert(header::SEC_WEBSOCKET_EXTENSIONS, extensions);
+        }
+
         (response, future)
     }
 }
@@ -290,6 +333,15 @@ impl WebSocketUpgrade {
             on_upgrade,
             sec_websocket_protocol,
             on_failed_upgrade: DefaultOnFailedUpgrade,
+            #[cfg(feature = "ws-deflate")]
+            sec_websocket_extensions: parts
+                .headers
+                .get_all(header::SEC_WEBSOCKET_EXTENSIONS)
+                .iter()
+                .cloned()
+                .collect(),
+            #[cfg(feature = "ws-deflate")]
+            extensions: None,
         })
     }
 }
@@ -324,7 +376,7 @@ pin_project! {
         Upgrade { #[pin] future: OnUpgrade },
         Stream {
             #[pin]
-            future: GetWebSocketStream<Upgraded>
+            future: GetWebSocketStream<Transport>
         },
         Call { #[pin] future: F },
     }
@@ -335,6 +387,7 @@ pin_project! {
     pub struct WebSocketHandlerFuture<T: Callback, F> {
         func: Option<T>,
         protocol: Option<HeaderValue>,
+        extensions: Option<Extensions>,
         config: Option<WebSocketConfig>,
         #[pin]
         state: UpgradeFutureState<T::Future>,
@@ -366,7 +419,11 @@ impl<T: Callback, F: OnFailedUpgrade> Future for WebSocketHandlerFuture<T, F> {
                     };
 
                     this.state.set(UpgradeFutureState::Stream {
-                        future: GetWebSocketStream::new(ret, Role::Server, this.config.take()),
+                        future: GetWebSocketStream::new(
+                            transport(ret, this.extensions.take().expect("extensions")),
+                            Role::Server,
+                            this.config.take(),
+                        ),
                     });
                 }
                 UpgradeFutureProj::Stream { future } => {
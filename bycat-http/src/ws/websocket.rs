@@ -6,10 +6,37 @@ use tungstenite::{Error as WsError, Message};
 
 use crate::{serve::FuturesIo, ws::stream::WebSocketStream};
 
+#[cfg(feature = "ws-deflate")]
+use crate::ws::deflate::{DeflateIo, Params};
+
+/// The connection under the websocket protocol.
+#[cfg(feature = "ws-deflate")]
+pub(crate) type Transport = DeflateIo<FuturesIo<Upgraded>>;
+
+#[cfg(not(feature = "ws-deflate"))]
+pub(crate) type Transport = FuturesIo<Upgraded>;
+
+/// The extensions negotiated in the handshake.
+#[cfg(feature = "ws-deflate")]
+pub(crate) type Extensions = Option<Params>;
+
+#[cfg(not(feature = "ws-deflate"))]
+pub(crate) type Extensions = ();
+
+#[cfg(feature = "ws-deflate")]
+pub(crate) fn transport(io: Upgraded, extensions: Extensions) -> Transport {
+    DeflateIo::new(FuturesIo::new(io), extensions)
+}
+
+#[cfg(not(feature = "ws-deflate"))]
+pub(crate) fn transport(io: Upgraded, _extensions: Extensions) -> Transport {
+    FuturesIo::new(io)
+}
+
 pin_project! {
     pub struct WebSocket {
         #[pin]
-        pub(crate) socket: WebSocketStream<FuturesIo<Upgraded>>,
+        pub(crate) socket: WebSocketStream<Transport>,
         pub(crate) protocol: Option<HeaderValue>,
     }
 }
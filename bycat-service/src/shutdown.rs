@@ -1,47 +1,167 @@
 use alloc::sync::Arc;
 use core::{
-    fmt::Debug,
-    pin::Pin,
+    fmt::{self, Debug},
+    pin::{Pin, pin},
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
     task::{Context, Poll},
 };
 use event_listener::{Event, EventListener};
+use futures::future::{Either, select};
 use pin_project_lite::pin_project;
 
+struct Inner {
+    shutdown: Event,
+    abort: Event,
+    idle: Event,
+    is_shutdown: AtomicBool,
+    is_aborted: AtomicBool,
+    connections: AtomicUsize,
+}
+
 #[derive(Clone)]
 pub struct Shutdown {
-    event: Arc<Event>,
+    inner: Arc<Inner>,
 }
 
 impl Debug for Shutdown {
-    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        f.debug_struct("Shutdown").finish_non_exhaustive()
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Shutdown")
+            .field("connections", &self.connections())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Shutdown::new()
     }
 }
 
 impl Shutdown {
     pub fn new() -> Self {
         Shutdown {
-            event: Event::new().into(),
+            inner: Arc::new(Inner {
+                shutdown: Event::new(),
+                abort: Event::new(),
+                idle: Event::new(),
+                is_shutdown: AtomicBool::new(false),
+                is_aborted: AtomicBool::new(false),
+                connections: AtomicUsize::new(0),
+            }),
         }
     }
 
     pub fn watch<C: GracefulShutdown>(&self, conn: C) -> GracefulWatchFuture<C> {
+        self.inner.connections.fetch_add(1, Ordering::AcqRel);
+
         GracefulWatchFuture {
             conn,
-            cancel: self.event.listen(),
-            guard: None,
+            cancel: self.inner.shutdown.listen(),
+            closing: false,
+            tracker: Tracker {
+                inner: self.inner.clone(),
+            },
+        }
+    }
+
+    /// Like [`watch`](Shutdown::watch), but the connection is also closed
+    /// when the shutdown is [aborted](Shutdown::abort).
+    pub fn try_watch<C: GracefulShutdown>(&self, conn: C) -> TryWatchFuture<C> {
+        TryWatchFuture {
+            abort: self.inner.abort.listen(),
+            watch: self.watch(conn),
         }
     }
 
     pub fn wait(&self) -> WaitFuture {
         WaitFuture {
-            future: self.event.listen(),
+            future: self.inner.shutdown.listen(),
+            inner: self.inner.clone(),
         }
     }
 
     pub fn shutdown(&self) {
-        self.event.notify(usize::MAX);
+        self.inner.is_shutdown.store(true, Ordering::Release);
+        self.inner.shutdown.notify(usize::MAX);
+    }
+
+    pub fn is_shutdown(&self) -> bool {
+        self.inner.is_shutdown.load(Ordering::Acquire)
+    }
+
+    /// Whether `self` and `other` are handles to the same shutdown.
+    pub fn ptr_eq(&self, other: &Shutdown) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+
+    /// Forcibly close all connections watched with [`try_watch`](Shutdown::try_watch).
+    pub fn abort(&self) {
+        self.inner.is_aborted.store(true, Ordering::Release);
+        self.inner.abort.notify(usize::MAX);
     }
+
+    /// The number of connections currently being watched.
+    pub fn connections(&self) -> usize {
+        self.inner.connections.load(Ordering::Acquire)
+    }
+
+    /// Resolves when no connections are being watched.
+    pub async fn idle(&self) {
+        loop {
+            if self.connections() == 0 {
+                return;
+            }
+
+            let listener = self.inner.idle.listen();
+
+            if self.connections() == 0 {
+                return;
+            }
+
+            listener.await;
+        }
+    }
+
+    /// Shutdown and wait for the watched connections to finish.
+    ///
+    /// Connections still open when `deadline` resolves are aborted, which
+    /// closes the ones watched with [`try_watch`](Shutdown::try_watch).
+    ///
+    /// ```ignore
+    /// let report = shutdown.drain(tokio::time::sleep(Duration::from_secs(30))).await;
+    /// if report.aborted > 0 {
+    ///     tracing::warn!("aborted {} connections", report.aborted);
+    /// }
+    /// ```
+    pub async fn drain<F: Future>(&self, deadline: F) -> DrainReport {
+        self.shutdown();
+
+        let connections = self.connections();
+
+        match select(pin!(self.idle()), pin!(deadline)).await {
+            Either::Left(_) => DrainReport {
+                connections,
+                aborted: 0,
+            },
+            Either::Right(_) => {
+                let aborted = self.connections();
+                self.abort();
+                DrainReport {
+                    connections,
+                    aborted,
+                }
+            }
+        }
+    }
+}
+
+/// The outcome of [`Shutdown::drain`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DrainReport {
+    /// Connections open when the drain started.
+    pub connections: usize,
+    /// Connections that didn't finish before the deadline.
+    pub aborted: usize,
 }
 
 pub trait GracefulShutdown: Future<Output = Result<(), Self::Error>> {
@@ -50,14 +170,50 @@ pub trait GracefulShutdown: Future<Output = Result<(), Self::Error>> {
     fn graceful_shutdown(self: Pin<&mut Self>);
 }
 
+#[derive(Debug)]
+pub enum WatchError<E> {
+    Connection(E),
+    Aborted,
+}
+
+impl<E: fmt::Display> fmt::Display for WatchError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WatchError::Connection(err) => err.fmt(f),
+            WatchError::Aborted => f.write_str("connection aborted"),
+        }
+    }
+}
+
+impl<E: core::error::Error + 'static> core::error::Error for WatchError<E> {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            WatchError::Connection(err) => Some(err),
+            WatchError::Aborted => None,
+        }
+    }
+}
+
+struct Tracker {
+    inner: Arc<Inner>,
+}
+
+impl Drop for Tracker {
+    fn drop(&mut self) {
+        if self.inner.connections.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.inner.idle.notify(usize::MAX);
+        }
+    }
+}
+
 pin_project! {
   pub struct GracefulWatchFuture<C: GracefulShutdown> {
     #[pin]
     conn: C,
     #[pin]
     cancel: EventListener,
-    #[pin]
-    guard: Option<()>
+    closing: bool,
+    tracker: Tracker,
   }
 }
 
@@ -69,22 +225,51 @@ where
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let mut this = self.project();
+        let inner = &this.tracker.inner;
 
-        if this.guard.is_none() {
-            if let Poll::Ready(guard) = this.cancel.poll(cx) {
-                this.guard.set(Some(guard));
-                this.conn.as_mut().graceful_shutdown();
-            }
+        if !*this.closing
+            && (inner.is_shutdown.load(Ordering::Acquire) || this.cancel.poll(cx).is_ready())
+        {
+            *this.closing = true;
+            this.conn.as_mut().graceful_shutdown();
         }
 
         this.conn.poll(cx)
     }
 }
 
+pin_project! {
+  pub struct TryWatchFuture<C: GracefulShutdown> {
+    #[pin]
+    watch: GracefulWatchFuture<C>,
+    #[pin]
+    abort: EventListener,
+  }
+}
+
+impl<C> Future for TryWatchFuture<C>
+where
+    C: GracefulShutdown,
+{
+    type Output = Result<(), WatchError<C::Error>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let aborted = this.watch.tracker.inner.is_aborted.load(Ordering::Acquire);
+
+        if aborted || this.abort.poll(cx).is_ready() {
+            return Poll::Ready(Err(WatchError::Aborted));
+        }
+
+        this.watch.poll(cx).map_err(WatchError::Connection)
+    }
+}
+
 pin_project! {
     pub struct WaitFuture {
         #[pin]
-        future: EventListener
+        future: EventListener,
+        inner: Arc<Inner>,
     }
 }
 
@@ -92,7 +277,11 @@ impl Future for WaitFuture {
     type Output = ();
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        self.project().future.poll(cx)
+        let this = self.project();
+        if this.inner.is_shutdown.load(Ordering::Acquire) {
+            return Poll::Ready(());
+        }
+        this.future.poll(cx)
     }
 }
 
@@ -226,4 +415,106 @@ mod tests {
         assert!(Pin::new(&mut fut).poll(&mut cx).is_ready());
         assert!(shutdown_called.get());
     }
+
+    struct StuckConn;
+
+    impl Future for StuckConn {
+        type Output = Result<(), &'static str>;
+
+        fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Self::Output> {
+            Poll::Pending
+        }
+    }
+
+    impl GracefulShutdown for StuckConn {
+        type Error = &'static str;
+
+        fn graceful_shutdown(self: Pin<&mut Self>) {}
+    }
+
+    #[test]
+    fn test_drain_waits_for_connections() {
+        let shutdown = Shutdown::new();
+        let shutdown_called = Rc::new(Cell::new(false));
+        let conn = TestConn {
+            polled: Rc::new(Cell::new(0)),
+            shutdown_called: shutdown_called.clone(),
+            ready: false,
+        };
+
+        let fut = shutdown.watch(conn);
+        assert_eq!(shutdown.connections(), 1);
+
+        let (report, result) = futures::executor::block_on(futures::future::join(
+            shutdown.drain(futures::future::pending::<()>()),
+            fut,
+        ));
+
+        assert!(result.is_ok());
+        assert!(shutdown_called.get());
+        assert_eq!(
+            report,
+            DrainReport {
+                connections: 1,
+                aborted: 0
+            }
+        );
+        assert_eq!(shutdown.connections(), 0);
+    }
+
+    #[test]
+    fn test_drain_aborts_after_deadline() {
+        let shutdown = Shutdown::new();
+        let mut fut = shutdown.try_watch(StuckConn);
+
+        let report = futures::executor::block_on(shutdown.drain(futures::future::ready(())));
+        assert_eq!(
+            report,
+            DrainReport {
+                connections: 1,
+                aborted: 1
+            }
+        );
+
+        let waker = dummy_waker();
+        let mut cx = Context::from_waker(&waker);
+        assert!(matches!(
+            Pin::new(&mut fut).poll(&mut cx),
+            Poll::Ready(Err(WatchError::Aborted))
+        ));
+
+        drop(fut);
+        assert_eq!(shutdown.connections(), 0);
+    }
+
+    #[test]
+    fn test_watch_ignores_abort() {
+        let shutdown = Shutdown::new();
+        let mut fut = shutdown.watch(StuckConn);
+        shutdown.abort();
+
+        let waker = dummy_waker();
+        let mut cx = Context::from_waker(&waker);
+        let poll: Poll<Result<(), &'static str>> = Pin::new(&mut fut).poll(&mut cx);
+        assert!(poll.is_pending());
+    }
+
+    #[test]
+    fn test_watch_after_shutdown() {
+        let shutdown = Shutdown::new();
+        shutdown.shutdown();
+
+        let shutdown_called = Rc::new(Cell::new(false));
+        let mut fut = shutdown.watch(TestConn {
+            polled: Rc::new(Cell::new(0)),
+            shutdown_called: shutdown_called.clone(),
+            ready: false,
+        });
+
+        let waker = dummy_waker();
+        let mut cx = Context::from_waker(&waker);
+        assert!(Pin::new(&mut fut).poll(&mut cx).is_ready());
+        assert!(shutdown_called.get());
+        futures::executor::block_on(shutdown.wait());
+    }
 }